@@ -349,6 +349,35 @@ impl BufferPoolManager {
         self.disk_manager.image_lsn()
     }
 
+    /// Overwrites a temporary page and writes it to disk right away.
+    /// Temporary pages (e.g. sort runs) are never logged, so unlike table
+    /// pages they don't have to stay in memory until a checkpoint
+    pub fn write_temp_page(&mut self, page_id: PageId, bytes: &[u8]) -> Result<()> {
+        self.fetch_frame(page_id, None)?;
+        let page = self.frames[self.page_table[&page_id]].writer();
+        page.write_bytes(0, bytes.len(), bytes);
+        self.disk_manager.write_to_file(page, None)?;
+        page.mark_clean();
+        self.unpin(&page_id, None);
+        Ok(())
+    }
+
+    /// Drops a temporary page from the pool and from disk, a pinned page is
+    /// left as it is
+    pub fn delete_temp_page(&mut self, page_id: PageId) -> Result<()> {
+        if let Some(&frame_id) = self.page_table.get(&page_id) {
+            if self.frames[frame_id].get_pin_count() != 0 {
+                return Err(anyhow!("temp page {page_id} deleted while pinned"));
+            }
+            self.page_table.remove(&page_id);
+            self.frames[frame_id] = Frame::new();
+            self.replacer.remove(frame_id);
+            self.free_frames.push_back(frame_id);
+        }
+
+        self.disk_manager.delete_file(page_id)
+    }
+
     pub fn flush(&mut self, page_id: PageId) -> Result<()> {
         let frame_id = self.page_table.get(&page_id).unwrap();
         let page = self.frames[*frame_id].reader();
@@ -427,6 +456,26 @@ pub mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_delete_pinned_temp_page() -> Result<()> {
+        let path = test_path();
+
+        let mut bpm = test_bpm(2, &path);
+
+        let page_id = bpm.new_page()?.reader().get_page_id();
+        bpm.write_temp_page(page_id, &[1, 2, 3])?;
+
+        let _ = bpm.fetch_frame(page_id, None)?;
+        assert!(bpm.delete_temp_page(page_id).is_err());
+        assert_eq!(bpm.get_pin_count(&page_id), Some(1));
+
+        bpm.unpin(&page_id, None);
+        bpm.delete_temp_page(page_id)?;
+        assert_eq!(bpm.get_pin_count(&page_id), None);
+
+        Ok(())
+    }
+
     #[test]
     fn test_shared_latch() -> Result<()> {
         let path = test_path();
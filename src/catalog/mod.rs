@@ -13,7 +13,9 @@ use crate::types::{Types, Value, ValueFactory};
 use crate::wal::manager::ArcLogManager;
 use anyhow::{bail, Result};
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
 use std::sync::Arc;
 use versioned_map::VersionedMap;
 
@@ -70,6 +72,99 @@ impl CatalogRow {
     }
 }
 
+/// A change to a table by ALTER TABLE
+#[derive(Serialize, Deserialize, Clone)]
+pub enum AlterOperation {
+    /// the existing rows get the value in the new column
+    AddColumn(Field, Value),
+    DropColumn(String),
+    RenameColumn {
+        old: String,
+        new: String,
+    },
+    RenameTable(String),
+}
+
+impl AlterOperation {
+    /// The table's schema after the change
+    pub fn apply(&self, schema: &Schema) -> Result<Schema> {
+        let position = |name: &str| schema.fields.iter().position(|f| f.name == name);
+        let mut fields = schema.fields.clone();
+
+        match self {
+            AlterOperation::AddColumn(field, _) => {
+                if position(&field.name).is_some() {
+                    bail!(Error::ColumnExists(field.name.clone()));
+                }
+                if field.constraints.unique && fields.iter().any(|f| f.constraints.unique) {
+                    bail!(Error::Unsupported(
+                        "Only one unique field is allowed".into()
+                    ));
+                }
+                fields.push(field.clone());
+            }
+            AlterOperation::DropColumn(name) => {
+                let i = position(name).ok_or(Error::ColumnNotFound(name.clone()))?;
+                if fields.len() == 1 {
+                    bail!(Error::Unsupported(format!(
+                        "Dropping {name}, the only column of the table"
+                    )));
+                }
+                fields.remove(i);
+            }
+            AlterOperation::RenameColumn { old, new } => {
+                let i = position(old).ok_or(Error::ColumnNotFound(old.clone()))?;
+                if position(new).is_some() {
+                    bail!(Error::ColumnExists(new.clone()));
+                }
+                fields[i].name = new.clone();
+            }
+            AlterOperation::RenameTable(_) => {}
+        }
+
+        Ok(Schema::new(fields))
+    }
+
+    /// A row of the table laid out in the new schema, `schema` is the old one
+    pub fn apply_to_row(&self, schema: &Schema, mut row: Vec<Value>) -> Vec<Value> {
+        match self {
+            AlterOperation::AddColumn(_, default) => row.push(default.clone()),
+            AlterOperation::DropColumn(name) => {
+                if let Some(i) = schema.fields.iter().position(|f| &f.name == name) {
+                    row.remove(i);
+                }
+            }
+            AlterOperation::RenameColumn { .. } | AlterOperation::RenameTable(_) => {}
+        }
+        row
+    }
+
+    /// Whether the rows are stored differently after the change
+    pub fn rewrites_rows(&self) -> bool {
+        matches!(
+            self,
+            AlterOperation::AddColumn(..) | AlterOperation::DropColumn(_)
+        )
+    }
+}
+
+impl Display for AlterOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlterOperation::AddColumn(field, default) => write!(
+                f,
+                "ADD COLUMN {} DEFAULT {default}",
+                Schema::new(vec![field.clone()]).to_sql()
+            ),
+            AlterOperation::DropColumn(name) => write!(f, "DROP COLUMN {name}"),
+            AlterOperation::RenameColumn { old, new } => {
+                write!(f, "RENAME COLUMN {old} TO {new}")
+            }
+            AlterOperation::RenameTable(name) => write!(f, "RENAME TO {name}"),
+        }
+    }
+}
+
 pub type ArcCatalog = Arc<RwLock<Catalog>>;
 
 pub struct Catalog {
@@ -239,6 +334,11 @@ impl Catalog {
             .map(|(_, table)| table)
     }
 
+    /// For operators that need scratch pages, e.g. sort runs
+    pub fn get_bpm(&self) -> ArcBufferPool {
+        self.bpm.clone()
+    }
+
     pub fn commit(&mut self, txn: TxnId) -> Result<()> {
         // tables changed during the txn
         let mut committed_keys = self.txn_tables.remove(&txn).unwrap_or_default();
@@ -247,9 +347,13 @@ impl Catalog {
 
         printdbg!("Txn {} committed tables {:?}", txn, committed_keys);
 
+        // a table renamed during the txn is gone under its old name
         committed_keys
             .iter()
-            .try_for_each(|key| self.tables_map.get_mut(None, key).unwrap().1.commit_txn())?;
+            .try_for_each(|key| match self.tables_map.get_mut(None, key) {
+                Some((_, table)) => table.commit_txn(),
+                None => Ok(()),
+            })?;
 
         Ok(())
     }
@@ -282,6 +386,51 @@ impl Catalog {
         Ok(())
     }
 
+    /// Replaces the table with an altered duplicate in the txn's version of
+    /// the catalog, and its catalog row with one for the new schema
+    pub fn alter_table(
+        &mut self,
+        table_name: &String,
+        operation: &AlterOperation,
+        txn: TxnId,
+    ) -> Result<()> {
+        if let AlterOperation::RenameTable(new_name) = operation {
+            if self.get_table(new_name, Some(txn)).is_some() {
+                bail!(Error::TableExists(new_name.clone()));
+            }
+        }
+
+        let altered = match self.get_table_mut(table_name, Some(txn)) {
+            Some(table) => table?.alter(operation, txn)?,
+            None => bail!(Error::TableNotFound(table_name.clone())),
+        };
+        let tuple_id = self.tables_map.get(Some(txn), table_name).unwrap().0;
+
+        let row = CatalogRow {
+            name: altered.name.clone(),
+            schema: altered.get_schema(),
+            first_page: altered.get_first_page_id(),
+            last_page: altered.get_last_page_id(),
+            index_page: Some(altered.get_index_page_id()),
+        };
+
+        self.table().start_txn(txn)?;
+        self.txn_tables
+            .entry(txn)
+            .or_default()
+            .insert(CATALOG_NAME.to_string());
+
+        let tuple_id = self.table().update(Some(tuple_id), row.to_values()?)?;
+
+        if &altered.name != table_name {
+            self.tables_map.remove(Some(txn), table_name);
+        }
+        self.tables_map
+            .insert(Some(txn), altered.name.clone(), (tuple_id, altered));
+
+        Ok(())
+    }
+
     pub fn drop_table(
         &mut self,
         table_name: &String,
@@ -1,18 +1,163 @@
 use crate::catalog::ArcCatalog;
 use crate::errors::Error;
 use crate::execution::result_set::ResultSet;
-use crate::sql::logical_plan::optimizer::optimize_logical_plan;
-use crate::sql::logical_plan::LogicalPlanBuilder;
-use crate::sql::parser::parse;
+use crate::sql::logical_plan::optimizer::{optimize_bound_plan, optimize_logical_plan};
+use crate::sql::logical_plan::plan::LogicalPlan;
+use crate::sql::logical_plan::{bind_params, LogicalPlanBuilder};
+use crate::sql::parser::{parse, parse_prepared};
+use crate::sql::physical_plan::create_physical_plan;
+use crate::tuple::schema::Schema;
 use crate::txn_manager::{ArcTransactionManager, TxnId};
+use crate::types::{Types, Value};
 use anyhow::{ensure, Result};
+use parking_lot::Mutex;
 use sqlparser::ast::Statement;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A statement parsed once, to be executed with the values of its `$1`,
+/// `$2`... parameters
+#[derive(Clone)]
+pub struct PreparedStatement {
+    statement: Statement,
+    params: usize,
+    /// the types declared by PREPARE, if any
+    types: Vec<Types>,
+    /// the optimized plan with the parameters left to bind, shared by the clones
+    plan: Arc<Mutex<Option<PreparedPlan>>>,
+}
+
+struct PreparedPlan {
+    /// the types of the parameters it's built for
+    types: Vec<Types>,
+    /// the tables it uses, with their schemas at the time
+    tables: Vec<(String, Schema)>,
+    plan: LogicalPlan,
+}
+
+impl PreparedStatement {
+    fn new(statement: Statement, params: usize) -> Self {
+        Self {
+            statement,
+            params,
+            types: vec![],
+            plan: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// How many parameters the statement takes
+    pub fn params(&self) -> usize {
+        self.params
+    }
+
+    /// The parameters converted to the types declared by PREPARE
+    fn coerce_params(&self, params: &[Value]) -> Result<Vec<Value>> {
+        ensure!(
+            params.len() == self.params,
+            Error::Expected(
+                format!("{} parameters", self.params),
+                params.len().to_string()
+            )
+        );
+
+        if self.types.is_empty() {
+            return Ok(params.to_vec());
+        }
+
+        self.types
+            .iter()
+            .zip(params)
+            .map(|(ty, value)| {
+                value.coerce(ty).map_err(|_| {
+                    let actual = params.iter().map(Value::get_type).collect();
+                    Error::TypeMismatch(self.types.clone(), actual).into()
+                })
+            })
+            .collect()
+    }
+
+    /// The plan of the statement with the parameters bound. It's planned once
+    /// for the parameters' types, and planned again when they change or when
+    /// one of the tables it uses changed its schema since
+    fn plan(
+        &self,
+        catalog: &ArcCatalog,
+        txn_id: Option<TxnId>,
+        params: &[Value],
+    ) -> Result<LogicalPlan> {
+        let types = match self.types.is_empty() {
+            true => params.iter().map(Value::get_type).collect(),
+            false => self.types.clone(),
+        };
+
+        let mut prepared = self.plan.lock();
+        let valid = prepared.as_ref().is_some_and(|prepared| {
+            prepared.types == types
+                && prepared.tables.iter().all(|(name, schema)| {
+                    catalog.read().get_schema(name, txn_id).as_ref() == Some(schema)
+                })
+        });
+        if !valid {
+            *prepared = self.build_plan(catalog, txn_id, types);
+        }
+
+        match prepared.as_ref() {
+            Some(prepared) => Ok(optimize_bound_plan(bind_params(
+                prepared.plan.clone(),
+                params,
+            )?)),
+            None => {
+                let plan_builder =
+                    LogicalPlanBuilder::new(catalog.clone()).with_params(params.to_vec());
+                let plan = plan_builder.build_initial_plan(self.statement.clone(), txn_id)?;
+                Ok(optimize_logical_plan(plan))
+            }
+        }
+    }
+
+    /// Plans queries and DML statements for parameters of the given types.
+    /// None for other statements, or when the plan needs the values
+    /// themselves, e.g. the keys of a PREWHERE, those are planned for the
+    /// values of each EXECUTE
+    fn build_plan(
+        &self,
+        catalog: &ArcCatalog,
+        txn_id: Option<TxnId>,
+        types: Vec<Types>,
+    ) -> Option<PreparedPlan> {
+        if !matches!(
+            self.statement,
+            Statement::Query(_)
+                | Statement::Insert(_)
+                | Statement::Update { .. }
+                | Statement::Delete(_)
+                | Statement::Merge { .. }
+        ) {
+            return None;
+        }
+
+        let plan_builder = LogicalPlanBuilder::new(catalog.clone()).with_param_types(types.clone());
+        let plan = plan_builder
+            .build_initial_plan(self.statement.clone(), txn_id)
+            .ok()?;
+
+        Some(PreparedPlan {
+            types,
+            tables: plan_builder.tables(),
+            plan: optimize_logical_plan(plan),
+        })
+    }
+}
 
 pub struct Context {
     catalog: ArcCatalog,
     txn_manager: ArcTransactionManager,
     active_txn: Option<TxnId>,
     catalog_changed: bool,
+    /// rows of the running recursive CTEs' previous iterations
+    work_tables: HashMap<String, ResultSet>,
+    /// statements prepared with PREPARE, by name
+    prepared: HashMap<String, PreparedStatement>,
 }
 
 impl Context {
@@ -22,6 +167,8 @@ impl Context {
             txn_manager,
             active_txn: None,
             catalog_changed: false,
+            work_tables: HashMap::new(),
+            prepared: HashMap::new(),
         }
     }
 
@@ -38,6 +185,19 @@ impl Context {
         self.active_txn
     }
 
+    pub fn get_work_table(&self, name: &str) -> Option<&ResultSet> {
+        self.work_tables.get(name)
+    }
+
+    /// Returns the work table it replaced, a CTE can shadow another of the same name
+    pub fn set_work_table(&mut self, name: &str, rows: ResultSet) -> Option<ResultSet> {
+        self.work_tables.insert(name.to_string(), rows)
+    }
+
+    pub fn remove_work_table(&mut self, name: &str) {
+        self.work_tables.remove(name);
+    }
+
     pub fn start_txn(&mut self) -> Result<TxnId> {
         if let Some(id) = self.active_txn {
             return Ok(id);
@@ -87,41 +247,136 @@ impl Context {
 
         let results = statements
             .into_iter()
-            .map(|statement| {
-                let implicit = self.active_txn.is_none() && Self::mutates(&statement);
-                if implicit {
-                    self.start_txn()?;
-                }
+            .map(|statement| self.execute_statement(statement))
+            .collect::<Result<Vec<ResultSet>>>();
 
-                let plan_builder = LogicalPlanBuilder::new(self.catalog.clone());
+        let result = results.and_then(|result_set| {
+            Ok(result_set
+                .into_iter()
+                .last()
+                .ok_or(Error::Expected("At least one statement".into(), sql))?)
+        });
+        self.rollback_on_error(result)
+    }
 
-                let plan = plan_builder.build_initial_plan(statement, self.active_txn)?;
-                let plan = optimize_logical_plan(plan);
+    /// Parses a statement with `$1`, `$2`... or `?` placeholders to execute
+    /// it many times with different parameters
+    pub fn prepare(&self, sql: impl Into<String>) -> Result<PreparedStatement> {
+        let (statement, params) = parse_prepared(sql)?;
 
-                let result = plan.execute(self)?;
+        Ok(PreparedStatement::new(statement, params))
+    }
 
-                if implicit {
-                    self.commit_txn()?;
-                }
+    /// Executes a prepared statement, the parameters are bound as values and
+    /// never parsed as SQL
+    pub fn execute(&mut self, prepared: &PreparedStatement, params: &[Value]) -> Result<ResultSet> {
+        let result = self.execute_prepared(prepared, params);
+        self.rollback_on_error(result)
+    }
 
-                Ok(result)
-            })
-            .collect::<Result<Vec<ResultSet>>>();
+    fn rollback_on_error(&mut self, result: Result<ResultSet>) -> Result<ResultSet> {
+        if result.is_err() && self.active_txn.is_some() {
+            self.rollback_txn()?;
+        }
 
-        match results {
-            Ok(result_set) => Ok(result_set
-                .into_iter()
-                .last()
-                .ok_or(Error::Expected("At least one statement".into(), sql))?),
+        result
+    }
 
-            Err(e) => {
-                if self.active_txn.is_some() {
-                    self.rollback_txn()?;
+    fn execute_statement(&mut self, statement: Statement) -> Result<ResultSet> {
+        let plan_builder = LogicalPlanBuilder::new(self.catalog.clone());
+
+        match statement {
+            Statement::Prepare {
+                name,
+                data_types,
+                statement,
+            } => {
+                ensure!(
+                    !self.prepared.contains_key(&name.value),
+                    Error::Expected(
+                        "a name not taken by another prepared statement".into(),
+                        name.value
+                    )
+                );
+
+                // numbers the `?` placeholders
+                let mut prepared = self.prepare(statement.to_string())?;
+                if !data_types.is_empty() {
+                    ensure!(
+                        data_types.len() >= prepared.params,
+                        Error::Expected(
+                            format!("a type for each of the {} parameters", prepared.params),
+                            format!("{} types", data_types.len())
+                        )
+                    );
+                    prepared.params = data_types.len();
+                    prepared.types = data_types
+                        .iter()
+                        .map(|ty| Types::from_sql(&ty.to_string()))
+                        .collect::<Result<_>>()?;
+                    *prepared.plan.lock() =
+                        prepared.build_plan(&self.catalog, self.active_txn, prepared.types.clone());
                 }
 
-                Err(e)
+                self.prepared.insert(name.value, prepared);
+                Ok(ResultSet::default())
             }
+            Statement::Execute {
+                name, parameters, ..
+            } => {
+                let prepared = self.prepared_statement(&name.value)?.clone();
+                let params = plan_builder.build_params(&parameters)?;
+                self.execute_prepared(&prepared, &params)
+            }
+            Statement::Deallocate { name, .. } => {
+                self.prepared_statement(&name.value)?;
+                self.prepared.remove(&name.value);
+                Ok(ResultSet::default())
+            }
+            statement => self.execute_plan(Self::mutates(&statement), |ctx| {
+                let plan = plan_builder.build_initial_plan(statement, ctx.active_txn)?;
+                Ok(optimize_logical_plan(plan))
+            }),
+        }
+    }
+
+    fn execute_prepared(
+        &mut self,
+        prepared: &PreparedStatement,
+        params: &[Value],
+    ) -> Result<ResultSet> {
+        let params = prepared.coerce_params(params)?;
+        self.execute_plan(Self::mutates(&prepared.statement), |ctx| {
+            prepared.plan(&ctx.catalog, ctx.active_txn, &params)
+        })
+    }
+
+    /// Executes the plan, in a transaction of its own if it writes and
+    /// none is active
+    fn execute_plan(
+        &mut self,
+        mutates: bool,
+        plan: impl FnOnce(&Self) -> Result<LogicalPlan>,
+    ) -> Result<ResultSet> {
+        let implicit = self.active_txn.is_none() && mutates;
+        if implicit {
+            self.start_txn()?;
+        }
+
+        let plan = create_physical_plan(plan(self)?);
+        let result = plan.execute(self)?;
+
+        if implicit {
+            self.commit_txn()?;
         }
+
+        Ok(result)
+    }
+
+    fn prepared_statement(&self, name: &str) -> Result<&PreparedStatement> {
+        self.prepared.get(name).ok_or(
+            Error::Expected("the name of a prepared statement".into(), name.to_string()).into(),
+        )
     }
 
     fn mutates(statement: &Statement) -> bool {
@@ -129,7 +384,9 @@ impl Context {
             Statement::Insert(_)
             | Statement::Update { .. }
             | Statement::Delete(_)
+            | Statement::Merge { .. }
             | Statement::CreateTable(_)
+            | Statement::AlterTable { .. }
             | Statement::Drop { .. }
             | Statement::Truncate { .. } => true,
             Statement::Explain { statement, .. } => Self::mutates(statement),
@@ -363,6 +620,14 @@ pub mod tests {
 
         let expected_plan = r#"Logical Plan:
 -- Projection: [#a,#b]
+---- IndexScan: test Scan( a range [1,1] ) [#a,#b]
+
+Optimized Logical Plan:
+-- Projection: [#a,#b]
+---- IndexScan: test Scan( a range [1,1] ) [#a,#b]
+
+Physical Plan:
+-- Projection: [#a,#b]
 ---- IndexScan: test Scan( a range [1,1] ) [#a,#b]"#;
 
         let result = ctx.execute_sql("EXPLAIN ANALYZE SELECT * FROM test PREWHERE a = 1;")?;
@@ -371,6 +636,14 @@ pub mod tests {
 
         let expected_plan = r#"Logical Plan:
 -- Projection: [#a,#b]
+---- IndexScan: test Scan( a range (,3) ) [#a,#b]
+
+Optimized Logical Plan:
+-- Projection: [#a,#b]
+---- IndexScan: test Scan( a range (,3) ) [#a,#b]
+
+Physical Plan:
+-- Projection: [#a,#b]
 ---- IndexScan: test Scan( a range (,3) ) [#a,#b]"#;
 
         let result = ctx.execute_sql("EXPLAIN ANALYZE SELECT * FROM test PREWHERE a < 3;")?;
@@ -380,6 +653,14 @@ pub mod tests {
 
         let expected_plan = r#"Logical Plan:
 -- Projection: [#a,#b]
+---- IndexScan: test Scan( a range (1,) ) [#a,#b]
+
+Optimized Logical Plan:
+-- Projection: [#a,#b]
+---- IndexScan: test Scan( a range (1,) ) [#a,#b]
+
+Physical Plan:
+-- Projection: [#a,#b]
 ---- IndexScan: test Scan( a range (1,) ) [#a,#b]"#;
 
         let result = ctx.execute_sql("EXPLAIN ANALYZE SELECT * FROM test PREWHERE a > 1;")?;
@@ -390,6 +671,16 @@ pub mod tests {
         let expected_plan = r#"Logical Plan:
 -- Projection: [#a,#b]
 ---- Filter: #a <> 3
+------ IndexScan: test Scan( a range [1,) ) [#a,#b]
+
+Optimized Logical Plan:
+-- Projection: [#a,#b]
+---- Filter: #a <> 3
+------ IndexScan: test Scan( a range [1,) ) [#a,#b]
+
+Physical Plan:
+-- Projection: [#a,#b]
+---- Filter: #a <> 3
 ------ IndexScan: test Scan( a range [1,) ) [#a,#b]"#;
 
         let result =
@@ -400,6 +691,14 @@ pub mod tests {
 
         let expected_plan = r#"Logical Plan:
 -- Projection: [#a,#b]
+---- IndexScan: test Scan( a range [1,3] ) [#a,#b]
+
+Optimized Logical Plan:
+-- Projection: [#a,#b]
+---- IndexScan: test Scan( a range [1,3] ) [#a,#b]
+
+Physical Plan:
+-- Projection: [#a,#b]
 ---- IndexScan: test Scan( a range [1,3] ) [#a,#b]"#;
 
         let result =
@@ -421,4 +720,160 @@ pub mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_decorrelate_exists() -> Result<()> {
+        let mut ctx = test_context();
+        ctx.execute_sql("CREATE TABLE t1 (a int, b int);")?;
+        ctx.execute_sql("INSERT INTO t1 VALUES (1, 2), (3, 4);")?;
+
+        ctx.execute_sql("CREATE TABLE t2 (c int, d int);")?;
+        ctx.execute_sql("INSERT INTO t2 VALUES (1, 5), (1, 6), (2, 7);")?;
+
+        let expected_plan = r#"Logical Plan:
+-- Projection: [#a]
+---- Semi Join: (#__subquery_1.c = #a)
+------ Scan: t1 [#a,#b]
+------ Projection: [#c AS __subquery_1.c]
+-------- Filter: #d > 5
+---------- Scan: t2 [#c,#d]
+
+Optimized Logical Plan:
+-- Projection: [#a]
+---- Semi Join: (#__subquery_1.c = #a)
+------ Scan: t1 [#a]
+------ Projection: [#c AS __subquery_1.c]
+-------- Filter: #d > 5
+---------- Scan: t2 [#c,#d]
+
+Physical Plan:
+-- Projection: [#a]
+---- Semi HashJoin: [#a = #__subquery_1.c]
+------ SeqScan: t1 [#a]
+------ Projection: [#c AS __subquery_1.c]
+-------- Filter: #d > 5
+---------- SeqScan: t2 [#c,#d]"#;
+
+        let result = ctx.execute_sql(
+            "EXPLAIN ANALYZE SELECT a FROM t1 WHERE EXISTS (SELECT * FROM t2 WHERE c = a AND d > 5);",
+        )?;
+        assert_plan(&result, expected_plan);
+        assert_eq!(result.rows(), vec![vec![lit!(Int, "1")?]]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recursive_cte() -> Result<()> {
+        let mut ctx = test_context();
+        ctx.execute_sql("CREATE TABLE t (id int, parent int);")?;
+        ctx.execute_sql("INSERT INTO t VALUES (1, 0), (2, 1), (3, 2), (4, 1);")?;
+
+        let result = ctx.execute_sql(
+            "EXPLAIN ANALYZE WITH RECURSIVE r AS (SELECT id FROM t WHERE id = 2 \
+            UNION ALL SELECT t.id FROM t INNER JOIN r ON t.parent = r.id) SELECT id FROM r;",
+        )?;
+
+        let expected_plan = r#"Logical Plan:
+-- Projection: [#id]
+---- Recursive Query All: r
+------ Projection: [#id]
+-------- Filter: #id = 2
+---------- Scan: t [#id,#parent]
+------ Projection: [#t.id]
+-------- Join: (#t.parent = #r.id)
+---------- Scan: t [#id,#parent]
+---------- Work Table Scan: r [#id]
+
+Optimized Logical Plan:
+-- Projection: [#id]
+---- Recursive Query All: r
+------ Projection: [#id]
+-------- Filter: #id = 2
+---------- Scan: t [#id]
+------ Projection: [#t.id]
+-------- Join: (#t.parent = #r.id)
+---------- Scan: t [#id,#parent]
+---------- Work Table Scan: r [#id]
+
+Physical Plan:
+-- Projection: [#id]
+---- Recursive Query All: r
+------ Projection: [#id]
+-------- Filter: #id = 2
+---------- SeqScan: t [#id]
+------ Projection: [#t.id]
+-------- HashJoin: [#t.parent = #r.id]
+---------- SeqScan: t [#id,#parent]
+---------- Work Table Scan: r [#id]"#;
+
+        assert_plan(&result, expected_plan);
+        assert_eq!(
+            result.rows(),
+            vec![vec![lit!(Int, "2")?], vec![lit!(Int, "3")?]]
+        );
+
+        // the work table is gone once the query is done
+        assert!(ctx.get_work_table("r").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prepared_statements() -> Result<()> {
+        let mut ctx = test_context();
+        ctx.execute_sql("CREATE TABLE t (a int UNIQUE NOT NULL, b text);")?;
+
+        let insert = ctx.prepare("INSERT INTO t VALUES (?, ?);")?;
+        assert_eq!(insert.params(), 2);
+        for (a, b) in [("1", "one"), ("-2", "two"), ("3", "it's")] {
+            ctx.execute(&insert, &[lit!(Int, a)?, lit!(Str, b)?])?;
+        }
+
+        // bound values are never parsed as SQL
+        let select = ctx.prepare("SELECT a FROM t WHERE b = $1;")?;
+        let result = ctx.execute(&select, &[lit!(Str, "one' OR 'x' = 'x")?])?;
+        assert!(result.rows().is_empty());
+        let result = ctx.execute(&select, &[lit!(Str, "it's")?])?;
+        assert_eq!(result.rows(), vec![vec![lit!(Int, "3")?]]);
+
+        // the optimizer sees the bound values
+        let explain = ctx.prepare("EXPLAIN SELECT b FROM t WHERE a = $1;")?;
+        let result = ctx.execute(&explain, &[lit!(Int, "3")?])?;
+        assert!(result.get_info().contains("IndexScan"));
+
+        let select = ctx.prepare("SELECT b FROM t WHERE a = $1;")?;
+        let result = ctx.execute(&select, &[lit!(Int, "-2")?])?;
+        assert_eq!(result.rows(), vec![vec![lit!(Str, "two")?]]);
+
+        assert!(ctx.execute(&select, &[]).is_err());
+        assert!(ctx.execute_sql("SELECT b FROM t WHERE a = $1;").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prepared_plan_reuse() -> Result<()> {
+        let mut ctx = test_context();
+        ctx.execute_sql("CREATE TABLE t (a int UNIQUE NOT NULL, b text);")?;
+        ctx.execute_sql("INSERT INTO t VALUES (1, 'one'), (2, 'two');")?;
+
+        let select = ctx.prepare("SELECT b FROM t WHERE a = $1;")?;
+        ctx.execute(&select, &[lit!(Int, "1")?])?;
+        let planned = select.plan.lock().as_ref().unwrap().plan.print();
+        assert!(planned.contains("$1") && !planned.contains("IndexScan"));
+
+        // bound and optimized again, the parameter picks the index
+        let plan = select.plan(&ctx.catalog, None, &[lit!(Int, "2")?])?;
+        assert!(plan.print().contains("IndexScan"));
+        let result = ctx.execute(&select, &[lit!(Int, "2")?])?;
+        assert_eq!(result.rows(), vec![vec![lit!(Str, "two")?]]);
+        assert_eq!(select.plan.lock().as_ref().unwrap().plan.print(), planned);
+
+        ctx.execute_sql("ALTER TABLE t RENAME COLUMN b TO c;")?;
+        let result = ctx.execute(&select, &[lit!(Int, "2")?]);
+        assert!(result.is_err());
+
+        Ok(())
+    }
 }
@@ -3,7 +3,7 @@ use crate::pages::traits::Serialize;
 use crate::pages::{PageId, INVALID_PAGE};
 use crate::txn_manager::TxnId;
 use anyhow::{anyhow, bail, Context, Result};
-use std::fs::{create_dir_all, read_dir, remove_dir_all, rename, File, OpenOptions};
+use std::fs::{create_dir_all, read_dir, remove_dir_all, remove_file, rename, File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 
@@ -241,6 +241,12 @@ impl DiskManager {
         Ok(page)
     }
 
+    pub fn delete_file(&self, page_id: PageId) -> Result<()> {
+        let path = Path::join(&self.pages_dir(), Path::new(&page_id.to_string()));
+        remove_file(path)?;
+        Ok(())
+    }
+
     pub fn start_txn(&self, txn_id: TxnId) -> Result<()> {
         let txn_cache = Path::join(&self.txn_dir(), Path::new(&txn_id.to_string()));
 
@@ -75,6 +75,9 @@ impl Engine {
             Record::Truncate(name) => {
                 catalog.truncate_table(&name, txn)?;
             }
+            Record::AlterTable(name, operation) => {
+                catalog.alter_table(&name, &operation, txn)?;
+            }
             Record::Operation(RowOperation::Insert(name, values)) => {
                 catalog
                     .get_table_mut(&name, Some(txn))
@@ -172,4 +175,38 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn altered_tables_are_replayed_and_checkpointed() -> Result<()> {
+        let dir = test_path();
+
+        let first = Engine::with_pool_size(&dir, 50);
+        let mut ctx = first.context();
+        ctx.execute_sql("CREATE TABLE t (a int, c int)")?;
+        ctx.execute_sql("INSERT INTO t VALUES (1, 0)")?;
+        ctx.execute_sql("ALTER TABLE t ADD COLUMN b int DEFAULT 2, DROP COLUMN c")?;
+        ctx.execute_sql("ALTER TABLE t RENAME TO u")?;
+        ctx.execute_sql("INSERT INTO u VALUES (3, 4)")?;
+
+        let rows = |engine: &Engine| -> Result<Vec<String>> {
+            Ok(engine
+                .context()
+                .execute_sql("SELECT a, b FROM u ORDER BY a")?
+                .rows()
+                .iter()
+                .map(|row| format!("{} {}", row[0], row[1]))
+                .collect())
+        };
+
+        // replaying the log applies the changes in order, once
+        let second = Engine::with_pool_size(&dir, 50);
+        assert_eq!(rows(&second)?, ["1 2", "3 4"]);
+
+        // the replay was checkpointed, the schema is read from the catalog
+        let third = Engine::with_pool_size(&dir, 50);
+        assert_eq!(rows(&third)?, ["1 2", "3 4"]);
+        assert!(third.context().execute_sql("SELECT * FROM t").is_err());
+
+        Ok(())
+    }
 }
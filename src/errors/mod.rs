@@ -10,6 +10,7 @@ pub enum Error {
     TupleNotFound,
     TableNotFound(String),
     ColumnNotFound(String),
+    ColumnExists(String),
     ColumnsNotFound(Vec<String>),
     Unimplemented(String),
     Unsupported(String),
@@ -20,8 +21,11 @@ pub enum Error {
     TransactionActive,
     NoActiveTransaction,
     DivisionByZero,
+    /// the operation whose result doesn't fit its type
+    OutOfRange(String),
     DuplicateValue(String, String),
     NullNotAllowed(String),
+    UngroupedColumn(String),
     /// Value, Type, Reason
     ParseFailed(String, Types, String),
 }
@@ -48,11 +52,17 @@ impl std::fmt::Display for Error {
                 )
             }
             Error::DivisionByZero => write!(f, "Division by zero."),
+            Error::OutOfRange(operation) => write!(f, "Result of {operation} out of range."),
             Error::DuplicateValue(value, column) => {
                 write!(f, "Duplicate value {value} in column {column}.")
             }
             Error::NullNotAllowed(col) => write!(f, "NULL is not allowed in column {col}."),
+            Error::UngroupedColumn(col) => write!(
+                f,
+                "Column {col} must appear in the GROUP BY clause or be used in an aggregate function."
+            ),
             Error::ColumnNotFound(col) => write!(f, "Column {col} not found."),
+            Error::ColumnExists(col) => write!(f, "Column {col} already exists."),
             Error::TupleTooBig(expecetd, actual) => write!(
                 f,
                 "Tuple is too big. Expected {expecetd} bytes, but got {actual} bytes."
@@ -0,0 +1,114 @@
+use anyhow::{ensure, Result};
+
+use crate::errors::Error;
+use crate::sql::logical_plan::expr::AggregateFunction;
+use crate::types::{Float, Int, UInt, Value};
+
+/// Running state of a single aggregate function over a single group.
+/// NULLs are skipped by every function, a group of only NULLs
+/// yields NULL (or 0 for COUNT)
+#[derive(Clone)]
+pub enum Accumulator {
+    Count(u32),
+    Sum(Option<WideSum>),
+    Avg { sum: f64, count: u32 },
+    Min(Option<Value>),
+    Max(Option<Value>),
+}
+
+impl Accumulator {
+    pub fn new(func: &AggregateFunction) -> Self {
+        match func {
+            AggregateFunction::Count => Accumulator::Count(0),
+            AggregateFunction::Sum => Accumulator::Sum(None),
+            AggregateFunction::Avg => Accumulator::Avg { sum: 0.0, count: 0 },
+            AggregateFunction::Min => Accumulator::Min(None),
+            AggregateFunction::Max => Accumulator::Max(None),
+        }
+    }
+
+    pub fn update(&mut self, value: &Value) -> Result<()> {
+        if value.is_null() {
+            return Ok(());
+        }
+
+        match self {
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::Sum(sum) => {
+                *sum = Some(match sum.take() {
+                    Some(sum) => sum.add(value),
+                    None => WideSum::new(value),
+                })
+            }
+            Accumulator::Avg { sum, count } => {
+                *sum += value.as_f64();
+                *count += 1;
+            }
+            Accumulator::Min(min) => {
+                if min.as_ref().is_none_or(|min| value < min) {
+                    *min = Some(value.clone());
+                }
+            }
+            Accumulator::Max(max) => {
+                if max.as_ref().is_none_or(|max| value > max) {
+                    *max = Some(value.clone());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fails if a SUM doesn't fit the type of the column it sums
+    pub fn finish(self) -> Result<Value> {
+        Ok(match self {
+            Accumulator::Count(count) => Value::UInt(UInt(count)),
+            Accumulator::Avg { count: 0, .. } => Value::Null,
+            Accumulator::Avg { sum, count } => Value::Float(Float((sum / count as f64) as f32)),
+            Accumulator::Sum(None) => Value::Null,
+            Accumulator::Sum(Some(sum)) => sum.finish()?,
+            Accumulator::Min(v) | Accumulator::Max(v) => v.unwrap_or(Value::Null),
+        })
+    }
+}
+
+/// A SUM accumulated in a wider type than the column's, so only a total
+/// outside of the column type's range is an error, not a partial sum
+#[derive(Clone)]
+pub enum WideSum {
+    Int(i64),
+    UInt(i64),
+    Float(f64),
+}
+
+impl WideSum {
+    fn new(value: &Value) -> Self {
+        match value {
+            Value::Int(Int(v)) => WideSum::Int(*v as i64),
+            Value::UInt(UInt(v)) => WideSum::UInt(*v as i64),
+            v => WideSum::Float(v.as_f64()),
+        }
+    }
+
+    fn add(self, value: &Value) -> Self {
+        match self {
+            WideSum::Int(sum) => WideSum::Int(sum + value.as_f64() as i64),
+            WideSum::UInt(sum) => WideSum::UInt(sum + value.as_f64() as i64),
+            WideSum::Float(sum) => WideSum::Float(sum + value.as_f64()),
+        }
+    }
+
+    fn finish(self) -> Result<Value> {
+        let out_of_range = || Error::OutOfRange("SUM".into());
+        Ok(match self {
+            WideSum::Int(sum) => Value::Int(Int(i32::try_from(sum).map_err(|_| out_of_range())?)),
+            WideSum::UInt(sum) => {
+                Value::UInt(UInt(u32::try_from(sum).map_err(|_| out_of_range())?))
+            }
+            WideSum::Float(sum) => {
+                ensure!(sum.is_nan() || sum.abs() <= f32::MAX as f64, out_of_range());
+                Value::Float(Float(sum as f32))
+            }
+        })
+    }
+}
@@ -0,0 +1,1041 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use anyhow::{bail, Result};
+use bincode::serialized_size;
+use serde::{Deserialize, Serialize};
+
+use super::operator::{collect, Map, Once, Operator, BATCH_SIZE};
+use super::result_set::ResultSet;
+use super::scan::{get_table, ScanColumns};
+use super::spill::Spill;
+use crate::catalog::ArcCatalog;
+use crate::context::Context;
+use crate::errors::Error;
+use crate::lit;
+use crate::pages::indexes::b_plus_tree::Key;
+use crate::sql::logical_plan::expr::LogicalExpr;
+use crate::sql::logical_plan::optimizer::to_key;
+use crate::sql::logical_plan::plan::JoinType;
+use crate::sql::physical_plan::plan::{
+    HashJoin, IndexNestedLoopJoin, NestedLoopJoin, PhysicalPlan,
+};
+use crate::table::Table;
+use crate::tuple::schema::{Field, Schema};
+use crate::tuple::{Tuple, TupleId};
+use crate::txn_manager::TxnId;
+use crate::types::{Types, Value, ValueFactory};
+
+/// Lays the rows of one side of a join out as `fields`. Scans prepend tuple
+/// ids and an empty projection carries no columns, so only the trailing
+/// columns are kept
+fn join_columns(input: ResultSet, fields: &[Field]) -> ResultSet {
+    let n = input.fields().len();
+
+    if n < fields.len() {
+        return ResultSet::from_rows(fields.to_vec(), vec![]);
+    }
+
+    let mut input = input.select((n - fields.len()..n).collect());
+    input.schema = Schema::new(fields.to_vec());
+    input
+}
+
+/// Opens one side of a join with its columns named as in the join
+fn join_input<'a>(
+    plan: &'a PhysicalPlan,
+    fields: Vec<Field>,
+    ctx: &mut Context,
+) -> Result<Box<dyn Operator + 'a>> {
+    // a query without FROM still has a row
+    if matches!(plan, PhysicalPlan::Empty) {
+        return Ok(Box::new(Once::new(|_| Ok(ResultSet::with_capacity(1)))));
+    }
+
+    let input = plan.open(ctx)?;
+    Ok(Box::new(Map::new(input, move |batch| {
+        Ok(join_columns(batch, &fields))
+    })))
+}
+
+/// The columns of the two sides of a join, as named in the join
+fn join_fields(
+    left: &PhysicalPlan,
+    right: &PhysicalPlan,
+    join_type: JoinType,
+    schema: &Schema,
+) -> (Vec<Field>, Vec<Field>) {
+    let (left_fields, right_fields) = schema.fields.split_at(left.schema().fields.len());
+
+    // the right side's columns are not part of the output
+    let right_fields = match join_type.drops_right() {
+        true => right.schema().fields,
+        false => right_fields.to_vec(),
+    };
+
+    (left_fields.to_vec(), right_fields)
+}
+
+/// The output rows of left rows joined with the right rows each of them matched
+fn join_matches(
+    join_type: JoinType,
+    left_rows: Vec<Vec<Value>>,
+    matches: Vec<Vec<usize>>,
+    right_rows: &[Vec<Value>],
+    right_width: usize,
+) -> Result<Vec<Vec<Value>>> {
+    let mut output_rows: Vec<Vec<Value>> = vec![];
+
+    for (left_row, matches) in left_rows.into_iter().zip(matches) {
+        match join_type {
+            JoinType::Semi if !matches.is_empty() => output_rows.push(left_row),
+            JoinType::Anti if matches.is_empty() => output_rows.push(left_row),
+            JoinType::Semi | JoinType::Anti => {}
+            JoinType::Mark => {
+                let mark = lit!(Bool, (!matches.is_empty()).to_string())?;
+                output_rows.push(left_row.into_iter().chain([mark]).collect());
+            }
+            JoinType::Single if matches.len() > 1 => bail!(too_many_rows(matches.len())),
+            _ => {
+                for &i in matches.iter() {
+                    let row = left_row.iter().chain(&right_rows[i]).cloned().collect();
+                    output_rows.push(row);
+                }
+
+                if matches.is_empty() && join_type.keeps_left() {
+                    let nulls = vec![Value::Null; right_width];
+                    output_rows.push(left_row.into_iter().chain(nulls).collect());
+                }
+            }
+        }
+    }
+
+    Ok(output_rows)
+}
+
+fn too_many_rows(rows: usize) -> Error {
+    Error::Expected(
+        "a subquery used as a value to return at most one row".into(),
+        format!("{rows} rows"),
+    )
+}
+
+/// The right rows no left row matched, padded with NULLs
+fn unmatched_right(
+    right_rows: Vec<Vec<Value>>,
+    right_matches: &[usize],
+    left_width: usize,
+) -> Vec<Vec<Value>> {
+    right_rows
+        .into_iter()
+        .zip(right_matches)
+        .filter(|(_, &matches)| matches == 0)
+        .map(|(right_row, _)| {
+            let nulls = vec![Value::Null; left_width];
+            nulls.into_iter().chain(right_row).collect()
+        })
+        .collect()
+}
+
+/// Streams the left side of a join and compares each left row with every
+/// row of the right side, which is kept in memory. The right rows no left
+/// row matched come last
+struct NestedLoopJoinOperator<'a> {
+    join: &'a NestedLoopJoin,
+    left: Box<dyn Operator + 'a>,
+    right: ResultSet,
+    right_rows: Vec<Vec<Value>>,
+    right_matches: Vec<usize>,
+    done: bool,
+}
+
+impl NestedLoopJoinOperator<'_> {
+    /// The right rows each row of a left batch matches, and whether the
+    /// condition is unknown for some right row
+    fn matches(&self, left: &ResultSet) -> Result<(Vec<Vec<usize>>, Vec<bool>)> {
+        left.rows()
+            .into_iter()
+            .map(|left_row| {
+                let ll = ResultSet::from_tuple(left.fields().clone(), left_row, self.right.len());
+                let mask = self.join.on.evaluate(&ll.concat(self.right.clone()))?;
+                let matches = mask
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, m)| m.is_truthy().then_some(i))
+                    .collect();
+                Ok((matches, mask.iter().any(Value::is_null)))
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(|rows| rows.into_iter().unzip())
+    }
+}
+
+/// The output rows of a null-aware join, a left row without a match is
+/// unknown rather than unmatched when its condition is unknown for some
+/// right row
+fn null_aware_matches(
+    join_type: JoinType,
+    left_rows: Vec<Vec<Value>>,
+    matches: Vec<Vec<usize>>,
+    unknown: Vec<bool>,
+) -> Result<Vec<Vec<Value>>> {
+    let mut output_rows = vec![];
+
+    for ((left_row, matches), unknown) in left_rows.into_iter().zip(matches).zip(unknown) {
+        let matched = !matches.is_empty();
+        match join_type {
+            JoinType::NullAwareAnti if !matched && !unknown => output_rows.push(left_row),
+            JoinType::NullAwareAnti => {}
+            _ => {
+                let mark = match (matched, unknown) {
+                    (false, true) => Value::Null,
+                    _ => lit!(Bool, matched.to_string())?,
+                };
+                output_rows.push(left_row.into_iter().chain([mark]).collect());
+            }
+        }
+    }
+
+    Ok(output_rows)
+}
+
+impl Operator for NestedLoopJoinOperator<'_> {
+    fn next(&mut self, ctx: &mut Context) -> Result<Option<ResultSet>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let join_type = self.join.join_type;
+        let fields = self.join.schema.fields.clone();
+
+        if let Some(left) = self.left.next(ctx)? {
+            let (matches, unknown) = self.matches(&left)?;
+            if join_type.is_null_aware() {
+                let rows = null_aware_matches(join_type, left.rows(), matches, unknown)?;
+                return Ok(Some(ResultSet::from_rows(fields, rows)));
+            }
+
+            for &i in matches.iter().flatten() {
+                self.right_matches[i] += 1;
+            }
+
+            let right_width = self.right.fields().len();
+            let rows = join_matches(
+                join_type,
+                left.rows(),
+                matches,
+                &self.right_rows,
+                right_width,
+            )?;
+            return Ok(Some(ResultSet::from_rows(fields, rows)));
+        }
+
+        self.done = true;
+        if !join_type.keeps_right() {
+            return Ok(None);
+        }
+
+        let left_width = fields.len() - self.right.fields().len();
+        let right_rows = std::mem::take(&mut self.right_rows);
+        let rows = unmatched_right(right_rows, &self.right_matches, left_width);
+        Ok(Some(ResultSet::from_rows(fields, rows)))
+    }
+}
+
+pub fn nested_loop_join<'a>(
+    join: &'a NestedLoopJoin,
+    ctx: &mut Context,
+) -> Result<Box<dyn Operator + 'a>> {
+    let (left_fields, right_fields) =
+        join_fields(&join.left, &join.right, join.join_type, &join.schema);
+
+    let left = join_input(&join.left, left_fields, ctx)?;
+    let right = collect(join_input(&join.right, right_fields, ctx)?, ctx)?;
+
+    Ok(Box::new(NestedLoopJoinOperator {
+        join,
+        left,
+        right_rows: right.rows(),
+        right_matches: vec![0; right.len()],
+        right,
+        done: false,
+    }))
+}
+
+/// Streams the outer side of a join and looks up the row each outer row
+/// matches through the index of the other side's table. The looked up rows
+/// are never kept unmatched, so nothing is left once the outer side ends
+struct IndexNestedLoopJoinOperator<'a> {
+    join: &'a IndexNestedLoopJoin,
+    outer: Box<dyn Operator + 'a>,
+    catalog: ArcCatalog,
+    txn_id: Option<TxnId>,
+    columns: ScanColumns,
+    // the unique column as stored in the table, and its position in the rows
+    key_field: Field,
+    key_column: usize,
+    indexed_fields: Vec<Field>,
+    pair_fields: Vec<Field>,
+}
+
+impl IndexNestedLoopJoinOperator<'_> {
+    /// The indexed row whose key is `key`, keys are truncated or wrapped
+    /// values so the row's value may still differ
+    fn lookup(&self, table: &Table, key: &Value) -> Option<(TupleId, Tuple)> {
+        let key = match (&self.key_field.ty, key) {
+            // a point lookup doesn't need keys ordered like the values
+            (Types::Int, Value::Int(v)) => v.0 as Key,
+            _ => to_key(&self.key_field, key)?.0,
+        };
+
+        let tuple_id = table.get_index().as_ref()?.search(self.txn_id, key)?;
+        Some((tuple_id, table.get_tuple(tuple_id)?))
+    }
+
+    /// The indexed rows, and the one each outer row matches
+    fn matches(&self, outer: &ResultSet) -> Result<(ResultSet, Vec<Option<usize>>)> {
+        let (_, keys) = self.join.outer_key.evaluate(outer)?;
+
+        let catalog = self.catalog.read();
+        let table = get_table(&catalog, &self.join.table_name, self.txn_id)?;
+
+        let mut tuples = vec![];
+        let mut matches = vec![];
+        for key in keys.iter() {
+            matches.push(self.lookup(table, key).map(|tuple| {
+                tuples.push(tuple);
+                tuples.len() - 1
+            }));
+        }
+
+        let indexed = join_columns(self.columns.batch(table, tuples)?, &self.indexed_fields);
+        let indexed_keys = &indexed.cols()[self.key_column];
+
+        for (key, m) in keys.iter().zip(matches.iter_mut()) {
+            if let Some(i) = *m {
+                if !indexed_keys[i].equ(key)? {
+                    *m = None;
+                }
+            }
+        }
+
+        Ok((indexed, matches))
+    }
+
+    /// The rows of the pairs, in the join's column order
+    fn pair(&self, outer_row: &[Value], indexed_row: &[Value]) -> Vec<Value> {
+        let (left, right) = match self.join.index_left {
+            true => (indexed_row, outer_row),
+            false => (outer_row, indexed_row),
+        };
+        left.iter().chain(right).cloned().collect()
+    }
+}
+
+impl Operator for IndexNestedLoopJoinOperator<'_> {
+    fn next(&mut self, ctx: &mut Context) -> Result<Option<ResultSet>> {
+        let Some(outer) = self.outer.next(ctx)? else {
+            return Ok(None);
+        };
+
+        let (indexed, mut matches) = self.matches(&outer)?;
+        let (outer_rows, indexed_rows) = (outer.rows(), indexed.rows());
+
+        if let Some(filter) = &self.join.filter {
+            let pairs: Vec<(usize, usize)> = matches
+                .iter()
+                .enumerate()
+                .filter_map(|(o, m)| m.map(|i| (o, i)))
+                .collect();
+
+            let rows = pairs
+                .iter()
+                .map(|&(o, i)| self.pair(&outer_rows[o], &indexed_rows[i]))
+                .collect();
+            let mask = filter.evaluate(&ResultSet::from_rows(self.pair_fields.clone(), rows))?;
+
+            for ((o, _), m) in pairs.into_iter().zip(mask) {
+                if !m.is_truthy() {
+                    matches[o] = None;
+                }
+            }
+        }
+
+        let join_type = self.join.join_type;
+        let fields = self.join.schema.fields.clone();
+
+        if !self.join.index_left {
+            let matches = matches.into_iter().map(Vec::from_iter).collect();
+            let width = self.indexed_fields.len();
+            let rows = join_matches(join_type, outer_rows, matches, &indexed_rows, width)?;
+            return Ok(Some(ResultSet::from_rows(fields, rows)));
+        }
+
+        let mut rows = vec![];
+        for (outer_row, m) in outer_rows.iter().zip(matches) {
+            match m {
+                Some(i) => rows.push(self.pair(outer_row, &indexed_rows[i])),
+                None if join_type.keeps_right() => {
+                    rows.push(self.pair(outer_row, &vec![Value::Null; self.indexed_fields.len()]))
+                }
+                None => {}
+            }
+        }
+
+        Ok(Some(ResultSet::from_rows(fields, rows)))
+    }
+}
+
+pub fn index_nested_loop_join<'a>(
+    join: &'a IndexNestedLoopJoin,
+    ctx: &mut Context,
+) -> Result<Box<dyn Operator + 'a>> {
+    let fields = &join.schema.fields;
+    let indexed_width = join.table_schema.fields.len();
+    let outer_width = join.outer.schema().fields.len();
+
+    let (outer_fields, indexed_fields) = match (join.index_left, join.join_type) {
+        (true, _) => (
+            fields[indexed_width..].to_vec(),
+            fields[..indexed_width].to_vec(),
+        ),
+        // the indexed side's columns are not part of the output
+        (false, join_type) if join_type.drops_right() => (
+            fields[..outer_width].to_vec(),
+            join.table_schema.fields.clone(),
+        ),
+        (false, _) => (
+            fields[..outer_width].to_vec(),
+            fields[outer_width..].to_vec(),
+        ),
+    };
+
+    let pair_fields = match join.index_left {
+        true => [indexed_fields.clone(), outer_fields.clone()].concat(),
+        false => [outer_fields.clone(), indexed_fields.clone()].concat(),
+    };
+
+    let txn_id = ctx.get_active_txn();
+    let catalog = ctx.get_catalog();
+    let (columns, key_field) = {
+        let catalog = catalog.read();
+        let table = get_table(&catalog, &join.table_name, txn_id)?;
+        let schema = table.get_schema();
+        let key_field = schema.fields.iter().find(|f| f.name == join.column_name);
+        let key_field = key_field.unwrap().clone();
+        (ScanColumns::new(table, &join.table_schema), key_field)
+    };
+    let key_column = join
+        .table_schema
+        .fields
+        .iter()
+        .position(|f| f.name == join.column_name)
+        .unwrap();
+
+    Ok(Box::new(IndexNestedLoopJoinOperator {
+        join,
+        outer: join_input(&join.outer, outer_fields, ctx)?,
+        catalog,
+        txn_id,
+        columns,
+        key_field,
+        key_column,
+        indexed_fields,
+        pair_fields,
+    }))
+}
+
+/// The values of the keys for each row of the input
+fn key_rows(keys: &[LogicalExpr], input: &ResultSet) -> Result<Vec<Vec<Value>>> {
+    let cols = keys
+        .iter()
+        .map(|key| Ok(key.evaluate(input)?.1))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((0..input.len())
+        .map(|row| cols.iter().map(|col| col[row].clone()).collect())
+        .collect())
+}
+
+/// How many bytes of rows a hash join keeps in memory before it partitions
+/// both of its inputs to disk
+pub const HASH_JOIN_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+
+/// How many partitions each input is split into when neither fits in memory
+const PARTITIONS: usize = 8;
+
+/// How many times a partition is split again. Rows with equal keys always
+/// end up together, past this depth the build side is kept in memory anyway
+const MAX_DEPTH: usize = 3;
+
+/// A row along with its already evaluated join key
+#[derive(Serialize, Deserialize)]
+struct KeyedRow {
+    key: Vec<Value>,
+    row: Vec<Value>,
+}
+
+enum Source<'a> {
+    Input {
+        input: Box<dyn Operator + 'a>,
+        keys: &'a [LogicalExpr],
+    },
+    Partition(Spill<KeyedRow>),
+}
+
+/// One side of a hash join. The batches read ahead to pick the build side
+/// are kept in memory and returned first
+struct Side<'a> {
+    source: Source<'a>,
+    buffered: VecDeque<Vec<KeyedRow>>,
+    bytes: usize,
+    done: bool,
+}
+
+impl<'a> Side<'a> {
+    fn new(source: Source<'a>) -> Self {
+        Self {
+            source,
+            buffered: VecDeque::new(),
+            bytes: 0,
+            done: false,
+        }
+    }
+
+    fn read(&mut self, ctx: &mut Context) -> Result<Option<Vec<KeyedRow>>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let batch = match &mut self.source {
+            Source::Input { input, keys } => match input.next(ctx)? {
+                Some(batch) => {
+                    let keys = key_rows(keys, &batch)?;
+                    let rows = keys.into_iter().zip(batch.rows());
+                    Some(rows.map(|(key, row)| KeyedRow { key, row }).collect())
+                }
+                None => None,
+            },
+            Source::Partition(spill) => {
+                let mut rows = vec![];
+                while rows.len() < BATCH_SIZE {
+                    match spill.next()? {
+                        Some(row) => rows.push(row),
+                        None => break,
+                    }
+                }
+                (!rows.is_empty()).then_some(rows)
+            }
+        };
+
+        self.done = batch.is_none();
+        Ok(batch)
+    }
+
+    fn read_ahead(&mut self, ctx: &mut Context) -> Result<()> {
+        if let Some(batch) = self.read(ctx)? {
+            for row in batch.iter() {
+                self.bytes += serialized_size(row)? as usize;
+            }
+            self.buffered.push_back(batch);
+        }
+
+        Ok(())
+    }
+
+    fn next(&mut self, ctx: &mut Context) -> Result<Option<Vec<KeyedRow>>> {
+        match self.buffered.pop_front() {
+            Some(batch) => Ok(Some(batch)),
+            None => self.read(ctx),
+        }
+    }
+}
+
+/// The rows of the build side by key, with how many probe rows each matched
+struct HashTable {
+    rows: Vec<Vec<Value>>,
+    index: HashMap<Vec<Value>, Vec<usize>>,
+    matches: Vec<usize>,
+}
+
+impl HashTable {
+    fn build(mut side: Side, ctx: &mut Context) -> Result<Self> {
+        let mut rows = vec![];
+        let mut index: HashMap<Vec<Value>, Vec<usize>> = HashMap::new();
+
+        while let Some(batch) = side.next(ctx)? {
+            for KeyedRow { key, row } in batch {
+                // a NULL key is never equal to another one, the row can only
+                // be part of the output as an unmatched row
+                if !key.iter().any(Value::is_null) {
+                    index.entry(key).or_default().push(rows.len());
+                }
+                rows.push(row);
+            }
+        }
+
+        Ok(Self {
+            matches: vec![0; rows.len()],
+            rows,
+            index,
+        })
+    }
+}
+
+/// A hash table built on one side, probed with the batches of the other
+struct Probe<'a> {
+    table: HashTable,
+    build_left: bool,
+    probe: Side<'a>,
+}
+
+impl Probe<'_> {
+    /// The output rows of a probe batch
+    fn probe(&mut self, join: &HashJoinOperator, batch: Vec<KeyedRow>) -> Result<Vec<Vec<Value>>> {
+        let table = &mut self.table;
+        let mut pairs: Vec<(usize, usize)> = batch
+            .iter()
+            .enumerate()
+            .flat_map(|(p, row)| {
+                let rows = table.index.get(&row.key).into_iter().flatten();
+                rows.map(move |&b| (p, b))
+            })
+            .collect();
+
+        // the filter is evaluated once per batch, over every pair with equal keys
+        if let Some(filter) = &join.join.filter {
+            let rows = pairs
+                .iter()
+                .map(|&(p, b)| {
+                    let (left, right) = match self.build_left {
+                        true => (&table.rows[b], &batch[p].row),
+                        false => (&batch[p].row, &table.rows[b]),
+                    };
+                    left.iter().chain(right).cloned().collect()
+                })
+                .collect();
+
+            let mask = filter.evaluate(&ResultSet::from_rows(join.pair_fields(), rows))?;
+            pairs = pairs
+                .into_iter()
+                .zip(mask)
+                .filter_map(|(pair, m)| m.is_truthy().then_some(pair))
+                .collect();
+        }
+
+        for &(_, b) in pairs.iter() {
+            table.matches[b] += 1;
+        }
+
+        let join_type = join.join.join_type;
+
+        if !self.build_left {
+            let mut matches = vec![vec![]; batch.len()];
+            for (p, b) in pairs {
+                matches[p].push(b);
+            }
+
+            let left_rows = batch.into_iter().map(|r| r.row).collect();
+            let right_width = join.right_fields.len();
+            return join_matches(join_type, left_rows, matches, &table.rows, right_width);
+        }
+
+        // the left rows of semi, anti and mark joins are all known at the end
+        let mut output_rows = vec![];
+        let mut probe_matched = vec![false; batch.len()];
+
+        for (p, b) in pairs {
+            probe_matched[p] = true;
+            if !join_type.drops_right() {
+                let row = table.rows[b].iter().chain(&batch[p].row).cloned().collect();
+                output_rows.push(row);
+            }
+        }
+
+        if join_type.keeps_right() {
+            let right_rows = batch.into_iter().map(|r| r.row).collect();
+            let matches: Vec<usize> = probe_matched.into_iter().map(usize::from).collect();
+            output_rows.extend(unmatched_right(
+                right_rows,
+                &matches,
+                join.left_fields.len(),
+            ));
+        }
+
+        Ok(output_rows)
+    }
+
+    /// The output rows that depend on which build rows were matched
+    fn finish(self, join: &HashJoinOperator) -> Result<Vec<Vec<Value>>> {
+        let join_type = join.join.join_type;
+        let HashTable { rows, matches, .. } = self.table;
+
+        if !self.build_left {
+            return Ok(match join_type.keeps_right() {
+                true => unmatched_right(rows, &matches, join.left_fields.len()),
+                false => vec![],
+            });
+        }
+
+        let rows = rows.into_iter().zip(matches);
+        match join_type {
+            JoinType::Semi => Ok(rows.filter(|(_, m)| *m > 0).map(|(row, _)| row).collect()),
+            JoinType::Anti => Ok(rows.filter(|(_, m)| *m == 0).map(|(row, _)| row).collect()),
+            JoinType::Mark => rows
+                .map(|(row, m)| {
+                    let mark = lit!(Bool, (m > 0).to_string())?;
+                    Ok(row.into_iter().chain([mark]).collect())
+                })
+                .collect(),
+            _ => {
+                let mut output_rows = vec![];
+                for (row, m) in rows {
+                    if join_type == JoinType::Single && m > 1 {
+                        bail!(too_many_rows(m));
+                    }
+
+                    if m == 0 && join_type.keeps_left() {
+                        let nulls = vec![Value::Null; join.right_fields.len()];
+                        output_rows.push(row.into_iter().chain(nulls).collect());
+                    }
+                }
+                Ok(output_rows)
+            }
+        }
+    }
+}
+
+/// Builds a hash table on the smaller input and probes it with the other.
+/// Which input is smaller is found by reading both, a batch at a time, until
+/// one of them ends. When neither fits in the memory budget both are split
+/// by key into partitions on disk, and each pair of partitions is joined
+/// the same way
+pub struct HashJoinOperator<'a> {
+    join: &'a HashJoin,
+    budget: usize,
+    left_fields: Vec<Field>,
+    right_fields: Vec<Field>,
+    // pairs of left and right partitions still to join, and their depth
+    pending: VecDeque<(Side<'a>, Side<'a>, usize)>,
+    probe: Option<Probe<'a>>,
+    started: bool,
+}
+
+impl<'a> HashJoinOperator<'a> {
+    pub fn new(join: &'a HashJoin, budget: usize, ctx: &mut Context) -> Result<Self> {
+        let (left_fields, right_fields) =
+            join_fields(&join.left, &join.right, join.join_type, &join.schema);
+
+        let left = Source::Input {
+            input: join_input(&join.left, left_fields.clone(), ctx)?,
+            keys: &join.left_keys,
+        };
+        let right = Source::Input {
+            input: join_input(&join.right, right_fields.clone(), ctx)?,
+            keys: &join.right_keys,
+        };
+
+        Ok(Self {
+            join,
+            budget,
+            left_fields,
+            right_fields,
+            pending: VecDeque::from([(Side::new(left), Side::new(right), 0)]),
+            probe: None,
+            started: false,
+        })
+    }
+
+    fn pair_fields(&self) -> Vec<Field> {
+        let mut fields = self.left_fields.clone();
+        fields.extend(self.right_fields.iter().cloned());
+        fields
+    }
+
+    /// Picks the build side of a pair of inputs, or partitions both
+    fn start(
+        &mut self,
+        mut left: Side<'a>,
+        mut right: Side<'a>,
+        depth: usize,
+        ctx: &mut Context,
+    ) -> Result<()> {
+        let budget = match depth < MAX_DEPTH {
+            true => self.budget,
+            false => usize::MAX,
+        };
+
+        let build_left = loop {
+            let (left_fits, right_fits) = (left.bytes <= budget, right.bytes <= budget);
+
+            match (left.done && left_fits, right.done && right_fits) {
+                (true, true) => break left.bytes < right.bytes,
+                (true, false) => break true,
+                (false, true) => break false,
+                (false, false) if !left_fits && !right_fits => {
+                    return self.partition(left, right, depth, ctx);
+                }
+                _ => {}
+            }
+
+            // keep both sides at about the same size, the first to end is the smaller
+            let read_left =
+                !left.done && left_fits && (right.done || !right_fits || left.bytes <= right.bytes);
+            match read_left {
+                true => left.read_ahead(ctx)?,
+                false => right.read_ahead(ctx)?,
+            }
+        };
+
+        let (build, probe) = match build_left {
+            true => (left, right),
+            false => (right, left),
+        };
+
+        self.probe = Some(Probe {
+            table: HashTable::build(build, ctx)?,
+            build_left,
+            probe,
+        });
+
+        Ok(())
+    }
+
+    fn partition(
+        &mut self,
+        left: Side<'a>,
+        right: Side<'a>,
+        depth: usize,
+        ctx: &mut Context,
+    ) -> Result<()> {
+        let bpm = ctx.get_catalog().read().get_bpm();
+
+        let mut split = |mut side: Side<'a>| -> Result<Vec<Spill<KeyedRow>>> {
+            let mut partitions: Vec<_> = (0..PARTITIONS).map(|_| Spill::new(bpm.clone())).collect();
+
+            while let Some(batch) = side.next(ctx)? {
+                for row in batch {
+                    // each depth hashes differently to split a partition further
+                    let mut hasher = DefaultHasher::new();
+                    (depth, &row.key).hash(&mut hasher);
+                    partitions[hasher.finish() as usize % PARTITIONS].push(&row)?;
+                }
+            }
+
+            partitions.iter_mut().try_for_each(Spill::seal)?;
+            Ok(partitions)
+        };
+
+        let left = split(left)?;
+        let right = split(right)?;
+
+        for (left, right) in left.into_iter().zip(right) {
+            if left.is_empty() && right.is_empty() {
+                continue;
+            }
+
+            let (left, right) = (Source::Partition(left), Source::Partition(right));
+            self.pending
+                .push_back((Side::new(left), Side::new(right), depth + 1));
+        }
+
+        Ok(())
+    }
+}
+
+impl Operator for HashJoinOperator<'_> {
+    fn next(&mut self, ctx: &mut Context) -> Result<Option<ResultSet>> {
+        loop {
+            if let Some(probe) = self.probe.as_mut() {
+                let rows = match probe.probe.next(ctx)? {
+                    Some(batch) => {
+                        let mut probe = self.probe.take().unwrap();
+                        let rows = probe.probe(self, batch);
+                        self.probe = Some(probe);
+                        rows?
+                    }
+                    None => self.probe.take().unwrap().finish(self)?,
+                };
+
+                if rows.is_empty() {
+                    continue;
+                }
+
+                self.started = true;
+                let fields = self.join.schema.fields.clone();
+                return Ok(Some(ResultSet::from_rows(fields, rows)));
+            }
+
+            let Some((left, right, depth)) = self.pending.pop_front() else {
+                break;
+            };
+            self.start(left, right, depth, ctx)?;
+        }
+
+        if self.started {
+            return Ok(None);
+        }
+
+        // the first batch is returned even without rows
+        self.started = true;
+        let fields = self.join.schema.fields.clone();
+        Ok(Some(ResultSet::from_rows(fields, vec![])))
+    }
+}
+
+pub fn hash_join<'a>(join: &'a HashJoin, ctx: &mut Context) -> Result<Box<dyn Operator + 'a>> {
+    let operator = HashJoinOperator::new(join, HASH_JOIN_BUFFER_SIZE, ctx)?;
+    Ok(Box::new(operator))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::tests::test_context;
+    use crate::execution::tests::{assert_unpinned, sorted_rows, values_plan};
+    use crate::sql::logical_plan::expr::BinaryExpr;
+    use crate::tuple::constraints::Constraints;
+    use sqlparser::ast::BinaryOperator;
+
+    fn int_rows(keys: impl Iterator<Item = usize>) -> Result<Vec<Vec<Value>>> {
+        keys.enumerate()
+            .map(|(i, key)| Ok(vec![lit!(Int, key.to_string())?, lit!(Int, i.to_string())?]))
+            .collect()
+    }
+
+    fn fields(key: &str, value: &str) -> Vec<Field> {
+        vec![
+            Field::new(key, Types::Int, Constraints::nullable(false)),
+            Field::new(value, Types::Int, Constraints::nullable(false)),
+        ]
+    }
+
+    fn join_schema(join_type: JoinType) -> Schema {
+        let mut schema = fields("a", "b");
+        match join_type {
+            JoinType::Semi | JoinType::Anti => {}
+            JoinType::Mark => schema.push(Field::new(
+                "mark",
+                Types::Bool,
+                Constraints::nullable(false),
+            )),
+            _ => schema.extend(fields("c", "d")),
+        }
+        Schema::new(schema)
+    }
+
+    fn column(name: &str) -> LogicalExpr {
+        LogicalExpr::Column(name.to_string())
+    }
+
+    fn hash_join_plan(left: &[Vec<Value>], right: &[Vec<Value>], join_type: JoinType) -> HashJoin {
+        HashJoin::new(
+            values_plan(left, &fields("a", "b")),
+            values_plan(right, &fields("c", "d")),
+            vec![(column("a"), column("c"))],
+            None,
+            join_type,
+            join_schema(join_type),
+        )
+    }
+
+    #[test]
+    fn test_partitioned_hash_join() -> Result<()> {
+        let mut ctx = test_context();
+        let left = int_rows((0..300).map(|i| i % 50))?;
+        let right = int_rows((0..200).map(|i| i % 70 + 20))?;
+
+        for join_type in [
+            JoinType::Inner,
+            JoinType::Left,
+            JoinType::Right,
+            JoinType::Full,
+            JoinType::Semi,
+            JoinType::Anti,
+            JoinType::Mark,
+        ] {
+            let join = hash_join_plan(&left, &right, join_type);
+            // neither side fits, both are split into partitions
+            let mut operator = HashJoinOperator::new(&join, 512, &mut ctx)?;
+            let first = operator.next(&mut ctx)?.unwrap();
+            assert!(!operator.pending.is_empty());
+
+            let rest = collect(Box::new(operator), &mut ctx)?;
+            let hash = first.union(rest)?;
+
+            let nested_loop = PhysicalPlan::NestedLoopJoin(Box::new(NestedLoopJoin::new(
+                values_plan(&left, &fields("a", "b")),
+                values_plan(&right, &fields("c", "d")),
+                BinaryExpr::new(column("a"), BinaryOperator::Eq, column("c")),
+                join_type,
+                join_schema(join_type),
+            )))
+            .execute(&mut ctx)?;
+
+            assert_eq!(sorted_rows(&hash), sorted_rows(&nested_loop));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_null_keys_never_match() -> Result<()> {
+        let mut ctx = test_context();
+        let left = vec![
+            vec![lit!(Int, "1")?, lit!(Int, "0")?],
+            vec![Value::Null, lit!(Int, "1")?],
+        ];
+        let right = vec![
+            vec![Value::Null, lit!(Int, "2")?],
+            vec![lit!(Int, "1")?, lit!(Int, "3")?],
+        ];
+
+        for (join_type, rows) in [(JoinType::Inner, 1), (JoinType::Full, 3)] {
+            let join = hash_join_plan(&left, &right, join_type);
+            let operator = HashJoinOperator::new(&join, HASH_JOIN_BUFFER_SIZE, &mut ctx)?;
+            let result = collect(Box::new(operator), &mut ctx)?;
+            assert_eq!(result.rows().len(), rows);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_on_smaller_input() -> Result<()> {
+        let mut ctx = test_context();
+        let small = int_rows(0..2)?;
+        let large = int_rows((0..3 * BATCH_SIZE).map(|i| i % 4))?;
+
+        let join = hash_join_plan(&small, &large, JoinType::Inner);
+        let mut operator = HashJoinOperator::new(&join, HASH_JOIN_BUFFER_SIZE, &mut ctx)?;
+        operator.next(&mut ctx)?;
+        assert!(operator.probe.as_ref().unwrap().build_left);
+
+        let join = hash_join_plan(&large, &small, JoinType::Inner);
+        let mut operator = HashJoinOperator::new(&join, HASH_JOIN_BUFFER_SIZE, &mut ctx)?;
+        operator.next(&mut ctx)?;
+        assert!(!operator.probe.as_ref().unwrap().build_left);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_join_unpins_pages() -> Result<()> {
+        let mut ctx = test_context();
+        ctx.execute_sql("CREATE TABLE t1 (a int);")?;
+        ctx.execute_sql("CREATE TABLE t2 (c int UNIQUE NOT NULL, d int);")?;
+        ctx.execute_sql("INSERT INTO t1 VALUES (1), (2), (3), (4);")?;
+        ctx.execute_sql("INSERT INTO t2 VALUES (1, 10), (3, 30), (5, 50);")?;
+
+        let sql = "SELECT a, d FROM t1 JOIN t2 ON a = c;";
+        let plan = ctx.execute_sql(format!("EXPLAIN {sql}"))?;
+        assert!(plan.get_info().contains("IndexNestedLoopJoin"));
+
+        let rows = ctx.execute_sql(sql)?;
+        assert_eq!(rows.len(), 2);
+
+        assert_unpinned(&ctx, &["t1", "t2"]);
+
+        Ok(())
+    }
+}
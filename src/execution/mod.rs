@@ -1,27 +1,42 @@
+mod accumulator;
+mod join;
+mod operator;
 pub mod result_set;
+mod scan;
+mod sort;
+mod spill;
+mod window;
 
-use crate::catalog::CATALOG_NAME;
+use crate::catalog::AlterOperation;
 use crate::context::Context;
 use crate::errors::Error;
 use crate::lit;
 use crate::sql::logical_plan::expr::BinaryExpr;
-use crate::sql::logical_plan::expr::{BooleanBinaryExpr, LogicalExpr};
-use crate::sql::logical_plan::plan::{
-    CreateTable, Delete, DropTables, Filter, IndexScan, Insert, Join, Limit, LogicalPlan, Scan,
-    Truncate, Union, Update, Values,
+use crate::sql::logical_plan::expr::{
+    is_comparison_op, is_pattern_op, BooleanBinaryExpr, DistinctFromExpr, InListExpr, LogicalExpr,
+};
+use crate::sql::logical_plan::plan::{ConflictAction, MergeAction, MergeClause, OnConflict};
+use crate::sql::physical_plan::plan::{
+    AlterTable, CreateTable, Delete, DropTables, Except, Explain, Filter, HashAggregate, Insert,
+    Intersect, Merge, PhysicalPlan, Projection, RecursiveQuery, Truncate, Update, Values,
+    WorkTableScan,
 };
-use crate::sql::logical_plan::plan::{Explain, Projection};
 use crate::table::Table;
-use crate::tuple::constraints::Constraints;
 use crate::tuple::schema::{Field, Schema};
 use crate::tuple::TupleId;
 use crate::txn_manager::TxnId;
-use crate::types::Types;
 use crate::types::Value;
 use crate::types::ValueFactory;
+use crate::types::{Bool, Types};
+use accumulator::Accumulator;
 use anyhow::{anyhow, bail, Result};
+use join::{hash_join, index_nested_loop_join, nested_loop_join};
+use operator::{collect, Map, Once, Operator};
 use result_set::ResultSet;
+use scan::{IndexScanOperator, SeqScanOperator};
+use sort::SortOperator;
 use sqlparser::ast::BinaryOperator;
+use std::collections::{HashMap, HashSet};
 
 trait Executable {
     /// Context is passed for client controls like
@@ -32,53 +47,256 @@ trait Executable {
     fn execute(&self, ctx: &mut Context) -> Result<ResultSet>;
 }
 
-impl LogicalPlan {
+impl PhysicalPlan {
+    /// Runs the plan and pulls all of its rows
     pub fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
-        match self {
-            LogicalPlan::Projection(plan) => (*plan).execute(ctx),
-            LogicalPlan::Scan(scan) => scan.execute(ctx),
-            LogicalPlan::Filter(filter) => filter.execute(ctx),
-            LogicalPlan::CreateTable(create) => create.execute(ctx),
-            LogicalPlan::Explain(explain) => explain.execute(ctx),
-            LogicalPlan::Insert(i) => i.execute(ctx),
-            LogicalPlan::Values(v) => v.execute(ctx),
-            LogicalPlan::DropTables(d) => d.execute(ctx),
-            LogicalPlan::Truncate(t) => t.execute(ctx),
-            LogicalPlan::Update(u) => u.execute(ctx),
-            LogicalPlan::Delete(d) => d.execute(ctx),
-            LogicalPlan::Empty => Ok(ResultSet::default()),
-            LogicalPlan::Join(j) => j.execute(ctx),
-            LogicalPlan::Union(u) => u.execute(ctx),
-            LogicalPlan::Limit(l) => l.execute(ctx),
-            LogicalPlan::IndexScan(i) => i.execute(ctx),
-            LogicalPlan::StartTxn => {
+        let mut output = collect(self.open(ctx)?, ctx)?;
+
+        // the columns are kept, plans above a subquery still read them
+        if matches!(self, PhysicalPlan::Projection(_)) && output.is_empty() {
+            output.set_info("Empty Table".to_string());
+        }
+
+        Ok(output)
+    }
+
+    /// Starts running the plan, its rows are pulled in batches from the
+    /// returned operator
+    fn open<'a>(&'a self, ctx: &mut Context) -> Result<Box<dyn Operator + 'a>> {
+        let operator: Box<dyn Operator + 'a> = match self {
+            PhysicalPlan::SeqScan(scan) => Box::new(SeqScanOperator::new(scan, ctx)?),
+            PhysicalPlan::IndexScan(scan) => Box::new(IndexScanOperator::new(scan, ctx)?),
+            PhysicalPlan::Filter(filter) => {
+                let input = filter.input.open(ctx)?;
+                Box::new(Map::new(input, |batch| filter.apply(batch)))
+            }
+            PhysicalPlan::Projection(projection) => {
+                let input = projection.input.open_or_single_row(ctx)?;
+                Box::new(Map::new(input, |batch| projection.apply(&batch)))
+            }
+            PhysicalPlan::Limit(limit) => {
+                let input = limit.input.open(ctx)?;
+                Box::new(operator::Limit::new(input, limit.limit, limit.offset))
+            }
+            PhysicalPlan::Union(union) => {
+                let (left, right) = (union.left.open(ctx)?, union.right.open(ctx)?);
+                Box::new(operator::Union::new(left, right))
+            }
+            PhysicalPlan::Distinct(distinct) => {
+                let mut seen = HashSet::new();
+                Box::new(Map::new(distinct.input.open(ctx)?, move |batch| {
+                    let rows = batch
+                        .rows()
+                        .into_iter()
+                        .filter(|row| seen.insert(row.clone()))
+                        .collect();
+                    Ok(ResultSet::from_rows(batch.schema.fields, rows))
+                }))
+            }
+            PhysicalPlan::Sort(sort) => Box::new(SortOperator::new(sort, sort.input.open(ctx)?)),
+            PhysicalPlan::HashAggregate(aggregate) => {
+                let input = aggregate.input.open_or_single_row(ctx)?;
+                Box::new(Once::new(move |ctx| aggregate.aggregate(input, ctx)))
+            }
+            PhysicalPlan::Window(window) => {
+                let input = window.input.open_or_single_row(ctx)?;
+                Box::new(Once::new(move |ctx| window.compute(input, ctx)))
+            }
+            PhysicalPlan::HashJoin(join) => hash_join(join, ctx)?,
+            PhysicalPlan::NestedLoopJoin(join) => nested_loop_join(join, ctx)?,
+            PhysicalPlan::IndexNestedLoopJoin(join) => index_nested_loop_join(join, ctx)?,
+            PhysicalPlan::CreateTable(create) => once(create),
+            PhysicalPlan::Explain(explain) => once(explain.as_ref()),
+            PhysicalPlan::Insert(i) => once(i.as_ref()),
+            PhysicalPlan::Values(v) => once(v),
+            PhysicalPlan::DropTables(d) => once(d),
+            PhysicalPlan::Truncate(t) => once(t),
+            PhysicalPlan::AlterTable(a) => once(a),
+            PhysicalPlan::Update(u) => once(u.as_ref()),
+            PhysicalPlan::Delete(d) => once(d.as_ref()),
+            PhysicalPlan::Merge(m) => once(m.as_ref()),
+            PhysicalPlan::Intersect(i) => once(i.as_ref()),
+            PhysicalPlan::Except(e) => once(e.as_ref()),
+            PhysicalPlan::RecursiveQuery(r) => once(r.as_ref()),
+            PhysicalPlan::WorkTableScan(w) => once(w),
+            PhysicalPlan::Empty => Box::new(Once::new(|_| Ok(ResultSet::default()))),
+            PhysicalPlan::StartTxn => Box::new(Once::new(|ctx| {
                 ctx.start_txn()?;
                 Ok(ResultSet::with_info("Transaction started".into()))
-            }
-            LogicalPlan::CommitTxn => {
+            })),
+            PhysicalPlan::CommitTxn => Box::new(Once::new(|ctx| {
                 ctx.commit_txn()?;
                 Ok(ResultSet::with_info("Transaction committed".into()))
-            }
-            LogicalPlan::RollbackTxn => {
+            })),
+            PhysicalPlan::RollbackTxn => Box::new(Once::new(|ctx| {
                 ctx.rollback_txn()?;
                 Ok(ResultSet::with_info("Transaction rolled back".into()))
-            }
+            })),
+        };
+
+        Ok(operator)
+    }
+
+    /// Opens the plan, a query without FROM reads a single row without columns
+    fn open_or_single_row<'a>(&'a self, ctx: &mut Context) -> Result<Box<dyn Operator + 'a>> {
+        match self {
+            PhysicalPlan::Empty => Ok(Box::new(Once::new(|_| Ok(ResultSet::with_capacity(1))))),
+            _ => self.open(ctx),
         }
     }
 }
 
-impl Executable for Union {
+/// Plans that don't stream run when their result is first pulled
+fn once<'a>(plan: &'a dyn Executable) -> Box<dyn Operator + 'a> {
+    Box::new(Once::new(move |ctx| plan.execute(ctx)))
+}
+
+impl Executable for Intersect {
+    fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
+        let left = self.left.execute(ctx)?;
+        let right = self.right.execute(ctx)?;
+        left.intersect(right, self.all)
+    }
+}
+
+impl Executable for Except {
     fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
         let left = self.left.execute(ctx)?;
         let right = self.right.execute(ctx)?;
-        left.union(right)
+        left.except(right, self.all)
     }
 }
 
-impl Executable for Limit {
+impl Executable for RecursiveQuery {
     fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
-        let input = self.input.execute(ctx)?;
-        Ok(input.skip(self.offset).take(self.limit))
+        let fields = self.schema().fields;
+        let mut seen = HashSet::new();
+
+        // with UNION, only rows never seen before are kept and fed back
+        let mut new_rows = |rows: ResultSet| {
+            if !self.distinct {
+                return rows;
+            }
+            let rows = rows
+                .rows()
+                .into_iter()
+                .filter(|row| seen.insert(row.clone()))
+                .collect();
+            ResultSet::from_rows(fields.clone(), rows)
+        };
+
+        let mut output = new_rows(self.static_term.execute(ctx)?);
+        let mut working = output.clone();
+        let shadowed = ctx.get_work_table(&self.name).cloned();
+
+        while !working.is_empty() {
+            ctx.set_work_table(&self.name, working);
+            let rows = match self.recursive_term.execute(ctx) {
+                Ok(rows) => rows,
+                Err(e) => {
+                    restore_work_table(ctx, &self.name, shadowed);
+                    return Err(e);
+                }
+            };
+
+            working = new_rows(rows);
+            output = output.union(working.clone())?;
+        }
+
+        restore_work_table(ctx, &self.name, shadowed);
+        Ok(output)
+    }
+}
+
+fn restore_work_table(ctx: &mut Context, name: &str, shadowed: Option<ResultSet>) {
+    match shadowed {
+        Some(rows) => {
+            ctx.set_work_table(name, rows);
+        }
+        None => ctx.remove_work_table(name),
+    }
+}
+
+impl Executable for WorkTableScan {
+    fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
+        let rows = ctx
+            .get_work_table(&self.name)
+            .ok_or(Error::Internal(format!(
+                "Work table {} is not set",
+                self.name
+            )))?;
+
+        Ok(ResultSet::from_rows(
+            self.schema.fields.clone(),
+            rows.rows(),
+        ))
+    }
+}
+
+impl HashAggregate {
+    /// Folds every batch of the input into the groups' accumulators
+    fn aggregate(&self, mut input: Box<dyn Operator + '_>, ctx: &mut Context) -> Result<ResultSet> {
+        let accumulators = || {
+            self.aggregates
+                .iter()
+                .map(|agg| Accumulator::new(&agg.func))
+                .collect::<Vec<_>>()
+        };
+
+        // groups are emitted in the order they are first seen
+        let mut groups: HashMap<Vec<Value>, usize> = HashMap::new();
+        let mut states: Vec<(Vec<Value>, Vec<Accumulator>)> = vec![];
+
+        while let Some(batch) = input.next(ctx)? {
+            let keys = self
+                .group_by
+                .iter()
+                .map(|expr| Ok(expr.evaluate(&batch)?.1))
+                .collect::<Result<Vec<_>>>()?;
+
+            // COUNT(*) is COUNT(true)
+            let args = self
+                .aggregates
+                .iter()
+                .map(|agg| {
+                    let arg = agg
+                        .arg
+                        .clone()
+                        .unwrap_or(LogicalExpr::Literal(lit!(Bool, "true".to_string())?));
+                    Ok(arg.evaluate(&batch)?.1)
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            for row in 0..batch.len() {
+                let key = keys.iter().map(|col| col[row].clone()).collect::<Vec<_>>();
+                let group = *groups.entry(key.clone()).or_insert_with(|| {
+                    states.push((key, accumulators()));
+                    states.len() - 1
+                });
+
+                for (acc, arg) in states[group].1.iter_mut().zip(&args) {
+                    acc.update(&arg[row])?;
+                }
+            }
+        }
+
+        // aggregating an empty input without GROUP BY still yields a row
+        if states.is_empty() && self.group_by.is_empty() {
+            states.push((vec![], accumulators()));
+        }
+
+        let rows = states
+            .into_iter()
+            .map(|(key, accs)| {
+                let values = accs
+                    .into_iter()
+                    .map(Accumulator::finish)
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(key.into_iter().chain(values).collect())
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(ResultSet::from_rows(self.schema().fields, rows))
     }
 }
 
@@ -88,28 +306,36 @@ fn tuple_id_of(row: &[Value]) -> TupleId {
 }
 
 /// Shared skeleton for row-mutating statements: run the input plan, keep the
-/// rows the selection matches, then apply `f` to each one.
+/// rows the selection matches, then apply `f` to each one with the input's
+/// columns. The rows are all read before any is changed
 fn for_each_selected_row<F>(
     ctx: &mut Context,
     table_name: &str,
     txn_id: TxnId,
-    input_plan: &LogicalPlan,
+    input_plan: &PhysicalPlan,
     selection: &LogicalExpr,
     mut f: F,
 ) -> Result<usize>
 where
-    F: FnMut(&mut Table, &[Value], &ResultSet) -> Result<()>,
+    F: FnMut(&mut Table, &[Value], &[Field]) -> Result<()>,
 {
-    let input = input_plan.execute(ctx)?;
-
-    let (_, mask) = selection.evaluate(&input)?;
-
-    let selected_rows = input
-        .rows()
-        .into_iter()
-        .enumerate()
-        .filter_map(|(i, row)| mask[i].is_truthy().then_some(row))
-        .collect::<Vec<_>>();
+    let mut input = input_plan.open(ctx)?;
+    let mut fields = vec![];
+    let mut selected_rows = vec![];
+
+    while let Some(batch) = input.next(ctx)? {
+        let (_, mask) = selection.evaluate(&batch)?;
+
+        selected_rows.extend(
+            batch
+                .rows()
+                .into_iter()
+                .zip(mask)
+                .filter_map(|(row, m)| m.is_truthy().then_some(row)),
+        );
+        fields = batch.schema.fields;
+    }
+    drop(input);
 
     let c = ctx.get_catalog();
     let mut catalog = c.write();
@@ -121,152 +347,87 @@ where
     table.start_txn(txn_id)?;
 
     for row in selected_rows.iter() {
-        f(table, row, &input)?;
+        f(table, row, &fields)?;
     }
 
     Ok(selected_rows.len())
 }
 
+/// The table's row with the SET assignments evaluated over the single row
+/// of `input`, each value coerced to its column's type. Shared by UPDATE,
+/// ON CONFLICT DO UPDATE and MERGE, [`Table::update`] checks NOT NULL
+fn apply_assignments(
+    mut row: Vec<Value>,
+    assignments: &[(String, LogicalExpr)],
+    schema: &Schema,
+    input: &ResultSet,
+) -> Result<Vec<Value>> {
+    for (col, expr) in assignments {
+        let index = schema
+            .fields
+            .iter()
+            .position(|f| &f.name == col)
+            .ok_or(Error::ColumnNotFound(col.clone()))?;
+        if let Some(value) = expr.evaluate(input)?.1.pop() {
+            // the builder made sure the value coerces to the column's type
+            row[index] = value.coerce(&schema.fields[index].ty)?;
+        }
+    }
+
+    Ok(row)
+}
+
+/// Evaluates a RETURNING list over the rows a statement changed, laid out
+/// like the table's columns. Without one only `info` is returned
+fn returning(
+    exprs: &[LogicalExpr],
+    fields: &[Field],
+    rows: Vec<Vec<Value>>,
+    info: String,
+) -> Result<ResultSet> {
+    if exprs.is_empty() {
+        return Ok(ResultSet::with_info(info));
+    }
+
+    let input = ResultSet::from_rows(fields.to_vec(), rows);
+    let mut output = exprs
+        .iter()
+        .map(|e| e.evaluate(&input))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .map(|(field, data)| ResultSet::new(vec![field], vec![data]))
+        .reduce(|a, b| a.concat(b))
+        .unwrap_or_default();
+
+    output.set_info(info);
+    Ok(output)
+}
+
 impl Executable for Delete {
     fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
         let txn_id = ctx.get_active_txn().ok_or(Error::Internal(
             "DELETE requires an active transaction".into(),
         ))?;
 
+        let mut deleted = vec![];
+
         let count = for_each_selected_row(
             ctx,
             &self.table_name,
             txn_id,
             &self.input,
             &self.selection,
-            |table, row, _| table.delete(tuple_id_of(row)),
-        )?;
-
-        Ok(ResultSet::with_info(format!("Deleted {count} rows")))
-    }
-}
-
-impl Executable for IndexScan {
-    fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
-        let txn_id = ctx.get_active_txn();
-        let arc_catalog = ctx.get_catalog();
-        let catalog = arc_catalog.read();
-        let table = catalog.get_table(&self.table_name, txn_id).unwrap();
-
-        let schema = table.get_schema();
-
-        let mut tuple_ids = vec![];
-
-        let scanner = |(key, tuple_id): &(u32, TupleId)| {
-            if let Some(from) = self.from {
-                if *key == from && !self.include_from {
-                    return Ok(());
-                }
-            }
-
-            if let Some(to) = self.to {
-                if *key > to || (*key == to && !self.include_to) {
-                    return Err(anyhow!("End of loop"));
-                };
-            }
-            tuple_ids.push(*tuple_id);
-            Ok(())
-        };
-
-        let index = table.get_index().as_ref().unwrap();
-
-        let _ = if let Some(from) = self.from {
-            index.scan_from(txn_id, from, scanner)
-        } else {
-            index.scan(txn_id, scanner)
-        };
-
-        let tuples = tuple_ids
-            .into_iter()
-            .map(|tuple_id| {
-                (
-                    tuple_id,
-                    table
-                        .get_tuple(tuple_id)
-                        .expect("Index returned a deleted record"),
-                )
-            })
-            .collect::<Vec<_>>();
-
-        let mut cols: Vec<Vec<Value>> = vec![vec![]; schema.fields.len() + 2];
-
-        // TODO: pass the tuple_id as tuple type for update to use
-        // need to define a tuple type first though
-        tuples
-            .into_iter()
-            .try_for_each(|((page_id, slot_id), tuple)| -> Result<()> {
-                let mut values = vec![
-                    lit!(UInt, page_id.to_string())?,
-                    lit!(UInt, slot_id.to_string())?,
-                ];
-
-                values.extend(table.get_portable_values(&tuple)?);
-
-                values.into_iter().enumerate().for_each(|(i, v)| {
-                    cols[i].push(v);
-                });
-
-                Ok(())
-            })?;
-
-        let mut fields = vec![
-            Field::new("page_id", Types::UInt, Constraints::nullable(false)),
-            Field::new("slot_id", Types::UInt, Constraints::nullable(false)),
-        ];
-
-        fields.extend(schema.fields.clone());
-
-        Ok(ResultSet::new(fields, cols))
-    }
-}
-
-impl Executable for Join {
-    fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
-        let left_name = match self.left {
-            LogicalPlan::Scan(Scan { ref table_name, .. }) => table_name.clone(),
-            _ => unreachable!(),
-        };
-        let right_name = match self.right {
-            LogicalPlan::Scan(Scan { ref table_name, .. }) => table_name.clone(),
-            _ => unreachable!(),
-        };
-        let left = self.left.execute(ctx)?;
-        let right = self.right.execute(ctx)?;
-
-        // drop the first page_id and slot_id cols from each table
-        let left_fields = left.fields().len();
-        let right_fields = right.fields().len();
-        let mut left = left.select((2..left_fields).collect());
-        let mut right = right.select((2..right_fields).collect());
-
-        let join_schema = match left.schema.join(right.schema.clone()) {
-            Ok(schema) => schema,
-            Err(_) => {
-                left.schema = left.schema.add_qualifier(&left_name);
-                right.schema = right.schema.add_qualifier(&right_name);
-                left.schema.join(right.schema.clone()).unwrap()
-            }
-        };
-
-        let mut output_rows: Vec<Vec<Value>> = vec![];
-
-        for left_row in left.rows() {
-            let ll = ResultSet::from_tuple(left.fields().clone(), left_row.to_vec(), right.len());
-            let input = ll.concat(right.clone());
-            let mask = self.on.evaluate(&input)?;
-            for (i, row) in input.rows().into_iter().enumerate() {
-                if mask[i].is_truthy() {
-                    output_rows.push(row);
+            |table, row, _| {
+                if !self.returning.is_empty() {
+                    deleted.push(row[2..].to_vec());
                 }
-            }
-        }
+                table.delete(tuple_id_of(row))
+            },
+        )?;
 
-        Ok(ResultSet::from_rows(join_schema.fields, output_rows))
+        // the input scans every column of the table
+        let info = format!("Deleted {count} rows");
+        returning(&self.returning, &self.input.schema().fields, deleted, info)
     }
 }
 
@@ -276,25 +437,13 @@ impl Executable for Update {
             "UPDATE requires an active transaction".into(),
         ))?;
 
-        let (selected_cols, exprs): (Vec<String>, Vec<_>) =
-            self.assignments.iter().cloned().unzip();
-
         let schema = ctx
             .get_catalog()
             .read()
             .get_schema(&self.table_name, Some(txn_id))
             .ok_or(Error::TableNotFound(self.table_name.clone()))?;
 
-        let updated_cols_ids = selected_cols
-            .into_iter()
-            .map(|col| {
-                schema
-                    .fields
-                    .iter()
-                    .position(|f| f.name == col)
-                    .ok_or(Error::ColumnNotFound(col).into())
-            })
-            .collect::<Result<Vec<_>>>()?;
+        let mut updated = vec![];
 
         let count = for_each_selected_row(
             ctx,
@@ -302,13 +451,13 @@ impl Executable for Update {
             txn_id,
             &self.input,
             &self.selection,
-            |table, row, input| {
-                let mut new_tuple = row[2..].to_vec();
+            |table, row, fields| {
+                let input = ResultSet::from_rows(fields.to_vec(), vec![row.to_vec()]);
+                let new_tuple =
+                    apply_assignments(row[2..].to_vec(), &self.assignments, &schema, &input)?;
 
-                for (updated_col_id, expr) in updated_cols_ids.iter().zip(exprs.iter()) {
-                    for value in expr.evaluate(input)?.1 {
-                        new_tuple[*updated_col_id] = value;
-                    }
+                if !self.returning.is_empty() {
+                    updated.push(new_tuple.clone());
                 }
 
                 table.update(Some(tuple_id_of(row)), new_tuple)?;
@@ -317,7 +466,8 @@ impl Executable for Update {
             },
         )?;
 
-        Ok(ResultSet::with_info(format!("Updated {count} rows")))
+        let info = format!("Updated {count} rows");
+        returning(&self.returning, &schema.fields, updated, info)
     }
 }
 
@@ -339,6 +489,28 @@ impl Executable for Truncate {
     }
 }
 
+impl Executable for AlterTable {
+    fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
+        let txn_id = ctx.get_active_txn().ok_or(Error::Internal(
+            "ALTER TABLE requires an active transaction".into(),
+        ))?;
+
+        let mut table_name = self.table_name.clone();
+
+        for operation in self.operations.iter() {
+            ctx.get_catalog()
+                .write()
+                .alter_table(&table_name, operation, txn_id)?;
+
+            if let AlterOperation::RenameTable(name) = operation {
+                table_name = name.clone();
+            }
+        }
+
+        Ok(ResultSet::with_info(format!("Altered table {table_name}")))
+    }
+}
+
 impl Executable for DropTables {
     fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
         let txn_id = ctx.get_active_txn().ok_or(Error::Internal(
@@ -398,26 +570,236 @@ impl Executable for Insert {
             ));
         }
 
-        let count = input.len();
+        let key = self.on_conflict.as_ref().map(|on_conflict| {
+            self.table_schema
+                .fields
+                .iter()
+                .position(|f| f.name == on_conflict.column)
+                .unwrap()
+        });
+
+        let (mut inserted, mut updated) = (0, 0);
+        let mut changed = vec![];
+        // the rows this statement wrote, DO UPDATE can't change them again
+        let mut written = HashSet::new();
 
         for row in input.rows() {
-            let row = self.reorder(row)?;
+            // the builder made sure every column coerces to the table's type
+            let row = self
+                .reorder(row)?
+                .iter()
+                .zip(self.table_schema.fields.iter())
+                .map(|(value, field)| value.coerce(&field.ty))
+                .collect::<Result<Vec<_>>>()?;
 
-            let _tuple_id = ctx
-                .get_catalog()
-                .write()
+            let catalog = ctx.get_catalog();
+            let mut catalog = catalog.write();
+            let table = catalog
                 .get_table_mut(&self.table_name, txn_id)
-                .ok_or_else(|| Error::TableNotFound(self.table_name.clone()))??
-                .insert(row)?;
+                .ok_or_else(|| Error::TableNotFound(self.table_name.clone()))??;
+
+            // a NULL key never conflicts, the insert rejects it
+            let conflict = match key {
+                Some(key) if !row[key].is_null() => table.find_by_values(&row)?,
+                _ => None,
+            };
+
+            let row = match (conflict, self.on_conflict.as_ref().map(|o| &o.action)) {
+                (Some(tuple_id), Some(ConflictAction::Update { .. }))
+                    if written.contains(&tuple_id) =>
+                {
+                    let key = key.unwrap();
+                    bail!(Error::Expected(
+                        "ON CONFLICT DO UPDATE to affect each row at most once".into(),
+                        format!(
+                            "a second row with {} {}",
+                            self.table_schema.fields[key].name, row[key]
+                        )
+                    ));
+                }
+                (Some(tuple_id), Some(ConflictAction::Update { .. })) => {
+                    match self.resolve_conflict(table, tuple_id, row)? {
+                        Some(row) => {
+                            written.insert(table.update(Some(tuple_id), row.clone())?);
+                            updated += 1;
+                            row
+                        }
+                        None => continue,
+                    }
+                }
+                (Some(_), _) => continue,
+                (None, _) => {
+                    written.insert(table.insert(row.clone())?);
+                    inserted += 1;
+                    row
+                }
+            };
+
+            if !self.returning.is_empty() {
+                changed.push(row);
+            }
         }
 
-        Ok(ResultSet::with_info(format!("Inserted {count} rows")))
+        let info = match self.on_conflict {
+            Some(_) => format!("Inserted {inserted} rows, updated {updated} rows"),
+            None => format!("Inserted {inserted} rows"),
+        };
+        returning(&self.returning, &self.table_schema.fields, changed, info)
+    }
+}
+
+impl Insert {
+    /// The existing row with the DO UPDATE assignments applied, None if
+    /// their selection isn't true for it
+    fn resolve_conflict(
+        &self,
+        table: &Table,
+        tuple_id: TupleId,
+        row: Vec<Value>,
+    ) -> Result<Option<Vec<Value>>> {
+        let Some(OnConflict {
+            action:
+                ConflictAction::Update {
+                    assignments,
+                    selection,
+                },
+            ..
+        }) = &self.on_conflict
+        else {
+            return Ok(None);
+        };
+
+        let tuple = table.get_tuple(tuple_id).ok_or(Error::Internal(format!(
+            "Conflicting row {tuple_id:?} of {} not found",
+            self.table_name
+        )))?;
+        let existing = table.get_portable_values(&tuple)?;
+
+        let fields = OnConflict::input_schema(&self.table_schema).fields;
+        let values = existing.iter().cloned().chain(row).collect();
+        let input = ResultSet::from_rows(fields, vec![values]);
+
+        if !selection.evaluate(&input)?.1[0].is_truthy() {
+            return Ok(None);
+        }
+
+        let new_row = apply_assignments(existing, assignments, &self.table_schema, &input)?;
+        Ok(Some(new_row))
+    }
+}
+
+impl Executable for Merge {
+    fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
+        let txn_id = ctx.get_active_txn().ok_or(Error::Internal(
+            "MERGE requires an active transaction".into(),
+        ))?;
+
+        let schema = ctx
+            .get_catalog()
+            .read()
+            .get_schema(&self.table_name, Some(txn_id))
+            .ok_or(Error::TableNotFound(self.table_name.clone()))?;
+
+        // every row of the join is read before any is changed, so rows
+        // updated or inserted by this statement aren't matched again
+        let rows = self.input.execute(ctx)?.rows();
+
+        let catalog = ctx.get_catalog();
+        let mut catalog = catalog.write();
+        let table = catalog
+            .get_table_mut(&self.table_name, Some(txn_id))
+            .ok_or(Error::TableNotFound(self.table_name.clone()))??;
+
+        table.start_txn(txn_id)?;
+
+        let (mut inserted, mut updated, mut deleted) = (0, 0, 0);
+        let mut matched = HashSet::new();
+
+        for row in rows {
+            // the tuple id comes first, the clauses read the rest
+            let values = row[2..].to_vec();
+            let input = ResultSet::from_rows(self.input_schema.fields.clone(), vec![values]);
+
+            // a source row that matched nothing has no tuple id
+            if row[0].is_null() {
+                let Some(MergeAction::Insert(exprs)) = self.clause_for(false, &input)? else {
+                    continue;
+                };
+
+                // the builder made sure every value coerces to the column's type
+                let row = exprs
+                    .iter()
+                    .zip(schema.fields.iter())
+                    .map(|(expr, field)| expr.evaluate(&input)?.1[0].coerce(&field.ty))
+                    .collect::<Result<Vec<_>>>()?;
+
+                table.insert(row)?;
+                inserted += 1;
+                continue;
+            }
+
+            let target = row[2..2 + schema.fields.len()].to_vec();
+            if !matched.insert(tuple_id_of(&row)) {
+                bail!(Error::Expected(
+                    "at most one source row matching each target row".into(),
+                    format!(
+                        "more than one matching ({})",
+                        target
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                ));
+            }
+
+            match self.clause_for(true, &input)? {
+                Some(MergeAction::Update(assignments)) => {
+                    let new_tuple = apply_assignments(target, assignments, &schema, &input)?;
+                    table.update(Some(tuple_id_of(&row)), new_tuple)?;
+                    updated += 1;
+                }
+                Some(MergeAction::Delete) => {
+                    table.delete(tuple_id_of(&row))?;
+                    deleted += 1;
+                }
+                Some(MergeAction::Insert(_)) | None => {}
+            }
+        }
+
+        Ok(ResultSet::with_info(format!(
+            "Inserted {inserted} rows, updated {updated} rows, deleted {deleted} rows"
+        )))
+    }
+}
+
+impl Merge {
+    /// The action of the first WHEN [NOT] MATCHED clause that holds for the
+    /// single row of `input`, if any
+    fn clause_for(&self, matched: bool, input: &ResultSet) -> Result<Option<&MergeAction>> {
+        for MergeClause {
+            matched: m,
+            predicate,
+            action,
+        } in self.clauses.iter()
+        {
+            if *m == matched && predicate.evaluate(input)?.1[0].is_truthy() {
+                return Ok(Some(action));
+            }
+        }
+
+        Ok(None)
     }
 }
 
 impl Executable for Explain {
     fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
-        let plan = self.input.print();
+        let plan = format!(
+            "{}\nPhysical Plan:\n{}",
+            self.logical_plan,
+            self.input.print()
+        );
+
         if self.analyze {
             let start = std::time::Instant::now();
 
@@ -468,23 +850,25 @@ impl Executable for CreateTable {
     }
 }
 
-impl Executable for Filter {
-    fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
-        let input = self.input.execute(ctx)?;
+impl Filter {
+    /// The rows of the batch the predicate holds for
+    fn apply(&self, input: ResultSet) -> Result<ResultSet> {
         let mask = self.expr.evaluate(&input)?;
+        Ok(input.filter(&mask))
+    }
+}
 
-        let output = input
-            .cols
+impl Projection {
+    fn apply(&self, input: &ResultSet) -> Result<ResultSet> {
+        Ok(self
+            .projections
+            .iter()
+            .map(|p| p.evaluate(input))
+            .collect::<Result<Vec<_>, _>>()?
             .into_iter()
-            .map(|col| {
-                col.into_iter()
-                    .zip(&mask)
-                    .filter_map(|(value, &mask)| if mask { Some(value) } else { None })
-                    .collect::<Vec<_>>()
-            })
-            .collect::<Vec<_>>();
-
-        Ok(ResultSet::new(input.schema.fields, output))
+            .map(|(field, data)| ResultSet::new(vec![field], vec![data]))
+            .reduce(|a, b| a.concat(b))
+            .unwrap_or_default())
     }
 }
 
@@ -516,25 +900,189 @@ impl LogicalExpr {
 
                 Ok((field, result.1))
             }
+            LogicalExpr::AggregateExpr(ref expr) => bail!(Error::Internal(format!(
+                "Aggregate {} evaluated outside of an Aggregate",
+                expr.print()
+            ))),
+            LogicalExpr::WindowExpr(ref expr) => bail!(Error::Internal(format!(
+                "Window function {} evaluated outside of a Window",
+                expr.print()
+            ))),
+            LogicalExpr::Parameter(n, _) => bail!(Error::Internal(format!(
+                "Parameter ${n} evaluated before it was bound"
+            ))),
+            LogicalExpr::FunctionExpr(ref expr) => {
+                let args = expr
+                    .args
+                    .iter()
+                    .map(|arg| Ok(arg.evaluate(input)?.1))
+                    .collect::<Result<Vec<_>>>()?;
+
+                let data = (0..size)
+                    .map(|i| {
+                        let row = args.iter().map(|col| col[i].clone()).collect::<Vec<_>>();
+                        expr.func.call(&row)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                let schema = Schema::new(input.fields().clone());
+                Ok((self.to_field(&schema), data))
+            }
+            LogicalExpr::DistinctFrom(ref expr) => {
+                let (_, left) = expr.left.evaluate(input)?;
+                let (_, right) = expr.right.evaluate(input)?;
+                let data = left
+                    .iter()
+                    .zip(right.iter())
+                    .map(|(l, r)| expr.eval_op(l, r))
+                    .collect::<Result<Vec<_>>>()?;
+
+                let schema = Schema::new(input.fields().clone());
+                Ok((self.to_field(&schema), data))
+            }
+            LogicalExpr::Cast(ref expr, ref ty) => {
+                let (_, values) = expr.evaluate(input)?;
+                let data = values
+                    .iter()
+                    .map(|v| v.cast(ty))
+                    .collect::<Result<Vec<_>>>()?;
+
+                let schema = Schema::new(input.fields().clone());
+                Ok((self.to_field(&schema), data))
+            }
+            LogicalExpr::Not(ref expr) => {
+                let (_, values) = expr.evaluate(input)?;
+                let data = values.iter().map(Value::not).collect::<Result<Vec<_>>>()?;
+
+                let schema = Schema::new(input.fields().clone());
+                Ok((self.to_field(&schema), data))
+            }
+            LogicalExpr::InList(ref expr) => {
+                let (_, values) = expr.expr.evaluate(input)?;
+                let list = expr
+                    .list
+                    .iter()
+                    .map(|e| Ok(e.evaluate(input)?.1))
+                    .collect::<Result<Vec<_>>>()?;
+
+                let data = values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| {
+                        let row = list.iter().map(|col| col[i].clone()).collect::<Vec<_>>();
+                        expr.eval_op(value, &row)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                let schema = Schema::new(input.fields().clone());
+                Ok((self.to_field(&schema), data))
+            }
+            LogicalExpr::Case(ref expr) => {
+                let mut data = vec![Value::Null; size];
+
+                // a result is only evaluated for the rows that reach its
+                // branch, so `CASE WHEN b <> 0 THEN a / b END` can't fail
+                let mut rows = input.clone();
+                let mut positions = (0..size).collect::<Vec<_>>();
+
+                for (when, then) in expr.branches.iter() {
+                    if positions.is_empty() {
+                        break;
+                    }
+
+                    let (_, conditions) = when.evaluate(&rows)?;
+                    let mask = conditions.iter().map(Value::is_truthy).collect::<Vec<_>>();
+
+                    let (_, results) = then.evaluate(&rows.clone().filter(&mask))?;
+                    let matched = positions.iter().zip(&mask).filter(|(_, &m)| m);
+                    for ((&position, _), value) in matched.zip(results) {
+                        data[position] = value;
+                    }
+
+                    let unmatched = mask.iter().map(|m| !m).collect::<Vec<_>>();
+                    positions = positions
+                        .into_iter()
+                        .zip(&unmatched)
+                        .filter_map(|(position, &keep)| keep.then_some(position))
+                        .collect();
+                    rows = rows.filter(&unmatched);
+                }
+
+                if let Some(else_result) = expr.else_result.as_ref() {
+                    if !positions.is_empty() {
+                        let (_, results) = else_result.evaluate(&rows)?;
+                        for (position, value) in positions.into_iter().zip(results) {
+                            data[position] = value;
+                        }
+                    }
+                }
+
+                let schema = Schema::new(input.fields().clone());
+                Ok((self.to_field(&schema), data))
+            }
+        }
+    }
+}
+
+/// A NULL operand makes the result NULL, even when negated
+fn eval_pattern_op(op: &BinaryOperator, left: &Value, right: &Value) -> Result<Value> {
+    match op {
+        BinaryOperator::PGLikeMatch => left.like(right, false),
+        BinaryOperator::PGILikeMatch => left.like(right, true),
+        BinaryOperator::PGNotLikeMatch => left.like(right, false)?.not(),
+        BinaryOperator::PGNotILikeMatch => left.like(right, true)?.not(),
+        BinaryOperator::PGRegexMatch => left.regex_match(right, false),
+        BinaryOperator::PGRegexIMatch => left.regex_match(right, true),
+        BinaryOperator::PGRegexNotMatch => left.regex_match(right, false)?.not(),
+        BinaryOperator::PGRegexNotIMatch => left.regex_match(right, true)?.not(),
+        e => bail!(Error::Internal(format!("{e} is not a pattern match"))),
+    }
+}
+
+impl DistinctFromExpr {
+    pub(crate) fn eval_op(&self, left: &Value, right: &Value) -> Result<Value> {
+        let distinct = !left.equ(right)?;
+        Ok(Value::Bool(Bool(distinct != self.negated)))
+    }
+}
+
+impl InListExpr {
+    /// True if the value equals an element, unknown if it or an element is
+    /// NULL and none matched
+    pub(crate) fn eval_op(&self, value: &Value, list: &[Value]) -> Result<Value> {
+        let found = match value {
+            Value::Null => Value::Null,
+            v if list.iter().any(|e| !e.is_null() && e == v) => Value::Bool(Bool(true)),
+            _ if list.iter().any(Value::is_null) => Value::Null,
+            _ => Value::Bool(Bool(false)),
+        };
+
+        match self.negated {
+            true => found.not(),
+            false => Ok(found),
         }
     }
 }
 
 impl BinaryExpr {
-    fn eval_op(&self, left: &Value, right: &Value) -> Result<Value> {
+    pub(crate) fn eval_op(&self, left: &Value, right: &Value) -> Result<Value> {
         match &self.op {
             BinaryOperator::Plus => Ok(left.add(right)?),
             BinaryOperator::Minus => Ok(left.sub(right)?),
             BinaryOperator::Multiply => Ok(left.mul(right)?),
             BinaryOperator::Divide => Ok(left.div(right)?),
+            BinaryOperator::StringConcat => Ok(left.concat(right)),
+            BinaryOperator::And => left.and(right),
+            BinaryOperator::Or => left.or(right),
+            // comparing with NULL is unknown
+            op if is_comparison_op(op) && (left.is_null() || right.is_null()) => Ok(Value::Null),
             BinaryOperator::Eq => Ok(lit!(Bool, (left == right).to_string())?),
-            BinaryOperator::And => Ok(lit!(Bool, left.and(right)?.to_string())?),
-            BinaryOperator::Or => Ok(lit!(Bool, left.or(right)?.to_string())?),
             BinaryOperator::Lt => Ok(lit!(Bool, (left < right).to_string())?),
             BinaryOperator::Gt => Ok(lit!(Bool, (left > right).to_string())?),
             BinaryOperator::LtEq => Ok(lit!(Bool, (left <= right).to_string())?),
             BinaryOperator::GtEq => Ok(lit!(Bool, (left >= right).to_string())?),
             BinaryOperator::NotEq => Ok(lit!(Bool, (left != right).to_string())?),
+            op if is_pattern_op(op) => eval_pattern_op(op, left, right),
             e => bail!(Error::Unsupported(format!("Operator evaluation {e}"))),
         }
     }
@@ -648,13 +1196,30 @@ impl BinaryExpr {
                     .map(|(l, r)| self.eval_op(l, r))
                     .collect::<Result<_>>()?)
             }
+            (LogicalExpr::AggregateExpr(expr), _) | (_, LogicalExpr::AggregateExpr(expr)) => {
+                bail!(Error::Internal(format!(
+                    "Aggregate {} evaluated outside of an Aggregate",
+                    expr.print()
+                )))
+            }
+            (l, r) => {
+                let (_, left) = l.evaluate(input)?;
+                let (_, right) = r.evaluate(input)?;
+                Ok(left
+                    .iter()
+                    .zip(right.iter())
+                    .map(|(l, r)| self.eval_op(l, r))
+                    .collect::<Result<_>>()?)
+            }
         }
     }
 }
 
 impl BooleanBinaryExpr {
+    /// Unknown is false, the predicate only holds if it's true
     fn eval_op(&self, left: &Value, right: &Value) -> Result<bool> {
         match &self.op {
+            op if is_comparison_op(op) && (left.is_null() || right.is_null()) => Ok(false),
             BinaryOperator::Eq => Ok(left == right),
             BinaryOperator::NotEq => Ok(left != right),
             BinaryOperator::Gt => Ok(left > right),
@@ -663,6 +1228,7 @@ impl BooleanBinaryExpr {
             BinaryOperator::LtEq => Ok(left <= right),
             BinaryOperator::And => Ok(left.is_truthy() && right.is_truthy()),
             BinaryOperator::Or => Ok(left.is_truthy() || right.is_truthy()),
+            op if is_pattern_op(op) => Ok(eval_pattern_op(op, left, right)?.is_truthy()),
             e => bail!(Error::Unimplemented(format!(
                 "Binary Operator evaluation {e}"
             ))),
@@ -718,85 +1284,15 @@ impl BooleanBinaryExpr {
                     .map(|(l, r)| self.eval_op(&l, &r))
                     .collect::<Result<Vec<_>>>()?)
             }
-            (l, r) => bail!(Error::Unimplemented(format!(
-                "Boolean Expr Eval: {:?} {} {:?}",
-                l, self.op, r
-            ))),
-        }
-    }
-}
-
-impl Executable for Projection {
-    fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
-        let input = if matches!(self.input, LogicalPlan::Empty) {
-            ResultSet::with_capacity(1)
-        } else {
-            self.input.execute(ctx)?
-        };
-
-        let output = self
-            .projections
-            .iter()
-            .map(|p| p.evaluate(&input))
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .map(|(field, data)| ResultSet::new(vec![field], vec![data]))
-            .reduce(|a, b| a.concat(b))
-            .unwrap_or_default();
-
-        if output.is_empty() {
-            return Ok(ResultSet::with_info("Empty Table".to_string()));
-        }
-
-        Ok(output)
-    }
-}
-
-impl Executable for Scan {
-    fn execute(&self, ctx: &mut Context) -> Result<ResultSet> {
-        let txn_id = ctx.get_active_txn();
-        let arc_catalog = ctx.get_catalog();
-        let catalog = arc_catalog.read();
-        let table = catalog.get_table(&self.table_name, txn_id).unwrap();
-
-        let schema = table.get_schema();
-
-        let mut cols: Vec<Vec<Value>> = vec![vec![]; schema.fields.len() + 2];
-
-        // TODO: pass the tuple_id as tuple type for update to use
-        // need to define a tuple type first though
-        table.scan(txn_id, |((page_id, slot_id), (_, tuple))| {
-            let mut values = vec![
-                lit!(UInt, page_id.to_string())?,
-                lit!(UInt, slot_id.to_string())?,
-            ];
-
-            values.extend(table.get_portable_values(tuple)?);
-
-            values.into_iter().enumerate().for_each(|(i, v)| {
-                cols[i].push(v);
-            });
-
-            Ok(())
-        })?;
-
-        if self.table_name == CATALOG_NAME {
-            // deserialize the schema and print as sql
-            cols[6] = cols[6]
-                .iter()
-                .map(|v| Schema::from_bytes(v.str().as_bytes()).to_sql())
-                .map(|s| lit!(Str, s).unwrap())
-                .collect();
+            (l, r) => {
+                let (_, left) = l.evaluate(input)?;
+                let (_, right) = r.evaluate(input)?;
+                left.iter()
+                    .zip(right.iter())
+                    .map(|(l, r)| self.eval_op(l, r))
+                    .collect()
+            }
         }
-
-        let mut fields = vec![
-            Field::new("page_id", Types::UInt, Constraints::nullable(false)),
-            Field::new("slot_id", Types::UInt, Constraints::nullable(false)),
-        ];
-
-        fields.extend(schema.fields.clone());
-
-        Ok(ResultSet::new(fields, cols))
     }
 }
 
@@ -805,10 +1301,27 @@ mod tests {
     use crate::context::tests::test_context;
 
     use super::*;
+    use crate::sql::logical_plan::plan::JoinType;
+    use crate::sql::physical_plan::plan::{HashJoin, Limit, NestedLoopJoin, SeqScan};
+    use crate::tuple::constraints::Constraints;
     use anyhow::Result;
+    use operator::BATCH_SIZE;
+
+    /// Checks no page of the tables is left pinned once their operators are done
+    pub(super) fn assert_unpinned(ctx: &Context, tables: &[&str]) {
+        let catalog = ctx.get_catalog();
+        let catalog = catalog.read();
+        let bpm = catalog.get_bpm();
+        for name in tables {
+            let table = catalog.get_table(name, None).unwrap();
+            for page in table.first_page..=table.last_page {
+                assert_eq!(bpm.lock().get_pin_count(&page).unwrap_or(0), 0);
+            }
+        }
+    }
 
-    fn values_plan(values: &[Vec<Value>], fields: &[Field]) -> LogicalPlan {
-        LogicalPlan::Values(Values::new(
+    pub(super) fn values_plan(values: &[Vec<Value>], fields: &[Field]) -> PhysicalPlan {
+        PhysicalPlan::Values(Values::new(
             values_to_exprs(values),
             Schema::new(fields.to_vec()),
         ))
@@ -874,7 +1387,7 @@ mod tests {
             LogicalExpr::Literal(lit!(UInt, "2")?),
         );
 
-        let plan = Filter::new(root, filter);
+        let plan = PhysicalPlan::Filter(Box::new(Filter::new(root, filter)));
 
         let expected = ResultSet::from_rows(schema.fields.clone(), values[2..].to_vec());
         let output = plan.execute(&mut ctx)?;
@@ -907,7 +1420,7 @@ mod tests {
             LogicalExpr::Column("col_3".to_string()),
         ];
 
-        let plan = Projection::new(root, projections);
+        let plan = PhysicalPlan::Projection(Box::new(Projection::new(root, projections)));
 
         let expected =
             ResultSet::from_rows(schema.fields.clone(), values.to_vec()).select(vec![0, 2]);
@@ -916,4 +1429,183 @@ mod tests {
         assert_eq!(output, expected);
         Ok(())
     }
+
+    /// The rows of a result in a fixed order, joins don't keep any
+    pub(super) fn sorted_rows(result: &ResultSet) -> Vec<Vec<Value>> {
+        let mut rows = result.rows();
+        rows.sort_by_key(|row| format!("{row:?}"));
+        rows
+    }
+
+    #[test]
+    fn test_hash_join_matches_nested_loop_join() -> Result<()> {
+        let mut ctx = test_context();
+
+        let left_fields = vec![
+            Field::new("a", Types::Int, Constraints::nullable(false)),
+            Field::new("b", Types::Int, Constraints::nullable(false)),
+        ];
+        let right_fields = vec![
+            Field::new("c", Types::Int, Constraints::nullable(false)),
+            Field::new("d", Types::Int, Constraints::nullable(false)),
+        ];
+        let int = |v: &str| lit!(Int, v);
+        let left = vec![
+            vec![int("1")?, int("10")?],
+            vec![int("2")?, int("20")?],
+            vec![int("3")?, int("30")?],
+        ];
+        let right = vec![
+            vec![int("1")?, int("5")?],
+            vec![int("1")?, int("15")?],
+            vec![int("3")?, int("50")?],
+            vec![int("4")?, int("40")?],
+        ];
+
+        let column = |name: &str| LogicalExpr::Column(name.to_string());
+        // a = c AND b > d
+        let on = BinaryExpr::new(
+            LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+                column("a"),
+                BinaryOperator::Eq,
+                column("c"),
+            ))),
+            BinaryOperator::And,
+            LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+                column("b"),
+                BinaryOperator::Gt,
+                column("d"),
+            ))),
+        );
+        let filter = BinaryExpr::new(column("b"), BinaryOperator::Gt, column("d"));
+
+        for join_type in [
+            JoinType::Inner,
+            JoinType::Left,
+            JoinType::Right,
+            JoinType::Full,
+            JoinType::Semi,
+            JoinType::Anti,
+            JoinType::Mark,
+        ] {
+            let mut fields = left_fields.clone();
+            match join_type {
+                JoinType::Semi | JoinType::Anti => {}
+                JoinType::Mark => fields.push(Field::new(
+                    "mark",
+                    Types::Bool,
+                    Constraints::nullable(false),
+                )),
+                _ => fields.extend(right_fields.clone()),
+            }
+            let schema = Schema::new(fields);
+
+            let nested_loop = PhysicalPlan::NestedLoopJoin(Box::new(NestedLoopJoin::new(
+                values_plan(&left, &left_fields),
+                values_plan(&right, &right_fields),
+                on.clone(),
+                join_type,
+                schema.clone(),
+            )));
+            let hash = PhysicalPlan::HashJoin(Box::new(HashJoin::new(
+                values_plan(&left, &left_fields),
+                values_plan(&right, &right_fields),
+                vec![(column("a"), column("c"))],
+                Some(filter.clone()),
+                join_type,
+                schema,
+            )));
+
+            let (hash, nested_loop) = (hash.execute(&mut ctx)?, nested_loop.execute(&mut ctx)?);
+            assert_eq!(hash.fields(), nested_loop.fields());
+            assert_eq!(sorted_rows(&hash), sorted_rows(&nested_loop));
+        }
+
+        Ok(())
+    }
+
+    /// A table with three batches worth of rows over many pages
+    fn large_table(ctx: &mut Context) -> Result<(Schema, u32, u32)> {
+        ctx.execute_sql("CREATE TABLE t (a int);")?;
+        let values = (0..3 * BATCH_SIZE)
+            .map(|i| format!("({i})"))
+            .collect::<Vec<_>>()
+            .join(",");
+        ctx.execute_sql(format!("INSERT INTO t VALUES {values};"))?;
+
+        let catalog = ctx.get_catalog();
+        let catalog = catalog.read();
+        let table = catalog.get_table("t", None).unwrap();
+        Ok((table.get_schema(), table.first_page, table.last_page))
+    }
+
+    #[test]
+    fn test_limit_stops_scan_early() -> Result<()> {
+        let mut ctx = test_context();
+        let (schema, first_page, last_page) = large_table(&mut ctx)?;
+        let bpm = ctx.get_catalog().read().get_bpm();
+        let pinned = || -> u16 {
+            (first_page..=last_page)
+                .map(|page| bpm.lock().get_pin_count(&page).unwrap_or(0))
+                .sum()
+        };
+
+        let scan = PhysicalPlan::SeqScan(SeqScan::new("t".into(), schema));
+        let plan = PhysicalPlan::Limit(Box::new(Limit::new(scan, 1, BATCH_SIZE as u32)));
+
+        let mut operator = plan.open(&mut ctx)?;
+        let mut rows = vec![];
+        while let Some(batch) = operator.next(&mut ctx)? {
+            // after the tuple id
+            rows.extend(batch.rows().into_iter().map(|row| row[2].clone()));
+        }
+        assert_eq!(rows, vec![lit!(Int, BATCH_SIZE.to_string())?]);
+
+        // the scan stopped in the middle of the table, on a page it still holds
+        assert_eq!(pinned(), 1);
+        drop(operator);
+        assert_eq!(pinned(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_scan_unpins_pages() -> Result<()> {
+        let mut ctx = test_context();
+        ctx.execute_sql("CREATE TABLE t (id int UNIQUE NOT NULL, a int);")?;
+        ctx.execute_sql("INSERT INTO t VALUES (1, 10), (2, 20), (3, 30), (4, 40);")?;
+
+        for (sql, rows) in [
+            ("SELECT a FROM t WHERE id = 2;", 1),
+            ("SELECT a FROM t WHERE id > 1 AND id < 4;", 2),
+        ] {
+            let plan = ctx.execute_sql(format!("EXPLAIN {sql}"))?;
+            assert!(plan.get_info().contains("IndexScan"));
+            assert_eq!(ctx.execute_sql(sql)?.len(), rows);
+        }
+
+        assert_unpinned(&ctx, &["t"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiple_batches() -> Result<()> {
+        let mut ctx = test_context();
+        large_table(&mut ctx)?;
+
+        let count = ctx.execute_sql("SELECT COUNT(*) FROM t WHERE a > 100;")?;
+        assert_eq!(count.rows(), vec![vec![lit!(Int, "2971")?]]);
+
+        let rows = ctx.execute_sql("SELECT a FROM t ORDER BY a DESC LIMIT 2 OFFSET 1025;")?;
+        assert_eq!(
+            rows.rows(),
+            vec![vec![lit!(Int, "2046")?], vec![lit!(Int, "2045")?]]
+        );
+
+        let rows = ctx.execute_sql("SELECT DISTINCT a FROM t UNION ALL SELECT a FROM t;")?;
+        assert_eq!(rows.len(), 6 * BATCH_SIZE);
+
+        Ok(())
+    }
 }
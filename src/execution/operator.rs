@@ -0,0 +1,154 @@
+use anyhow::Result;
+
+use super::result_set::ResultSet;
+use crate::context::Context;
+
+/// How many rows an operator returns at a time
+pub const BATCH_SIZE: usize = 1024;
+
+/// A running plan that returns its rows in batches of up to `BATCH_SIZE`
+/// rows, pulled by the operator above it. The first batch is always
+/// returned, even if empty, so the columns are known without rows
+pub trait Operator {
+    /// The next batch of rows, None once there are no more
+    fn next(&mut self, ctx: &mut Context) -> Result<Option<ResultSet>>;
+}
+
+type Run<'a> = Box<dyn FnOnce(&mut Context) -> Result<ResultSet> + 'a>;
+
+/// Returns the result of a plan that doesn't stream as a single batch, the
+/// plan runs when the batch is first pulled
+pub struct Once<'a> {
+    run: Option<Run<'a>>,
+}
+
+impl<'a> Once<'a> {
+    pub fn new(run: impl FnOnce(&mut Context) -> Result<ResultSet> + 'a) -> Self {
+        Self {
+            run: Some(Box::new(run)),
+        }
+    }
+}
+
+impl Operator for Once<'_> {
+    fn next(&mut self, ctx: &mut Context) -> Result<Option<ResultSet>> {
+        self.run.take().map(|run| run(ctx)).transpose()
+    }
+}
+
+/// Applies `f` to each batch of the input
+pub struct Map<'a, F> {
+    input: Box<dyn Operator + 'a>,
+    f: F,
+}
+
+impl<'a, F> Map<'a, F>
+where
+    F: FnMut(ResultSet) -> Result<ResultSet>,
+{
+    pub fn new(input: Box<dyn Operator + 'a>, f: F) -> Self {
+        Self { input, f }
+    }
+}
+
+impl<F> Operator for Map<'_, F>
+where
+    F: FnMut(ResultSet) -> Result<ResultSet>,
+{
+    fn next(&mut self, ctx: &mut Context) -> Result<Option<ResultSet>> {
+        self.input.next(ctx)?.map(&mut self.f).transpose()
+    }
+}
+
+/// Stops pulling the input once `limit` rows after the first `offset` ones
+/// were returned
+pub struct Limit<'a> {
+    input: Box<dyn Operator + 'a>,
+    offset: usize,
+    remaining: usize,
+    started: bool,
+}
+
+impl<'a> Limit<'a> {
+    pub fn new(input: Box<dyn Operator + 'a>, limit: u32, offset: u32) -> Self {
+        Self {
+            input,
+            offset: offset as usize,
+            remaining: limit as usize,
+            started: false,
+        }
+    }
+}
+
+impl Operator for Limit<'_> {
+    fn next(&mut self, ctx: &mut Context) -> Result<Option<ResultSet>> {
+        while !self.started || self.remaining > 0 {
+            let Some(batch) = self.input.next(ctx)? else {
+                return Ok(None);
+            };
+
+            let skipped = self.offset.min(batch.len());
+            self.offset -= skipped;
+            let batch = batch.skip(skipped as u32).take(self.remaining as u32);
+            self.remaining -= batch.len();
+
+            if !batch.is_empty() || !self.started {
+                self.started = true;
+                return Ok(Some(batch));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Returns the batches of the left input and then the ones of the right,
+/// under the left's columns
+pub struct Union<'a> {
+    left: Box<dyn Operator + 'a>,
+    right: Box<dyn Operator + 'a>,
+    columns: Option<ResultSet>,
+    left_done: bool,
+}
+
+impl<'a> Union<'a> {
+    pub fn new(left: Box<dyn Operator + 'a>, right: Box<dyn Operator + 'a>) -> Self {
+        Self {
+            left,
+            right,
+            columns: None,
+            left_done: false,
+        }
+    }
+}
+
+impl Operator for Union<'_> {
+    fn next(&mut self, ctx: &mut Context) -> Result<Option<ResultSet>> {
+        if !self.left_done {
+            match self.left.next(ctx)? {
+                Some(batch) => {
+                    if self.columns.is_none() {
+                        self.columns = Some(batch.clone().take(0));
+                    }
+                    return Ok(Some(batch));
+                }
+                None => self.left_done = true,
+            }
+        }
+
+        let columns = self.columns.clone().unwrap_or_default();
+        self.right
+            .next(ctx)?
+            .map(|batch| columns.union(batch))
+            .transpose()
+    }
+}
+
+/// Pulls every batch of the operator into a single result
+pub fn collect(mut operator: Box<dyn Operator + '_>, ctx: &mut Context) -> Result<ResultSet> {
+    let mut output = operator.next(ctx)?.unwrap_or_default();
+    while let Some(batch) = operator.next(ctx)? {
+        output = output.union(batch)?;
+    }
+    Ok(output)
+}
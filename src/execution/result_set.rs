@@ -1,10 +1,11 @@
+use std::collections::{HashMap, HashSet};
 use std::mem::take;
 
 use anyhow::{bail, Result};
 
 use crate::errors::Error;
 use crate::tuple::schema::{Field, Schema};
-use crate::types::Value;
+use crate::types::{Types, Value};
 
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct ResultSet {
@@ -73,6 +74,7 @@ impl ResultSet {
         self.cols
             .iter_mut()
             .for_each(|col| col.truncate(cap as usize));
+        self.cap = self.cap.min(cap as usize);
         self
     }
 
@@ -82,9 +84,30 @@ impl ResultSet {
             .into_iter()
             .map(|col| col.into_iter().skip(skip as usize).collect())
             .collect();
+        self.cap = self.cap.saturating_sub(skip as usize);
         self
     }
 
+    /// The rows the mask is true for
+    pub fn filter(self, mask: &[bool]) -> Self {
+        let cols = self
+            .cols
+            .into_iter()
+            .map(|col| {
+                col.into_iter()
+                    .zip(mask)
+                    .filter_map(|(value, &keep)| keep.then_some(value))
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            cols,
+            cap: mask.iter().filter(|keep| **keep).count(),
+            ..self
+        }
+    }
+
     pub fn from_tuple(field: Vec<Field>, tuple: Vec<Value>, cap: usize) -> Self {
         let cols = (0..field.len())
             .map(|i| (0..cap).map(|_| tuple[i].clone()).collect())
@@ -109,12 +132,12 @@ impl ResultSet {
         self.len() == 0
     }
 
-    pub fn union(mut self, other: ResultSet) -> Result<Self> {
+    fn check_compatible(&self, other: &ResultSet) -> Result<()> {
         if self
             .fields()
             .iter()
             .zip(other.fields())
-            .any(|(f1, f2)| !f1.ty.is_compatible(&f2.ty))
+            .any(|(f1, f2)| Types::common(&[f1.ty.clone(), f2.ty.clone()]).is_none())
         {
             bail!(Error::TypeMismatch(
                 self.fields().iter().map(|f| f.ty.clone()).collect(),
@@ -122,6 +145,26 @@ impl ResultSet {
             ));
         }
 
+        Ok(())
+    }
+
+    /// How many times each row appears
+    fn row_counts(&self) -> HashMap<Vec<Value>, usize> {
+        let mut counts = HashMap::new();
+        for row in self.rows() {
+            *counts.entry(row).or_default() += 1;
+        }
+        counts
+    }
+
+    pub fn union(mut self, other: ResultSet) -> Result<Self> {
+        self.check_compatible(&other)?;
+
+        // an empty projection carries no columns at all
+        if self.cols.is_empty() {
+            return Ok(other);
+        }
+
         for (i, col) in other.cols.into_iter().enumerate() {
             self.cols[i].extend(col);
         }
@@ -130,6 +173,63 @@ impl ResultSet {
         Ok(self)
     }
 
+    /// Keeps the first occurrence of every row
+    pub fn distinct(self) -> Self {
+        let mut seen = HashSet::new();
+        let rows = self
+            .rows()
+            .into_iter()
+            .filter(|row| seen.insert(row.clone()))
+            .collect();
+
+        Self::from_rows(self.schema.fields, rows)
+    }
+
+    /// Rows of self that are also in other. With `all` a row
+    /// appears as many times as it does in both, otherwise once
+    pub fn intersect(self, other: ResultSet, all: bool) -> Result<Self> {
+        self.check_compatible(&other)?;
+
+        let mut counts = other.row_counts();
+        let rows = self
+            .rows()
+            .into_iter()
+            .filter(|row| match counts.get_mut(row) {
+                Some(count) if *count > 0 => {
+                    *count = if all { *count - 1 } else { 0 };
+                    true
+                }
+                _ => false,
+            })
+            .collect();
+
+        Ok(Self::from_rows(self.schema.fields, rows))
+    }
+
+    /// Rows of self that are not in other. With `all` each row in
+    /// other cancels out a single matching row, otherwise all of them
+    pub fn except(self, other: ResultSet, all: bool) -> Result<Self> {
+        self.check_compatible(&other)?;
+
+        let mut counts = other.row_counts();
+        let mut seen = HashSet::new();
+        let rows = self
+            .rows()
+            .into_iter()
+            .filter(|row| match counts.get_mut(row) {
+                Some(count) if all && *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                Some(_) if all => true,
+                Some(_) => false,
+                None => all || seen.insert(row.clone()),
+            })
+            .collect();
+
+        Ok(Self::from_rows(self.schema.fields, rows))
+    }
+
     pub fn select(mut self, indexes: Vec<usize>) -> Self {
         self.schema = Schema::new(
             indexes
@@ -0,0 +1,305 @@
+use anyhow::{anyhow, Result};
+
+use super::operator::{Operator, BATCH_SIZE};
+use super::result_set::ResultSet;
+use crate::catalog::{ArcCatalog, Catalog, CATALOG_NAME};
+use crate::context::Context;
+use crate::errors::Error;
+use crate::lit;
+use crate::pages::indexes::b_plus_tree::Key;
+use crate::sql::logical_plan::plan::TUPLE_ID_COLUMNS;
+use crate::sql::physical_plan::plan::{IndexScan, SeqScan};
+use crate::table::table_iterator::TableIterator;
+use crate::table::Table;
+use crate::tuple::constraints::Constraints;
+use crate::tuple::schema::{Field, Schema};
+use crate::tuple::{Tuple, TupleId};
+use crate::txn_manager::TxnId;
+use crate::types::{Types, Value, ValueFactory};
+
+/// The columns of a scanned table, the tuple id comes first
+pub(super) struct ScanColumns {
+    fields: Vec<Field>,
+    // the optimizer may have pruned columns nobody reads
+    indexes: Vec<usize>,
+}
+
+impl ScanColumns {
+    pub(super) fn new(table: &Table, schema: &Schema) -> Self {
+        let table_schema = table.get_schema();
+
+        let mut fields = vec![
+            Field::new("page_id", Types::UInt, Constraints::nullable(false)),
+            Field::new("slot_id", Types::UInt, Constraints::nullable(false)),
+        ];
+        fields.extend(table_schema.fields.clone());
+
+        // a scan that asks for the tuple id as columns of its own already
+        // starts with it
+        let asks_tuple_id = schema
+            .fields
+            .iter()
+            .any(|f| TUPLE_ID_COLUMNS.contains(&f.name.as_str()));
+        let mut indexes = match asks_tuple_id {
+            true => vec![],
+            false => vec![0, 1],
+        };
+        indexes.extend(schema.fields.iter().map(|field| {
+            match TUPLE_ID_COLUMNS.iter().position(|c| *c == field.name) {
+                Some(i) => i,
+                None => {
+                    2 + table_schema
+                        .fields
+                        .iter()
+                        .position(|f| f.name == field.name)
+                        .unwrap()
+                }
+            }
+        }));
+
+        Self { fields, indexes }
+    }
+
+    pub(super) fn batch(&self, table: &Table, tuples: Vec<(TupleId, Tuple)>) -> Result<ResultSet> {
+        let mut cols: Vec<Vec<Value>> = vec![vec![]; self.fields.len()];
+
+        // TODO: pass the tuple_id as tuple type for update to use
+        // need to define a tuple type first though
+        for ((page_id, slot_id), tuple) in tuples {
+            let mut values = vec![
+                lit!(UInt, page_id.to_string())?,
+                lit!(UInt, slot_id.to_string())?,
+            ];
+
+            values.extend(table.get_portable_values(&tuple)?);
+
+            values.into_iter().enumerate().for_each(|(i, v)| {
+                cols[i].push(v);
+            });
+        }
+
+        if table.name == CATALOG_NAME {
+            // deserialize the schema and print as sql
+            cols[6] = cols[6]
+                .iter()
+                .map(|v| Schema::from_bytes(v.str().as_bytes()).to_sql())
+                .map(|s| lit!(Str, s).unwrap())
+                .collect();
+        }
+
+        Ok(ResultSet::new(self.fields.clone(), cols).select(self.indexes.clone()))
+    }
+}
+
+pub(super) fn get_table<'c>(
+    catalog: &'c Catalog,
+    table_name: &str,
+    txn_id: Option<TxnId>,
+) -> Result<&'c Table> {
+    catalog
+        .get_table(table_name, txn_id)
+        .ok_or(Error::TableNotFound(table_name.to_string()).into())
+}
+
+/// Reads the table's pages in order, a batch at a time. Only the page being
+/// read stays pinned between batches
+pub struct SeqScanOperator {
+    table_name: String,
+    catalog: ArcCatalog,
+    txn_id: Option<TxnId>,
+    columns: ScanColumns,
+    tuples: TableIterator,
+    started: bool,
+    done: bool,
+}
+
+impl SeqScanOperator {
+    pub fn new(scan: &SeqScan, ctx: &mut Context) -> Result<Self> {
+        let txn_id = ctx.get_active_txn();
+        let catalog = ctx.get_catalog();
+        let guard = catalog.read();
+        let table = get_table(&guard, &scan.table_name, txn_id)?;
+
+        let columns = ScanColumns::new(table, &scan.schema);
+        let tuples = table.iter(txn_id);
+        drop(guard);
+
+        Ok(Self {
+            table_name: scan.table_name.clone(),
+            catalog,
+            txn_id,
+            columns,
+            tuples,
+            started: false,
+            done: false,
+        })
+    }
+}
+
+impl Operator for SeqScanOperator {
+    fn next(&mut self, _: &mut Context) -> Result<Option<ResultSet>> {
+        if self.done && self.started {
+            return Ok(None);
+        }
+
+        let tuples = self
+            .tuples
+            .by_ref()
+            .take(BATCH_SIZE)
+            .map(|(id, (_, tuple))| (id, tuple))
+            .collect::<Vec<_>>();
+
+        self.done = tuples.len() < BATCH_SIZE;
+        if tuples.is_empty() && self.started {
+            return Ok(None);
+        }
+        self.started = true;
+
+        let catalog = self.catalog.read();
+        let table = get_table(&catalog, &self.table_name, self.txn_id)?;
+        self.columns.batch(table, tuples).map(Some)
+    }
+}
+
+/// Walks the index over the scan's range of keys, a batch at a time. Each
+/// batch starts a new walk after the last key returned, keys are unique.
+/// A scan of a list of keys looks each of them up instead
+pub struct IndexScanOperator<'a> {
+    scan: &'a IndexScan,
+    catalog: ArcCatalog,
+    txn_id: Option<TxnId>,
+    columns: ScanColumns,
+    // the lower bound of the next batch, and whether it's inclusive
+    from: Option<(Key, bool)>,
+    // how many of the scan's keys were looked up
+    looked_up: usize,
+    started: bool,
+    done: bool,
+}
+
+impl<'a> IndexScanOperator<'a> {
+    pub fn new(scan: &'a IndexScan, ctx: &mut Context) -> Result<Self> {
+        let txn_id = ctx.get_active_txn();
+        let catalog = ctx.get_catalog();
+        let columns = ScanColumns::new(
+            get_table(&catalog.read(), &scan.table_name, txn_id)?,
+            &scan.schema,
+        );
+
+        Ok(Self {
+            scan,
+            catalog,
+            txn_id,
+            columns,
+            from: scan.from.map(|key| (key, scan.include_from)),
+            looked_up: 0,
+            started: false,
+            done: false,
+        })
+    }
+
+    fn tuple_ids(&mut self, table: &Table) -> Result<Vec<(Key, TupleId)>> {
+        let mut tuple_ids = vec![];
+        let (to, include_to) = (self.scan.to, self.scan.include_to);
+        let from = self.from;
+        // the scanner fails to stop the walk, which isn't an error
+        let mut stopped = false;
+
+        let scanner = |&(key, tuple_id): &(Key, TupleId)| {
+            if let Some((from, include_from)) = from {
+                if key == from && !include_from {
+                    return Ok(());
+                }
+            }
+
+            if let Some(to) = to {
+                if key > to || (key == to && !include_to) {
+                    stopped = true;
+                    return Err(anyhow!("End of loop"));
+                };
+            }
+
+            tuple_ids.push((key, tuple_id));
+            match tuple_ids.len() < BATCH_SIZE {
+                true => Ok(()),
+                false => {
+                    stopped = true;
+                    Err(anyhow!("End of batch"))
+                }
+            }
+        };
+
+        let index = table.get_index().as_ref().unwrap();
+
+        let scanned = match from {
+            Some((from, _)) => index.scan_from(self.txn_id, from, scanner),
+            None => index.scan(self.txn_id, scanner),
+        };
+        if !stopped {
+            scanned?;
+        }
+
+        Ok(tuple_ids)
+    }
+
+    /// Looks up keys until a batch of them was found, keys without a tuple
+    /// are skipped
+    fn lookups(&mut self, table: &Table, keys: &[Key]) -> Vec<(Key, TupleId)> {
+        let index = table.get_index().as_ref().unwrap();
+        let mut tuple_ids = vec![];
+
+        while tuple_ids.len() < BATCH_SIZE && self.looked_up < keys.len() {
+            let key = keys[self.looked_up];
+            self.looked_up += 1;
+            if let Some(tuple_id) = index.search(self.txn_id, key) {
+                tuple_ids.push((key, tuple_id));
+            }
+        }
+
+        tuple_ids
+    }
+}
+
+impl Operator for IndexScanOperator<'_> {
+    fn next(&mut self, _: &mut Context) -> Result<Option<ResultSet>> {
+        if self.done && self.started {
+            return Ok(None);
+        }
+
+        let catalog = self.catalog.clone();
+        let catalog = catalog.read();
+        let table = get_table(&catalog, &self.scan.table_name, self.txn_id)?;
+
+        let tuple_ids = match &self.scan.keys {
+            Some(keys) => {
+                let tuple_ids = self.lookups(table, keys);
+                self.done = self.looked_up == keys.len();
+                tuple_ids
+            }
+            None => {
+                let tuple_ids = self.tuple_ids(table)?;
+                self.done = tuple_ids.len() < BATCH_SIZE;
+                if let Some(&(key, _)) = tuple_ids.last() {
+                    self.from = Some((key, false));
+                }
+                tuple_ids
+            }
+        };
+        if tuple_ids.is_empty() && self.started {
+            return Ok(None);
+        }
+        self.started = true;
+
+        let tuples = tuple_ids
+            .into_iter()
+            .map(|(_, tuple_id)| {
+                let tuple = table
+                    .get_tuple(tuple_id)
+                    .expect("Index returned a deleted record");
+                (tuple_id, tuple)
+            })
+            .collect();
+
+        self.columns.batch(table, tuples).map(Some)
+    }
+}
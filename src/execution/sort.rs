@@ -0,0 +1,324 @@
+use std::cmp::Ordering;
+
+use anyhow::Result;
+use bincode::serialized_size;
+use serde::{Deserialize, Serialize};
+
+use super::operator::{Operator, BATCH_SIZE};
+use super::result_set::ResultSet;
+use super::spill::Spill;
+use crate::buffer_pool::ArcBufferPool;
+use crate::context::Context;
+use crate::sql::logical_plan::expr::SortExpr;
+use crate::sql::physical_plan::plan::Sort;
+use crate::tuple::schema::Field;
+use crate::types::Value;
+
+/// How many bytes of rows are sorted in memory before being spilled as a run
+pub const SORT_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+
+/// A row along with its already evaluated sort keys
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    keys: Vec<Value>,
+    row: Vec<Value>,
+}
+
+pub(super) fn compare(exprs: &[SortExpr], left: &[Value], right: &[Value]) -> Ordering {
+    for (expr, (l, r)) in exprs.iter().zip(left.iter().zip(right)) {
+        let ord = match (l.is_null(), r.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) if expr.nulls_first => Ordering::Less,
+            (true, false) => Ordering::Greater,
+            (false, true) if expr.nulls_first => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => {
+                let ord = l.partial_cmp(r).unwrap_or(Ordering::Equal);
+                if expr.asc {
+                    ord
+                } else {
+                    ord.reverse()
+                }
+            }
+        };
+
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+
+    Ordering::Equal
+}
+
+/// External merge sort. Rows are buffered and sorted in memory until they
+/// exceed the budget, each sorted buffer is then spilled to temporary pages
+/// through the buffer pool and the runs are merged back when finished.
+/// The sort is stable
+pub struct ExternalSorter<'a> {
+    exprs: &'a [SortExpr],
+    bpm: ArcBufferPool,
+    budget: usize,
+    buffer: Vec<Entry>,
+    buffered: usize,
+    runs: Vec<Spill<Entry>>,
+}
+
+impl<'a> ExternalSorter<'a> {
+    pub fn new(exprs: &'a [SortExpr], bpm: ArcBufferPool, budget: usize) -> Self {
+        Self {
+            exprs,
+            bpm,
+            budget,
+            buffer: vec![],
+            buffered: 0,
+            runs: vec![],
+        }
+    }
+
+    pub fn push(&mut self, keys: Vec<Value>, row: Vec<Value>) -> Result<()> {
+        let entry = Entry { keys, row };
+        self.buffered += serialized_size(&entry)? as usize;
+        self.buffer.push(entry);
+
+        if self.buffered > self.budget {
+            self.spill()?;
+        }
+
+        Ok(())
+    }
+
+    fn sort_buffer(&mut self) {
+        let exprs = self.exprs;
+        self.buffer.sort_by(|l, r| compare(exprs, &l.keys, &r.keys));
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        self.sort_buffer();
+        let mut run = Spill::new(self.bpm.clone());
+        for entry in self.buffer.iter() {
+            run.push(entry)?;
+        }
+        run.seal()?;
+        self.runs.push(run);
+        self.buffer.clear();
+        self.buffered = 0;
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub fn spilled_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn finish(mut self) -> Result<SortedRows<'a>> {
+        if self.runs.is_empty() {
+            self.sort_buffer();
+        } else if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+
+        let heads = self
+            .runs
+            .iter_mut()
+            .map(|run| run.next())
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(SortedRows {
+            exprs: self.exprs,
+            buffer: std::mem::take(&mut self.buffer).into_iter(),
+            runs: std::mem::take(&mut self.runs),
+            heads,
+        })
+    }
+}
+
+/// The rows of a finished sort, spilled runs are merged as the rows are read
+pub struct SortedRows<'a> {
+    exprs: &'a [SortExpr],
+    buffer: std::vec::IntoIter<Entry>,
+    runs: Vec<Spill<Entry>>,
+    heads: Vec<Option<Entry>>,
+}
+
+impl SortedRows<'_> {
+    fn merge_next(&mut self) -> Result<Option<Vec<Value>>> {
+        // runs hold consecutive chunks of the input, picking the first of
+        // equal heads keeps the merge stable
+        let mut min: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            let Some(entry) = head else { continue };
+            let is_smaller = match min {
+                None => true,
+                Some(m) => {
+                    let current = self.heads[m].as_ref().unwrap();
+                    compare(self.exprs, &entry.keys, &current.keys) == Ordering::Less
+                }
+            };
+            if is_smaller {
+                min = Some(i);
+            }
+        }
+
+        let Some(i) = min else { return Ok(None) };
+        let next = self.runs[i].next()?;
+        let entry = std::mem::replace(&mut self.heads[i], next).unwrap();
+        Ok(Some(entry.row))
+    }
+}
+
+impl Iterator for SortedRows<'_> {
+    type Item = Result<Vec<Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.runs.is_empty() {
+            return self.buffer.next().map(|entry| Ok(entry.row));
+        }
+
+        self.merge_next().transpose()
+    }
+}
+
+/// Sorts every row of the input before returning the first batch, the
+/// sorted rows are then read back a batch at a time
+pub struct SortOperator<'a> {
+    sort: &'a Sort,
+    input: Option<Box<dyn Operator + 'a>>,
+    fields: Vec<Field>,
+    rows: Option<SortedRows<'a>>,
+}
+
+impl<'a> SortOperator<'a> {
+    pub fn new(sort: &'a Sort, input: Box<dyn Operator + 'a>) -> Self {
+        Self {
+            sort,
+            input: Some(input),
+            fields: vec![],
+            rows: None,
+        }
+    }
+
+    fn sort_input(&mut self, mut input: Box<dyn Operator + 'a>, ctx: &mut Context) -> Result<()> {
+        let sort = self.sort;
+        let bpm = ctx.get_catalog().read().get_bpm();
+        let mut sorter = ExternalSorter::new(&sort.exprs, bpm, SORT_BUFFER_SIZE);
+
+        while let Some(batch) = input.next(ctx)? {
+            let keys = sort
+                .exprs
+                .iter()
+                .map(|e| Ok(e.expr.evaluate(&batch)?.1))
+                .collect::<Result<Vec<_>>>()?;
+
+            for (i, row) in batch.rows().into_iter().enumerate() {
+                sorter.push(keys.iter().map(|col| col[i].clone()).collect(), row)?;
+            }
+
+            self.fields = batch.schema.fields;
+        }
+
+        self.rows = Some(sorter.finish()?);
+        Ok(())
+    }
+}
+
+impl Operator for SortOperator<'_> {
+    fn next(&mut self, ctx: &mut Context) -> Result<Option<ResultSet>> {
+        let first = match self.input.take() {
+            Some(input) => {
+                self.sort_input(input, ctx)?;
+                true
+            }
+            None => false,
+        };
+
+        let rows = self
+            .rows
+            .as_mut()
+            .unwrap()
+            .take(BATCH_SIZE)
+            .collect::<Result<Vec<_>>>()?;
+
+        if rows.is_empty() && !first {
+            return Ok(None);
+        }
+
+        Ok(Some(ResultSet::from_rows(self.fields.clone(), rows)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer_pool::tests::test_arc_bpm;
+    use crate::pages::PAGE_SIZE;
+    use crate::sql::logical_plan::expr::LogicalExpr;
+    use crate::types::{Str, UInt};
+
+    fn sort_expr(asc: bool, nulls_first: Option<bool>) -> SortExpr {
+        SortExpr::new(LogicalExpr::Column("a".into()), Some(asc), nulls_first)
+    }
+
+    #[test]
+    fn test_in_memory_sort() -> Result<()> {
+        let bpm = test_arc_bpm(10);
+        let exprs = [sort_expr(false, None)];
+        let mut sorter = ExternalSorter::new(&exprs, bpm, SORT_BUFFER_SIZE);
+
+        for v in [Value::UInt(UInt(2)), Value::Null, Value::UInt(UInt(3))] {
+            sorter.push(vec![v.clone()], vec![v])?;
+        }
+
+        assert_eq!(sorter.spilled_runs(), 0);
+        assert_eq!(
+            sorter.finish()?.collect::<Result<Vec<_>>>()?,
+            vec![
+                vec![Value::Null],
+                vec![Value::UInt(UInt(3))],
+                vec![Value::UInt(UInt(2))]
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_spill_to_disk() -> Result<()> {
+        // fewer frames than the pages the runs need
+        let bpm = test_arc_bpm(10);
+        let exprs = [sort_expr(true, Some(true))];
+        let mut sorter = ExternalSorter::new(&exprs, bpm, PAGE_SIZE);
+
+        let padding = "x".repeat(500);
+        let rows = 1000;
+        for i in 0..rows {
+            let key = match i % 100 {
+                0 => Value::Null,
+                _ => Value::UInt(UInt((i * 7919) % 100)),
+            };
+            // the second column records the input order
+            let row = vec![
+                key.clone(),
+                Value::UInt(UInt(i)),
+                Value::Str(Str(padding.clone())),
+            ];
+            sorter.push(vec![key], row)?;
+        }
+
+        assert!(sorter.spilled_runs() > 1);
+
+        let output = sorter.finish()?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(output.len(), rows as usize);
+
+        for pair in output.windows(2) {
+            let (l, r) = (&pair[0], &pair[1]);
+            match (&l[0], &r[0]) {
+                (Value::Null, Value::Null) => assert!(l[1] < r[1]),
+                (Value::Null, _) => {}
+                (_, Value::Null) => panic!("NULL sorted after a value"),
+                (a, b) => assert!(a < b || (a == b && l[1] < r[1])),
+            }
+        }
+
+        Ok(())
+    }
+}
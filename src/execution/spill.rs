@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+use anyhow::Result;
+use bincode::{deserialize, serialize};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::buffer_pool::ArcBufferPool;
+use crate::pages::traits::Serialize as _;
+use crate::pages::{PageId, PAGE_SIZE};
+
+/// Entries stored on temporary pages as a stream of length-prefixed
+/// entries, an entry may span multiple pages. Entries are read back in the
+/// order they were pushed, after `seal`. Pages are deleted as soon as they
+/// are read back
+pub struct Spill<T> {
+    bpm: ArcBufferPool,
+    pages: VecDeque<PageId>,
+    len: usize,
+    page: Vec<u8>,
+    offset: usize,
+    entries: PhantomData<T>,
+}
+
+impl<T: Serialize + DeserializeOwned> Spill<T> {
+    pub fn new(bpm: ArcBufferPool) -> Self {
+        Self {
+            bpm,
+            pages: VecDeque::new(),
+            len: 0,
+            page: Vec::with_capacity(PAGE_SIZE),
+            offset: 0,
+            entries: PhantomData,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, entry: &T) -> Result<()> {
+        let bytes = serialize(entry)?;
+        self.append(&(bytes.len() as u32).to_ne_bytes())?;
+        self.append(&bytes)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Writes the last, partially filled, page. No entry can be pushed after
+    pub fn seal(&mut self) -> Result<()> {
+        if !self.page.is_empty() {
+            self.page.resize(PAGE_SIZE, 0);
+            self.flush_page()?;
+        }
+
+        Ok(())
+    }
+
+    fn append(&mut self, mut bytes: &[u8]) -> Result<()> {
+        while !bytes.is_empty() {
+            let n = bytes.len().min(PAGE_SIZE - self.page.len());
+            self.page.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+
+            if self.page.len() == PAGE_SIZE {
+                self.flush_page()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn flush_page(&mut self) -> Result<()> {
+        let mut bpm = self.bpm.lock();
+        let page_id = bpm.new_page()?.reader().get_page_id();
+        bpm.write_temp_page(page_id, &self.page)?;
+        self.pages.push_back(page_id);
+        self.page.clear();
+        Ok(())
+    }
+
+    fn read(&mut self, mut n: usize) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(n);
+
+        while n > 0 {
+            if self.offset == self.page.len() {
+                self.load_page()?;
+            }
+
+            let take = n.min(self.page.len() - self.offset);
+            bytes.extend_from_slice(&self.page[self.offset..self.offset + take]);
+            self.offset += take;
+            n -= take;
+        }
+
+        Ok(bytes)
+    }
+
+    fn load_page(&mut self) -> Result<()> {
+        let page_id = self
+            .pages
+            .pop_front()
+            .expect("read past the end of a spill");
+
+        let mut bpm = self.bpm.lock();
+        self.page = bpm.fetch_frame(page_id, None)?.reader().to_bytes().to_vec();
+        bpm.unpin(&page_id, None);
+        bpm.delete_temp_page(page_id)?;
+
+        self.offset = 0;
+        Ok(())
+    }
+
+    pub fn next(&mut self) -> Result<Option<T>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        self.len -= 1;
+
+        let len = u32::from_ne_bytes(self.read(size_of::<u32>())?.try_into().unwrap());
+        Ok(Some(deserialize(&self.read(len as usize)?)?))
+    }
+}
+
+impl<T> Drop for Spill<T> {
+    fn drop(&mut self) {
+        let mut bpm = self.bpm.lock();
+        // a page that can't be deleted stays on disk, drop can't fail
+        for page_id in self.pages.drain(..) {
+            let _ = bpm.delete_temp_page(page_id);
+        }
+    }
+}
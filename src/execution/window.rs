@@ -0,0 +1,201 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use anyhow::Result;
+
+use super::accumulator::Accumulator;
+use super::operator::{collect, Operator};
+use super::result_set::ResultSet;
+use super::sort::compare;
+use crate::context::Context;
+use crate::sql::logical_plan::expr::{
+    FrameBound, FrameUnits, LogicalExpr, SortExpr, WindowExpr, WindowFrame, WindowFunction,
+};
+use crate::sql::physical_plan::plan::Window;
+use crate::tuple::schema::Schema;
+use crate::types::{UInt, Value};
+
+impl Window {
+    /// Appends a column per window function to the input, which is sorted
+    /// by the partition keys and then the order keys
+    pub(super) fn compute(
+        &self,
+        input: Box<dyn Operator + '_>,
+        ctx: &mut Context,
+    ) -> Result<ResultSet> {
+        let input = collect(input, ctx)?;
+
+        // the input's columns are kept as they are, scans carry more
+        // columns than their schema
+        let schema = Schema::new(input.fields().clone());
+        let mut fields = input.fields().clone();
+        let mut cols = input.cols().clone();
+        for window in self.windows.iter() {
+            fields.push(window.to_field(&schema));
+            cols.push(evaluate(window, &input)?);
+        }
+
+        Ok(ResultSet::new(fields, cols))
+    }
+}
+
+/// The values of `exprs` for every row of the input
+fn keys(exprs: impl Iterator<Item = LogicalExpr>, input: &ResultSet) -> Result<Vec<Vec<Value>>> {
+    let cols = exprs
+        .map(|e| Ok(e.evaluate(input)?.1))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((0..input.len())
+        .map(|row| cols.iter().map(|col| col[row].clone()).collect())
+        .collect())
+}
+
+/// Splits `range` into runs of rows whose keys compare equal
+fn runs(exprs: &[SortExpr], keys: &[Vec<Value>], range: Range<usize>) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = vec![];
+    for row in range {
+        match runs.last_mut() {
+            Some(run) if compare(exprs, &keys[run.start], &keys[row]) == Ordering::Equal => {
+                run.end = row + 1
+            }
+            _ => runs.push(row..row + 1),
+        }
+    }
+    runs
+}
+
+fn evaluate(window: &WindowExpr, input: &ResultSet) -> Result<Vec<Value>> {
+    let partition_by = window
+        .partition_by
+        .iter()
+        .map(|e| SortExpr::new(e.clone(), None, None))
+        .collect::<Vec<_>>();
+    let partition_keys = keys(window.partition_by.iter().cloned(), input)?;
+    let order_keys = keys(window.order_by.iter().map(|e| e.expr.clone()), input)?;
+
+    // COUNT(*) is COUNT(true)
+    let args = match window.args.is_empty() {
+        true => vec![true.into()],
+        false => window.args.clone(),
+    };
+    let args = args
+        .into_iter()
+        .map(|arg| Ok(arg.evaluate(input)?.1))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut output = Vec::with_capacity(input.len());
+    for partition in runs(&partition_by, &partition_keys, 0..input.len()) {
+        // rows are peers if they're equal in ORDER BY
+        let peers = runs(&window.order_by, &order_keys, partition.clone());
+        let partition = Partition {
+            rows: partition,
+            peers,
+        };
+        output.extend(partition.evaluate(window, &args)?);
+    }
+
+    Ok(output)
+}
+
+struct Partition {
+    rows: Range<usize>,
+    peers: Vec<Range<usize>>,
+}
+
+impl Partition {
+    fn evaluate(&self, window: &WindowExpr, args: &[Vec<Value>]) -> Result<Vec<Value>> {
+        let start = self.rows.start;
+        let number = |n: usize| Value::UInt(UInt(n as u32));
+
+        // the default of LAG and LEAD, NULL if there's none
+        let default = |row: usize| args.get(1).map_or(Value::Null, |col| col[row].clone());
+
+        Ok(match &window.func {
+            WindowFunction::RowNumber => self
+                .rows
+                .clone()
+                .map(|row| number(row - start + 1))
+                .collect(),
+            WindowFunction::Rank => self
+                .peers
+                .iter()
+                .flat_map(|peers| peers.clone().map(|_| number(peers.start - start + 1)))
+                .collect(),
+            WindowFunction::DenseRank => self
+                .peers
+                .iter()
+                .enumerate()
+                .flat_map(|(rank, peers)| peers.clone().map(move |_| number(rank + 1)))
+                .collect(),
+            WindowFunction::Lag(offset) => self
+                .rows
+                .clone()
+                .map(|row| match row.checked_sub(*offset as usize) {
+                    Some(from) if from >= start => args[0][from].clone(),
+                    _ => default(row),
+                })
+                .collect(),
+            WindowFunction::Lead(offset) => self
+                .rows
+                .clone()
+                .map(|row| match row + *offset as usize {
+                    from if from < self.rows.end => args[0][from].clone(),
+                    _ => default(row),
+                })
+                .collect(),
+            WindowFunction::Aggregate(func) => {
+                let values = &args[0];
+                let mut output = Vec::with_capacity(self.rows.len());
+
+                // frames only grow from the start of the partition, so the
+                // rows are added to a single accumulator as they enter it
+                if window.frame.start == FrameBound::UnboundedPreceding {
+                    let mut accumulator = Accumulator::new(func);
+                    let mut added = start;
+                    for row in self.rows.clone() {
+                        let end = self.frame(&window.frame, row).end.max(added);
+                        for value in values[added..end].iter() {
+                            accumulator.update(value)?;
+                        }
+                        added = end;
+                        output.push(accumulator.clone().finish()?);
+                    }
+                } else {
+                    for row in self.rows.clone() {
+                        let mut accumulator = Accumulator::new(func);
+                        for value in values[self.frame(&window.frame, row)].iter() {
+                            accumulator.update(value)?;
+                        }
+                        output.push(accumulator.finish()?);
+                    }
+                }
+
+                output
+            }
+        })
+    }
+
+    /// The rows of the partition in the current row's frame
+    fn frame(&self, frame: &WindowFrame, row: usize) -> Range<usize> {
+        let (start, end) = (self.rows.start, self.rows.end);
+        let peers = &self.peers[self.peers.partition_point(|peers| peers.end <= row)];
+
+        // an offset can reach past either end of the partition
+        let bound = |bound: &FrameBound, is_end: bool| match (bound, &frame.units) {
+            (FrameBound::UnboundedPreceding, _) => start,
+            (FrameBound::UnboundedFollowing, _) => end,
+            (FrameBound::CurrentRow, FrameUnits::Range) if is_end => peers.end,
+            (FrameBound::CurrentRow, FrameUnits::Range) => peers.start,
+            (FrameBound::CurrentRow, FrameUnits::Rows) => row + is_end as usize,
+            (FrameBound::Preceding(n), _) => (row + is_end as usize)
+                .saturating_sub(*n as usize)
+                .clamp(start, end),
+            (FrameBound::Following(n), _) => {
+                (row + is_end as usize + *n as usize).clamp(start, end)
+            }
+        };
+
+        let (from, to) = (bound(&frame.start, false), bound(&frame.end, true));
+        from..to.max(from)
+    }
+}
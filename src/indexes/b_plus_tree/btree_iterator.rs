@@ -9,6 +9,7 @@ pub struct IndexPageIterator {
     index: usize,
     bpm: ArcBufferPool,
     txn_id: Option<TxnId>,
+    done: bool,
 }
 
 impl IndexPageIterator {
@@ -19,6 +20,7 @@ impl IndexPageIterator {
             index,
             bpm,
             txn_id,
+            done: false,
         }
     }
 }
@@ -27,11 +29,18 @@ impl Iterator for IndexPageIterator {
     type Item = (Key, TupleId);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.page.len() {
-            self.bpm.lock().unpin(&self.page.get_page_id(), self.txn_id);
-            if self.page.get_next_page_id() == INVALID_PAGE {
+        loop {
+            if self.done {
                 return None;
-            } else {
+            }
+
+            if self.index >= self.page.len() {
+                self.bpm.lock().unpin(&self.page.get_page_id(), self.txn_id);
+                if self.page.get_next_page_id() == INVALID_PAGE {
+                    self.done = true;
+                    return None;
+                }
+
                 self.page = self
                     .bpm
                     .lock()
@@ -40,17 +49,25 @@ impl Iterator for IndexPageIterator {
                     .reader()
                     .into();
                 self.index = 0;
+                continue;
             }
-        }
 
-        let (key, value) = self.page.get_pair_at(self.index);
+            let (key, value) = self.page.get_pair_at(self.index);
 
-        self.index += 1;
+            self.index += 1;
 
-        if value.is_deleted {
-            self.next()
-        } else {
-            Some((key, value.tuple_id()))
+            if !value.is_deleted {
+                return Some((key, value.tuple_id()));
+            }
+        }
+    }
+}
+
+// callers that stop early leave the current page pinned
+impl Drop for IndexPageIterator {
+    fn drop(&mut self) {
+        if !self.done {
+            self.bpm.lock().unpin(&self.page.get_page_id(), self.txn_id);
         }
     }
 }
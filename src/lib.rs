@@ -10,7 +10,7 @@ mod sql;
 mod table;
 mod tuple;
 mod txn_manager;
-mod types;
+pub mod types;
 mod wal;
 
 #[cfg(test)]
@@ -64,6 +64,14 @@ macro_rules! is_boolean_op {
                 | BinaryOperator::Lt
                 | BinaryOperator::GtEq
                 | BinaryOperator::LtEq
+                | BinaryOperator::PGLikeMatch
+                | BinaryOperator::PGILikeMatch
+                | BinaryOperator::PGNotLikeMatch
+                | BinaryOperator::PGNotILikeMatch
+                | BinaryOperator::PGRegexMatch
+                | BinaryOperator::PGRegexIMatch
+                | BinaryOperator::PGRegexNotMatch
+                | BinaryOperator::PGRegexNotIMatch
         )
     };
 }
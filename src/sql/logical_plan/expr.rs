@@ -1,11 +1,15 @@
+use std::fmt::Display;
+
 use sqlparser::ast::BinaryOperator;
 
+use super::functions::ScalarFunction;
 use crate::{
+    is_boolean_op,
     tuple::{
         constraints::Constraints,
         schema::{Field, Schema},
     },
-    types::Value,
+    types::{Types, Value},
 };
 
 #[derive(Clone, Debug)]
@@ -14,6 +18,34 @@ pub enum LogicalExpr {
     Column(String),
     BinaryExpr(Box<BinaryExpr>),
     AliasedExpr(Box<LogicalExpr>, String),
+    /// only valid as input to an Aggregate plan, the builder replaces it
+    /// with a column reference to the aggregate's output everywhere else
+    AggregateExpr(Box<AggregateExpr>),
+    FunctionExpr(Box<FunctionExpr>),
+    /// `IS [NOT] DISTINCT FROM`, `IS [NOT] NULL` compares with a NULL literal
+    DistinctFrom(Box<DistinctFromExpr>),
+    /// a simple CASE is planned as a searched one comparing the operand
+    /// in every branch
+    Case(Box<CaseExpr>),
+    /// CAST(expr AS type), also added by the builder where a value is used
+    /// as a wider type
+    Cast(Box<LogicalExpr>, Types),
+    /// NOT of a boolean, NOT NULL is NULL
+    Not(Box<LogicalExpr>),
+    InList(Box<InListExpr>),
+    /// only valid as input to a Window plan, the builder replaces it with
+    /// a column reference to the window's output everywhere else
+    WindowExpr(Box<WindowExpr>),
+    /// `$n` of a prepared statement, of the type its values are planned
+    /// for. Replaced by a literal before the plan is executed
+    Parameter(usize, Types),
+}
+
+/// A NULL literal, what's left of an expression taken out to be rebuilt
+impl Default for LogicalExpr {
+    fn default() -> Self {
+        LogicalExpr::Literal(Value::Null)
+    }
 }
 
 impl LogicalExpr {
@@ -30,6 +62,35 @@ impl LogicalExpr {
                 }
             }
             LogicalExpr::AliasedExpr(expr, alias) => format!("{} AS {}", expr.print(), alias),
+            LogicalExpr::AggregateExpr(agg) => agg.print(),
+            LogicalExpr::FunctionExpr(func) => func.print(),
+            LogicalExpr::DistinctFrom(e) => format!("({})", e.print()),
+            LogicalExpr::Case(e) => e.print(),
+            LogicalExpr::Cast(e, ty) => format!("CAST({} AS {})", e.print(), ty.to_sql()),
+            LogicalExpr::Not(e) => format!("(NOT {})", e.print()),
+            LogicalExpr::InList(e) => format!("({})", e.print()),
+            LogicalExpr::WindowExpr(e) => e.print(),
+            LogicalExpr::Parameter(n, _) => format!("${n}"),
+        }
+    }
+
+    pub fn contains_aggregate(&self) -> bool {
+        match self {
+            LogicalExpr::AggregateExpr(_) => true,
+            LogicalExpr::BinaryExpr(e) => {
+                e.left.contains_aggregate() || e.right.contains_aggregate()
+            }
+            LogicalExpr::AliasedExpr(e, _) | LogicalExpr::Cast(e, _) | LogicalExpr::Not(e) => {
+                e.contains_aggregate()
+            }
+            LogicalExpr::FunctionExpr(e) => e.args.iter().any(LogicalExpr::contains_aggregate),
+            LogicalExpr::DistinctFrom(e) => {
+                e.left.contains_aggregate() || e.right.contains_aggregate()
+            }
+            LogicalExpr::Case(e) => e.exprs().any(LogicalExpr::contains_aggregate),
+            LogicalExpr::InList(e) => e.exprs().any(LogicalExpr::contains_aggregate),
+            LogicalExpr::WindowExpr(e) => e.exprs().any(LogicalExpr::contains_aggregate),
+            LogicalExpr::Literal(_) | LogicalExpr::Column(_) | LogicalExpr::Parameter(..) => false,
         }
     }
 
@@ -46,10 +107,398 @@ impl LogicalExpr {
                 let field = e.to_field(schema);
                 Field::new(alias, field.ty, field.constraints.clone())
             }
+            LogicalExpr::AggregateExpr(agg) => agg.to_field(schema),
+            LogicalExpr::FunctionExpr(func) => func.to_field(schema),
+            LogicalExpr::DistinctFrom(e) => e.to_field(schema),
+            LogicalExpr::Case(e) => e.to_field(schema),
+            LogicalExpr::Cast(e, ty) => {
+                let field = e.to_field(schema);
+                Field::new(&field.name, ty.clone(), field.constraints.clone())
+            }
+            LogicalExpr::Not(e) => {
+                let field = e.to_field(schema);
+                Field::new(
+                    &format!("NOT {}", field.name),
+                    Types::Bool,
+                    field.constraints.clone(),
+                )
+            }
+            LogicalExpr::InList(e) => e.to_field(schema),
+            LogicalExpr::WindowExpr(e) => e.to_field(schema),
+            LogicalExpr::Parameter(n, ty) => {
+                Field::new(&format!("${n}"), ty.clone(), Constraints::nullable(true))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateFunction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "count" => Some(AggregateFunction::Count),
+            "sum" => Some(AggregateFunction::Sum),
+            "avg" => Some(AggregateFunction::Avg),
+            "min" => Some(AggregateFunction::Min),
+            "max" => Some(AggregateFunction::Max),
+            _ => None,
+        }
+    }
+
+    /// Result type of the aggregate given its input type, None if
+    /// the function can't be applied to that type
+    pub fn return_type(&self, input: &Types) -> Option<Types> {
+        match (self, input) {
+            (AggregateFunction::Count, _) => Some(Types::UInt),
+            (AggregateFunction::Sum, Types::UInt | Types::Int | Types::Float) => {
+                Some(input.clone())
+            }
+            (AggregateFunction::Avg, Types::UInt | Types::Int | Types::Float) => Some(Types::Float),
+            (AggregateFunction::Min | AggregateFunction::Max, Types::StrAddr) => None,
+            (AggregateFunction::Min | AggregateFunction::Max, _) => Some(input.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for AggregateFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AggregateFunction::Count => write!(f, "count"),
+            AggregateFunction::Sum => write!(f, "sum"),
+            AggregateFunction::Avg => write!(f, "avg"),
+            AggregateFunction::Min => write!(f, "min"),
+            AggregateFunction::Max => write!(f, "max"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AggregateExpr {
+    pub func: AggregateFunction,
+    /// None for COUNT(*)
+    pub arg: Option<LogicalExpr>,
+}
+
+impl AggregateExpr {
+    pub fn new(func: AggregateFunction, arg: Option<LogicalExpr>) -> Self {
+        Self { func, arg }
+    }
+
+    pub fn print(&self) -> String {
+        match &self.arg {
+            Some(arg) => format!("{}({})", self.func, arg.print()),
+            None => format!("{}(*)", self.func),
+        }
+    }
+
+    pub fn to_field(&self, schema: &Schema) -> Field {
+        let (name, ty) = match &self.arg {
+            Some(arg) => {
+                let field = arg.to_field(schema);
+                // the builder checks the type before creating the plan
+                let ty = self.func.return_type(&field.ty).unwrap_or(field.ty);
+                (format!("{}({})", self.func, field.name), ty)
+            }
+            None => (format!("{}(*)", self.func), Types::UInt),
+        };
+
+        let nullable = self.func != AggregateFunction::Count;
+
+        Field::new(&name, ty, Constraints::nullable(nullable))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WindowFunction {
+    RowNumber,
+    Rank,
+    DenseRank,
+    /// the value that many rows before the current one in the partition
+    Lag(u32),
+    /// the value that many rows after the current one in the partition
+    Lead(u32),
+    /// computed over the rows of the frame
+    Aggregate(AggregateFunction),
+}
+
+impl WindowFunction {
+    /// Result type of the function given its argument types, None if
+    /// the function can't be applied to them
+    pub fn return_type(&self, args: &[Types]) -> Option<Types> {
+        match (self, args) {
+            (WindowFunction::RowNumber | WindowFunction::Rank | WindowFunction::DenseRank, []) => {
+                Some(Types::UInt)
+            }
+            // the default is returned before the first or after the last row
+            (WindowFunction::Lag(_) | WindowFunction::Lead(_), [_] | [_, _]) => Types::common(args),
+            (WindowFunction::Aggregate(AggregateFunction::Count), []) => Some(Types::UInt),
+            (WindowFunction::Aggregate(func), [arg]) => func.return_type(arg),
+            _ => None,
         }
     }
 }
 
+impl Display for WindowFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WindowFunction::RowNumber => write!(f, "row_number"),
+            WindowFunction::Rank => write!(f, "rank"),
+            WindowFunction::DenseRank => write!(f, "dense_rank"),
+            WindowFunction::Lag(_) => write!(f, "lag"),
+            WindowFunction::Lead(_) => write!(f, "lead"),
+            WindowFunction::Aggregate(func) => write!(f, "{func}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameUnits {
+    Rows,
+    /// bounds are peers of the current row, rows equal to it in ORDER BY
+    Range,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameBound {
+    UnboundedPreceding,
+    Preceding(u32),
+    CurrentRow,
+    Following(u32),
+    UnboundedFollowing,
+}
+
+impl Display for FrameBound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameBound::UnboundedPreceding => write!(f, "UNBOUNDED PRECEDING"),
+            FrameBound::Preceding(n) => write!(f, "{n} PRECEDING"),
+            FrameBound::CurrentRow => write!(f, "CURRENT ROW"),
+            FrameBound::Following(n) => write!(f, "{n} FOLLOWING"),
+            FrameBound::UnboundedFollowing => write!(f, "UNBOUNDED FOLLOWING"),
+        }
+    }
+}
+
+/// The rows of the partition an aggregate window function reads
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WindowFrame {
+    pub units: FrameUnits,
+    pub start: FrameBound,
+    pub end: FrameBound,
+}
+
+/// Up to the current row and its peers, the whole partition without ORDER BY
+impl Default for WindowFrame {
+    fn default() -> Self {
+        Self {
+            units: FrameUnits::Range,
+            start: FrameBound::UnboundedPreceding,
+            end: FrameBound::CurrentRow,
+        }
+    }
+}
+
+impl Display for WindowFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let units = match self.units {
+            FrameUnits::Rows => "ROWS",
+            FrameUnits::Range => "RANGE",
+        };
+        write!(f, "{units} BETWEEN {} AND {}", self.start, self.end)
+    }
+}
+
+/// `func(args) OVER (PARTITION BY ... ORDER BY ... frame)`, computed for
+/// every row from the rows of its partition
+#[derive(Clone, Debug)]
+pub struct WindowExpr {
+    pub func: WindowFunction,
+    /// the value and the default of LAG and LEAD, empty for COUNT(*)
+    pub args: Vec<LogicalExpr>,
+    pub partition_by: Vec<LogicalExpr>,
+    pub order_by: Vec<SortExpr>,
+    pub frame: WindowFrame,
+}
+
+impl WindowExpr {
+    pub fn new(
+        func: WindowFunction,
+        args: Vec<LogicalExpr>,
+        partition_by: Vec<LogicalExpr>,
+        order_by: Vec<SortExpr>,
+        frame: WindowFrame,
+    ) -> Self {
+        Self {
+            func,
+            args,
+            partition_by,
+            order_by,
+            frame,
+        }
+    }
+
+    /// Arguments, partition keys and order keys, in order
+    pub fn exprs(&self) -> impl Iterator<Item = &LogicalExpr> {
+        self.args
+            .iter()
+            .chain(self.partition_by.iter())
+            .chain(self.order_by.iter().map(|e| &e.expr))
+    }
+
+    /// The PARTITION BY and ORDER BY clauses, windows that print the same
+    /// read their input in the same order
+    pub fn print_over(&self) -> String {
+        let partition_by = self.partition_by.iter().map(LogicalExpr::print).collect();
+        let order_by = self.order_by.iter().map(SortExpr::print).collect();
+        over(partition_by, order_by)
+    }
+
+    pub fn print(&self) -> String {
+        let args = self.args.iter().map(LogicalExpr::print).collect();
+        self.display(args, self.print_over())
+    }
+
+    fn display(&self, mut args: Vec<String>, mut over: String) -> String {
+        match self.func {
+            WindowFunction::Lag(offset) | WindowFunction::Lead(offset) => {
+                args.insert(1.min(args.len()), offset.to_string())
+            }
+            WindowFunction::Aggregate(AggregateFunction::Count) if args.is_empty() => {
+                args.push("*".into())
+            }
+            _ => {}
+        }
+        if self.frame != WindowFrame::default() {
+            over = [over, self.frame.to_string()].join(" ").trim().to_string();
+        }
+        format!("{}({}) OVER ({over})", self.func, args.join(", "))
+    }
+
+    pub fn to_field(&self, schema: &Schema) -> Field {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| arg.to_field(schema))
+            .collect::<Vec<_>>();
+        let types = args.iter().map(|f| f.ty.clone()).collect::<Vec<_>>();
+        let names = args.into_iter().map(|f| f.name).collect();
+
+        let partition_by = self
+            .partition_by
+            .iter()
+            .map(|e| e.to_field(schema).name)
+            .collect();
+        let order_by = self
+            .order_by
+            .iter()
+            .map(|e| e.display(e.expr.to_field(schema).name))
+            .collect();
+
+        // the builder checks the types before creating the plan
+        let ty = self.func.return_type(&types).unwrap_or(Types::Null);
+        let nullable = !matches!(
+            self.func,
+            WindowFunction::RowNumber
+                | WindowFunction::Rank
+                | WindowFunction::DenseRank
+                | WindowFunction::Aggregate(AggregateFunction::Count)
+        );
+
+        Field::new(
+            &self.display(names, over(partition_by, order_by)),
+            ty,
+            Constraints::nullable(nullable),
+        )
+    }
+}
+
+fn over(partition_by: Vec<String>, order_by: Vec<String>) -> String {
+    let mut clauses = vec![];
+    if !partition_by.is_empty() {
+        clauses.push(format!("PARTITION BY {}", partition_by.join(", ")));
+    }
+    if !order_by.is_empty() {
+        clauses.push(format!("ORDER BY {}", order_by.join(", ")));
+    }
+    clauses.join(" ")
+}
+
+#[derive(Clone, Debug)]
+pub struct FunctionExpr {
+    pub func: &'static ScalarFunction,
+    pub args: Vec<LogicalExpr>,
+}
+
+impl FunctionExpr {
+    pub fn new(func: &'static ScalarFunction, args: Vec<LogicalExpr>) -> Self {
+        Self { func, args }
+    }
+
+    pub fn print(&self) -> String {
+        let args = self.args.iter().map(LogicalExpr::print).collect::<Vec<_>>();
+        format!("{}({})", self.func.name, args.join(", "))
+    }
+
+    pub fn to_field(&self, schema: &Schema) -> Field {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| arg.to_field(schema))
+            .collect::<Vec<_>>();
+        let types = args.iter().map(|f| f.ty.clone()).collect::<Vec<_>>();
+        let names = args.iter().map(|f| f.name.clone()).collect::<Vec<_>>();
+
+        // the builder checks the types before creating the plan
+        let ty = self.func.return_type(&types).unwrap_or(Types::Null);
+
+        Field::new(
+            &format!("{}({})", self.func.name, names.join(", ")),
+            ty,
+            Constraints::nullable(true),
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SortExpr {
+    pub expr: LogicalExpr,
+    pub asc: bool,
+    pub nulls_first: bool,
+}
+
+impl SortExpr {
+    /// NULLs are larger than any value by default, like in postgres
+    pub fn new(expr: LogicalExpr, asc: Option<bool>, nulls_first: Option<bool>) -> Self {
+        let asc = asc.unwrap_or(true);
+        Self {
+            expr,
+            asc,
+            nulls_first: nulls_first.unwrap_or(!asc),
+        }
+    }
+
+    pub fn print(&self) -> String {
+        self.display(self.expr.print())
+    }
+
+    fn display(&self, expr: String) -> String {
+        format!(
+            "{expr} {} NULLS {}",
+            if self.asc { "ASC" } else { "DESC" },
+            if self.nulls_first { "FIRST" } else { "LAST" }
+        )
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BinaryExpr {
     pub left: LogicalExpr,
@@ -70,14 +519,207 @@ impl BinaryExpr {
         let left = self.left.to_field(schema);
         let right = self.right.to_field(schema);
 
+        let ty = match self.op {
+            BinaryOperator::StringConcat => Types::Str,
+            ref op if is_boolean_op!(op) => Types::Bool,
+            _ => Types::common(&[left.ty.clone(), right.ty]).unwrap_or(left.ty),
+        };
+
         Field::new(
             &format!("{} {} {}", left.name, self.op, right.name),
-            left.ty,
+            ty,
             left.constraints.clone(),
         )
     }
 }
 
+pub fn is_arithmetic_op(op: &BinaryOperator) -> bool {
+    matches!(
+        op,
+        BinaryOperator::Plus
+            | BinaryOperator::Minus
+            | BinaryOperator::Multiply
+            | BinaryOperator::Divide
+    )
+}
+
+pub fn is_comparison_op(op: &BinaryOperator) -> bool {
+    matches!(
+        op,
+        BinaryOperator::Eq
+            | BinaryOperator::NotEq
+            | BinaryOperator::Lt
+            | BinaryOperator::Gt
+            | BinaryOperator::LtEq
+            | BinaryOperator::GtEq
+    )
+}
+
+/// LIKE, ILIKE and regular expression matches, and their negations
+pub fn is_pattern_op(op: &BinaryOperator) -> bool {
+    matches!(
+        op,
+        BinaryOperator::PGLikeMatch
+            | BinaryOperator::PGILikeMatch
+            | BinaryOperator::PGNotLikeMatch
+            | BinaryOperator::PGNotILikeMatch
+            | BinaryOperator::PGRegexMatch
+            | BinaryOperator::PGRegexIMatch
+            | BinaryOperator::PGRegexNotMatch
+            | BinaryOperator::PGRegexNotIMatch
+    )
+}
+
+/// NULLs are equal to each other and distinct from any value, the result
+/// is never NULL
+#[derive(Clone, Debug)]
+pub struct DistinctFromExpr {
+    pub left: LogicalExpr,
+    pub right: LogicalExpr,
+    pub negated: bool,
+}
+
+impl DistinctFromExpr {
+    pub fn new(left: LogicalExpr, right: LogicalExpr, negated: bool) -> Self {
+        Self {
+            left,
+            right,
+            negated,
+        }
+    }
+
+    pub fn print(&self) -> String {
+        self.display(self.left.print(), self.right.print())
+    }
+
+    fn display(&self, left: String, right: String) -> String {
+        let not = if self.negated { " NOT" } else { "" };
+        match self.right {
+            LogicalExpr::Literal(Value::Null) => {
+                // IS NOT DISTINCT FROM NULL is IS NULL
+                let not = if self.negated { "" } else { " NOT" };
+                format!("{left} IS{not} NULL")
+            }
+            _ => format!("{left} IS{not} DISTINCT FROM {right}"),
+        }
+    }
+
+    fn to_field(&self, schema: &Schema) -> Field {
+        let left = self.left.to_field(schema);
+        let right = self.right.to_field(schema);
+
+        Field::new(
+            &self.display(left.name, right.name),
+            Types::Bool,
+            Constraints::nullable(false),
+        )
+    }
+}
+
+/// Branches are tried in order, rows no condition holds for get the ELSE
+/// result or NULL
+#[derive(Clone, Debug)]
+pub struct CaseExpr {
+    /// WHEN condition THEN result
+    pub branches: Vec<(LogicalExpr, LogicalExpr)>,
+    pub else_result: Option<LogicalExpr>,
+}
+
+impl CaseExpr {
+    pub fn new(
+        branches: Vec<(LogicalExpr, LogicalExpr)>,
+        else_result: Option<LogicalExpr>,
+    ) -> Self {
+        Self {
+            branches,
+            else_result,
+        }
+    }
+
+    /// Conditions and results, in order
+    pub fn exprs(&self) -> impl Iterator<Item = &LogicalExpr> {
+        self.branches
+            .iter()
+            .flat_map(|(when, then)| [when, then])
+            .chain(self.else_result.iter())
+    }
+
+    pub fn results(&self) -> impl Iterator<Item = &LogicalExpr> {
+        self.branches
+            .iter()
+            .map(|(_, then)| then)
+            .chain(self.else_result.iter())
+    }
+
+    pub fn print(&self) -> String {
+        let mut s = "CASE".to_string();
+        for (when, then) in self.branches.iter() {
+            s += &format!(" WHEN {} THEN {}", when.print(), then.print());
+        }
+        if let Some(else_result) = &self.else_result {
+            s += &format!(" ELSE {}", else_result.print());
+        }
+        s + " END"
+    }
+
+    fn to_field(&self, schema: &Schema) -> Field {
+        let types = self
+            .results()
+            .map(|e| e.to_field(schema).ty)
+            .collect::<Vec<_>>();
+
+        // the builder checks the types before creating the plan
+        let ty = Types::common(&types).unwrap_or(Types::Null);
+
+        Field::new("case", ty, Constraints::nullable(true))
+    }
+}
+
+/// `expr [NOT] IN (list)`, unknown if `expr` or a NULL in the list could
+/// be the match
+#[derive(Clone, Debug)]
+pub struct InListExpr {
+    pub expr: LogicalExpr,
+    pub list: Vec<LogicalExpr>,
+    pub negated: bool,
+}
+
+impl InListExpr {
+    pub fn new(expr: LogicalExpr, list: Vec<LogicalExpr>, negated: bool) -> Self {
+        Self {
+            expr,
+            list,
+            negated,
+        }
+    }
+
+    /// The tested expression followed by the list
+    pub fn exprs(&self) -> impl Iterator<Item = &LogicalExpr> {
+        std::iter::once(&self.expr).chain(self.list.iter())
+    }
+
+    pub fn print(&self) -> String {
+        let list = self.list.iter().map(LogicalExpr::print).collect::<Vec<_>>();
+        self.display(self.expr.print(), list)
+    }
+
+    fn display(&self, expr: String, list: Vec<String>) -> String {
+        let not = if self.negated { " NOT" } else { "" };
+        format!("{expr}{not} IN ({})", list.join(", "))
+    }
+
+    fn to_field(&self, schema: &Schema) -> Field {
+        let list = self.list.iter().map(|e| e.to_field(schema).name).collect();
+
+        Field::new(
+            &self.display(self.expr.to_field(schema).name, list),
+            Types::Bool,
+            Constraints::nullable(true),
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct BooleanBinaryExpr {
     pub left: LogicalExpr,
     pub op: BinaryOperator,
@@ -0,0 +1,536 @@
+use anyhow::{bail, ensure, Result};
+
+use crate::errors::Error;
+use crate::types::{Float, Int, Str, Types, UInt, Value};
+
+/// A scalar function callable from SQL. `return_type` checks the argument
+/// types at plan time, `eval` is called once per row
+#[derive(Debug)]
+pub struct ScalarFunction {
+    pub name: &'static str,
+    /// how the function is called, shown when the arguments don't fit
+    pub signature: &'static str,
+    /// None if the function can't be applied to these argument types
+    return_type: fn(&[Types]) -> Option<Types>,
+    eval: fn(&[Value]) -> Result<Value>,
+    /// any NULL argument makes the result NULL without calling `eval`
+    strict: bool,
+    /// the arguments are cast to the result type, like the results of a CASE
+    casts_args: bool,
+}
+
+const fn strict(
+    name: &'static str,
+    signature: &'static str,
+    return_type: fn(&[Types]) -> Option<Types>,
+    eval: fn(&[Value]) -> Result<Value>,
+) -> ScalarFunction {
+    ScalarFunction {
+        name,
+        signature,
+        return_type,
+        eval,
+        strict: true,
+        casts_args: false,
+    }
+}
+
+/// NULL arguments are passed to `eval`
+const fn non_strict(
+    name: &'static str,
+    signature: &'static str,
+    return_type: fn(&[Types]) -> Option<Types>,
+    eval: fn(&[Value]) -> Result<Value>,
+) -> ScalarFunction {
+    ScalarFunction {
+        name,
+        signature,
+        return_type,
+        eval,
+        strict: false,
+        casts_args: false,
+    }
+}
+
+/// NULL arguments are passed to `eval`, the others are of the result type
+const fn same_type(
+    name: &'static str,
+    signature: &'static str,
+    return_type: fn(&[Types]) -> Option<Types>,
+    eval: fn(&[Value]) -> Result<Value>,
+) -> ScalarFunction {
+    ScalarFunction {
+        name,
+        signature,
+        return_type,
+        eval,
+        strict: false,
+        casts_args: true,
+    }
+}
+
+/// Every scalar function, adding one only takes an entry here
+static FUNCTIONS: &[ScalarFunction] = &[
+    strict("upper", "upper(TEXT)", text_to_text, upper),
+    strict("lower", "lower(TEXT)", text_to_text, lower),
+    strict("length", "length(TEXT)", length_type, length),
+    strict(
+        "substring",
+        "substring(TEXT, INT [, INT])",
+        substring_type,
+        substring,
+    ),
+    strict("trim", "trim(TEXT [, TEXT])", trim_type, trim),
+    strict("ltrim", "ltrim(TEXT [, TEXT])", trim_type, ltrim),
+    strict("rtrim", "rtrim(TEXT [, TEXT])", trim_type, rtrim),
+    non_strict("concat", "concat(ANY [, ...])", concat_type, concat),
+    strict("abs", "abs(NUMBER)", number_to_number, abs),
+    strict("round", "round(NUMBER [, INT])", round_type, round),
+    strict("floor", "floor(NUMBER)", number_to_number, floor),
+    strict("ceil", "ceil(NUMBER)", number_to_number, ceil),
+    strict("mod", "mod(NUMBER, NUMBER)", mod_type, modulo),
+    strict("power", "power(NUMBER, NUMBER)", power_type, power),
+    same_type("coalesce", "coalesce(T [, ...])", coalesce_type, coalesce),
+    non_strict("nullif", "nullif(T, T)", nullif_type, nullif),
+];
+
+impl ScalarFunction {
+    pub fn from_name(name: &str) -> Option<&'static Self> {
+        let name = name.to_lowercase();
+        FUNCTIONS.iter().find(|f| f.name == name)
+    }
+
+    /// Result type of a call with arguments of these types
+    pub fn return_type(&self, args: &[Types]) -> Result<Types> {
+        let Some(ty) = (self.return_type)(args) else {
+            let args = args
+                .iter()
+                .map(|ty| match ty {
+                    Types::Null => "NULL".to_string(),
+                    ty => ty.to_sql(),
+                })
+                .collect::<Vec<_>>();
+            bail!(Error::Expected(
+                self.signature.into(),
+                format!("{}({})", self.name, args.join(", "))
+            ));
+        };
+
+        Ok(ty)
+    }
+
+    pub fn casts_args(&self) -> bool {
+        self.casts_args
+    }
+
+    pub fn call(&self, args: &[Value]) -> Result<Value> {
+        if self.strict && args.iter().any(Value::is_null) {
+            return Ok(Value::Null);
+        }
+
+        (self.eval)(args)
+    }
+}
+
+fn is_text(ty: &Types) -> bool {
+    matches!(ty, Types::Str | Types::Null)
+}
+
+fn is_integer(ty: &Types) -> bool {
+    matches!(ty, Types::UInt | Types::Int | Types::Null)
+}
+
+fn is_number(ty: &Types) -> bool {
+    is_integer(ty) || matches!(ty, Types::Float)
+}
+
+fn text_to_text(args: &[Types]) -> Option<Types> {
+    match args {
+        [s] if is_text(s) => Some(Types::Str),
+        _ => None,
+    }
+}
+
+fn length_type(args: &[Types]) -> Option<Types> {
+    match args {
+        [s] if is_text(s) => Some(Types::Int),
+        _ => None,
+    }
+}
+
+fn substring_type(args: &[Types]) -> Option<Types> {
+    match args {
+        [s, bounds @ ..] if is_text(s) && matches!(bounds.len(), 1 | 2) => {
+            bounds.iter().all(is_integer).then_some(Types::Str)
+        }
+        _ => None,
+    }
+}
+
+fn trim_type(args: &[Types]) -> Option<Types> {
+    match args {
+        [_] | [_, _] => args.iter().all(is_text).then_some(Types::Str),
+        _ => None,
+    }
+}
+
+fn concat_type(args: &[Types]) -> Option<Types> {
+    (!args.is_empty()).then_some(Types::Str)
+}
+
+fn number_to_number(args: &[Types]) -> Option<Types> {
+    match args {
+        [n] if is_number(n) => Some(n.clone()),
+        _ => None,
+    }
+}
+
+fn round_type(args: &[Types]) -> Option<Types> {
+    match args {
+        [n] if is_number(n) => Some(n.clone()),
+        [n, digits] if is_number(n) && is_integer(digits) => Some(n.clone()),
+        _ => None,
+    }
+}
+
+/// Same as the arithmetic operators, a float operand makes the result a float
+fn mod_type(args: &[Types]) -> Option<Types> {
+    match args {
+        [l, r] if is_number(l) && is_number(r) => Some(match (l, r) {
+            (Types::Float, _) | (_, Types::Float) => Types::Float,
+            (Types::Null, ty) | (ty, Types::Null) => ty.clone(),
+            (Types::UInt, Types::UInt) => Types::UInt,
+            _ => Types::Int,
+        }),
+        _ => None,
+    }
+}
+
+fn power_type(args: &[Types]) -> Option<Types> {
+    match args {
+        [l, r] if is_number(l) && is_number(r) => Some(Types::Float),
+        _ => None,
+    }
+}
+
+fn coalesce_type(args: &[Types]) -> Option<Types> {
+    match args {
+        [] => None,
+        args => Types::common(args),
+    }
+}
+
+fn nullif_type(args: &[Types]) -> Option<Types> {
+    match args {
+        [l, _] => Types::common(args).map(|_| l.clone()),
+        _ => None,
+    }
+}
+
+fn as_i64(value: &Value) -> Result<i64> {
+    match value {
+        Value::Int(Int(v)) => Ok(*v as i64),
+        Value::UInt(UInt(v)) => Ok(*v as i64),
+        v => bail!(Error::TypeMismatch(
+            vec![Types::UInt, Types::Int],
+            vec![v.get_type()]
+        )),
+    }
+}
+
+fn upper(args: &[Value]) -> Result<Value> {
+    Ok(Value::Str(Str(args[0].str().to_uppercase())))
+}
+
+fn lower(args: &[Value]) -> Result<Value> {
+    Ok(Value::Str(Str(args[0].str().to_lowercase())))
+}
+
+fn length(args: &[Value]) -> Result<Value> {
+    Ok(Value::Int(Int(args[0].str().chars().count() as i32)))
+}
+
+/// Characters are counted from 1, the part of the range before the first
+/// character is cut off like in postgres
+fn substring(args: &[Value]) -> Result<Value> {
+    let s = args[0].str();
+    let from = as_i64(&args[1])?;
+    let end = match args.get(2) {
+        Some(len) => {
+            let len = as_i64(len)?;
+            ensure!(
+                len >= 0,
+                Error::Expected("a non-negative substring length".into(), len.to_string())
+            );
+            from + len
+        }
+        None => i64::MAX,
+    };
+
+    let skip = (from.max(1) - 1) as usize;
+    let take = (end - from.max(1)).max(0) as usize;
+
+    Ok(Value::Str(Str(s.chars().skip(skip).take(take).collect())))
+}
+
+fn trim_chars(args: &[Value]) -> Vec<char> {
+    match args.get(1) {
+        Some(chars) => chars.str().chars().collect(),
+        None => vec![' '],
+    }
+}
+
+fn trim(args: &[Value]) -> Result<Value> {
+    let chars = trim_chars(args);
+    Ok(Value::Str(Str(args[0]
+        .str()
+        .trim_matches(chars.as_slice())
+        .to_string())))
+}
+
+fn ltrim(args: &[Value]) -> Result<Value> {
+    let chars = trim_chars(args);
+    Ok(Value::Str(Str(args[0]
+        .str()
+        .trim_start_matches(chars.as_slice())
+        .to_string())))
+}
+
+fn rtrim(args: &[Value]) -> Result<Value> {
+    let chars = trim_chars(args);
+    Ok(Value::Str(Str(args[0]
+        .str()
+        .trim_end_matches(chars.as_slice())
+        .to_string())))
+}
+
+fn concat(args: &[Value]) -> Result<Value> {
+    Ok(Value::Str(Str(args
+        .iter()
+        .filter(|v| !v.is_null())
+        .map(Value::to_string_unquoted)
+        .collect())))
+}
+
+/// The first argument that isn't NULL
+fn coalesce(args: &[Value]) -> Result<Value> {
+    Ok(args
+        .iter()
+        .find(|v| !v.is_null())
+        .cloned()
+        .unwrap_or(Value::Null))
+}
+
+/// NULL if both arguments are equal, the first one otherwise
+fn nullif(args: &[Value]) -> Result<Value> {
+    match (&args[0], &args[1]) {
+        (l, r) if !l.is_null() && !r.is_null() && l.equ(r)? => Ok(Value::Null),
+        (l, _) => Ok(l.clone()),
+    }
+}
+
+fn abs(args: &[Value]) -> Result<Value> {
+    Ok(match &args[0] {
+        Value::Int(Int(v)) => Value::Int(Int(v
+            .checked_abs()
+            .ok_or(Error::OutOfRange(format!("abs({v})")))?)),
+        Value::Float(Float(v)) => Value::Float(Float(v.abs())),
+        v => v.clone(),
+    })
+}
+
+fn round(args: &[Value]) -> Result<Value> {
+    let digits = args
+        .get(1)
+        .map(as_i64)
+        .transpose()?
+        .unwrap_or(0)
+        .clamp(-38, 38) as i32;
+    let scale = 10f64.powi(digits);
+    let round = |v: f64| (v * scale).round() / scale;
+    let out_of_range = || Error::OutOfRange(format!("round({}, {digits})", args[0]));
+
+    Ok(match &args[0] {
+        Value::Float(Float(v)) => Value::Float(Float(round(*v as f64) as f32)),
+        // only negative digits change integers, and can carry them past the type's range
+        Value::Int(Int(v)) => {
+            let rounded = round(*v as f64) as i64;
+            Value::Int(Int(i32::try_from(rounded).map_err(|_| out_of_range())?))
+        }
+        Value::UInt(UInt(v)) => {
+            let rounded = round(*v as f64) as i64;
+            Value::UInt(UInt(u32::try_from(rounded).map_err(|_| out_of_range())?))
+        }
+        v => v.clone(),
+    })
+}
+
+fn floor(args: &[Value]) -> Result<Value> {
+    Ok(match &args[0] {
+        Value::Float(Float(v)) => Value::Float(Float(v.floor())),
+        v => v.clone(),
+    })
+}
+
+fn ceil(args: &[Value]) -> Result<Value> {
+    Ok(match &args[0] {
+        Value::Float(Float(v)) => Value::Float(Float(v.ceil())),
+        v => v.clone(),
+    })
+}
+
+/// The remainder has the sign of the dividend
+fn modulo(args: &[Value]) -> Result<Value> {
+    Ok(match (&args[0], &args[1]) {
+        (_, Value::UInt(UInt(0)) | Value::Int(Int(0)) | Value::Float(Float(0.0))) => {
+            bail!(Error::DivisionByZero)
+        }
+        (Value::UInt(UInt(l)), Value::UInt(UInt(r))) => Value::UInt(UInt(l % r)),
+        (l @ Value::Float(_), r) | (l, r @ Value::Float(_)) => {
+            Value::Float(Float(l.as_f32() % r.as_f32()))
+        }
+        (l, r) => Value::Int(Int((as_i64(l)? % as_i64(r)?) as i32)),
+    })
+}
+
+/// Computed in double precision, the result has to fit a FLOAT
+fn power(args: &[Value]) -> Result<Value> {
+    let result = args[0].as_f64().powf(args[1].as_f64());
+    ensure!(
+        result.is_nan() || result.abs() <= f32::MAX as f64,
+        Error::OutOfRange(format!("power({}, {})", args[0], args[1]))
+    );
+    Ok(Value::Float(Float(result as f32)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lit;
+    use crate::types::ValueFactory;
+
+    fn call(name: &str, args: &[Value]) -> Result<Value> {
+        ScalarFunction::from_name(name).unwrap().call(args)
+    }
+
+    #[test]
+    fn test_return_types() -> Result<()> {
+        let upper = ScalarFunction::from_name("UPPER").unwrap();
+        assert_eq!(upper.return_type(&[Types::Str])?, Types::Str);
+        assert_eq!(upper.return_type(&[Types::Null])?, Types::Str);
+        assert!(upper.return_type(&[Types::Int]).is_err());
+        assert!(upper.return_type(&[]).is_err());
+
+        let substring = ScalarFunction::from_name("substring").unwrap();
+        assert!(substring.return_type(&[Types::Str, Types::UInt]).is_ok());
+        assert!(substring.return_type(&[Types::Str, Types::Float]).is_err());
+
+        let modulo = ScalarFunction::from_name("mod").unwrap();
+        assert_eq!(
+            modulo.return_type(&[Types::UInt, Types::UInt])?,
+            Types::UInt
+        );
+        assert_eq!(modulo.return_type(&[Types::Int, Types::UInt])?, Types::Int);
+        assert_eq!(
+            modulo.return_type(&[Types::Int, Types::Float])?,
+            Types::Float
+        );
+
+        let coalesce = ScalarFunction::from_name("coalesce").unwrap();
+        assert_eq!(
+            coalesce.return_type(&[Types::Null, Types::Int, Types::UInt])?,
+            Types::Int
+        );
+        assert!(coalesce.return_type(&[Types::Str, Types::Int]).is_err());
+
+        assert!(ScalarFunction::from_name("nope").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_functions() -> Result<()> {
+        let hello = lit!(Str, "hello")?;
+
+        assert_eq!(
+            call("upper", std::slice::from_ref(&hello))?,
+            lit!(Str, "HELLO")?
+        );
+        assert_eq!(
+            call("length", std::slice::from_ref(&hello))?,
+            lit!(Int, "5")?
+        );
+        assert!(call("upper", &[Value::Null])?.is_null());
+
+        let substring = |from: &str, len: Option<&str>| -> Result<Value> {
+            let mut args = vec![hello.clone(), lit!(Int, from)?];
+            if let Some(len) = len {
+                args.push(lit!(Int, len)?);
+            }
+            call("substring", &args)
+        };
+        assert_eq!(substring("2", Some("3"))?, lit!(Str, "ell")?);
+        assert_eq!(substring("2", None)?, lit!(Str, "ello")?);
+        assert_eq!(substring("0", Some("3"))?, lit!(Str, "he")?);
+        assert_eq!(substring("-5", Some("3"))?, lit!(Str, "")?);
+        assert_eq!(substring("4", Some("10"))?, lit!(Str, "lo")?);
+        assert!(substring("1", Some("-1")).is_err());
+
+        assert_eq!(call("trim", &[lit!(Str, "  a b ")?])?, lit!(Str, "a b")?);
+        assert_eq!(
+            call("ltrim", &[lit!(Str, "xxaxx")?, lit!(Str, "x")?])?,
+            lit!(Str, "axx")?
+        );
+
+        assert_eq!(
+            call("concat", &[hello, Value::Null, lit!(UInt, "1")?])?,
+            lit!(Str, "hello1")?
+        );
+
+        assert_eq!(call("abs", &[lit!(Int, "-3")?])?, lit!(Int, "3")?);
+        assert!(call("abs", &[lit!(Int, "-2147483648")?]).is_err());
+        assert!(call("substring", &[lit!(Str, "abc")?, lit!(Float, "1.5")?]).is_err());
+        assert_eq!(call("round", &[lit!(Float, "2.5")?])?, lit!(Float, "3.0")?);
+        assert_eq!(
+            call("round", &[lit!(Float, "1.256")?, lit!(UInt, "2")?])?,
+            lit!(Float, "1.26")?
+        );
+        assert_eq!(
+            call("round", &[lit!(Int, "1250")?, lit!(Int, "-2")?])?,
+            lit!(Int, "1300")?
+        );
+        assert!(call("round", &[lit!(Int, "2147483647")?, lit!(Int, "-1")?]).is_err());
+        assert!(call("round", &[lit!(UInt, "4294967295")?, lit!(Int, "-1")?]).is_err());
+        assert_eq!(
+            call("floor", &[lit!(Float, "-1.5")?])?,
+            lit!(Float, "-2.0")?
+        );
+        assert_eq!(call("ceil", &[lit!(Float, "1.2")?])?, lit!(Float, "2.0")?);
+        assert_eq!(
+            call("mod", &[lit!(Int, "-7")?, lit!(UInt, "3")?])?,
+            lit!(Int, "-1")?
+        );
+        assert!(call("mod", &[lit!(UInt, "7")?, lit!(UInt, "0")?]).is_err());
+        assert_eq!(
+            call("power", &[lit!(UInt, "2")?, lit!(UInt, "10")?])?,
+            lit!(Float, "1024.0")?
+        );
+        assert_eq!(
+            call("power", &[lit!(UInt, "3")?, lit!(UInt, "20")?])?,
+            lit!(Float, "3486784401.0")?
+        );
+        assert!(call("power", &[lit!(UInt, "10")?, lit!(UInt, "39")?]).is_err());
+
+        let one = lit!(UInt, "1")?;
+        assert_eq!(
+            call("coalesce", &[Value::Null, one.clone(), Value::Null])?,
+            one
+        );
+        assert!(call("coalesce", &[Value::Null])?.is_null());
+        assert!(call("nullif", &[one.clone(), lit!(Int, "1")?])?.is_null());
+        assert_eq!(call("nullif", &[one.clone(), lit!(UInt, "2")?])?, one);
+        assert_eq!(call("nullif", &[one.clone(), Value::Null])?, one);
+
+        Ok(())
+    }
+}
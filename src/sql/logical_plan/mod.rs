@@ -1,36 +1,221 @@
 pub mod expr;
+pub mod functions;
 pub mod optimizer;
 pub mod plan;
 
-use expr::{BinaryExpr, BooleanBinaryExpr, LogicalExpr};
+use expr::{
+    is_arithmetic_op, is_comparison_op, is_pattern_op, AggregateExpr, AggregateFunction,
+    BinaryExpr, BooleanBinaryExpr, CaseExpr, DistinctFromExpr, FrameBound, FrameUnits,
+    FunctionExpr, InListExpr, LogicalExpr, SortExpr, WindowExpr, WindowFrame, WindowFunction,
+};
+use functions::ScalarFunction;
 use plan::{
-    CreateTable, Delete, DropTables, Explain, Filter, IndexScan, Insert, Join, Limit, LogicalPlan,
-    Projection, Scan, Truncate, Union, Update, Values,
+    Aggregate, AlterTable, ConflictAction, CreateTable, Delete, Distinct, DropTables, Except,
+    Explain, Filter, IndexScan, Insert, Intersect, Join, JoinType, Limit, LogicalPlan, Merge,
+    MergeAction, MergeClause, OnConflict, Projection, RecursiveQuery, RowCount, Scan, Sort,
+    Truncate, Union, Update, Values, Window, WorkTableScan,
 };
 use sqlparser::ast::{
-    Assignment, AssignmentTarget, BinaryOperator, ColumnDef, CreateTable as SqlCreateTable,
-    Delete as SqlDelete, Expr, FromTable, Ident, Insert as SqlInsert, Join as SqlJoin,
-    JoinConstraint, JoinOperator, ObjectName, ObjectType, Offset, OffsetRows, Query, SelectItem,
-    SetExpr, SetOperator, SetQuantifier, Statement, TableFactor, TableWithJoins,
-    TruncateTableTarget, UnaryOperator, Value as SqlValue, Values as SqlValues,
+    AlterTableOperation, Assignment, AssignmentTarget, BinaryOperator, CastKind, CeilFloorKind,
+    ColumnDef, ColumnOption, ConflictTarget, CreateTable as SqlCreateTable, DateTimeField,
+    Delete as SqlDelete, Distinct as SqlDistinct, DoUpdate, DuplicateTreatment, Expr, FromTable,
+    Function, FunctionArg, FunctionArgExpr, FunctionArgumentList, FunctionArguments, GroupByExpr,
+    Ident, Insert as SqlInsert, Join as SqlJoin, JoinConstraint, JoinOperator,
+    MergeAction as SqlMergeAction, MergeClause as SqlMergeClause, MergeClauseKind, MergeInsertExpr,
+    MergeInsertKind, ObjectName, ObjectType, Offset, OffsetRows, OnConflict as SqlOnConflict,
+    OnConflictAction, OnInsert, OrderBy, OrderByExpr, Query, Select, SelectItem, SetExpr,
+    SetOperator, SetQuantifier, Statement, TableFactor, TableWithJoins, TrimWhereField,
+    TruncateTableTarget, UnaryOperator, Value as SqlValue, Values as SqlValues, WindowFrameBound,
+    WindowFrameUnits, WindowSpec, WindowType, With,
 };
 
 use anyhow::{anyhow, bail, ensure, Result};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 
-use crate::catalog::ArcCatalog;
+use crate::catalog::{AlterOperation, ArcCatalog};
 use crate::errors::Error;
-use crate::tuple::schema::Schema;
+use crate::tuple::constraints::Constraints;
+use crate::tuple::schema::{Field, Schema};
 use crate::txn_manager::TxnId;
-use crate::types::{Types, Value, ValueFactory};
+use crate::types::{with_escape, Str, Types, UInt, Value, ValueFactory};
 use crate::{is_boolean_op, lit, printdbg};
 
 pub struct LogicalPlanBuilder {
     catalog: ArcCatalog,
+    /// counts the statement's subqueries, each one's columns are
+    /// qualified by a generated alias to keep them apart
+    subqueries: Cell<usize>,
+    /// CTEs visible to the part of the query being built, innermost last
+    ctes: RefCell<Vec<CommonTableExpr>>,
+    /// what the `$1`, `$2`... placeholders of a prepared statement stand for
+    params: Params,
+    /// the tables the plan reads or writes, with the schemas it's built for
+    tables: RefCell<Vec<(String, Schema)>>,
+}
+
+enum Params {
+    /// the values of an EXECUTE, placeholders are built as literals
+    Values(Vec<Value>),
+    /// the types of the values, placeholders are built as parameters to
+    /// bind each EXECUTE's values to
+    Types(Vec<Types>),
+}
+
+impl Params {
+    fn len(&self) -> usize {
+        match self {
+            Params::Values(values) => values.len(),
+            Params::Types(types) => types.len(),
+        }
+    }
+}
+
+/// Relations in a query's FROM clause and their column names. Columns can
+/// be referenced as `relation.column` or as `column`, unless the source is
+/// qualified because column names clash, then they must be qualified
+#[derive(Default)]
+struct Scope {
+    relations: Vec<(String, Vec<String>)>,
+    qualified: bool,
+}
+
+impl Scope {
+    fn output_name(&self, relation: &str, column: &str) -> String {
+        match self.qualified {
+            true => format!("{relation}.{column}"),
+            false => column.to_string(),
+        }
+    }
+
+    /// Ambiguous or unknown names are left as is
+    fn resolve(&self, name: &str) -> String {
+        if self.qualified {
+            return name.to_string();
+        }
+
+        let matches = |relation: &str, column: &str| {
+            self.relations
+                .iter()
+                .filter(|(r, cols)| {
+                    (relation.is_empty() || r == relation) && cols.iter().any(|c| c == column)
+                })
+                .map(|(r, _)| self.output_name(r, column))
+                .collect::<Vec<_>>()
+        };
+
+        let candidates = match name.split_once('.') {
+            Some((relation, column)) => matches(relation, column),
+            None => matches("", name),
+        };
+
+        match candidates.as_slice() {
+            [candidate] => candidate.clone(),
+            _ => name.to_string(),
+        }
+    }
+
+    fn resolve_expr(&self, expr: LogicalExpr) -> LogicalExpr {
+        map_columns(expr, &mut |name| self.resolve(&name))
+    }
+
+    /// Output names of every column of a relation, for `relation.*`
+    fn columns_of(&self, relation: &str) -> Option<Vec<String>> {
+        self.relations
+            .iter()
+            .find(|(r, _)| r == relation)
+            .map(|(r, cols)| cols.iter().map(|c| self.output_name(r, c)).collect())
+    }
+}
+
+/// A subquery planned as the right side of a join
+struct Subquery {
+    plan: LogicalPlan,
+    /// the qualified columns of the select list
+    columns: Vec<String>,
+    /// predicates pulled out of a correlated subquery, they
+    /// compare its columns with the outer query's
+    correlation: Vec<LogicalExpr>,
+}
+
+/// A CTE of a WITH clause
+enum CommonTableExpr {
+    /// planned again wherever it's referenced
+    Query {
+        name: String,
+        columns: Vec<String>,
+        query: Box<Query>,
+        recursive: bool,
+    },
+    /// what a recursive CTE's recursive term sees of itself,
+    /// the rows added by the previous iteration
+    WorkTable {
+        name: String,
+        schema: Schema,
+        referenced: bool,
+    },
+}
+
+impl CommonTableExpr {
+    fn name(&self) -> &str {
+        match self {
+            CommonTableExpr::Query { name, .. } | CommonTableExpr::WorkTable { name, .. } => name,
+        }
+    }
 }
 
 impl LogicalPlanBuilder {
     pub fn new(catalog: ArcCatalog) -> Self {
-        Self { catalog }
+        Self {
+            catalog,
+            subqueries: Cell::new(0),
+            ctes: RefCell::new(vec![]),
+            params: Params::Values(vec![]),
+            tables: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn with_params(mut self, params: Vec<Value>) -> Self {
+        self.params = Params::Values(params);
+        self
+    }
+
+    /// Builds the plan once for parameters of these types, see [`bind_params`]
+    pub fn with_param_types(mut self, types: Vec<Types>) -> Self {
+        self.params = Params::Types(types);
+        self
+    }
+
+    /// The tables the built plans use, with the schemas they were built for
+    pub fn tables(&self) -> Vec<(String, Schema)> {
+        self.tables.borrow().clone()
+    }
+
+    /// Looks up a table the plan uses
+    fn table_schema(&self, table_name: &str, txn_id: Option<TxnId>) -> Result<Schema> {
+        let schema = self
+            .catalog
+            .read()
+            .get_schema(table_name, txn_id)
+            .ok_or(Error::TableNotFound(table_name.to_string()))?;
+        self.tables
+            .borrow_mut()
+            .push((table_name.to_string(), schema.clone()));
+        Ok(schema)
+    }
+
+    /// The values of the arguments of an EXECUTE statement
+    pub fn build_params(&self, exprs: &[Expr]) -> Result<Vec<Value>> {
+        exprs
+            .iter()
+            .map(|expr| match self.build_expr(expr)? {
+                LogicalExpr::Literal(value) => Ok(value),
+                _ => bail!(Error::Expected(
+                    "a value as parameter".into(),
+                    expr.to_string()
+                )),
+            })
+            .collect()
     }
 }
 
@@ -48,9 +233,10 @@ impl LogicalPlanBuilder {
                 table_name,
                 source,
                 columns,
+                on,
                 returning,
                 ..
-            }) => self.build_insert(table_name, source, columns, returning, txn_id),
+            }) => self.build_insert(table_name, source, columns, on, returning, txn_id),
             Statement::Query(query) => self.build_query(query, txn_id),
             Statement::Drop {
                 object_type,
@@ -62,14 +248,21 @@ impl LogicalPlanBuilder {
                 table,
                 assignments,
                 selection,
+                returning,
                 ..
-            } => self.build_update(table, assignments, selection, txn_id),
+            } => self.build_update(table, assignments, selection, returning, txn_id),
             Statement::CreateTable(SqlCreateTable {
                 name,
                 columns,
                 if_not_exists,
                 ..
             }) => self.build_create(name, columns, if_not_exists),
+            Statement::AlterTable {
+                name,
+                if_exists,
+                operations,
+                ..
+            } => self.build_alter_table(name, if_exists, operations, txn_id),
             Statement::Truncate {
                 table_names, table, ..
             } => self.build_truncate(table_names, table, txn_id),
@@ -77,8 +270,18 @@ impl LogicalPlanBuilder {
             Statement::Commit { .. } => self.build_commit_transaction(),
             Statement::Rollback { .. } => self.build_rollback_transaction(),
             Statement::Delete(SqlDelete {
-                from, selection, ..
-            }) => self.build_delete(from, selection, txn_id),
+                from,
+                selection,
+                returning,
+                ..
+            }) => self.build_delete(from, selection, returning, txn_id),
+            Statement::Merge {
+                table,
+                source,
+                on,
+                clauses,
+                ..
+            } => self.build_merge(table, source, *on, clauses, txn_id),
             e => bail!(Error::Unimplemented(format!("Statement: {e:?}"))),
         }
     }
@@ -87,10 +290,11 @@ impl LogicalPlanBuilder {
         &self,
         from: FromTable,
         selection: Option<Expr>,
+        returning: Option<Vec<SelectItem>>,
         txn_id: Option<TxnId>,
     ) -> Result<LogicalPlan> {
         let filter = match selection {
-            Some(expr) => build_expr(&expr)?,
+            Some(expr) => self.build_expr(&expr)?,
             None => {
                 bail!(Error::Unsupported(
                     "DELETE must contain a WHERE, else use TRUNCATE".into()
@@ -116,15 +320,13 @@ impl LogicalPlanBuilder {
             )),
         };
 
-        let schema = self
-            .catalog
-            .read()
-            .get_schema(&table_name, txn_id)
-            .ok_or(Error::TableNotFound(table_name.clone()))?;
+        let schema = self.table_schema(&table_name, txn_id)?;
+
+        let returning = self.build_returning(returning, &table_name, &schema)?;
 
         let root = LogicalPlan::Scan(Scan::new(table_name.clone(), schema));
 
-        let root = LogicalPlan::Delete(Box::new(Delete::new(root, table_name, filter)));
+        let root = LogicalPlan::Delete(Box::new(Delete::new(root, table_name, filter, returning)));
 
         Ok(root)
     }
@@ -222,6 +424,56 @@ impl LogicalPlanBuilder {
         )))
     }
 
+    /// `col IN (values)` looks up each value in the index
+    fn build_index_lookups(
+        &self,
+        table_name: String,
+        schema: Schema,
+        expr: InListExpr,
+    ) -> Result<LogicalPlan> {
+        let InListExpr {
+            expr,
+            list,
+            negated,
+        } = expr;
+
+        let LogicalExpr::Column(col) = expr else {
+            bail!(Error::Unsupported(
+                "Invalid index lookup, must be of form {{col}} IN ({{value}}, ...)".into()
+            ));
+        };
+
+        if negated {
+            bail!(Error::Unsupported("NOT IN in PREWHERE clause".into()));
+        }
+
+        match schema.fields.iter().find(|f| f.name == col) {
+            Some(field) if !field.constraints.unique => bail!(Error::Unsupported(
+                "Index scan only supported on unique fields".into()
+            )),
+            Some(_) => {}
+            None => bail!(Error::ColumnNotFound(col)),
+        }
+
+        let mut keys = list
+            .into_iter()
+            .map(|e| match e {
+                LogicalExpr::Literal(v @ (Value::UInt(_) | Value::Int(_) | Value::Float(_))) => {
+                    Ok(v.as_u32())
+                }
+                _ => bail!(Error::Unsupported(
+                    "Index scan only supported on UInt".into()
+                )),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        keys.sort();
+        keys.dedup();
+
+        Ok(LogicalPlan::IndexScan(IndexScan::lookups(
+            table_name, schema, col, keys,
+        )))
+    }
+
     fn build_start_transaction(&self) -> Result<LogicalPlan> {
         Ok(LogicalPlan::StartTxn)
     }
@@ -312,7 +564,7 @@ impl LogicalPlanBuilder {
             .map(|row| {
                 row.into_iter()
                     .map(|expr| match expr {
-                        Expr::Value(_) | Expr::UnaryOp { .. } => build_expr(&expr),
+                        Expr::Value(_) | Expr::UnaryOp { .. } => self.build_expr(&expr),
                         e => bail!(Error::Unsupported(format!(
                             "Unsupported expression in VALUES: {e:?}"
                         ))),
@@ -339,74 +591,283 @@ impl LogicalPlanBuilder {
         Ok(LogicalPlan::Values(Values::new(rows, Schema::new(fields))))
     }
 
-    fn build_query(&self, query: Box<Query>, txn_id: Option<TxnId>) -> Result<LogicalPlan> {
+    fn build_query(&self, mut query: Box<Query>, txn_id: Option<TxnId>) -> Result<LogicalPlan> {
+        if let Some(with) = query.with.take() {
+            let depth = self.ctes.borrow().len();
+            let plan = self
+                .push_ctes(with)
+                .and_then(|_| self.build_query(query, txn_id));
+            self.ctes.borrow_mut().truncate(depth);
+            return plan;
+        }
+
         let Query {
             body,
+            order_by,
             limit,
             offset,
             ..
         } = *query;
 
+        let order_by = match order_by {
+            Some(OrderBy {
+                exprs,
+                interpolate: None,
+            }) => exprs,
+            Some(e) => bail!(Error::Unsupported(format!("{e}"))),
+            None => vec![],
+        };
+
         let input = match *body {
-            SetExpr::Select(_) => self.build_select(body, limit, offset, txn_id)?,
+            SetExpr::Select(_) => return self.build_select(body, order_by, limit, offset, txn_id),
             SetExpr::Values(SqlValues { rows, .. }) => self.build_values(rows)?,
             SetExpr::SetOperation {
-                op: SetOperator::Union,
+                op,
                 left,
                 right,
                 set_quantifier,
-            } => self.build_union(left, right, set_quantifier, txn_id)?,
+            } => self.build_set_operation(op, left, right, set_quantifier, txn_id)?,
             e => bail!(Error::Unsupported(format!("Query: {e}"))),
         };
 
-        Ok(input)
+        let mut root = input;
+
+        if !order_by.is_empty() {
+            // only the output columns can be referenced when sorting a set operation
+            let outputs = root
+                .schema()
+                .fields
+                .iter()
+                .map(|f| LogicalExpr::Column(f.name.clone()))
+                .collect::<Vec<_>>();
+
+            let exprs = self.build_sort_exprs(order_by, &outputs)?;
+            for e in exprs.iter() {
+                check_columns(&e.expr, &root.schema())?;
+            }
+
+            root = LogicalPlan::Sort(Box::new(Sort::new(root, exprs)));
+        }
+
+        self.build_limit(root, limit, offset)
+    }
+
+    /// Makes the CTEs visible to the rest of the query, each can
+    /// reference the ones before it and a recursive one itself
+    fn push_ctes(&self, with: With) -> Result<()> {
+        let mut names = HashSet::new();
+
+        for cte in with.cte_tables {
+            let name = cte.alias.name.value.clone();
+            if !names.insert(name.clone()) {
+                bail!(Error::Expected(
+                    "a unique name for every CTE".into(),
+                    format!("{name} more than once")
+                ));
+            }
+
+            if let Some(from) = cte.from {
+                bail!(Error::Unsupported(format!("CTE {name} FROM {from}")));
+            }
+
+            self.ctes.borrow_mut().push(CommonTableExpr::Query {
+                name,
+                columns: cte.alias.columns.iter().map(|c| c.value.clone()).collect(),
+                query: cte.query,
+                recursive: with.recursive,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Index of the innermost CTE with the given name
+    fn find_cte(&self, name: &str) -> Option<usize> {
+        self.ctes
+            .borrow()
+            .iter()
+            .rposition(|cte| cte.name() == name)
+    }
+
+    /// Plans a reference to a CTE. Only the CTEs defined before
+    /// it are visible while planning its query
+    fn build_cte(&self, index: usize, txn_id: Option<TxnId>) -> Result<LogicalPlan> {
+        let (name, columns, query, recursive) = match &mut self.ctes.borrow_mut()[index] {
+            CommonTableExpr::WorkTable {
+                name,
+                schema,
+                referenced,
+            } => {
+                *referenced = true;
+                let scan = WorkTableScan::new(name.clone(), schema.clone());
+                return Ok(LogicalPlan::WorkTableScan(scan));
+            }
+            CommonTableExpr::Query {
+                name,
+                columns,
+                query,
+                recursive,
+            } => (name.clone(), columns.clone(), query.clone(), *recursive),
+        };
+
+        let hidden = self.ctes.borrow_mut().split_off(index);
+        let plan = match recursive {
+            true => self.build_recursive_cte(name, columns, query, txn_id),
+            false => self
+                .build_query(query, txn_id)
+                .and_then(|plan| rename_columns(plan, &columns)),
+        };
+        let mut ctes = self.ctes.borrow_mut();
+        ctes.truncate(index);
+        ctes.extend(hidden);
+
+        plan
     }
 
-    fn build_union(
+    /// A CTE of WITH RECURSIVE that is a UNION of a static term and a recursive
+    /// term referencing the CTE becomes a fixpoint, other queries are planned
+    /// as usual
+    fn build_recursive_cte(
         &self,
-        left: Box<SetExpr>,
-        right: Box<SetExpr>,
-        quantifier: SetQuantifier,
+        name: String,
+        columns: Vec<String>,
+        query: Box<Query>,
         txn_id: Option<TxnId>,
     ) -> Result<LogicalPlan> {
-        if quantifier == SetQuantifier::All {
-            bail!(Error::Unsupported("UNION ALL not supported".into()));
-        }
-
-        let left = match *left {
-            SetExpr::Select(_) => self.build_select(left, None, None, txn_id)?,
+        let is_union = matches!(
+            *query.body,
             SetExpr::SetOperation {
                 op: SetOperator::Union,
-                left,
-                right,
                 ..
-            } => self.build_union(left, right, quantifier, txn_id)?,
-            query => bail!(Error::Unsupported(format!("UNION with query: {query}"))),
+            }
+        );
+
+        if !is_union
+            || query.with.is_some()
+            || query.order_by.is_some()
+            || query.limit.is_some()
+            || query.offset.is_some()
+        {
+            let plan = self.build_query(query, txn_id)?;
+            return rename_columns(plan, &columns);
+        }
+
+        let SetExpr::SetOperation {
+            left,
+            right,
+            set_quantifier: quantifier,
+            ..
+        } = *query.body
+        else {
+            unreachable!()
+        };
+
+        let distinct = match quantifier {
+            SetQuantifier::All => false,
+            SetQuantifier::Distinct | SetQuantifier::None => true,
+            q => bail!(Error::Unsupported(format!("UNION {q} in a recursive CTE"))),
+        };
+
+        let static_term = rename_columns(self.build_set_expr(left, txn_id)?, &columns)?;
+
+        self.ctes.borrow_mut().push(CommonTableExpr::WorkTable {
+            name: name.clone(),
+            schema: static_term.schema(),
+            referenced: false,
+        });
+        let recursive_term = self.build_set_expr(right, txn_id);
+        let referenced = match self.ctes.borrow_mut().pop() {
+            Some(CommonTableExpr::WorkTable { referenced, .. }) => referenced,
+            _ => unreachable!("the work table is the innermost CTE"),
         };
+        let recursive_term = recursive_term?;
+
+        let types = check_set_operands(
+            SetOperator::Union,
+            &static_term.schema(),
+            &recursive_term.schema(),
+        )?;
+        let recursive_term = cast_columns(recursive_term, &types);
+
+        // without a reference to itself it's an ordinary UNION
+        if !referenced {
+            let static_term = cast_columns(static_term, &types);
+            let union = LogicalPlan::Union(Box::new(Union::new(static_term, recursive_term)));
+            return Ok(match distinct {
+                true => LogicalPlan::Distinct(Box::new(Distinct::new(union))),
+                false => union,
+            });
+        }
 
-        let right = match *right {
-            SetExpr::Select(_) => self.build_select(right, None, None, txn_id)?,
+        // the work table has the types of the static term
+        let static_types = static_term
+            .schema()
+            .fields
+            .into_iter()
+            .map(|f| f.ty)
+            .collect::<Vec<_>>();
+        ensure!(
+            types == static_types,
+            Error::TypeMismatch(static_types, types)
+        );
+
+        Ok(LogicalPlan::RecursiveQuery(Box::new(RecursiveQuery::new(
+            name,
+            static_term,
+            recursive_term,
+            distinct,
+        ))))
+    }
+
+    /// Operands of a set operation are full queries without
+    /// their own ORDER BY or LIMIT, unless parenthesized
+    fn build_set_expr(&self, expr: Box<SetExpr>, txn_id: Option<TxnId>) -> Result<LogicalPlan> {
+        match *expr {
+            SetExpr::Select(_) => self.build_select(expr, vec![], None, None, txn_id),
+            SetExpr::Values(SqlValues { rows, .. }) => self.build_values(rows),
+            SetExpr::Query(query) => self.build_query(query, txn_id),
             SetExpr::SetOperation {
-                op: SetOperator::Union,
+                op,
                 left,
                 right,
-                ..
-            } => self.build_union(left, right, quantifier, txn_id)?,
-            query => bail!(Error::Unsupported(format!("UNION with query: {query}"))),
+                set_quantifier,
+            } => self.build_set_operation(op, left, right, set_quantifier, txn_id),
+            query => bail!(Error::Unsupported(format!(
+                "Set operation with query: {query}"
+            ))),
+        }
+    }
+
+    fn build_set_operation(
+        &self,
+        op: SetOperator,
+        left: Box<SetExpr>,
+        right: Box<SetExpr>,
+        quantifier: SetQuantifier,
+        txn_id: Option<TxnId>,
+    ) -> Result<LogicalPlan> {
+        let all = match quantifier {
+            SetQuantifier::All => true,
+            SetQuantifier::Distinct | SetQuantifier::None => false,
+            q => bail!(Error::Unsupported(format!("{op} {q}"))),
         };
 
-        let left_types: Vec<_> = left.schema().fields.iter().map(|f| f.ty.clone()).collect();
-        let right_types: Vec<_> = right.schema().fields.iter().map(|f| f.ty.clone()).collect();
+        let left = self.build_set_expr(left, txn_id)?;
+        let right = self.build_set_expr(right, txn_id)?;
 
-        if left_types
-            .iter()
-            .zip(right_types.iter())
-            .any(|(a, b)| !a.is_compatible(b))
-        {
-            bail!(Error::TypeMismatch(left_types, right_types));
-        }
+        let types = check_set_operands(op, &left.schema(), &right.schema())?;
+        let (left, right) = (cast_columns(left, &types), cast_columns(right, &types));
 
-        Ok(LogicalPlan::Union(Box::new(Union::new(left, right))))
+        Ok(match op {
+            SetOperator::Union if all => LogicalPlan::Union(Box::new(Union::new(left, right))),
+            SetOperator::Union => LogicalPlan::Distinct(Box::new(Distinct::new(
+                LogicalPlan::Union(Box::new(Union::new(left, right))),
+            ))),
+            SetOperator::Intersect => {
+                LogicalPlan::Intersect(Box::new(Intersect::new(left, right, all)))
+            }
+            SetOperator::Except => LogicalPlan::Except(Box::new(Except::new(left, right, all))),
+        })
     }
 
     fn build_insert(
@@ -414,7 +875,8 @@ impl LogicalPlanBuilder {
         table_name: ObjectName,
         source: Option<Box<Query>>,
         columns: Vec<Ident>,
-        _returning: Option<Vec<SelectItem>>,
+        on: Option<OnInsert>,
+        returning: Option<Vec<SelectItem>>,
         txn_id: Option<TxnId>,
     ) -> Result<LogicalPlan> {
         if source.is_none() {
@@ -428,11 +890,7 @@ impl LogicalPlanBuilder {
         let input_schema = input.schema();
 
         let table_name = table_name.0.first().unwrap().value.clone();
-        let schema = self
-            .catalog
-            .read()
-            .get_schema(&table_name, txn_id)
-            .ok_or(Error::TableNotFound(table_name.clone()))?;
+        let schema = self.table_schema(&table_name, txn_id)?;
 
         let columns = if columns.is_empty() {
             schema.fields.iter().map(|f| f.name.clone()).collect()
@@ -452,22 +910,27 @@ impl LogicalPlanBuilder {
             names
         };
 
+        let on_conflict = self.build_on_conflict(on, &table_name, &schema)?;
+        let returning = self.build_returning(returning, &table_name, &schema)?;
+
         let insert = Insert::new(
             input,
             columns,
             table_name,
             schema.clone(),
-            Schema::default(),
+            on_conflict,
+            returning,
         );
 
         let input_types =
             insert.reorder(input_schema.fields.iter().map(|f| f.ty.clone()).collect())?;
         let table_types: Vec<_> = schema.fields.iter().map(|f| f.ty.clone()).collect();
 
+        // the values are coerced to the column types when inserted
         if table_types
             .iter()
             .zip(input_types.iter())
-            .any(|(a, b)| !a.is_compatible(b))
+            .any(|(table, input)| !input.coerces_to(table))
         {
             bail!(Error::TypeMismatch(table_types, input_types));
         }
@@ -482,10 +945,11 @@ impl LogicalPlanBuilder {
         table: TableWithJoins,
         assignments: Vec<Assignment>,
         selection: Option<Expr>,
+        returning: Option<Vec<SelectItem>>,
         txn_id: Option<TxnId>,
     ) -> Result<LogicalPlan> {
         let filter = match selection {
-            Some(expr) => build_expr(&expr)?,
+            Some(expr) => self.build_expr(&expr)?,
             None => LogicalExpr::Literal(lit!(Bool, "true".to_string())?),
         };
 
@@ -496,17 +960,46 @@ impl LogicalPlanBuilder {
             )),
         };
 
+        let schema = self.table_schema(&table_name, txn_id)?;
+
+        let filter = coerce_types(filter, &schema)?;
+        let assignments =
+            self.build_assignments(assignments, &table_name, &schema, &schema, |e| e)?;
+
+        let returning = self.build_returning(returning, &table_name, &schema)?;
+
+        let root = LogicalPlan::Scan(Scan::new(table_name.clone(), schema));
+
+        let root = LogicalPlan::Update(Box::new(Update::new(
+            root,
+            table_name,
+            assignments,
+            filter,
+            returning,
+        )));
+
+        Ok(root)
+    }
+
+    /// Plans the SET list of an UPDATE over rows of `input`, the values'
+    /// columns are renamed by `resolve`. Each value is cast to the type of
+    /// the column it's assigned to
+    fn build_assignments(
+        &self,
+        assignments: Vec<Assignment>,
+        table_name: &str,
+        schema: &Schema,
+        input: &Schema,
+        resolve: impl Fn(LogicalExpr) -> LogicalExpr,
+    ) -> Result<Vec<(String, LogicalExpr)>> {
         let assignments = assignments
             .into_iter()
-            .map(|a| self.build_assignemnt(a))
+            .map(|a| {
+                let (col, expr) = self.build_assignemnt(a)?;
+                Ok((col, resolve(expr)))
+            })
             .collect::<Result<Vec<_>>>()?;
 
-        let schema = self
-            .catalog
-            .read()
-            .get_schema(&table_name, txn_id)
-            .ok_or(Error::TableNotFound(table_name.clone()))?;
-
         for (col, _) in assignments.iter() {
             if !schema.fields.iter().any(|f| &f.name == col) {
                 if schema.is_qualified() {
@@ -521,518 +1014,3311 @@ impl LogicalPlanBuilder {
             }
         }
 
-        let root = LogicalPlan::Scan(Scan::new(table_name.clone(), schema));
-
-        let root =
-            LogicalPlan::Update(Box::new(Update::new(root, table_name, assignments, filter)));
-
-        Ok(root)
+        assignments
+            .into_iter()
+            .map(|(col, expr)| {
+                check_columns(&expr, input)?;
+                let expr = coerce_types(expr, input)?;
+                let from = expr.to_field(input).ty;
+                let to = &schema.fields.iter().find(|f| f.name == col).unwrap().ty;
+                ensure!(
+                    from.coerces_to(to),
+                    Error::TypeMismatch(vec![to.clone()], vec![from])
+                );
+                Ok((col, cast_expr(expr, &from, to)?))
+            })
+            .collect()
     }
 
-    fn build_assignemnt(&self, assignment: Assignment) -> Result<(String, LogicalExpr)> {
-        let Assignment { target, value } = assignment;
+    /// Plans ON CONFLICT. Conflicts are found through the index of the
+    /// table's unique column, so it's the only target allowed
+    fn build_on_conflict(
+        &self,
+        on: Option<OnInsert>,
+        table_name: &str,
+        schema: &Schema,
+    ) -> Result<Option<OnConflict>> {
+        let SqlOnConflict {
+            conflict_target,
+            action,
+        } = match on {
+            None => return Ok(None),
+            Some(OnInsert::OnConflict(on_conflict)) => on_conflict,
+            Some(e) => bail!(Error::Unsupported(e.to_string().trim().to_string())),
+        };
 
-        let col = match target {
-            AssignmentTarget::ColumnName(col) => col.0.first().unwrap().value.clone(),
-            AssignmentTarget::Tuple(_) => todo!(),
+        let Some(unique) = schema.fields.iter().find(|f| f.constraints.unique) else {
+            bail!(Error::Expected(
+                format!("a UNIQUE column in {table_name} for ON CONFLICT"),
+                "None".into()
+            ));
         };
 
-        Ok((col, build_expr(&value)?))
-    }
+        match &conflict_target {
+            Some(ConflictTarget::Columns(cols))
+                if cols.len() == 1 && cols[0].value == unique.name => {}
+            // the table has a single unique column to conflict on
+            None if matches!(action, OnConflictAction::DoNothing) => {}
+            Some(ConflictTarget::Columns(cols)) => bail!(Error::Expected(
+                format!("the UNIQUE column ({}) as the conflict target", unique.name),
+                format!(
+                    "({})",
+                    cols.iter()
+                        .map(|c| c.value.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            )),
+            Some(ConflictTarget::OnConstraint(name)) => {
+                bail!(Error::Unsupported(format!(
+                    "ON CONFLICT ON CONSTRAINT {name}"
+                )))
+            }
+            None => bail!(Error::Expected(
+                format!("a conflict target ({}) for DO UPDATE", unique.name),
+                "None".into()
+            )),
+        }
 
-    fn build_create(
-        &self,
-        name: ObjectName,
-        columns: Vec<ColumnDef>,
-        if_not_exists: bool,
-    ) -> Result<LogicalPlan> {
-        let root = LogicalPlan::default();
+        let action = match action {
+            OnConflictAction::DoNothing => ConflictAction::Nothing,
+            OnConflictAction::DoUpdate(DoUpdate {
+                assignments,
+                selection,
+            }) => {
+                let input = OnConflict::input_schema(schema);
 
-        let create = CreateTable::new(
-            root,
-            name.0.first().unwrap().value.clone(),
-            Schema::from_sql(columns)?,
-            if_not_exists,
-        );
+                // `column` and `table.column` read the existing row,
+                // `excluded.column` reads the row that was to be inserted
+                let resolve = |expr| {
+                    map_columns(expr, &mut |name| match name.split_once('.') {
+                        Some((relation, column)) if relation.eq_ignore_ascii_case("excluded") => {
+                            format!("excluded.{column}")
+                        }
+                        Some((relation, column)) if relation == table_name => column.to_string(),
+                        _ => name,
+                    })
+                };
 
-        Ok(LogicalPlan::CreateTable(Box::new(create)))
+                let assignments =
+                    self.build_assignments(assignments, table_name, schema, &input, resolve)?;
+
+                let selection = match selection {
+                    Some(expr) => resolve(self.build_expr(&expr)?),
+                    None => LogicalExpr::Literal(lit!(Bool, "true".to_string())?),
+                };
+                check_columns(&selection, &input)?;
+                let selection = coerce_types(selection, &input)?;
+
+                ConflictAction::Update {
+                    assignments,
+                    selection,
+                }
+            }
+        };
+
+        Ok(Some(OnConflict::new(unique.name.clone(), action)))
     }
 
-    fn build_source(
+    /// Plans MERGE as a join of the target and the source. The ON condition
+    /// and the clauses read the target's columns and the source's, which are
+    /// qualified when their names clash like the relations of a join. A
+    /// source row that matches nothing has no target columns, so WHEN NOT
+    /// MATCHED clauses only read the source's
+    fn build_merge(
         &self,
-        table: Option<&TableWithJoins>,
-        prewhere: Option<Expr>,
+        table: TableFactor,
+        source: TableFactor,
+        on: Expr,
+        clauses: Vec<SqlMergeClause>,
         txn_id: Option<TxnId>,
     ) -> Result<LogicalPlan> {
-        match &table {
-            None => Ok(LogicalPlan::Empty),
-            Some(TableWithJoins { relation, joins }) => {
-                let left_name = match relation {
-                    TableFactor::Table { name, .. } => name.0.first().unwrap().value.clone(),
-                    e => bail!(Error::Unsupported(e.to_string())),
+        let (table_name, alias) = match table {
+            TableFactor::Table { name, alias, .. } => {
+                let table_name = name.0.first().unwrap().value.clone();
+                let alias = match alias {
+                    Some(alias) if !alias.columns.is_empty() => {
+                        bail!(Error::Unsupported(format!("Column aliases in {alias}")))
+                    }
+                    Some(alias) => alias.name.value,
+                    None => table_name.clone(),
                 };
+                (table_name, alias)
+            }
+            e => bail!(Error::Unsupported(format!("MERGE INTO {e}"))),
+        };
 
-                if joins.len() > 1 {
-                    bail!(Error::Unsupported("Multiple joins".into()));
-                }
+        let schema = self.table_schema(&table_name, txn_id)?;
 
-                let mut left_schema = self
-                    .catalog
-                    .read()
-                    .get_schema(&left_name, txn_id)
-                    .ok_or(Error::TableNotFound(left_name.clone()))?;
+        let (source_name, source) = self.build_relation(&source, None, txn_id)?;
+        let source_schema = source.schema();
 
-                let root = if let Some(pre) = prewhere {
-                    let expr = build_expr(&pre)?;
-                    match expr {
-                        LogicalExpr::BinaryExpr(expr) => {
-                            self.build_index_scan(left_name.clone(), left_schema.clone(), *expr)
-                        }
-                        _ => bail!(Error::Unsupported(
-                            "Prewhere must be a binary expression".into()
-                        )),
-                    }?
-                } else {
-                    LogicalPlan::Scan(Scan::new(left_name.clone(), left_schema.clone()))
+        let mut names = HashSet::new();
+        let qualify = schema
+            .fields
+            .iter()
+            .chain(source_schema.fields.iter())
+            .any(|f| !names.insert(&f.name));
+
+        if qualify && alias == source_name {
+            bail!(Error::Expected(
+                "a unique alias for every relation".into(),
+                format!("{alias} more than once")
+            ));
+        }
+
+        let scope = Scope {
+            relations: vec![
+                (
+                    alias.clone(),
+                    schema.fields.iter().map(|f| f.name.clone()).collect(),
+                ),
+                (
+                    source_name.clone(),
+                    source_schema
+                        .fields
+                        .iter()
+                        .map(|f| f.name.clone())
+                        .collect(),
+                ),
+            ],
+            qualified: qualify,
+        };
+
+        let (target_schema, source_schema) = match qualify {
+            true => (
+                schema.add_qualifier(&alias),
+                source_schema.add_qualifier(&source_name),
+            ),
+            false => (schema.clone(), source_schema),
+        };
+        let input_schema = target_schema.join(source_schema.clone())?;
+
+        let build = |expr: &Expr, input: &Schema, clause: &str| -> Result<LogicalExpr> {
+            let expr = scope.resolve_expr(self.build_expr(expr)?);
+            check_columns(&expr, input)?;
+            ensure_no_windows(&expr, clause)?;
+            if expr.contains_aggregate() {
+                bail!(Error::Unsupported(format!(
+                    "Aggregate in {clause}: {}",
+                    expr.print()
+                )));
+            }
+            coerce_types(expr, input)
+        };
+
+        let on = build(&on, &input_schema, "MERGE")?;
+
+        let clauses = clauses
+            .into_iter()
+            .map(|clause| {
+                let SqlMergeClause {
+                    clause_kind,
+                    predicate,
+                    action,
+                } = clause;
+
+                let matched = match clause_kind {
+                    MergeClauseKind::Matched => true,
+                    MergeClauseKind::NotMatched | MergeClauseKind::NotMatchedByTarget => false,
+                    MergeClauseKind::NotMatchedBySource => {
+                        bail!(Error::Unsupported(format!("WHEN {clause_kind}")))
+                    }
                 };
 
-                let root = match &joins.first() {
-                    Some(SqlJoin {
-                        relation,
-                        join_operator,
-                        ..
-                    }) => match relation {
-                        TableFactor::Table { name, .. } => {
-                            let right_name = name.0.first().unwrap().value.clone();
-                            let mut right_schema = self
-                                .catalog
-                                .read()
-                                .get_schema(&right_name, txn_id)
-                                .ok_or(Error::TableNotFound(right_name.clone()))?;
-
-                            let right_scan = LogicalPlan::Scan(Scan::new(
-                                right_name.clone(),
-                                right_schema.clone(),
-                            ));
-
-                            let operator = match join_operator {
-                                JoinOperator::Inner(e) => match e {
-                                    JoinConstraint::On(expr) => match build_expr(expr)? {
-                                        LogicalExpr::BinaryExpr(expr) => expr,
-                                        _ => bail!(Error::Unsupported(
-                                                "Only Binary Expressions are supported in join conditions".into()
-                                        )),
-                                    },
-                                    JoinConstraint::Using(col) => {
-                                        if col.len() != 1 {
-                                            bail!(Error::Unsupported(
-                                                "Using clause must have a single column".into()
-                                            ))
-                                        }
-                                        let col = col.first().unwrap().value.clone();
-                                        Box::new(BinaryExpr::new(
-                                            LogicalExpr::Column(col.clone()),
-                                            BinaryOperator::Eq,
-                                            LogicalExpr::Column(col),
-                                        ))
-                                    }
-                                    JoinConstraint::None => {
-                                        bail!(Error::Expected("ON or USING".into(), "None".into()))
-                                    }
-                                    JoinConstraint::Natural => {
-                                        bail!(Error::Expected(
-                                            "ON or USING".into(),
-                                            "Natural".into()
-                                        ))
-                                    }
-                                },
-                                _ => bail!(Error::Unsupported("Only supports inner joins".into())),
-                            };
-
-                            let join_schema = match left_schema.join(right_schema.clone()) {
-                                Ok(s) => s,
-                                Err(_) => {
-                                    left_schema = left_schema.add_qualifier(&left_name);
-                                    right_schema = right_schema.add_qualifier(&right_name);
-                                    left_schema.join(right_schema).unwrap()
-                                }
-                            };
-
-                            LogicalPlan::Join(Box::new(Join::new(
-                                root,
-                                right_scan,
-                                *operator,
-                                join_schema,
-                            )))
-                        }
-                        _ => bail!(Error::Unsupported("Only supports tables with joins".into())),
-                    },
-                    None => root,
+                let input = match matched {
+                    true => &input_schema,
+                    false => &source_schema,
                 };
 
-                Ok(root)
-            }
+                let predicate = match predicate {
+                    Some(expr) => build(&expr, input, "WHEN")?,
+                    None => LogicalExpr::Literal(lit!(Bool, "true".to_string())?),
+                };
+
+                let action = match (matched, action) {
+                    (true, SqlMergeAction::Update { assignments }) => {
+                        MergeAction::Update(self.build_assignments(
+                            assignments,
+                            &table_name,
+                            &schema,
+                            &input_schema,
+                            |e| scope.resolve_expr(e),
+                        )?)
+                    }
+                    (true, SqlMergeAction::Delete) => MergeAction::Delete,
+                    (false, SqlMergeAction::Insert(insert)) => {
+                        let values =
+                            self.build_merge_insert(insert, &schema, &source_schema, |e| {
+                                build(e, &source_schema, "INSERT")
+                            })?;
+                        MergeAction::Insert(values)
+                    }
+                    (true, action) => bail!(Error::Expected(
+                        "UPDATE or DELETE for WHEN MATCHED".into(),
+                        action.to_string()
+                    )),
+                    (false, action) => bail!(Error::Expected(
+                        "INSERT for WHEN NOT MATCHED".into(),
+                        action.to_string()
+                    )),
+                };
+
+                Ok(MergeClause::new(matched, predicate, action))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // every source row is kept, those that match nothing are padded
+        // with NULLs for the target's columns and its tuple id
+        let target_width = Scan::tuple_id_fields().len() + schema.fields.len();
+        let mut join_schema = Scan::tuple_id_fields();
+        join_schema.extend(input_schema.fields.iter().cloned());
+        for field in join_schema.iter_mut().take(target_width) {
+            field.constraints.nullable = true;
         }
+
+        let input = LogicalPlan::Join(Box::new(Join::new(
+            LogicalPlan::Scan(Scan::with_tuple_ids(table_name.clone(), schema)),
+            source,
+            join_condition(vec![on]),
+            JoinType::Right,
+            Schema::new(join_schema),
+        )));
+
+        Ok(LogicalPlan::Merge(Box::new(Merge::new(
+            input,
+            table_name,
+            input_schema,
+            clauses,
+        ))))
     }
 
-    fn build_select(
+    /// The values a WHEN NOT MATCHED clause inserts, one for each column of
+    /// the table in order. Columns it leaves out are NULL
+    fn build_merge_insert(
         &self,
-        body: Box<SetExpr>,
-        limit: Option<Expr>,
-        offset: Option<Offset>,
-        txn_id: Option<TxnId>,
-    ) -> Result<LogicalPlan> {
-        let select = match *body {
-            SetExpr::Select(select) => select,
-            e => unreachable!("Should only be called on a select query, got: {:?}", e),
-        };
-
-        let mut root = self.build_source(select.from.first(), select.prewhere, txn_id)?;
+        insert: MergeInsertExpr,
+        schema: &Schema,
+        input: &Schema,
+        build: impl Fn(&Expr) -> Result<LogicalExpr>,
+    ) -> Result<Vec<LogicalExpr>> {
+        let MergeInsertExpr { columns, kind } = insert;
 
-        let filters = select.selection.clone().map(|e| match e {
-            Expr::BinaryOp { left, right, op } => self.parse_boolean_expr(*left, op, *right),
-            Expr::Value(SqlValue::Boolean(b)) => Ok(BooleanBinaryExpr::new(
-                b.into(),
-                BinaryOperator::Eq,
-                true.into(),
-            )),
-            Expr::Identifier(Ident { value, .. })
-                if root
-                    .schema()
-                    .fields
-                    .iter()
-                    .find(|field| field.name == value)
-                    .map(|f| matches!(f.ty, Types::Bool))
-                    .unwrap_or(false) =>
-            {
-                Ok(BooleanBinaryExpr::new(
-                    LogicalExpr::Column(value.clone()),
-                    BinaryOperator::Eq,
-                    true.into(),
-                ))
+        let row = match kind {
+            MergeInsertKind::Values(SqlValues { mut rows, .. }) if rows.len() == 1 => {
+                rows.pop().unwrap()
             }
-            Expr::CompoundIdentifier(idents) => {
-                let name = idents
-                    .into_iter()
-                    .map(|i| i.value.clone())
-                    .collect::<Vec<String>>()
-                    .join(".");
+            MergeInsertKind::Values(SqlValues { rows, .. }) => bail!(Error::Expected(
+                "a single row of values to insert".into(),
+                format!("{} rows", rows.len())
+            )),
+            MergeInsertKind::Row => bail!(Error::Unsupported("INSERT ROW".into())),
+        };
 
-                if !root.schema().fields.iter().any(|f| f.name == name) {
-                    bail!(Error::ColumnNotFound(name.clone()));
-                }
+        let columns: Vec<_> = match columns.is_empty() {
+            true => schema.fields.iter().map(|f| f.name.clone()).collect(),
+            false => columns.into_iter().map(|i| i.value).collect(),
+        };
 
-                Ok(BooleanBinaryExpr::new(
-                    LogicalExpr::Column(name),
-                    BinaryOperator::Eq,
-                    true.into(),
-                ))
-            }
-            e => bail!(Error::Unimplemented(format!("Expr: {e:?}"))),
-        });
+        let non_existant: Vec<_> = columns
+            .iter()
+            .filter(|c| !schema.fields.iter().any(|f| &f.name == *c))
+            .cloned()
+            .collect();
 
-        if let Some(filter) = filters {
-            root = LogicalPlan::Filter(Box::new(Filter::new(root, filter?)));
+        if !non_existant.is_empty() {
+            bail!(Error::ColumnsNotFound(non_existant));
         }
 
-        for projection in select.projection.iter() {
-            if matches!(root, LogicalPlan::Empty)
-                && matches!(projection, SelectItem::UnnamedExpr(Expr::Identifier(_)))
-            {
-                return Err(anyhow!("Can't select col without a table"));
-            }
+        if row.len() != columns.len() {
+            bail!(Error::Expected(
+                format!("{} values", columns.len()),
+                row.len().to_string()
+            ));
         }
 
-        let offset = match offset {
-            Some(offset) => {
-                ensure!(
-                    offset.rows == OffsetRows::None,
-                    "OFFSET [ROWS|ROW] is not supported"
-                );
-                Some(offset.value)
-            }
-            None => None,
+        let mut values = columns
+            .into_iter()
+            .zip(row.iter())
+            .map(|(col, expr)| Ok((col, build(expr)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        // the values are coerced to the column types when inserted
+        schema
+            .fields
+            .iter()
+            .map(|field| match values.remove(&field.name) {
+                Some(value) => {
+                    let from = value.to_field(input).ty;
+                    ensure!(
+                        from.coerces_to(&field.ty),
+                        Error::TypeMismatch(vec![field.ty.clone()], vec![from])
+                    );
+                    Ok(value)
+                }
+                None => Ok(LogicalExpr::Literal(Value::Null)),
+            })
+            .collect()
+    }
+
+    /// Plans the RETURNING list of an INSERT, UPDATE or DELETE over the
+    /// columns of the rows it changes
+    fn build_returning(
+        &self,
+        returning: Option<Vec<SelectItem>>,
+        table_name: &str,
+        schema: &Schema,
+    ) -> Result<Vec<LogicalExpr>> {
+        let Some(items) = returning else {
+            return Ok(vec![]);
         };
 
-        root = self.build_limit(root, limit, offset)?;
+        let columns = schema.fields.iter().map(|f| f.name.clone()).collect();
+        let scope = Scope {
+            relations: vec![(table_name.to_string(), columns)],
+            qualified: false,
+        };
 
-        let projections = self.build_projections(select.projection, root.schema())?;
+        let exprs = self.build_projections(items, schema.clone(), &scope)?;
+        for expr in exprs.iter() {
+            check_columns(expr, schema)?;
+            ensure_no_windows(expr, "RETURNING")?;
+            if expr.contains_aggregate() {
+                bail!(Error::Unsupported(format!(
+                    "Aggregate in RETURNING: {}",
+                    expr.print()
+                )));
+            }
+        }
 
-        root = LogicalPlan::Projection(Box::new(Projection::new(root, projections)));
+        Ok(exprs)
+    }
 
-        Ok(root)
+    fn build_assignemnt(&self, assignment: Assignment) -> Result<(String, LogicalExpr)> {
+        let Assignment { target, value } = assignment;
+
+        let col = match target {
+            AssignmentTarget::ColumnName(col) => col.0.first().unwrap().value.clone(),
+            AssignmentTarget::Tuple(_) => todo!(),
+        };
+
+        Ok((col, self.build_expr(&value)?))
     }
 
-    fn build_limit(
+    fn build_create(
         &self,
-        root: LogicalPlan,
-        limit: Option<Expr>,
-        offset: Option<Expr>,
+        name: ObjectName,
+        columns: Vec<ColumnDef>,
+        if_not_exists: bool,
     ) -> Result<LogicalPlan> {
-        match limit {
-            Some(limit) => {
-                let limit = match limit {
-                    Expr::Value(SqlValue::Number(s, _)) => match build_number(&s, false)? {
-                        Value::UInt(u) => Ok(u.0),
-                        e => Err(Error::Expected(
-                            "LIMIT to be an unsigned integer".into(),
-                            format!("{e}"),
-                        )),
-                    },
-                    e => Err(Error::Expected(
-                        "LIMIT to be an unsigned integer".into(),
-                        format!("{e}"),
-                    )),
-                }?;
+        let root = LogicalPlan::default();
 
-                let offset = match offset {
-                    Some(offset) => match offset {
-                        Expr::Value(SqlValue::Number(s, _)) => match build_number(&s, false)? {
-                            Value::UInt(u) => Ok(u.0),
-                            e => Err(Error::Expected(
-                                "OFFSET to be an unsigned integer".into(),
-                                format!("{e}"),
-                            )),
-                        },
-                        e => Err(Error::Expected(
-                            "OFFSET to be an unsigned integer".into(),
-                            format!("{e}"),
-                        )),
-                    },
-                    None => Ok(0),
-                }?;
+        let create = CreateTable::new(
+            root,
+            name.0.first().unwrap().value.clone(),
+            Schema::from_sql(columns)?,
+            if_not_exists,
+        );
 
-                Ok(LogicalPlan::Limit(Box::new(Limit::new(
-                    root, limit, offset,
-                ))))
-            }
-            None if offset.is_some() => {
-                bail!(Error::Unsupported("OFFSET without LIMIT".into()));
-            }
-            None => Ok(root),
-        }
+        Ok(LogicalPlan::CreateTable(Box::new(create)))
     }
 
-    fn build_projections(
+    /// Plans ALTER TABLE. Each operation is checked against the schema the
+    /// ones before it leave, IF [NOT] EXISTS skips the ones with nothing to do
+    fn build_alter_table(
         &self,
-        projections: Vec<SelectItem>,
-        schema: Schema,
-    ) -> Result<Vec<LogicalExpr>> {
-        let mut projs = vec![];
+        name: ObjectName,
+        if_exists: bool,
+        operations: Vec<AlterTableOperation>,
+        txn_id: Option<TxnId>,
+    ) -> Result<LogicalPlan> {
+        let table_name = name.0.first().unwrap().value.clone();
+        let catalog = self.catalog.read();
 
-        projections.into_iter().try_for_each(|e| {
-            let exprs = match e {
-                SelectItem::UnnamedExpr(Expr::Value(SqlValue::Number(s, _))) => {
-                    vec![LogicalExpr::Literal(build_number(&s, false)?)]
-                }
-                SelectItem::UnnamedExpr(Expr::Value(SqlValue::SingleQuotedString(s))) => {
-                    vec![LogicalExpr::Literal(lit!(Str, s)?)]
-                }
-                SelectItem::UnnamedExpr(Expr::Value(SqlValue::Boolean(b))) => {
-                    vec![LogicalExpr::Literal(lit!(Bool, b.to_string())?)]
-                }
-                SelectItem::UnnamedExpr(Expr::Identifier(ident)) => {
-                    let name = ident.value.clone();
+        let Some(mut schema) = catalog.get_schema(&table_name, txn_id) else {
+            if if_exists {
+                return Ok(LogicalPlan::AlterTable(AlterTable::new(table_name, vec![])));
+            }
+            bail!(Error::TableNotFound(table_name));
+        };
 
-                    // Error handling if the column name is not found
-                    if !schema.fields.iter().any(|f| f.name == name) {
-                        if schema.is_qualified() {
-                            bail!("Please use qualified column names {}", name);
-                        } else {
-                            bail!(Error::ColumnNotFound(name));
-                        }
-                    } else {
-                        vec![LogicalExpr::Column(ident.value.clone())]
+        let exists = |schema: &Schema, column: &str| schema.fields.iter().any(|f| f.name == column);
+        let mut altered = vec![];
+
+        for operation in operations {
+            let operation = match operation {
+                AlterTableOperation::AddColumn {
+                    if_not_exists,
+                    column_def,
+                    column_position,
+                    ..
+                } => {
+                    if let Some(position) = column_position {
+                        bail!(Error::Unsupported(format!("ADD COLUMN ... {position}")));
                     }
+
+                    if if_not_exists && exists(&schema, &column_def.name.value) {
+                        continue;
+                    }
+
+                    let default = column_def.options.iter().find_map(|o| match &o.option {
+                        ColumnOption::Default(expr) => Some(expr),
+                        _ => None,
+                    });
+
+                    let field = Schema::from_sql(vec![column_def.clone()])?
+                        .fields
+                        .pop()
+                        .unwrap();
+
+                    // the existing rows are backfilled with the default
+                    let default = match default.map(|e| self.build_expr(e)).transpose()? {
+                        None => Value::Null,
+                        Some(LogicalExpr::Literal(value)) => value.coerce(&field.ty)?,
+                        Some(expr) => {
+                            bail!(Error::Expected("a value as DEFAULT".into(), expr.print()))
+                        }
+                    };
+
+                    AlterOperation::AddColumn(field, default)
                 }
-                SelectItem::Wildcard(_) => schema
-                    .fields
-                    .iter()
-                    .map(|f| f.name.clone())
-                    .map(LogicalExpr::Column)
-                    .collect(),
-                SelectItem::UnnamedExpr(Expr::Tuple(fields)) => {
-                    fields.iter().map(build_expr).collect::<Result<Vec<_>>>()?
-                }
-                SelectItem::UnnamedExpr(Expr::BinaryOp { left, right, op }) => {
-                    let left = build_expr(&left)?;
-                    let right = build_expr(&right)?;
-                    vec![LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
-                        left,
-                        op.clone(),
-                        right,
-                    )))]
-                }
-                SelectItem::ExprWithAlias { expr, alias } => {
-                    let expr = build_expr(&expr)?;
-                    vec![LogicalExpr::AliasedExpr(
-                        Box::new(expr),
-                        alias.value.clone(),
-                    )]
+                AlterTableOperation::DropColumn {
+                    column_name,
+                    if_exists,
+                    ..
+                } => {
+                    if if_exists && !exists(&schema, &column_name.value) {
+                        continue;
+                    }
+                    AlterOperation::DropColumn(column_name.value)
                 }
-                SelectItem::UnnamedExpr(expr) => {
-                    let expr = build_expr(&expr)?;
-                    match expr {
-                        LogicalExpr::Column(ref name) => {
-                            if !schema.fields.iter().any(|f| &f.name == name) {
-                                bail!(Error::ColumnNotFound(name.clone()))
-                            } else {
-                                vec![expr]
-                            }
-                        }
-                        LogicalExpr::Literal(_) => vec![expr],
-                        e => bail!(Error::Unsupported(format!("Select Item: {e:?}"))),
+                AlterTableOperation::RenameColumn {
+                    old_column_name,
+                    new_column_name,
+                } => AlterOperation::RenameColumn {
+                    old: old_column_name.value,
+                    new: new_column_name.value,
+                },
+                AlterTableOperation::RenameTable { table_name: name } => {
+                    let name = name.0.first().unwrap().value.clone();
+                    if catalog.get_table(&name, txn_id).is_some() {
+                        bail!(Error::TableExists(name));
                     }
+                    AlterOperation::RenameTable(name)
                 }
-                // For unsupported SelectItems
-                e => bail!(Error::Unsupported(format!("Select Item: {e}"))),
+                e => bail!(Error::Unsupported(format!("ALTER TABLE ... {e}"))),
             };
 
-            projs.extend(exprs);
-            Ok(())
-        })?;
+            schema = operation.apply(&schema)?;
+            altered.push(operation);
+        }
 
-        Ok(projs)
+        Ok(LogicalPlan::AlterTable(AlterTable::new(
+            table_name, altered,
+        )))
     }
 
-    fn parse_boolean_expr(
+    /// Builds a relation in FROM, named by its alias or table name
+    fn build_relation(
         &self,
-        left: Expr,
-        op: BinaryOperator,
-        right: Expr,
-    ) -> Result<BooleanBinaryExpr> {
-        if !is_boolean_op!(op) {
-            bail!(Error::Unsupported(format!(
-                "Expected a boolean binary operator, got: {op:?}"
-            )));
-        }
+        relation: &TableFactor,
+        prewhere: Option<Expr>,
+        txn_id: Option<TxnId>,
+    ) -> Result<(String, LogicalPlan)> {
+        match relation {
+            TableFactor::Table { name, alias, .. } => {
+                let table_name = name.0.first().unwrap().value.clone();
+                let name = match alias {
+                    Some(alias) if !alias.columns.is_empty() => {
+                        bail!(Error::Unsupported(format!("Column aliases in {alias}")))
+                    }
+                    Some(alias) => alias.name.value.clone(),
+                    None => table_name.clone(),
+                };
 
-        Ok(BooleanBinaryExpr::new(
-            left.try_into()?,
-            op,
-            right.try_into()?,
-        ))
-    }
-}
+                if let Some(index) = self.find_cte(&table_name) {
+                    if prewhere.is_some() {
+                        bail!(Error::Unsupported("PREWHERE on a CTE".into()));
+                    }
+                    return Ok((name, self.build_cte(index, txn_id)?));
+                }
 
-// convenience method
-impl TryFrom<Expr> for LogicalExpr {
-    type Error = anyhow::Error;
+                let schema = self.table_schema(&table_name, txn_id)?;
 
-    fn try_from(expr: Expr) -> Result<LogicalExpr> {
-        match expr {
-            Expr::Identifier(ident) => Ok(LogicalExpr::Column(ident.to_string())),
-            Expr::UnaryOp {
-                op: UnaryOperator::Minus,
-                expr,
-            } => {
+                let plan = if let Some(pre) = prewhere {
+                    let expr = self.build_expr(&pre)?;
+                    match expr {
+                        LogicalExpr::BinaryExpr(expr) => {
+                            self.build_index_scan(table_name, schema, *expr)
+                        }
+                        LogicalExpr::InList(expr) => {
+                            self.build_index_lookups(table_name, schema, *expr)
+                        }
+                        _ => bail!(Error::Unsupported(
+                            "Prewhere must be a binary expression".into()
+                        )),
+                    }?
+                } else {
+                    LogicalPlan::Scan(Scan::new(table_name, schema))
+                };
+
+                Ok((name, plan))
+            }
+            TableFactor::Derived {
+                lateral,
+                subquery,
+                alias,
+            } => {
+                if *lateral {
+                    bail!(Error::Unsupported("LATERAL subqueries".into()));
+                }
+
+                if prewhere.is_some() {
+                    bail!(Error::Unsupported("PREWHERE on a subquery".into()));
+                }
+
+                let name = match alias {
+                    Some(alias) if !alias.columns.is_empty() => {
+                        bail!(Error::Unsupported(format!("Column aliases in {alias}")))
+                    }
+                    Some(alias) => alias.name.value.clone(),
+                    None => bail!(Error::Expected(
+                        "an alias for the subquery in FROM".into(),
+                        "None".into()
+                    )),
+                };
+
+                Ok((name, self.build_query(subquery.clone(), txn_id)?))
+            }
+            e => bail!(Error::Unsupported(e.to_string())),
+        }
+    }
+
+    /// Joins every relation in FROM into a left-deep tree. Column names stay
+    /// unqualified unless two relations share a column name, in which case
+    /// all columns are qualified by their relation's alias or table name
+    fn build_source(
+        &self,
+        from: &[TableWithJoins],
+        prewhere: Option<Expr>,
+        txn_id: Option<TxnId>,
+    ) -> Result<(LogicalPlan, Scope)> {
+        let Some(first) = from.first() else {
+            return Ok((LogicalPlan::Empty, Scope::default()));
+        };
+
+        let mut relations = vec![self.build_relation(&first.relation, prewhere, txn_id)?];
+        // comma separated relations are cross joined
+        let mut operators = vec![];
+
+        for (i, TableWithJoins { relation, joins }) in from.iter().enumerate() {
+            if i > 0 {
+                relations.push(self.build_relation(relation, None, txn_id)?);
+                operators.push(JoinOperator::CrossJoin);
+            }
+
+            for SqlJoin {
+                relation,
+                join_operator,
+                ..
+            } in joins
+            {
+                relations.push(self.build_relation(relation, None, txn_id)?);
+                operators.push(join_operator.clone());
+            }
+        }
+
+        let mut names = HashSet::new();
+        let qualify = relations
+            .iter()
+            .flat_map(|(_, plan)| plan.schema().fields)
+            .any(|f| !names.insert(f.name));
+
+        let scope = Scope {
+            relations: relations
+                .iter()
+                .map(|(name, plan)| {
+                    let columns = plan.schema().fields.into_iter().map(|f| f.name);
+                    (name.clone(), columns.collect())
+                })
+                .collect(),
+            qualified: qualify,
+        };
+
+        if relations.len() == 1 {
+            return Ok((relations.pop().unwrap().1, scope));
+        }
+
+        let mut aliases = HashSet::new();
+        for (name, _) in relations.iter() {
+            if qualify && !aliases.insert(name) {
+                bail!(Error::Expected(
+                    "a unique alias for every relation".into(),
+                    format!("{name} more than once")
+                ));
+            }
+        }
+
+        let schema_of = |name: &str, plan: &LogicalPlan| match qualify {
+            true => plan.schema().add_qualifier(name),
+            false => plan.schema(),
+        };
+
+        let mut relations = relations.into_iter();
+        let (first_name, mut root) = relations.next().unwrap();
+        let mut root_schema = schema_of(&first_name, &root);
+
+        for ((name, right), operator) in relations.zip(operators) {
+            let right_schema = schema_of(&name, &right);
+
+            let (join_type, constraint) = match &operator {
+                JoinOperator::Inner(c) => (JoinType::Inner, Some(c)),
+                JoinOperator::LeftOuter(c) => (JoinType::Left, Some(c)),
+                JoinOperator::RightOuter(c) => (JoinType::Right, Some(c)),
+                JoinOperator::FullOuter(c) => (JoinType::Full, Some(c)),
+                JoinOperator::CrossJoin => (JoinType::Inner, None),
+                _ => bail!(Error::Unsupported(
+                    "Only supports inner, cross and outer joins".into()
+                )),
+            };
+
+            let on = match constraint {
+                // a cross join matches every pair of rows
+                None => BinaryExpr::new(true.into(), BinaryOperator::Eq, true.into()),
+                Some(JoinConstraint::On(expr)) => {
+                    match scope.resolve_expr(self.build_expr(expr)?) {
+                        LogicalExpr::BinaryExpr(expr) => *expr,
+                        expr @ LogicalExpr::DistinctFrom(_) => {
+                            BinaryExpr::new(expr, BinaryOperator::Eq, true.into())
+                        }
+                        _ => bail!(Error::Unsupported(
+                            "Only Binary Expressions are supported in join conditions".into()
+                        )),
+                    }
+                }
+                Some(JoinConstraint::Using(cols)) => {
+                    if cols.len() != 1 {
+                        bail!(Error::Unsupported(
+                            "Using clause must have a single column".into()
+                        ))
+                    }
+                    let col = cols.first().unwrap().value.clone();
+                    let (left, right) = match qualify {
+                        false => (col.clone(), col),
+                        true => {
+                            let suffix = format!(".{col}");
+                            let left = root_schema
+                                .fields
+                                .iter()
+                                .find(|f| f.name.ends_with(&suffix))
+                                .map(|f| f.name.clone())
+                                .ok_or(Error::ColumnNotFound(col.clone()))?;
+                            (left, format!("{name}{suffix}"))
+                        }
+                    };
+                    BinaryExpr::new(
+                        LogicalExpr::Column(left),
+                        BinaryOperator::Eq,
+                        LogicalExpr::Column(right),
+                    )
+                }
+                Some(JoinConstraint::None) => {
+                    bail!(Error::Expected("ON or USING".into(), "None".into()))
+                }
+                Some(JoinConstraint::Natural) => {
+                    bail!(Error::Expected("ON or USING".into(), "Natural".into()))
+                }
+            };
+
+            let left_len = root_schema.fields.len();
+            let mut join_schema = root_schema.join(right_schema)?;
+
+            // the side that gets padded can always be NULL
+            for (i, field) in join_schema.fields.iter_mut().enumerate() {
+                let padded = if i < left_len {
+                    join_type.keeps_right()
+                } else {
+                    join_type.keeps_left()
+                };
+                if padded {
+                    field.constraints.nullable = true;
+                }
+            }
+
+            root = LogicalPlan::Join(Box::new(Join::new(
+                root,
+                right,
+                on,
+                join_type,
+                join_schema.clone(),
+            )));
+            root_schema = join_schema;
+        }
+
+        Ok((root, scope))
+    }
+
+    fn build_select(
+        &self,
+        body: Box<SetExpr>,
+        order_by: Vec<OrderByExpr>,
+        limit: Option<Expr>,
+        offset: Option<Offset>,
+        txn_id: Option<TxnId>,
+    ) -> Result<LogicalPlan> {
+        let mut select = match *body {
+            SetExpr::Select(select) => select,
+            e => unreachable!("Should only be called on a select query, got: {:?}", e),
+        };
+
+        let (mut root, scope) = self.build_source(&select.from, select.prewhere, txn_id)?;
+
+        if let Some(selection) = select.selection {
+            root = self.build_selection(root, selection, &scope, txn_id)?;
+        }
+
+        // subqueries in the select list are joined after filtering
+        for item in select.projection.iter_mut() {
+            let (expr, unnamed) = match item {
+                SelectItem::UnnamedExpr(expr) => {
+                    let unnamed = matches!(expr, Expr::Subquery(_));
+                    (expr, unnamed)
+                }
+                SelectItem::ExprWithAlias { expr, .. } => (expr, false),
+                _ => continue,
+            };
+
+            root = self.replace_subqueries(root, expr, &scope, txn_id)?;
+
+            // named after the subquery's column instead of its generated alias
+            if let (true, Expr::Identifier(ident)) = (unnamed, &expr) {
+                let (_, name) = ident.value.split_once('.').unwrap();
+                *item = SelectItem::ExprWithAlias {
+                    alias: Ident::new(name),
+                    expr: expr.clone(),
+                };
+            }
+        }
+
+        for projection in select.projection.iter() {
+            if matches!(root, LogicalPlan::Empty)
+                && matches!(projection, SelectItem::UnnamedExpr(Expr::Identifier(_)))
+            {
+                return Err(anyhow!("Can't select col without a table"));
+            }
+        }
+
+        let mut projections = self.build_projections(select.projection, root.schema(), &scope)?;
+
+        let group_by = match &select.group_by {
+            GroupByExpr::Expressions(exprs, modifiers) if modifiers.is_empty() => exprs
+                .iter()
+                .map(|e| Ok(scope.resolve_expr(self.build_expr(e)?)))
+                .collect::<Result<Vec<_>>>()?,
+            e => bail!(Error::Unsupported(format!("{e}"))),
+        };
+
+        let having = match &select.having {
+            Some(e) => Some(scope.resolve_expr(self.build_expr(e)?)),
+            None => None,
+        };
+
+        for expr in group_by.iter() {
+            ensure_no_windows(expr, "GROUP BY")?;
+        }
+        if let Some(having) = &having {
+            ensure_no_windows(having, "HAVING")?;
+        }
+
+        let mut sort_exprs = self
+            .build_sort_exprs(order_by, &projections)?
+            .into_iter()
+            .map(|e| SortExpr {
+                expr: scope.resolve_expr(e.expr),
+                ..e
+            })
+            .collect::<Vec<_>>();
+
+        if !group_by.is_empty()
+            || having.is_some()
+            || projections.iter().any(LogicalExpr::contains_aggregate)
+            || sort_exprs.iter().any(|e| e.expr.contains_aggregate())
+        {
+            let predicate;
+            (root, projections, predicate, sort_exprs) =
+                self.build_aggregate(root, group_by, projections, having, sort_exprs)?;
+
+            if let Some(having) = predicate {
+                root = LogicalPlan::Filter(Box::new(Filter::new(root, having)));
+            }
+        }
+
+        // window functions see the rows left after grouping and filtering
+        let mut windows = vec![];
+        for expr in projections.iter().chain(sort_exprs.iter().map(|e| &e.expr)) {
+            collect_windows(expr, &mut windows);
+        }
+        if !windows.is_empty() {
+            (root, projections, sort_exprs) =
+                self.build_windows(root, windows, projections, sort_exprs)?;
+        }
+
+        let distinct = match select.distinct {
+            None => false,
+            Some(SqlDistinct::Distinct) => true,
+            Some(e) => bail!(Error::Unsupported(format!("{e}"))),
+        };
+
+        // duplicates are removed from the output rows, so
+        // sorting and limiting must happen after projecting
+        if distinct {
+            let input_schema = root.schema();
+            let sort_exprs = sort_exprs
+                .into_iter()
+                .map(|e| {
+                    let position = projections
+                        .iter()
+                        .position(|p| unaliased(p).print() == e.expr.print())
+                        .ok_or(Error::Expected(
+                            "ORDER BY expressions to appear in the SELECT DISTINCT list".into(),
+                            e.expr.print(),
+                        ))?;
+                    let name = projections[position].to_field(&input_schema).name;
+                    Ok(SortExpr {
+                        expr: LogicalExpr::Column(name),
+                        ..e
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            root = LogicalPlan::Projection(Box::new(Projection::new(root, projections)));
+            root = LogicalPlan::Distinct(Box::new(Distinct::new(root)));
+
+            if !sort_exprs.is_empty() {
+                root = LogicalPlan::Sort(Box::new(Sort::new(root, sort_exprs)));
+            }
+
+            return self.build_limit(root, limit, offset);
+        }
+
+        // a single row needs no sorting
+        if !sort_exprs.is_empty() && !matches!(root, LogicalPlan::Empty) {
+            let schema = root.schema();
+            for e in sort_exprs.iter_mut() {
+                check_columns(&e.expr, &schema)?;
+                e.expr = coerce_types(e.expr.clone(), &schema)?;
+            }
+            root = LogicalPlan::Sort(Box::new(Sort::new(root, sort_exprs)));
+        }
+
+        root = self.build_limit(root, limit, offset)?;
+
+        root = LogicalPlan::Projection(Box::new(Projection::new(root, projections)));
+
+        Ok(root)
+    }
+
+    /// Plans a WHERE clause. EXISTS and IN subqueries in its conjuncts become
+    /// semi and anti joins, any other subquery is joined to the input and
+    /// replaced with a reference to its value
+    fn build_selection(
+        &self,
+        mut root: LogicalPlan,
+        selection: Expr,
+        scope: &Scope,
+        txn_id: Option<TxnId>,
+    ) -> Result<LogicalPlan> {
+        let mut semi_joins = vec![];
+        let mut predicates = vec![];
+
+        for conjunct in split_conjuncts(selection) {
+            match conjunct {
+                Expr::Exists { .. } | Expr::InSubquery { .. } => semi_joins.push(conjunct),
+                mut predicate => {
+                    root = self.replace_subqueries(root, &mut predicate, scope, txn_id)?;
+                    predicates.push(predicate);
+                }
+            }
+        }
+
+        if let Some(predicate) = conjoin(predicates) {
+            let filter = self.build_filter(&root.schema(), predicate, scope)?;
+            ensure_no_windows(&filter.left, "WHERE")?;
+            ensure_no_windows(&filter.right, "WHERE")?;
+            root = LogicalPlan::Filter(Box::new(Filter::new(root, filter)));
+        }
+
+        for subquery in semi_joins {
+            (root, _) = self.join_subquery(root, subquery, true, scope, txn_id)?;
+        }
+
+        Ok(root)
+    }
+
+    fn build_filter(
+        &self,
+        schema: &Schema,
+        selection: Expr,
+        scope: &Scope,
+    ) -> Result<BooleanBinaryExpr> {
+        match selection {
+            Expr::BinaryOp { left, right, op } => {
+                let BooleanBinaryExpr { left, op, right } =
+                    self.parse_boolean_expr(*left, op, *right)?;
+                let (left, right) = (scope.resolve_expr(left), scope.resolve_expr(right));
+                let expr = LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(left, op, right)));
+                let LogicalExpr::BinaryExpr(e) = coerce_types(expr, schema)? else {
+                    unreachable!("types are coerced in place")
+                };
+                let BinaryExpr { left, op, right } = *e;
+                Ok(BooleanBinaryExpr::new(left, op, right))
+            }
+            Expr::Value(SqlValue::Boolean(b)) => Ok(BooleanBinaryExpr::new(
+                b.into(),
+                BinaryOperator::Eq,
+                true.into(),
+            )),
+            e @ (Expr::Like { .. }
+            | Expr::ILike { .. }
+            | Expr::RLike { .. }
+            | Expr::IsNull(_)
+            | Expr::IsNotNull(_)
+            | Expr::IsDistinctFrom(..)
+            | Expr::IsNotDistinctFrom(..)
+            | Expr::Function(_)
+            | Expr::Case { .. }
+            | Expr::Cast { .. }
+            | Expr::UnaryOp {
+                op: UnaryOperator::Not,
+                ..
+            }
+            | Expr::InList { .. }
+            | Expr::Between { .. }
+            | Expr::Nested(_)) => build_predicate(scope.resolve_expr(self.build_expr(&e)?), schema),
+            Expr::Identifier(Ident { value, .. })
+                if schema
+                    .fields
+                    .iter()
+                    .find(|field| field.name == scope.resolve(&value))
+                    .map(|f| matches!(f.ty, Types::Bool))
+                    .unwrap_or(false) =>
+            {
+                Ok(BooleanBinaryExpr::new(
+                    LogicalExpr::Column(scope.resolve(&value)),
+                    BinaryOperator::Eq,
+                    true.into(),
+                ))
+            }
+            Expr::CompoundIdentifier(idents) => {
+                let name = scope.resolve(
+                    &idents
+                        .into_iter()
+                        .map(|i| i.value.clone())
+                        .collect::<Vec<String>>()
+                        .join("."),
+                );
+
+                if !schema.fields.iter().any(|f| f.name == name) {
+                    bail!(Error::ColumnNotFound(name.clone()));
+                }
+
+                Ok(BooleanBinaryExpr::new(
+                    LogicalExpr::Column(name),
+                    BinaryOperator::Eq,
+                    true.into(),
+                ))
+            }
+            e => bail!(Error::Unimplemented(format!("Expr: {e:?}"))),
+        }
+    }
+
+    /// Joins every subquery in the expression to `root` and replaces it with
+    /// a reference to the column holding its value
+    fn replace_subqueries(
+        &self,
+        mut root: LogicalPlan,
+        expr: &mut Expr,
+        scope: &Scope,
+        txn_id: Option<TxnId>,
+    ) -> Result<LogicalPlan> {
+        match expr {
+            Expr::Exists { .. } | Expr::InSubquery { .. } | Expr::Subquery(_) => {
+                let subquery = std::mem::replace(expr, Expr::Value(SqlValue::Null));
+                (root, *expr) = self.join_subquery(root, subquery, false, scope, txn_id)?;
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                root = self.replace_subqueries(root, left, scope, txn_id)?;
+                root = self.replace_subqueries(root, right, scope, txn_id)?;
+            }
+            Expr::UnaryOp { expr, .. } | Expr::Nested(expr) => {
+                root = self.replace_subqueries(root, expr, scope, txn_id)?;
+            }
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                for e in [expr, low, high] {
+                    root = self.replace_subqueries(root, e, scope, txn_id)?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(root)
+    }
+
+    /// Joins an EXISTS, IN or scalar subquery to `root`. A conjunct of the
+    /// WHERE clause becomes a semi or anti join, otherwise EXISTS and IN
+    /// become a mark join and a scalar subquery a single join. Returns the
+    /// expression that reads the subquery's value
+    fn join_subquery(
+        &self,
+        root: LogicalPlan,
+        subquery: Expr,
+        conjunct: bool,
+        scope: &Scope,
+        txn_id: Option<TxnId>,
+    ) -> Result<(LogicalPlan, Expr)> {
+        let scalar = matches!(subquery, Expr::Subquery(_));
+
+        let (query, lhs, negated) = match subquery {
+            Expr::Exists { subquery, negated } => (subquery, None, negated),
+            Expr::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => (subquery, Some(*expr), negated),
+            Expr::Subquery(query) => (query, None, false),
+            e => unreachable!("Expected a subquery, got: {e}"),
+        };
+
+        let exists = !scalar && lhs.is_none();
+        let schema = root.schema();
+        // a mark column is named after the expression it replaces
+        let mark = match &lhs {
+            Some(lhs) => format!("{lhs} IN ({query})"),
+            None => format!("EXISTS ({query})"),
+        };
+
+        let Subquery {
+            plan,
+            columns,
+            mut correlation,
+        } = self.build_subquery(query, exists, scope, &schema, txn_id)?;
+
+        if !exists && columns.len() != 1 {
+            bail!(Error::Expected(
+                "the subquery to return a single column".into(),
+                format!("{} columns", columns.len())
+            ));
+        }
+
+        if let Some(lhs) = &lhs {
+            let lhs = scope.resolve_expr(self.build_expr(lhs)?);
+            check_columns(&lhs, &schema)?;
+
+            let left = lhs.to_field(&schema).ty;
+            let right = plan.schema().fields[0].ty.clone();
+            if Types::common(&[left.clone(), right.clone()]).is_none() {
+                bail!(Error::TypeMismatch(vec![left], vec![right]));
+            }
+
+            let column = LogicalExpr::Column(columns[0].clone());
+            correlation.insert(
+                0,
+                LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(lhs, BinaryOperator::Eq, column))),
+            );
+        }
+
+        let on = join_condition(correlation);
+
+        let (join_type, schema, value) = if scalar {
+            let mut fields = plan.schema().fields;
+            for field in fields.iter_mut() {
+                field.constraints.nullable = true;
+            }
+            let value = Expr::Identifier(Ident::new(columns[0].clone()));
+            (JoinType::Single, schema.join(Schema::new(fields))?, value)
+        } else if conjunct {
+            let join_type = match (negated, exists) {
+                (false, _) => JoinType::Semi,
+                (true, true) => JoinType::Anti,
+                // NOT IN is unknown rather than true when it compares with a NULL
+                (true, false) => JoinType::NullAwareAnti,
+            };
+            (join_type, schema, Expr::Value(SqlValue::Boolean(true)))
+        } else {
+            // IN is unknown rather than false when it compares with a NULL
+            let join_type = match exists {
+                true => JoinType::Mark,
+                false => JoinType::NullAwareMark,
+            };
+            let mut fields = schema.fields;
+            fields.push(Field::new(
+                &mark,
+                Types::Bool,
+                Constraints::nullable(!exists),
+            ));
+
+            let mut value = Expr::Identifier(Ident::new(mark));
+            if negated {
+                value = Expr::UnaryOp {
+                    op: UnaryOperator::Not,
+                    expr: Box::new(value),
+                };
+            }
+            (join_type, Schema::new(fields), value)
+        };
+
+        let join = Join::new(root, plan, on, join_type, schema);
+        Ok((LogicalPlan::Join(Box::new(join)), value))
+    }
+
+    /// Plans a subquery to be joined to the outer query, its columns are
+    /// qualified by a generated alias. The predicates of a correlated
+    /// subquery that read outer columns are pulled out of its WHERE to
+    /// become part of the join condition, so it runs once instead of
+    /// once per outer row
+    fn build_subquery(
+        &self,
+        query: Box<Query>,
+        exists: bool,
+        outer: &Scope,
+        outer_schema: &Schema,
+        txn_id: Option<TxnId>,
+    ) -> Result<Subquery> {
+        self.subqueries.set(self.subqueries.get() + 1);
+        let alias = format!("__subquery_{}", self.subqueries.get());
+
+        if let (SetExpr::Select(select), None, None, None, None) = (
+            &*query.body,
+            &query.with,
+            &query.order_by,
+            &query.limit,
+            &query.offset,
+        ) {
+            let (source, scope) =
+                self.build_source(&select.from, select.prewhere.clone(), txn_id)?;
+            let schema = source.schema();
+            let is_inner = |name: &str| schema.fields.iter().any(|f| f.name == scope.resolve(name));
+
+            let mut predicates = vec![];
+            let mut correlation = vec![];
+            let mut inner_columns: Vec<String> = vec![];
+
+            let conjuncts = select.selection.clone().map(split_conjuncts);
+            for conjunct in conjuncts.unwrap_or_default() {
+                if contains_subquery(&conjunct) {
+                    predicates.push(conjunct);
+                    continue;
+                }
+
+                let expr = self.build_expr(&conjunct)?;
+                let columns = collect_columns(&expr);
+                if columns.iter().all(|c| is_inner(c)) {
+                    predicates.push(conjunct);
+                    continue;
+                }
+
+                for column in columns.iter().filter(|c| !is_inner(c)) {
+                    let name = outer.resolve(column);
+                    if !outer_schema.fields.iter().any(|f| f.name == name) {
+                        bail!(Error::ColumnNotFound(column.clone()));
+                    }
+                }
+
+                correlation.push(map_columns(expr, &mut |name| {
+                    if !is_inner(&name) {
+                        return outer.resolve(&name);
+                    }
+                    let name = scope.resolve(&name);
+                    if !inner_columns.contains(&name) {
+                        inner_columns.push(name.clone());
+                    }
+                    format!("{alias}.{name}")
+                }));
+            }
+
+            if !correlation.is_empty() {
+                return self.build_correlated_subquery(
+                    alias,
+                    select,
+                    (source, scope),
+                    predicates,
+                    correlation,
+                    inner_columns,
+                    exists,
+                    txn_id,
+                );
+            }
+        }
+
+        let plan = self.build_query(query, txn_id)?;
+        let fields = plan.schema().fields;
+        let columns: Vec<_> = fields
+            .iter()
+            .map(|f| format!("{alias}.{}", f.name))
+            .collect();
+
+        let outputs = fields
+            .into_iter()
+            .zip(columns.iter())
+            .map(|(f, c)| {
+                LogicalExpr::AliasedExpr(Box::new(LogicalExpr::Column(f.name)), c.clone())
+            })
+            .collect();
+
+        Ok(Subquery {
+            plan: LogicalPlan::Projection(Box::new(Projection::new(plan, outputs))),
+            columns,
+            correlation: vec![],
+        })
+    }
+
+    /// Plans the rest of a correlated subquery once its correlated predicates
+    /// were pulled out. The subquery also outputs the columns the pulled
+    /// predicates read. An aggregate is grouped by those columns, so the
+    /// predicates must be equalities for each outer row to match one group
+    #[allow(clippy::too_many_arguments)]
+    fn build_correlated_subquery(
+        &self,
+        alias: String,
+        select: &Select,
+        (source, scope): (LogicalPlan, Scope),
+        predicates: Vec<Expr>,
+        correlation: Vec<LogicalExpr>,
+        inner_columns: Vec<String>,
+        exists: bool,
+        txn_id: Option<TxnId>,
+    ) -> Result<Subquery> {
+        let grouped = !matches!(
+            &select.group_by,
+            GroupByExpr::Expressions(exprs, modifiers) if exprs.is_empty() && modifiers.is_empty()
+        );
+        if grouped || select.having.is_some() || select.distinct.is_some() {
+            bail!(Error::Unsupported(
+                "GROUP BY, HAVING and DISTINCT in a correlated subquery".into()
+            ));
+        }
+
+        let mut root = source;
+        if let Some(selection) = conjoin(predicates) {
+            root = self.build_selection(root, selection, &scope, txn_id)?;
+        }
+
+        let mut projections =
+            self.build_projections(select.projection.clone(), root.schema(), &scope)?;
+
+        if projections.iter().any(LogicalExpr::contains_aggregate) {
+            let mut aggregates = vec![];
+            for p in projections.iter() {
+                collect_aggregates(p, &mut aggregates);
+            }
+
+            // an outer row without a group would read NULL instead of 0
+            if exists
+                || aggregates
+                    .iter()
+                    .any(|a| a.func == AggregateFunction::Count)
+            {
+                bail!(Error::Unsupported(format!(
+                    "Aggregate {} in a correlated subquery",
+                    aggregates[0].print()
+                )));
+            }
+
+            let prefix = format!("{alias}.");
+            let reads = |e: &LogicalExpr, inner: bool| {
+                let columns = collect_columns(e);
+                columns.iter().all(|c| c.starts_with(&prefix) == inner)
+                    && (!inner || !columns.is_empty())
+            };
+
+            for predicate in correlation.iter() {
+                let equality = match predicate {
+                    LogicalExpr::BinaryExpr(e) if e.op == BinaryOperator::Eq => {
+                        (reads(&e.left, true) && reads(&e.right, false))
+                            || (reads(&e.left, false) && reads(&e.right, true))
+                    }
+                    _ => false,
+                };
+
+                if !equality {
+                    bail!(Error::Expected(
+                        "correlated predicates of an aggregating subquery to be equalities".into(),
+                        predicate.print()
+                    ));
+                }
+            }
+
+            let group_by = inner_columns
+                .iter()
+                .cloned()
+                .map(LogicalExpr::Column)
+                .collect();
+
+            (root, projections, _, _) =
+                self.build_aggregate(root, group_by, projections, None, vec![])?;
+        }
+
+        // EXISTS only reads the correlated columns
+        if exists {
+            projections.clear();
+        }
+
+        let schema = root.schema();
+        let columns: Vec<_> = projections
+            .iter()
+            .map(|p| format!("{alias}.{}", p.to_field(&schema).name))
+            .collect();
+
+        let outputs = projections
+            .iter()
+            .zip(columns.iter())
+            .map(|(p, c)| LogicalExpr::AliasedExpr(Box::new(unaliased(p)), c.clone()))
+            .chain(inner_columns.iter().map(|c| {
+                LogicalExpr::AliasedExpr(
+                    Box::new(LogicalExpr::Column(c.clone())),
+                    format!("{alias}.{c}"),
+                )
+            }))
+            .collect();
+
+        Ok(Subquery {
+            plan: LogicalPlan::Projection(Box::new(Projection::new(root, outputs))),
+            columns,
+            correlation,
+        })
+    }
+
+    /// Plans an Aggregate over `input` and rewrites the projections, the HAVING
+    /// clause and the sort keys to read the aggregate's output columns instead
+    /// of the input's
+    #[allow(clippy::type_complexity)]
+    fn build_aggregate(
+        &self,
+        input: LogicalPlan,
+        group_by: Vec<LogicalExpr>,
+        projections: Vec<LogicalExpr>,
+        having: Option<LogicalExpr>,
+        sort_exprs: Vec<SortExpr>,
+    ) -> Result<(
+        LogicalPlan,
+        Vec<LogicalExpr>,
+        Option<BooleanBinaryExpr>,
+        Vec<SortExpr>,
+    )> {
+        let schema = input.schema();
+
+        let group_by = group_by
+            .into_iter()
+            .map(|expr| {
+                check_columns(&expr, &schema)?;
+                coerce_types(expr, &schema)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // coerced like the group expressions so that they print the same
+        let having = having.map(|e| coerce_types(e, &schema)).transpose()?;
+
+        for expr in group_by.iter() {
+            if expr.contains_aggregate() {
+                bail!(Error::Unsupported(format!(
+                    "Aggregate function in GROUP BY: {}",
+                    expr.print()
+                )));
+            }
+        }
+
+        let mut aggregates: Vec<AggregateExpr> = vec![];
+        for expr in projections
+            .iter()
+            .chain(having.iter())
+            .chain(sort_exprs.iter().map(|e| &e.expr))
+        {
+            collect_aggregates(expr, &mut aggregates);
+        }
+
+        for aggregate in aggregates.iter() {
+            if let Some(arg) = &aggregate.arg {
+                check_columns(arg, &schema)?;
+                let ty = arg.to_field(&schema).ty;
+                if aggregate.func.return_type(&ty).is_none() {
+                    bail!(Error::Unsupported(format!("{}({ty:?})", aggregate.func)));
+                }
+            }
+        }
+
+        // expressions are matched by how they print, e.g. `a + 1` in the
+        // projection reads the group column built from `a + 1` in GROUP BY
+        let grouped: Vec<(String, String)> = group_by
+            .iter()
+            .map(|e| (e.print(), e.to_field(&schema).name))
+            .collect();
+
+        let projections = projections
+            .into_iter()
+            .map(|e| rewrite_for_aggregate(e, &grouped, &schema))
+            .collect::<Result<Vec<_>>>()?;
+
+        let sort_exprs = sort_exprs
+            .into_iter()
+            .map(|e| {
+                Ok(SortExpr {
+                    expr: rewrite_for_aggregate(e.expr, &grouped, &schema)?,
+                    ..e
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let root = LogicalPlan::Aggregate(Box::new(Aggregate::new(input, group_by, aggregates)));
+
+        let having = match having {
+            Some(expr) => {
+                let expr = rewrite_for_aggregate(expr, &grouped, &schema)?;
+                Some(build_predicate(expr, &root.schema())?)
+            }
+            None => None,
+        };
+
+        Ok((root, projections, having, sort_exprs))
+    }
+
+    /// Plans a Window for each distinct PARTITION BY and ORDER BY over `input`
+    /// sorted by them, and rewrites the projections and the sort keys to read
+    /// the windows' output columns
+    fn build_windows(
+        &self,
+        input: LogicalPlan,
+        windows: Vec<WindowExpr>,
+        projections: Vec<LogicalExpr>,
+        sort_exprs: Vec<SortExpr>,
+    ) -> Result<(LogicalPlan, Vec<LogicalExpr>, Vec<SortExpr>)> {
+        let schema = input.schema();
+
+        // windows sharing their clauses read the same sorted input
+        let mut groups: Vec<(String, Vec<WindowExpr>)> = vec![];
+        for window in windows {
+            let window = coerce_window(window, &schema)?;
+            let over = window.print_over();
+            match groups.iter_mut().find(|(o, _)| *o == over) {
+                Some((_, group)) if group.iter().any(|w| w.print() == window.print()) => {}
+                Some((_, group)) => group.push(window),
+                None => groups.push((over, vec![window])),
+            }
+        }
+
+        let mut root = input;
+        for (_, group) in groups {
+            let keys = group[0]
+                .partition_by
+                .iter()
+                .map(|e| SortExpr::new(e.clone(), None, None))
+                .chain(group[0].order_by.iter().cloned())
+                .collect::<Vec<_>>();
+            if !keys.is_empty() {
+                root = LogicalPlan::Sort(Box::new(Sort::new(root, keys)));
+            }
+            root = LogicalPlan::Window(Box::new(Window::new(root, group)));
+        }
+
+        let projections = projections
+            .into_iter()
+            .map(|e| rewrite_windows(e, &schema))
+            .collect::<Result<Vec<_>>>()?;
+
+        let sort_exprs = sort_exprs
+            .into_iter()
+            .map(|e| {
+                Ok(SortExpr {
+                    expr: rewrite_windows(e.expr, &schema)?,
+                    ..e
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((root, projections, sort_exprs))
+    }
+
+    /// The number of rows of a LIMIT or OFFSET, a number or a parameter
+    fn build_row_count(&self, expr: Expr, clause: &str) -> Result<RowCount> {
+        let value = match &expr {
+            Expr::Value(SqlValue::Number(s, _)) => build_number(s, false)?,
+            Expr::Value(SqlValue::Placeholder(_)) => match self.build_expr(&expr)? {
+                LogicalExpr::Parameter(n, _) => return Ok(RowCount::Parameter(n)),
+                LogicalExpr::Literal(value) => value,
+                e => bail!(Error::Internal(format!("parameter built as {}", e.print()))),
+            },
+            _ => bail!(Error::Expected(
+                format!("{clause} to be an unsigned integer"),
+                expr.to_string()
+            )),
+        };
+
+        row_count(&value, clause).map(RowCount::Rows)
+    }
+
+    fn build_limit(
+        &self,
+        root: LogicalPlan,
+        limit: Option<Expr>,
+        offset: Option<Offset>,
+    ) -> Result<LogicalPlan> {
+        let offset = match offset {
+            Some(offset) => {
+                ensure!(
+                    offset.rows == OffsetRows::None,
+                    "OFFSET [ROWS|ROW] is not supported"
+                );
+                Some(offset.value)
+            }
+            None => None,
+        };
+
+        match limit {
+            Some(limit) => {
+                let limit = self.build_row_count(limit, "LIMIT")?;
+                let offset = match offset {
+                    Some(offset) => self.build_row_count(offset, "OFFSET")?,
+                    None => RowCount::Rows(0),
+                };
+
+                Ok(LogicalPlan::Limit(Box::new(Limit::new(
+                    root, limit, offset,
+                ))))
+            }
+            None if offset.is_some() => {
+                bail!(Error::Unsupported("OFFSET without LIMIT".into()));
+            }
+            None => Ok(root),
+        }
+    }
+
+    fn build_projections(
+        &self,
+        projections: Vec<SelectItem>,
+        schema: Schema,
+        scope: &Scope,
+    ) -> Result<Vec<LogicalExpr>> {
+        let mut projs = vec![];
+
+        projections.into_iter().try_for_each(|e| {
+            let exprs = match e {
+                SelectItem::UnnamedExpr(Expr::Value(SqlValue::Number(s, _))) => {
+                    vec![LogicalExpr::Literal(build_number(&s, false)?)]
+                }
+                SelectItem::UnnamedExpr(Expr::Value(SqlValue::SingleQuotedString(s))) => {
+                    vec![LogicalExpr::Literal(lit!(Str, s)?)]
+                }
+                SelectItem::UnnamedExpr(Expr::Value(SqlValue::Boolean(b))) => {
+                    vec![LogicalExpr::Literal(lit!(Bool, b.to_string())?)]
+                }
+                SelectItem::UnnamedExpr(Expr::Identifier(ident)) => {
+                    let name = scope.resolve(&ident.value);
+
+                    // Error handling if the column name is not found
+                    if !schema.fields.iter().any(|f| f.name == name) {
+                        if schema.is_qualified() {
+                            bail!("Please use qualified column names {}", name);
+                        } else {
+                            bail!(Error::ColumnNotFound(name));
+                        }
+                    } else {
+                        vec![LogicalExpr::Column(name)]
+                    }
+                }
+                SelectItem::QualifiedWildcard(name, _) => {
+                    let name = name.to_string();
+                    let columns = scope
+                        .columns_of(&name)
+                        .ok_or(Error::TableNotFound(name.clone()))?;
+                    columns.into_iter().map(LogicalExpr::Column).collect()
+                }
+                SelectItem::Wildcard(_) => schema
+                    .fields
+                    .iter()
+                    .map(|f| f.name.clone())
+                    .map(LogicalExpr::Column)
+                    .collect(),
+                SelectItem::UnnamedExpr(Expr::Tuple(fields)) => fields
+                    .iter()
+                    .map(|e| Ok(scope.resolve_expr(self.build_expr(e)?)))
+                    .collect::<Result<Vec<_>>>()?,
+                SelectItem::UnnamedExpr(Expr::BinaryOp { left, right, op }) => {
+                    let left = scope.resolve_expr(self.build_expr(&left)?);
+                    let right = scope.resolve_expr(self.build_expr(&right)?);
+                    vec![LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+                        left,
+                        op.clone(),
+                        right,
+                    )))]
+                }
+                SelectItem::ExprWithAlias { expr, alias } => {
+                    let expr = scope.resolve_expr(self.build_expr(&expr)?);
+                    vec![LogicalExpr::AliasedExpr(
+                        Box::new(expr),
+                        alias.value.clone(),
+                    )]
+                }
+                SelectItem::UnnamedExpr(expr) => {
+                    let expr = scope.resolve_expr(self.build_expr(&expr)?);
+                    match expr {
+                        LogicalExpr::Column(ref name) => {
+                            if !schema.fields.iter().any(|f| &f.name == name) {
+                                bail!(Error::ColumnNotFound(name.clone()))
+                            } else {
+                                vec![expr]
+                            }
+                        }
+                        LogicalExpr::Literal(_)
+                        | LogicalExpr::BinaryExpr(_)
+                        | LogicalExpr::AggregateExpr(_)
+                        | LogicalExpr::FunctionExpr(_)
+                        | LogicalExpr::DistinctFrom(_)
+                        | LogicalExpr::Case(_)
+                        | LogicalExpr::Cast(..)
+                        | LogicalExpr::Not(_)
+                        | LogicalExpr::InList(_)
+                        | LogicalExpr::WindowExpr(_) => vec![expr],
+                        e => bail!(Error::Unsupported(format!("Select Item: {e:?}"))),
+                    }
+                }
+            };
+
+            let exprs = exprs
+                .into_iter()
+                .map(|expr| coerce_types(expr, &schema))
+                .collect::<Result<Vec<_>>>()?;
+
+            projs.extend(exprs);
+            Ok(())
+        })?;
+
+        Ok(projs)
+    }
+
+    fn parse_boolean_expr(
+        &self,
+        left: Expr,
+        op: BinaryOperator,
+        right: Expr,
+    ) -> Result<BooleanBinaryExpr> {
+        if !is_boolean_op!(op) {
+            bail!(Error::Unsupported(format!(
+                "Expected a boolean binary operator, got: {op:?}"
+            )));
+        }
+
+        Ok(BooleanBinaryExpr::new(
+            self.build_expr(&left)?,
+            op,
+            self.build_expr(&right)?,
+        ))
+    }
+}
+
+impl From<bool> for LogicalExpr {
+    fn from(b: bool) -> LogicalExpr {
+        LogicalExpr::Literal(lit!(Bool, b.to_string()).unwrap())
+    }
+}
+
+impl LogicalPlanBuilder {
+    fn build_expr(&self, sql_expr: &Expr) -> Result<LogicalExpr> {
+        match sql_expr {
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr,
+            } => {
                 if let Expr::Value(SqlValue::Number(n, _)) = *expr.clone() {
                     Ok(LogicalExpr::Literal(build_number(&n, true)?))
                 } else {
-                    bail!(Error::Unsupported("Unsupported value type".into()))
+                    // subtracted from a signed zero, so a UINT negates to an INT
+                    Ok(LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+                        LogicalExpr::Literal(lit!(Int, "0")?),
+                        BinaryOperator::Minus,
+                        self.build_expr(expr)?,
+                    ))))
+                }
+            }
+            Expr::Value(SqlValue::Number(n, _)) => {
+                Ok(LogicalExpr::Literal(build_number(n, false)?))
+            }
+            Expr::Value(SqlValue::SingleQuotedString(s)) => Ok(LogicalExpr::Literal(lit!(Str, s)?)),
+            Expr::Identifier(Ident { value, .. }) => Ok(LogicalExpr::Column(value.clone())),
+            Expr::BinaryOp { left, op, right } => Ok(LogicalExpr::BinaryExpr(Box::new(
+                BinaryExpr::new(self.build_expr(left)?, op.clone(), self.build_expr(right)?),
+            ))),
+            Expr::Nested(e) => self.build_expr(e),
+            Expr::CompoundIdentifier(i) => {
+                if i.len() > 2 {
+                    bail!(Error::Unsupported(
+                        "Please use table.column or column".into()
+                    ))
+                }
+
+                Ok(LogicalExpr::Column(
+                    i.iter()
+                        .map(|i| i.value.clone())
+                        .collect::<Vec<_>>()
+                        .join("."),
+                ))
+            }
+            Expr::Value(SqlValue::Null) => Ok(LogicalExpr::Literal(Value::Null)),
+            Expr::Value(SqlValue::Placeholder(p)) => {
+                let n = p
+                    .strip_prefix('$')
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .filter(|n| (1..=self.params.len()).contains(n));
+
+                match (n, &self.params) {
+                    (Some(n), Params::Values(values)) => {
+                        Ok(LogicalExpr::Literal(values[n - 1].clone()))
+                    }
+                    (Some(n), Params::Types(types)) => {
+                        Ok(LogicalExpr::Parameter(n, types[n - 1].clone()))
+                    }
+                    (None, _) => bail!(Error::Expected(
+                        format!("a value for parameter {p}"),
+                        format!("{} parameters", self.params.len())
+                    )),
+                }
+            }
+            // NOT BETWEEN is outside of either bound
+            Expr::Between {
+                expr,
+                negated,
+                low,
+                high,
+            } => {
+                let (lower, op, upper) = match negated {
+                    true => (BinaryOperator::Lt, BinaryOperator::Or, BinaryOperator::Gt),
+                    false => (
+                        BinaryOperator::GtEq,
+                        BinaryOperator::And,
+                        BinaryOperator::LtEq,
+                    ),
+                };
+                let column = self.build_expr(expr)?;
+                let left = LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+                    column.clone(),
+                    lower,
+                    self.build_expr(low)?,
+                )));
+                let right = LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+                    column.clone(),
+                    upper,
+                    self.build_expr(high)?,
+                )));
+                Ok(LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+                    left, op, right,
+                ))))
+            }
+            Expr::UnaryOp {
+                op: UnaryOperator::Not,
+                expr,
+            } => Ok(LogicalExpr::Not(Box::new(self.build_expr(expr)?))),
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => Ok(LogicalExpr::InList(Box::new(InListExpr::new(
+                self.build_expr(expr)?,
+                list.iter()
+                    .map(|e| self.build_expr(e))
+                    .collect::<Result<_>>()?,
+                *negated,
+            )))),
+            Expr::Value(SqlValue::Boolean(b)) => {
+                Ok(LogicalExpr::Literal(lit!(Bool, b.to_string())?))
+            }
+            Expr::Function(function) => self.build_function(function),
+            Expr::Substring {
+                expr,
+                substring_from,
+                substring_for,
+                ..
+            } => {
+                let from = match substring_from {
+                    Some(from) => self.build_expr(from)?,
+                    None => LogicalExpr::Literal(build_number("1", false)?),
+                };
+                let mut args = vec![self.build_expr(expr)?, from];
+                if let Some(len) = substring_for {
+                    args.push(self.build_expr(len)?);
+                }
+                Ok(call_function("substring", args))
+            }
+            Expr::Trim {
+                expr,
+                trim_where,
+                trim_what,
+                trim_characters,
+            } => {
+                let name = match trim_where {
+                    None | Some(TrimWhereField::Both) => "trim",
+                    Some(TrimWhereField::Leading) => "ltrim",
+                    Some(TrimWhereField::Trailing) => "rtrim",
+                };
+                let mut args = vec![self.build_expr(expr)?];
+                match (trim_what, trim_characters.as_deref()) {
+                    (Some(chars), None) => args.push(self.build_expr(chars)?),
+                    (None, Some([chars])) => args.push(self.build_expr(chars)?),
+                    (None, None) => {}
+                    _ => bail!(Error::Unsupported(format!("{expr}"))),
+                }
+                Ok(call_function(name, args))
+            }
+            Expr::Like {
+                negated,
+                expr,
+                pattern,
+                escape_char,
+            } => {
+                let op = match negated {
+                    true => BinaryOperator::PGNotLikeMatch,
+                    false => BinaryOperator::PGLikeMatch,
+                };
+                self.build_like(expr, op, pattern, escape_char.as_deref())
+            }
+            Expr::ILike {
+                negated,
+                expr,
+                pattern,
+                escape_char,
+            } => {
+                let op = match negated {
+                    true => BinaryOperator::PGNotILikeMatch,
+                    false => BinaryOperator::PGILikeMatch,
+                };
+                self.build_like(expr, op, pattern, escape_char.as_deref())
+            }
+            Expr::RLike {
+                negated,
+                expr,
+                pattern,
+                ..
+            } => {
+                let op = match negated {
+                    true => BinaryOperator::PGRegexNotMatch,
+                    false => BinaryOperator::PGRegexMatch,
+                };
+                Ok(LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+                    self.build_expr(expr)?,
+                    op,
+                    self.build_expr(pattern)?,
+                ))))
+            }
+            Expr::IsNull(expr) | Expr::IsNotNull(expr) => {
+                Ok(LogicalExpr::DistinctFrom(Box::new(DistinctFromExpr::new(
+                    self.build_expr(expr)?,
+                    LogicalExpr::Literal(Value::Null),
+                    matches!(sql_expr, Expr::IsNull(_)),
+                ))))
+            }
+            Expr::IsDistinctFrom(left, right) | Expr::IsNotDistinctFrom(left, right) => {
+                Ok(LogicalExpr::DistinctFrom(Box::new(DistinctFromExpr::new(
+                    self.build_expr(left)?,
+                    self.build_expr(right)?,
+                    matches!(sql_expr, Expr::IsNotDistinctFrom(..)),
+                ))))
+            }
+            Expr::Cast {
+                kind: CastKind::Cast | CastKind::DoubleColon,
+                expr,
+                data_type,
+                format: None,
+            } => Ok(LogicalExpr::Cast(
+                Box::new(self.build_expr(expr)?),
+                Types::from_sql(&data_type.to_string())?,
+            )),
+            Expr::Case {
+                operand,
+                conditions,
+                results,
+                else_result,
+            } => {
+                let operand = operand.as_deref().map(|e| self.build_expr(e)).transpose()?;
+                let branches = conditions
+                    .iter()
+                    .zip(results.iter())
+                    .map(|(when, then)| {
+                        let when = match &operand {
+                            Some(operand) => LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+                                operand.clone(),
+                                BinaryOperator::Eq,
+                                self.build_expr(when)?,
+                            ))),
+                            None => self.build_expr(when)?,
+                        };
+                        Ok((when, self.build_expr(then)?))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let else_result = else_result
+                    .as_deref()
+                    .map(|e| self.build_expr(e))
+                    .transpose()?;
+
+                Ok(LogicalExpr::Case(Box::new(CaseExpr::new(
+                    branches,
+                    else_result,
+                ))))
+            }
+            Expr::Ceil {
+                expr,
+                field: CeilFloorKind::DateTimeField(DateTimeField::NoDateTime),
+            } => Ok(call_function("ceil", vec![self.build_expr(expr)?])),
+            Expr::Floor {
+                expr,
+                field: CeilFloorKind::DateTimeField(DateTimeField::NoDateTime),
+            } => Ok(call_function("floor", vec![self.build_expr(expr)?])),
+            e => bail!(Error::Unsupported(format!("Expr: {e}"))),
+        }
+    }
+
+    fn build_function(&self, function: &Function) -> Result<LogicalExpr> {
+        let Function {
+            name,
+            args,
+            filter,
+            over,
+            within_group,
+            ..
+        } = function;
+
+        if let Some(over) = over {
+            return self.build_window_function(function, over);
+        }
+
+        let Some(func) = AggregateFunction::from_name(&name.to_string()) else {
+            return self.build_scalar_function(function);
+        };
+
+        if filter.is_some() || over.is_some() || !within_group.is_empty() {
+            bail!(Error::Unsupported(format!(
+                "Aggregate clauses in {function}"
+            )));
+        }
+
+        let args = match args {
+            FunctionArguments::List(FunctionArgumentList {
+                duplicate_treatment: None | Some(DuplicateTreatment::All),
+                args,
+                clauses,
+            }) if clauses.is_empty() => args,
+            e => bail!(Error::Unsupported(format!("Arguments {e} in {name}"))),
+        };
+
+        let arg = match args.as_slice() {
+            [FunctionArg::Unnamed(FunctionArgExpr::Wildcard)]
+                if func == AggregateFunction::Count =>
+            {
+                None
+            }
+            [FunctionArg::Unnamed(FunctionArgExpr::Expr(expr))] => {
+                let arg = self.build_expr(expr)?;
+                if arg.contains_aggregate() {
+                    bail!(Error::Unsupported(format!(
+                        "Nested aggregates in {function}"
+                    )));
+                }
+                Some(arg)
+            }
+            _ => bail!(Error::Expected(
+                format!("a single argument to {func}"),
+                function.to_string()
+            )),
+        };
+
+        Ok(LogicalExpr::AggregateExpr(Box::new(AggregateExpr::new(
+            func, arg,
+        ))))
+    }
+
+    /// Plans `func(args) OVER (...)` for the ranking functions, LAG, LEAD
+    /// and the aggregates. Without a frame the aggregates read the rows up
+    /// to the current one and its peers
+    fn build_window_function(&self, function: &Function, over: &WindowType) -> Result<LogicalExpr> {
+        let Function {
+            name,
+            args,
+            filter,
+            within_group,
+            ..
+        } = function;
+
+        let spec = match over {
+            WindowType::WindowSpec(spec) if spec.window_name.is_none() => spec,
+            _ => bail!(Error::Unsupported(format!("Named windows in {function}"))),
+        };
+        if filter.is_some() || !within_group.is_empty() {
+            bail!(Error::Unsupported(format!(
+                "Aggregate clauses in {function}"
+            )));
+        }
+
+        let args = match args {
+            FunctionArguments::List(FunctionArgumentList {
+                duplicate_treatment: None | Some(DuplicateTreatment::All),
+                args,
+                clauses,
+            }) if clauses.is_empty() => args,
+            e => bail!(Error::Unsupported(format!("Arguments {e} in {name}"))),
+        };
+
+        let star = matches!(
+            args.as_slice(),
+            [FunctionArg::Unnamed(FunctionArgExpr::Wildcard)]
+        );
+        let mut args = match star {
+            true => vec![],
+            false => args
+                .iter()
+                .map(|arg| match arg {
+                    FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => self.build_expr(expr),
+                    e => bail!(Error::Unsupported(format!("Argument {e} in {name}"))),
+                })
+                .collect::<Result<Vec<_>>>()?,
+        };
+
+        for arg in args.iter() {
+            ensure_no_windows(arg, "window function arguments")?;
+        }
+
+        let name = name.to_string().to_lowercase();
+        let func = match (name.as_str(), args.len()) {
+            ("row_number", 0) => WindowFunction::RowNumber,
+            ("rank", 0) => WindowFunction::Rank,
+            ("dense_rank", 0) => WindowFunction::DenseRank,
+            ("row_number" | "rank" | "dense_rank", _) => bail!(Error::Expected(
+                format!("no arguments to {name}"),
+                function.to_string()
+            )),
+            ("lag" | "lead", 1..=3) => {
+                let offset = match args.len() {
+                    1 => 1,
+                    _ => match args.remove(1) {
+                        LogicalExpr::Literal(Value::UInt(offset)) => offset.0,
+                        e => bail!(Error::Expected(
+                            format!("an unsigned integer offset in {name}"),
+                            e.print()
+                        )),
+                    },
+                };
+                match name.as_str() {
+                    "lag" => WindowFunction::Lag(offset),
+                    _ => WindowFunction::Lead(offset),
+                }
+            }
+            ("lag" | "lead", _) => bail!(Error::Expected(
+                format!("{name}(value [, offset [, default]])"),
+                function.to_string()
+            )),
+            (name, n) => match AggregateFunction::from_name(name) {
+                Some(AggregateFunction::Count) if star => {
+                    WindowFunction::Aggregate(AggregateFunction::Count)
+                }
+                Some(func) if n == 1 => WindowFunction::Aggregate(func),
+                Some(func) => bail!(Error::Expected(
+                    format!("a single argument to {func}"),
+                    function.to_string()
+                )),
+                None => bail!(Error::Unsupported(format!("Window function: {name}"))),
+            },
+        };
+
+        let WindowSpec {
+            partition_by,
+            order_by,
+            window_frame,
+            ..
+        } = spec;
+
+        let partition_by = partition_by
+            .iter()
+            .map(|e| self.build_expr(e))
+            .collect::<Result<Vec<_>>>()?;
+
+        let order_by = order_by
+            .iter()
+            .map(|e| {
+                if let Some(fill) = &e.with_fill {
+                    bail!(Error::Unsupported(format!("{fill}")));
+                }
+                Ok(SortExpr::new(
+                    self.build_expr(&e.expr)?,
+                    e.asc,
+                    e.nulls_first,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let frame = match window_frame {
+            Some(frame) => {
+                let units = match frame.units {
+                    WindowFrameUnits::Rows => FrameUnits::Rows,
+                    WindowFrameUnits::Range => FrameUnits::Range,
+                    WindowFrameUnits::Groups => {
+                        bail!(Error::Unsupported(format!("{} frames", frame.units)))
+                    }
+                };
+                let start = self.build_frame_bound(&frame.start_bound, &units)?;
+                let end = match &frame.end_bound {
+                    Some(bound) => self.build_frame_bound(bound, &units)?,
+                    None => FrameBound::CurrentRow,
+                };
+                let frame = WindowFrame { units, start, end };
+                if frame.start == FrameBound::UnboundedFollowing
+                    || frame.end == FrameBound::UnboundedPreceding
+                {
+                    bail!(Error::Expected(
+                        "a frame that starts before it ends".into(),
+                        frame.to_string()
+                    ));
+                }
+                frame
+            }
+            None => WindowFrame::default(),
+        };
+
+        Ok(LogicalExpr::WindowExpr(Box::new(WindowExpr::new(
+            func,
+            args,
+            partition_by,
+            order_by,
+            frame,
+        ))))
+    }
+
+    /// RANGE frames only extend to the peers of the current row, offsets
+    /// count rows
+    fn build_frame_bound(
+        &self,
+        bound: &WindowFrameBound,
+        units: &FrameUnits,
+    ) -> Result<FrameBound> {
+        let offset = |expr: &Expr| match (self.build_expr(expr)?, units) {
+            (LogicalExpr::Literal(Value::UInt(offset)), FrameUnits::Rows) => Ok(offset.0),
+            (_, FrameUnits::Range) => bail!(Error::Unsupported(format!(
+                "RANGE frames with an offset: {bound}"
+            ))),
+            (e, _) => bail!(Error::Expected(
+                "an unsigned integer frame offset".into(),
+                e.print()
+            )),
+        };
+
+        Ok(match bound {
+            WindowFrameBound::CurrentRow => FrameBound::CurrentRow,
+            WindowFrameBound::Preceding(None) => FrameBound::UnboundedPreceding,
+            WindowFrameBound::Preceding(Some(n)) => FrameBound::Preceding(offset(n)?),
+            WindowFrameBound::Following(None) => FrameBound::UnboundedFollowing,
+            WindowFrameBound::Following(Some(n)) => FrameBound::Following(offset(n)?),
+        })
+    }
+
+    /// Patterns are matched with `\` as the escape character, a literal
+    /// pattern with another ESCAPE character is rewritten to use it
+    fn build_like(
+        &self,
+        expr: &Expr,
+        op: BinaryOperator,
+        pattern: &Expr,
+        escape: Option<&str>,
+    ) -> Result<LogicalExpr> {
+        let pattern = match (self.build_expr(pattern)?, escape) {
+            (pattern, None | Some("\\")) => pattern,
+            (LogicalExpr::Literal(Value::Str(Str(pattern))), Some(escape)) => {
+                LogicalExpr::Literal(Value::Str(Str(with_escape(&pattern, escape)?)))
+            }
+            (pattern, Some(escape)) => bail!(Error::Unsupported(format!(
+                "ESCAPE '{escape}' with the pattern {}",
+                pattern.print()
+            ))),
+        };
+
+        Ok(LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+            self.build_expr(expr)?,
+            op,
+            pattern,
+        ))))
+    }
+
+    fn build_scalar_function(&self, function: &Function) -> Result<LogicalExpr> {
+        let Function {
+            name,
+            args,
+            filter,
+            over,
+            within_group,
+            ..
+        } = function;
+
+        let func = ScalarFunction::from_name(&name.to_string())
+            .ok_or(Error::Unsupported(format!("Function: {name}")))?;
+
+        if filter.is_some() || over.is_some() || !within_group.is_empty() {
+            bail!(Error::Unsupported(format!(
+                "Aggregate clauses in {function}"
+            )));
+        }
+
+        let args = match args {
+            FunctionArguments::List(FunctionArgumentList {
+                duplicate_treatment: None,
+                args,
+                clauses,
+            }) if clauses.is_empty() => args,
+            e => bail!(Error::Unsupported(format!("Arguments {e} in {name}"))),
+        };
+
+        let args = args
+            .iter()
+            .map(|arg| match arg {
+                FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => self.build_expr(expr),
+                e => bail!(Error::Unsupported(format!("Argument {e} in {name}"))),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(LogicalExpr::FunctionExpr(Box::new(FunctionExpr::new(
+            func, args,
+        ))))
+    }
+
+    /// Sort keys may name an output column by its alias or by its 1-based position
+    fn build_sort_exprs(
+        &self,
+        order_by: Vec<OrderByExpr>,
+        outputs: &[LogicalExpr],
+    ) -> Result<Vec<SortExpr>> {
+        order_by
+            .into_iter()
+            .map(|e| {
+                let OrderByExpr {
+                    expr,
+                    asc,
+                    nulls_first,
+                    with_fill,
+                } = e;
+
+                if let Some(fill) = with_fill {
+                    bail!(Error::Unsupported(format!("{fill}")));
+                }
+
+                let expr = match expr {
+                    Expr::Value(SqlValue::Number(ref n, _)) => match n.parse::<usize>() {
+                        Ok(i) if (1..=outputs.len()).contains(&i) => unaliased(&outputs[i - 1]),
+                        _ => bail!(Error::Expected(
+                            format!("ORDER BY position between 1 and {}", outputs.len()),
+                            n.clone()
+                        )),
+                    },
+                    Expr::Identifier(ref ident) => outputs
+                        .iter()
+                        .find_map(|e| match e {
+                            LogicalExpr::AliasedExpr(e, alias) if *alias == ident.value => {
+                                Some(*e.clone())
+                            }
+                            _ => None,
+                        })
+                        .map_or_else(|| self.build_expr(&expr), Ok)?,
+                    expr => self.build_expr(&expr)?,
+                };
+
+                Ok(SortExpr::new(expr, asc, nulls_first))
+            })
+            .collect()
+    }
+}
+
+/// Splits a predicate into the expressions that are AND-ed together
+fn split_conjuncts(expr: Expr) -> Vec<Expr> {
+    match expr {
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            let mut conjuncts = split_conjuncts(*left);
+            conjuncts.extend(split_conjuncts(*right));
+            conjuncts
+        }
+        Expr::Nested(e)
+            if matches!(
+                *e,
+                Expr::BinaryOp {
+                    op: BinaryOperator::And,
+                    ..
                 }
+            ) =>
+        {
+            split_conjuncts(*e)
+        }
+        // the bounds are separate conjuncts, each can narrow an index scan
+        Expr::Between {
+            expr,
+            negated: false,
+            low,
+            high,
+        } => vec![
+            Expr::BinaryOp {
+                left: expr.clone(),
+                op: BinaryOperator::GtEq,
+                right: low,
+            },
+            Expr::BinaryOp {
+                left: expr,
+                op: BinaryOperator::LtEq,
+                right: high,
+            },
+        ],
+        e => vec![e],
+    }
+}
+
+fn conjoin(exprs: Vec<Expr>) -> Option<Expr> {
+    exprs.into_iter().reduce(|left, right| Expr::BinaryOp {
+        left: Box::new(left),
+        op: BinaryOperator::And,
+        right: Box::new(right),
+    })
+}
+
+/// ANDs the predicates into a join condition
+pub(crate) fn join_condition(predicates: Vec<LogicalExpr>) -> BinaryExpr {
+    predicates
+        .into_iter()
+        .map(|p| match p {
+            LogicalExpr::BinaryExpr(e) => *e,
+            e => BinaryExpr::new(e, BinaryOperator::Eq, true.into()),
+        })
+        .reduce(|left, right| {
+            BinaryExpr::new(
+                LogicalExpr::BinaryExpr(Box::new(left)),
+                BinaryOperator::And,
+                LogicalExpr::BinaryExpr(Box::new(right)),
+            )
+        })
+        .unwrap_or_else(|| BinaryExpr::new(true.into(), BinaryOperator::Eq, true.into()))
+}
+
+fn contains_subquery(expr: &Expr) -> bool {
+    match expr {
+        Expr::Exists { .. } | Expr::InSubquery { .. } | Expr::Subquery(_) => true,
+        Expr::BinaryOp { left, right, .. } => contains_subquery(left) || contains_subquery(right),
+        Expr::UnaryOp { expr, .. } | Expr::Nested(expr) => contains_subquery(expr),
+        Expr::Between {
+            expr, low, high, ..
+        } => contains_subquery(expr) || contains_subquery(low) || contains_subquery(high),
+        _ => false,
+    }
+}
+
+/// A call to a function of the registry, for SQL syntax like `TRIM(LEADING FROM s)`
+fn call_function(name: &str, args: Vec<LogicalExpr>) -> LogicalExpr {
+    let func = ScalarFunction::from_name(name).expect("a registered function");
+    LogicalExpr::FunctionExpr(Box::new(FunctionExpr::new(func, args)))
+}
+
+fn unaliased(expr: &LogicalExpr) -> LogicalExpr {
+    match expr {
+        LogicalExpr::AliasedExpr(e, _) => *e.clone(),
+        e => e.clone(),
+    }
+}
+
+/// Make sure every column the expression reads exists in the schema
+fn check_columns(expr: &LogicalExpr, schema: &Schema) -> Result<()> {
+    match expr {
+        LogicalExpr::Column(name) => {
+            if !schema.fields.iter().any(|f| &f.name == name) {
+                bail!(Error::ColumnNotFound(name.clone()));
             }
-            Expr::Value(value) => {
-                match value {
-                    SqlValue::Number(s, _) => {
-                        return Ok(LogicalExpr::Literal(build_number(&s, false)?))
+            Ok(())
+        }
+        LogicalExpr::Literal(_) | LogicalExpr::Parameter(..) => Ok(()),
+        LogicalExpr::BinaryExpr(e) => {
+            check_columns(&e.left, schema)?;
+            check_columns(&e.right, schema)
+        }
+        LogicalExpr::AliasedExpr(e, _) | LogicalExpr::Cast(e, _) | LogicalExpr::Not(e) => {
+            check_columns(e, schema)
+        }
+        LogicalExpr::AggregateExpr(e) => match &e.arg {
+            Some(arg) => check_columns(arg, schema),
+            None => Ok(()),
+        },
+        LogicalExpr::FunctionExpr(e) => {
+            e.args.iter().try_for_each(|arg| check_columns(arg, schema))
+        }
+        LogicalExpr::DistinctFrom(e) => {
+            check_columns(&e.left, schema)?;
+            check_columns(&e.right, schema)
+        }
+        LogicalExpr::Case(e) => e.exprs().try_for_each(|e| check_columns(e, schema)),
+        LogicalExpr::InList(e) => e.exprs().try_for_each(|e| check_columns(e, schema)),
+        LogicalExpr::WindowExpr(e) => e.exprs().try_for_each(|e| check_columns(e, schema)),
+    }
+}
+
+/// Make sure every function, operator and CASE in the expression accepts
+/// the types of its operands, operands of different types are cast to
+/// their common type
+fn coerce_types(expr: LogicalExpr, schema: &Schema) -> Result<LogicalExpr> {
+    Ok(match expr {
+        LogicalExpr::FunctionExpr(e) => {
+            let FunctionExpr { func, args } = *e;
+            let mut types = vec![];
+            let args = args
+                .into_iter()
+                .map(|arg| {
+                    check_columns(&arg, schema)?;
+                    let arg = coerce_types(arg, schema)?;
+                    types.push(arg.to_field(schema).ty);
+                    Ok(arg)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let ty = func.return_type(&types)?;
+            let args = match func.casts_args() {
+                true => args
+                    .into_iter()
+                    .zip(types.iter())
+                    .map(|(arg, from)| cast_expr(arg, from, &ty))
+                    .collect::<Result<_>>()?,
+                false => args,
+            };
+            LogicalExpr::FunctionExpr(Box::new(FunctionExpr::new(func, args)))
+        }
+        LogicalExpr::BinaryExpr(e) => {
+            let BinaryExpr { left, op, right } = *e;
+            let (mut left, mut right) = (coerce_types(left, schema)?, coerce_types(right, schema)?);
+
+            if is_pattern_op(&op) {
+                check_columns(&left, schema)?;
+                check_columns(&right, schema)?;
+                let left = left.to_field(schema).ty;
+                let right = right.to_field(schema).ty;
+                if ![&left, &right]
+                    .iter()
+                    .all(|ty| matches!(ty, Types::Str | Types::Null))
+                {
+                    bail!(Error::TypeMismatch(
+                        vec![Types::Str, Types::Str],
+                        vec![left, right]
+                    ));
+                }
+            } else if is_arithmetic_op(&op) || is_comparison_op(&op) {
+                // outer columns of correlated subqueries are checked later
+                if check_columns(&left, schema).is_ok() && check_columns(&right, schema).is_ok() {
+                    let mut l = left.to_field(schema).ty;
+                    let mut r = right.to_field(schema).ty;
+                    if is_arithmetic_op(&op) {
+                        // unsigned literals are signed unless the other side
+                        // stays unsigned, so 1 - 2 is -1 and u - 1 a UINT
+                        let (l_signed, r_signed) = (signed_literal(&left), signed_literal(&right));
+                        if l_signed && (r != Types::UInt || r_signed) {
+                            left = cast_expr(left, &l, &Types::Int)?;
+                            l = Types::Int;
+                        }
+                        if r_signed && (l != Types::UInt || l_signed) {
+                            right = cast_expr(right, &r, &Types::Int)?;
+                            r = Types::Int;
+                        }
+                    }
+                    let Some(ty) = Types::common(&[l.clone(), r.clone()]) else {
+                        bail!(Error::TypeMismatch(vec![l.clone()], vec![r]));
+                    };
+                    // integers compare exactly, casting one to the other's
+                    // type could fail
+                    if !(is_comparison_op(&op) && l.is_integer() && r.is_integer()) {
+                        left = cast_expr(left, &l, &ty)?;
+                        right = cast_expr(right, &r, &ty)?;
                     }
-                    SqlValue::Null => return Ok(LogicalExpr::Literal(Value::Null)),
-                    _ => (),
                 }
+            }
 
-                let (ty, v) = match value {
-                    SqlValue::SingleQuotedString(s) => (Types::Str, s),
-                    SqlValue::Boolean(b) => (Types::Bool, b.to_string()),
-                    e => bail!(Error::Unsupported(format!(
-                        "Unsupported value in Expr: {e}"
-                    ))),
-                };
+            LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(left, op, right)))
+        }
+        LogicalExpr::AliasedExpr(e, alias) => {
+            LogicalExpr::AliasedExpr(Box::new(coerce_types(*e, schema)?), alias)
+        }
+        LogicalExpr::AggregateExpr(e) => {
+            let AggregateExpr { func, arg } = *e;
+            let arg = arg.map(|arg| coerce_types(arg, schema)).transpose()?;
+            LogicalExpr::AggregateExpr(Box::new(AggregateExpr::new(func, arg)))
+        }
+        LogicalExpr::DistinctFrom(e) => {
+            let DistinctFromExpr {
+                left,
+                right,
+                negated,
+            } = *e;
+            LogicalExpr::DistinctFrom(Box::new(DistinctFromExpr::new(
+                coerce_types(left, schema)?,
+                coerce_types(right, schema)?,
+                negated,
+            )))
+        }
+        LogicalExpr::Case(e) => {
+            let CaseExpr {
+                branches,
+                else_result,
+            } = *e;
+            let mut results = vec![];
+            let mut conditions = vec![];
+            for (when, then) in branches {
+                let when = coerce_types(when, schema)?;
+                check_columns(&when, schema)?;
+                if !matches!(when.to_field(schema).ty, Types::Bool | Types::Null) {
+                    bail!(Error::Expected(
+                        "a boolean WHEN condition".into(),
+                        when.print()
+                    ));
+                }
+                conditions.push(when);
+                results.push(coerce_types(then, schema)?);
+            }
+            if let Some(else_result) = else_result {
+                results.push(coerce_types(else_result, schema)?);
+            }
 
-                Ok(LogicalExpr::Literal(ValueFactory::from_string(&ty, &v)?))
+            let mut types = vec![];
+            for result in results.iter() {
+                check_columns(result, schema)?;
+                types.push(result.to_field(schema).ty);
             }
-            Expr::BinaryOp { left, right, op } => {
-                let l: LogicalExpr = (*left).try_into()?;
-                let r: LogicalExpr = (*right).try_into()?;
-                Ok(LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(l, op, r))))
+            let Some(ty) = Types::common(&types) else {
+                bail!(Error::Expected(
+                    "CASE results of compatible types".into(),
+                    format!("{types:?}")
+                ));
+            };
+
+            // every branch returns the same type
+            let mut results = results
+                .into_iter()
+                .zip(types.iter())
+                .map(|(result, from)| cast_expr(result, from, &ty))
+                .collect::<Result<Vec<_>>>()?;
+            let else_result = match results.len() > conditions.len() {
+                true => results.pop(),
+                false => None,
+            };
+
+            LogicalExpr::Case(Box::new(CaseExpr::new(
+                conditions.into_iter().zip(results).collect(),
+                else_result,
+            )))
+        }
+        LogicalExpr::Cast(e, ty) => {
+            check_columns(&e, schema)?;
+            let e = coerce_types(*e, schema)?;
+            let from = e.to_field(schema).ty;
+            if !from.can_cast(&ty) {
+                bail!(Error::Unsupported(format!(
+                    "CAST from {} to {}",
+                    from.to_sql(),
+                    ty.to_sql()
+                )));
             }
-            e => bail!(Error::Unimplemented(format!(
-                "Casting Expr {e:?} to LogicalExpr"
-            ))),
+            LogicalExpr::Cast(Box::new(e), ty)
+        }
+        LogicalExpr::Not(e) => {
+            check_columns(&e, schema)?;
+            let e = coerce_types(*e, schema)?;
+            let ty = e.to_field(schema).ty;
+            if !matches!(ty, Types::Bool | Types::Null) {
+                bail!(Error::TypeMismatch(vec![Types::Bool], vec![ty]));
+            }
+            LogicalExpr::Not(Box::new(e))
+        }
+        // the list is compared with the expression like `=` would
+        LogicalExpr::InList(e) => {
+            let InListExpr {
+                expr,
+                list,
+                negated,
+            } = *e;
+            let exprs = std::iter::once(expr)
+                .chain(list)
+                .map(|e| {
+                    check_columns(&e, schema)?;
+                    coerce_types(e, schema)
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let types = exprs
+                .iter()
+                .map(|e| e.to_field(schema).ty)
+                .collect::<Vec<_>>();
+            let Some(ty) = Types::common(&types) else {
+                bail!(Error::TypeMismatch(
+                    vec![types[0].clone()],
+                    types[1..].to_vec()
+                ));
+            };
+
+            let mut exprs = match types.iter().all(Types::is_integer) {
+                true => exprs,
+                false => exprs
+                    .into_iter()
+                    .zip(types.iter())
+                    .map(|(e, from)| cast_expr(e, from, &ty))
+                    .collect::<Result<Vec<_>>>()?,
+            };
+            let expr = exprs.remove(0);
+            LogicalExpr::InList(Box::new(InListExpr::new(expr, exprs, negated)))
+        }
+        LogicalExpr::WindowExpr(e) => LogicalExpr::WindowExpr(Box::new(coerce_window(*e, schema)?)),
+        LogicalExpr::Literal(_) | LogicalExpr::Column(_) | LogicalExpr::Parameter(..) => expr,
+    })
+}
+
+/// The value and the default of LAG and LEAD are cast to their common type
+fn coerce_window(window: WindowExpr, schema: &Schema) -> Result<WindowExpr> {
+    let WindowExpr {
+        func,
+        args,
+        partition_by,
+        order_by,
+        frame,
+    } = window;
+
+    let mut coerce = |e: LogicalExpr| {
+        check_columns(&e, schema)?;
+        coerce_types(e, schema)
+    };
+    let args = args
+        .into_iter()
+        .map(&mut coerce)
+        .collect::<Result<Vec<_>>>()?;
+    let partition_by = partition_by
+        .into_iter()
+        .map(&mut coerce)
+        .collect::<Result<Vec<_>>>()?;
+    let order_by = order_by
+        .into_iter()
+        .map(|e| {
+            Ok(SortExpr {
+                expr: coerce(e.expr)?,
+                ..e
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let types = args
+        .iter()
+        .map(|arg| arg.to_field(schema).ty)
+        .collect::<Vec<_>>();
+    let Some(ty) = func.return_type(&types) else {
+        bail!(Error::Unsupported(format!("{func}({types:?})")));
+    };
+
+    let args = match func {
+        WindowFunction::Lag(_) | WindowFunction::Lead(_) => args
+            .into_iter()
+            .zip(types.iter())
+            .map(|(arg, from)| cast_expr(arg, from, &ty))
+            .collect::<Result<Vec<_>>>()?,
+        _ => args,
+    };
+
+    Ok(WindowExpr::new(func, args, partition_by, order_by, frame))
+}
+
+/// The expression as a value of type `to`, literals are converted right away
+/// An unsigned literal that fits in an INT
+fn signed_literal(expr: &LogicalExpr) -> bool {
+    matches!(expr, LogicalExpr::Literal(Value::UInt(UInt(v))) if i32::try_from(*v).is_ok())
+}
+
+fn cast_expr(expr: LogicalExpr, from: &Types, to: &Types) -> Result<LogicalExpr> {
+    Ok(match expr {
+        expr if from == to || *from == Types::Null => expr,
+        LogicalExpr::Literal(value) => LogicalExpr::Literal(value.cast(to)?),
+        expr => LogicalExpr::Cast(Box::new(expr), to.clone()),
+    })
+}
+
+/// Renames every column the expression reads
+fn map_columns(expr: LogicalExpr, f: &mut impl FnMut(String) -> String) -> LogicalExpr {
+    map_leaves(expr, &mut |leaf| match leaf {
+        LogicalExpr::Column(name) => LogicalExpr::Column(f(name)),
+        leaf => leaf,
+    })
+}
+
+/// Rebuilds the expression with `f` applied to its columns, literals and parameters
+fn map_leaves(expr: LogicalExpr, f: &mut impl FnMut(LogicalExpr) -> LogicalExpr) -> LogicalExpr {
+    match expr {
+        LogicalExpr::Column(_) | LogicalExpr::Literal(_) | LogicalExpr::Parameter(..) => f(expr),
+        LogicalExpr::BinaryExpr(e) => {
+            let BinaryExpr { left, op, right } = *e;
+            LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+                map_leaves(left, f),
+                op,
+                map_leaves(right, f),
+            )))
+        }
+        LogicalExpr::AliasedExpr(e, alias) => {
+            LogicalExpr::AliasedExpr(Box::new(map_leaves(*e, f)), alias)
+        }
+        LogicalExpr::Cast(e, ty) => LogicalExpr::Cast(Box::new(map_leaves(*e, f)), ty),
+        LogicalExpr::Not(e) => LogicalExpr::Not(Box::new(map_leaves(*e, f))),
+        LogicalExpr::InList(e) => {
+            let InListExpr {
+                expr,
+                list,
+                negated,
+            } = *e;
+            LogicalExpr::InList(Box::new(InListExpr::new(
+                map_leaves(expr, f),
+                list.into_iter().map(|e| map_leaves(e, f)).collect(),
+                negated,
+            )))
+        }
+        LogicalExpr::AggregateExpr(e) => {
+            let AggregateExpr { func, arg } = *e;
+            LogicalExpr::AggregateExpr(Box::new(AggregateExpr::new(
+                func,
+                arg.map(|arg| map_leaves(arg, f)),
+            )))
+        }
+        LogicalExpr::FunctionExpr(e) => {
+            let FunctionExpr { func, args } = *e;
+            LogicalExpr::FunctionExpr(Box::new(FunctionExpr::new(
+                func,
+                args.into_iter().map(|arg| map_leaves(arg, f)).collect(),
+            )))
+        }
+        LogicalExpr::DistinctFrom(e) => {
+            let DistinctFromExpr {
+                left,
+                right,
+                negated,
+            } = *e;
+            LogicalExpr::DistinctFrom(Box::new(DistinctFromExpr::new(
+                map_leaves(left, f),
+                map_leaves(right, f),
+                negated,
+            )))
+        }
+        LogicalExpr::Case(e) => {
+            let CaseExpr {
+                branches,
+                else_result,
+            } = *e;
+            let branches = branches
+                .into_iter()
+                .map(|(when, then)| (map_leaves(when, f), map_leaves(then, f)))
+                .collect();
+            let else_result = else_result.map(|e| map_leaves(e, f));
+            LogicalExpr::Case(Box::new(CaseExpr::new(branches, else_result)))
+        }
+        LogicalExpr::WindowExpr(e) => {
+            let WindowExpr {
+                func,
+                args,
+                partition_by,
+                order_by,
+                frame,
+            } = *e;
+            let order_by = order_by
+                .into_iter()
+                .map(|e| SortExpr {
+                    expr: map_leaves(e.expr, f),
+                    ..e
+                })
+                .collect();
+            LogicalExpr::WindowExpr(Box::new(WindowExpr::new(
+                func,
+                args.into_iter().map(|e| map_leaves(e, f)).collect(),
+                partition_by.into_iter().map(|e| map_leaves(e, f)).collect(),
+                order_by,
+                frame,
+            )))
         }
     }
 }
 
-impl From<bool> for LogicalExpr {
-    fn from(b: bool) -> LogicalExpr {
-        LogicalExpr::Literal(lit!(Bool, b.to_string()).unwrap())
+/// Replaces the parameters of a plan built by [`LogicalPlanBuilder::with_param_types`]
+/// with their values
+pub fn bind_params(plan: LogicalPlan, params: &[Value]) -> Result<LogicalPlan> {
+    let mut error = None;
+    let plan = plan.map_inputs(|input| {
+        bind_params(input, params).unwrap_or_else(|e| {
+            error.get_or_insert(e);
+            LogicalPlan::Empty
+        })
+    });
+    if let Some(e) = error {
+        return Err(e);
     }
+
+    let bind = |count: RowCount, clause: &str| match count {
+        RowCount::Parameter(n) => row_count(&params[n - 1], clause).map(RowCount::Rows),
+        rows => Ok(rows),
+    };
+    let plan = match plan {
+        LogicalPlan::Limit(mut limit) => {
+            limit.limit = bind(limit.limit, "LIMIT")?;
+            limit.offset = bind(limit.offset, "OFFSET")?;
+            LogicalPlan::Limit(limit)
+        }
+        plan => plan,
+    };
+
+    Ok(plan.map_exprs(|expr| {
+        map_leaves(expr, &mut |leaf| match leaf {
+            LogicalExpr::Parameter(n, _) => LogicalExpr::Literal(params[n - 1].clone()),
+            leaf => leaf,
+        })
+    }))
+}
+
+/// Names of the columns the expression reads
+pub(crate) fn collect_columns(expr: &LogicalExpr) -> Vec<String> {
+    let mut columns = vec![];
+    map_columns(expr.clone(), &mut |name| {
+        columns.push(name.clone());
+        name
+    });
+    columns
 }
 
-// moved outside the impl to satisfy Clippy
-// https://rust-lang.github.io/rust-clippy/master/index.html#only_used_in_recursion
-fn build_expr(expr: &Expr) -> Result<LogicalExpr> {
+/// Collects the distinct aggregate calls in the expression
+fn collect_aggregates(expr: &LogicalExpr, aggregates: &mut Vec<AggregateExpr>) {
     match expr {
-        Expr::UnaryOp {
-            op: UnaryOperator::Minus,
-            expr,
-        } => {
-            if let Expr::Value(SqlValue::Number(n, _)) = *expr.clone() {
-                Ok(LogicalExpr::Literal(build_number(&n, true)?))
-            } else {
-                bail!(Error::Unsupported("Expected a number".into()))
+        LogicalExpr::AggregateExpr(e) => {
+            if !aggregates.iter().any(|a| a.print() == e.print()) {
+                aggregates.push(*e.clone());
             }
         }
-        Expr::Value(SqlValue::Number(n, _)) => Ok(LogicalExpr::Literal(build_number(n, false)?)),
-        Expr::Value(SqlValue::SingleQuotedString(s)) => Ok(LogicalExpr::Literal(lit!(Str, s)?)),
-        Expr::Identifier(Ident { value, .. }) => Ok(LogicalExpr::Column(value.clone())),
-        Expr::BinaryOp { left, op, right } => Ok(LogicalExpr::BinaryExpr(Box::new(
-            BinaryExpr::new(build_expr(left)?, op.clone(), build_expr(right)?),
-        ))),
-        Expr::Nested(e) => build_expr(e),
-        Expr::CompoundIdentifier(i) => {
-            if i.len() > 2 {
-                bail!(Error::Unsupported(
-                    "Please use table.column or column".into()
-                ))
+        LogicalExpr::BinaryExpr(e) => {
+            collect_aggregates(&e.left, aggregates);
+            collect_aggregates(&e.right, aggregates);
+        }
+        LogicalExpr::AliasedExpr(e, _) | LogicalExpr::Cast(e, _) | LogicalExpr::Not(e) => {
+            collect_aggregates(e, aggregates)
+        }
+        LogicalExpr::InList(e) => {
+            for expr in e.exprs() {
+                collect_aggregates(expr, aggregates);
+            }
+        }
+        LogicalExpr::FunctionExpr(e) => {
+            for arg in e.args.iter() {
+                collect_aggregates(arg, aggregates);
+            }
+        }
+        LogicalExpr::DistinctFrom(e) => {
+            collect_aggregates(&e.left, aggregates);
+            collect_aggregates(&e.right, aggregates);
+        }
+        LogicalExpr::Case(e) => {
+            for expr in e.exprs() {
+                collect_aggregates(expr, aggregates);
+            }
+        }
+        LogicalExpr::WindowExpr(e) => {
+            for expr in e.exprs() {
+                collect_aggregates(expr, aggregates);
+            }
+        }
+        LogicalExpr::Column(_) | LogicalExpr::Literal(_) | LogicalExpr::Parameter(..) => {}
+    }
+}
+
+/// Collects the distinct window function calls in the expression
+fn collect_windows(expr: &LogicalExpr, windows: &mut Vec<WindowExpr>) {
+    match expr {
+        LogicalExpr::WindowExpr(e) => {
+            if !windows.iter().any(|w| w.print() == e.print()) {
+                windows.push(*e.clone());
+            }
+        }
+        LogicalExpr::BinaryExpr(e) => {
+            collect_windows(&e.left, windows);
+            collect_windows(&e.right, windows);
+        }
+        LogicalExpr::AliasedExpr(e, _) | LogicalExpr::Cast(e, _) | LogicalExpr::Not(e) => {
+            collect_windows(e, windows)
+        }
+        LogicalExpr::InList(e) => {
+            for expr in e.exprs() {
+                collect_windows(expr, windows);
+            }
+        }
+        LogicalExpr::FunctionExpr(e) => {
+            for arg in e.args.iter() {
+                collect_windows(arg, windows);
+            }
+        }
+        LogicalExpr::DistinctFrom(e) => {
+            collect_windows(&e.left, windows);
+            collect_windows(&e.right, windows);
+        }
+        LogicalExpr::Case(e) => {
+            for expr in e.exprs() {
+                collect_windows(expr, windows);
             }
+        }
+        LogicalExpr::AggregateExpr(e) => {
+            if let Some(arg) = &e.arg {
+                collect_windows(arg, windows);
+            }
+        }
+        LogicalExpr::Column(_) | LogicalExpr::Literal(_) | LogicalExpr::Parameter(..) => {}
+    }
+}
 
-            Ok(LogicalExpr::Column(
-                i.iter()
-                    .map(|i| i.value.clone())
-                    .collect::<Vec<_>>()
-                    .join("."),
-            ))
+/// Window functions are computed after WHERE, GROUP BY and HAVING
+fn ensure_no_windows(expr: &LogicalExpr, clause: &str) -> Result<()> {
+    let mut windows = vec![];
+    collect_windows(expr, &mut windows);
+    match windows.first() {
+        Some(window) => bail!(Error::Unsupported(format!(
+            "Window function in {clause}: {}",
+            window.print()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Replaces window function calls with references to the Window's output
+/// columns, `schema` is the schema of the windows' input
+fn rewrite_windows(expr: LogicalExpr, schema: &Schema) -> Result<LogicalExpr> {
+    Ok(match expr {
+        // coerced like the windows that were planned so that they're named the same
+        LogicalExpr::WindowExpr(e) => {
+            LogicalExpr::Column(coerce_window(*e, schema)?.to_field(schema).name)
         }
-        Expr::Value(SqlValue::Null) => Ok(LogicalExpr::Literal(Value::Null)),
-        Expr::Between {
-            expr,
-            negated,
-            low,
-            high,
-        } => {
-            if *negated {
-                bail!(Error::Unsupported("NOT BETWEEN in index search".into()));
-            };
-            let column = build_expr(expr)?;
-            let left = LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
-                column.clone(),
-                BinaryOperator::GtEq,
-                build_expr(low)?,
-            )));
-            let right = LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
-                column.clone(),
-                BinaryOperator::LtEq,
-                build_expr(high)?,
-            )));
-            Ok(LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+        LogicalExpr::Column(_) | LogicalExpr::Literal(_) | LogicalExpr::Parameter(..) => expr,
+        LogicalExpr::BinaryExpr(e) => {
+            let BinaryExpr { left, op, right } = *e;
+            LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+                rewrite_windows(left, schema)?,
+                op,
+                rewrite_windows(right, schema)?,
+            )))
+        }
+        LogicalExpr::AliasedExpr(e, alias) => {
+            LogicalExpr::AliasedExpr(Box::new(rewrite_windows(*e, schema)?), alias)
+        }
+        LogicalExpr::Cast(e, ty) => LogicalExpr::Cast(Box::new(rewrite_windows(*e, schema)?), ty),
+        LogicalExpr::Not(e) => LogicalExpr::Not(Box::new(rewrite_windows(*e, schema)?)),
+        LogicalExpr::InList(e) => {
+            let InListExpr {
+                expr,
+                list,
+                negated,
+            } = *e;
+            LogicalExpr::InList(Box::new(InListExpr::new(
+                rewrite_windows(expr, schema)?,
+                list.into_iter()
+                    .map(|e| rewrite_windows(e, schema))
+                    .collect::<Result<_>>()?,
+                negated,
+            )))
+        }
+        LogicalExpr::FunctionExpr(e) => {
+            let FunctionExpr { func, args } = *e;
+            LogicalExpr::FunctionExpr(Box::new(FunctionExpr::new(
+                func,
+                args.into_iter()
+                    .map(|arg| rewrite_windows(arg, schema))
+                    .collect::<Result<_>>()?,
+            )))
+        }
+        LogicalExpr::DistinctFrom(e) => {
+            let DistinctFromExpr {
+                left,
+                right,
+                negated,
+            } = *e;
+            LogicalExpr::DistinctFrom(Box::new(DistinctFromExpr::new(
+                rewrite_windows(left, schema)?,
+                rewrite_windows(right, schema)?,
+                negated,
+            )))
+        }
+        LogicalExpr::Case(e) => {
+            let CaseExpr {
+                branches,
+                else_result,
+            } = *e;
+            let branches = branches
+                .into_iter()
+                .map(|(when, then)| {
+                    Ok((
+                        rewrite_windows(when, schema)?,
+                        rewrite_windows(then, schema)?,
+                    ))
+                })
+                .collect::<Result<_>>()?;
+            let else_result = else_result
+                .map(|e| rewrite_windows(e, schema))
+                .transpose()?;
+            LogicalExpr::Case(Box::new(CaseExpr::new(branches, else_result)))
+        }
+        // aggregates are computed before the windows
+        LogicalExpr::AggregateExpr(_) => expr,
+    })
+}
+
+/// Replaces group expressions and aggregate calls with references to the
+/// Aggregate's output columns. `grouped` maps printed group expressions
+/// to their output column names
+fn rewrite_for_aggregate(
+    expr: LogicalExpr,
+    grouped: &[(String, String)],
+    input_schema: &Schema,
+) -> Result<LogicalExpr> {
+    let printed = expr.print();
+    if let Some((_, name)) = grouped.iter().find(|(e, _)| *e == printed) {
+        return Ok(LogicalExpr::Column(name.clone()));
+    }
+
+    Ok(match expr {
+        LogicalExpr::AggregateExpr(e) => LogicalExpr::Column(e.to_field(input_schema).name),
+        LogicalExpr::Column(name) => bail!(Error::UngroupedColumn(name)),
+        LogicalExpr::Literal(_) | LogicalExpr::Parameter(..) => expr,
+        LogicalExpr::BinaryExpr(e) => {
+            let BinaryExpr { left, op, right } = *e;
+            LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+                rewrite_for_aggregate(left, grouped, input_schema)?,
+                op,
+                rewrite_for_aggregate(right, grouped, input_schema)?,
+            )))
+        }
+        LogicalExpr::AliasedExpr(e, alias) => LogicalExpr::AliasedExpr(
+            Box::new(rewrite_for_aggregate(*e, grouped, input_schema)?),
+            alias,
+        ),
+        LogicalExpr::Cast(e, ty) => LogicalExpr::Cast(
+            Box::new(rewrite_for_aggregate(*e, grouped, input_schema)?),
+            ty,
+        ),
+        LogicalExpr::Not(e) => {
+            LogicalExpr::Not(Box::new(rewrite_for_aggregate(*e, grouped, input_schema)?))
+        }
+        LogicalExpr::InList(e) => {
+            let InListExpr {
+                expr,
+                list,
+                negated,
+            } = *e;
+            LogicalExpr::InList(Box::new(InListExpr::new(
+                rewrite_for_aggregate(expr, grouped, input_schema)?,
+                list.into_iter()
+                    .map(|e| rewrite_for_aggregate(e, grouped, input_schema))
+                    .collect::<Result<_>>()?,
+                negated,
+            )))
+        }
+        LogicalExpr::FunctionExpr(e) => {
+            let FunctionExpr { func, args } = *e;
+            LogicalExpr::FunctionExpr(Box::new(FunctionExpr::new(
+                func,
+                args.into_iter()
+                    .map(|arg| rewrite_for_aggregate(arg, grouped, input_schema))
+                    .collect::<Result<_>>()?,
+            )))
+        }
+        LogicalExpr::DistinctFrom(e) => {
+            let DistinctFromExpr {
                 left,
-                BinaryOperator::And,
                 right,
-            ))))
+                negated,
+            } = *e;
+            LogicalExpr::DistinctFrom(Box::new(DistinctFromExpr::new(
+                rewrite_for_aggregate(left, grouped, input_schema)?,
+                rewrite_for_aggregate(right, grouped, input_schema)?,
+                negated,
+            )))
+        }
+        LogicalExpr::Case(e) => {
+            let CaseExpr {
+                branches,
+                else_result,
+            } = *e;
+            let branches = branches
+                .into_iter()
+                .map(|(when, then)| {
+                    Ok((
+                        rewrite_for_aggregate(when, grouped, input_schema)?,
+                        rewrite_for_aggregate(then, grouped, input_schema)?,
+                    ))
+                })
+                .collect::<Result<_>>()?;
+            let else_result = else_result
+                .map(|e| rewrite_for_aggregate(e, grouped, input_schema))
+                .transpose()?;
+            LogicalExpr::Case(Box::new(CaseExpr::new(branches, else_result)))
+        }
+        LogicalExpr::WindowExpr(e) => {
+            let WindowExpr {
+                func,
+                args,
+                partition_by,
+                order_by,
+                frame,
+            } = *e;
+            let mut rewrite = |e| rewrite_for_aggregate(e, grouped, input_schema);
+            let args = args.into_iter().map(&mut rewrite).collect::<Result<_>>()?;
+            let partition_by = partition_by
+                .into_iter()
+                .map(&mut rewrite)
+                .collect::<Result<_>>()?;
+            let order_by = order_by
+                .into_iter()
+                .map(|e| {
+                    Ok(SortExpr {
+                        expr: rewrite(e.expr)?,
+                        ..e
+                    })
+                })
+                .collect::<Result<_>>()?;
+            LogicalExpr::WindowExpr(Box::new(WindowExpr::new(
+                func,
+                args,
+                partition_by,
+                order_by,
+                frame,
+            )))
+        }
+    })
+}
+
+/// Turns an arbitrary expression into something a Filter can evaluate
+fn build_predicate(expr: LogicalExpr, schema: &Schema) -> Result<BooleanBinaryExpr> {
+    let expr = coerce_types(expr, schema)?;
+
+    match expr {
+        LogicalExpr::BinaryExpr(e) => {
+            let BinaryExpr { left, op, right } = *e;
+            if !is_boolean_op!(op) {
+                bail!(Error::Expected(
+                    "a boolean expression".into(),
+                    op.to_string()
+                ));
+            }
+            Ok(BooleanBinaryExpr::new(left, op, right))
+        }
+        LogicalExpr::Column(ref name)
+            if schema
+                .fields
+                .iter()
+                .any(|f| &f.name == name && matches!(f.ty, Types::Bool)) =>
+        {
+            Ok(BooleanBinaryExpr::new(
+                expr,
+                BinaryOperator::Eq,
+                true.into(),
+            ))
+        }
+        LogicalExpr::Literal(Value::Bool(_))
+        | LogicalExpr::DistinctFrom(_)
+        | LogicalExpr::Not(_)
+        | LogicalExpr::InList(_) => Ok(BooleanBinaryExpr::new(
+            expr,
+            BinaryOperator::Eq,
+            true.into(),
+        )),
+        LogicalExpr::FunctionExpr(_) | LogicalExpr::Case(_) | LogicalExpr::Cast(..)
+            if matches!(expr.to_field(schema).ty, Types::Bool) =>
+        {
+            Ok(BooleanBinaryExpr::new(
+                expr,
+                BinaryOperator::Eq,
+                true.into(),
+            ))
         }
-        Expr::Value(SqlValue::Boolean(b)) => Ok(LogicalExpr::Literal(lit!(Bool, b.to_string())?)),
-        e => bail!(Error::Unsupported(format!("Expr: {e}"))),
+        e => bail!(Error::Expected("a boolean expression".into(), e.print())),
+    }
+}
+
+/// The types both sides of a set operation are converted to
+fn check_set_operands(op: SetOperator, left: &Schema, right: &Schema) -> Result<Vec<Types>> {
+    let left_types: Vec<_> = left.fields.iter().map(|f| f.ty.clone()).collect();
+    let right_types: Vec<_> = right.fields.iter().map(|f| f.ty.clone()).collect();
+
+    if left_types.len() != right_types.len() {
+        bail!(Error::Expected(
+            format!("both sides of {op} to have the same number of columns"),
+            format!("{} and {}", left_types.len(), right_types.len())
+        ));
+    }
+
+    let types = left_types
+        .iter()
+        .zip(right_types.iter())
+        .map(|(l, r)| Types::common(&[l.clone(), r.clone()]))
+        .collect::<Option<Vec<_>>>();
+
+    types.ok_or(Error::TypeMismatch(left_types, right_types).into())
+}
+
+/// Casts a plan's output columns to the types, keeping their names
+fn cast_columns(plan: LogicalPlan, types: &[Types]) -> LogicalPlan {
+    let fields = plan.schema().fields;
+    if fields.iter().zip(types).all(|(field, ty)| field.ty == *ty) {
+        return plan;
+    }
+
+    let projections = fields
+        .into_iter()
+        .zip(types)
+        .map(|(field, ty)| {
+            let column = LogicalExpr::Column(field.name.clone());
+            match field.ty == *ty {
+                true => column,
+                false => LogicalExpr::AliasedExpr(
+                    Box::new(LogicalExpr::Cast(Box::new(column), ty.clone())),
+                    field.name,
+                ),
+            }
+        })
+        .collect();
+
+    LogicalPlan::Projection(Box::new(Projection::new(plan, projections)))
+}
+
+/// Renames a plan's output columns by position, for column lists like `cte(a, b)`
+fn rename_columns(plan: LogicalPlan, names: &[String]) -> Result<LogicalPlan> {
+    if names.is_empty() {
+        return Ok(plan);
+    }
+
+    let fields = plan.schema().fields;
+    if fields.len() != names.len() {
+        bail!(Error::Expected(
+            format!("{} columns", names.len()),
+            format!("{} columns", fields.len())
+        ));
+    }
+
+    let projections = fields
+        .into_iter()
+        .zip(names)
+        .map(|(field, name)| {
+            LogicalExpr::AliasedExpr(Box::new(LogicalExpr::Column(field.name)), name.clone())
+        })
+        .collect();
+
+    Ok(LogicalPlan::Projection(Box::new(Projection::new(
+        plan,
+        projections,
+    ))))
+}
+
+/// The number of rows a LIMIT or OFFSET value stands for
+fn row_count(value: &Value, clause: &str) -> Result<u32> {
+    match value {
+        Value::UInt(u) => Ok(u.0),
+        Value::Int(i) if i.0 >= 0 => Ok(i.0 as u32),
+        _ => bail!(Error::Expected(
+            format!("{clause} to be an unsigned integer"),
+            value.to_string()
+        )),
     }
 }
 
@@ -1,5 +0,0 @@
-use super::plan::LogicalPlan;
-
-pub fn optimize_logical_plan(plan: LogicalPlan) -> LogicalPlan {
-    plan
-}
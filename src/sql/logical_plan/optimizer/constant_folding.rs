@@ -0,0 +1,208 @@
+use sqlparser::ast::BinaryOperator;
+
+use super::{expr_to_predicate, is_literal, predicate_to_expr, transform_up, OptimizerRule};
+use crate::sql::logical_plan::expr::{
+    AggregateExpr, BinaryExpr, CaseExpr, DistinctFromExpr, FunctionExpr, InListExpr, LogicalExpr,
+};
+use crate::sql::logical_plan::plan::{Filter, LogicalPlan, Projection};
+use crate::types::Value;
+
+/// Evaluates the parts of expressions that only involve literals, filters
+/// that are always true are dropped. Expressions that fail to evaluate are
+/// left as they are to fail at execution
+pub struct ConstantFolding;
+
+impl OptimizerRule for ConstantFolding {
+    fn name(&self) -> &str {
+        "Constant Folding"
+    }
+
+    fn optimize(&self, plan: LogicalPlan) -> LogicalPlan {
+        transform_up(plan, &|plan| match plan {
+            LogicalPlan::Projection(projection) => {
+                let Projection { input, projections } = *projection;
+                let schema = input.schema();
+
+                // folded columns keep their names
+                let projections = projections
+                    .into_iter()
+                    .map(|expr| {
+                        let folded = fold(expr.clone());
+                        match expr {
+                            LogicalExpr::BinaryExpr(_)
+                            | LogicalExpr::FunctionExpr(_)
+                            | LogicalExpr::DistinctFrom(_)
+                            | LogicalExpr::Case(_)
+                            | LogicalExpr::Cast(..)
+                            | LogicalExpr::Not(_)
+                            | LogicalExpr::InList(_)
+                                if folded.print() != expr.print() =>
+                            {
+                                let name = expr.to_field(&schema).name;
+                                LogicalExpr::AliasedExpr(Box::new(folded), name)
+                            }
+                            _ => folded,
+                        }
+                    })
+                    .collect();
+
+                LogicalPlan::Projection(Box::new(Projection::new(input, projections)))
+            }
+            LogicalPlan::Filter(filter) => {
+                let Filter { input, expr } = *filter;
+                let expr = fold(predicate_to_expr(expr));
+                if is_literal(&expr, true) {
+                    return input;
+                }
+                LogicalPlan::Filter(Box::new(Filter::new(input, expr_to_predicate(expr))))
+            }
+            LogicalPlan::Join(mut join) => {
+                join.on = match fold(LogicalExpr::BinaryExpr(Box::new(join.on))) {
+                    LogicalExpr::BinaryExpr(on) => *on,
+                    // matches every pair of rows or none
+                    e => BinaryExpr::new(e, BinaryOperator::Eq, true.into()),
+                };
+                LogicalPlan::Join(join)
+            }
+            plan => plan,
+        })
+    }
+}
+
+fn fold(expr: LogicalExpr) -> LogicalExpr {
+    match expr {
+        LogicalExpr::BinaryExpr(e) => {
+            let BinaryExpr { left, op, right } = *e;
+            let (left, right) = (fold(left), fold(right));
+
+            match (&left, &op, &right) {
+                (LogicalExpr::Literal(l), _, LogicalExpr::Literal(r)) => {
+                    let expr = BinaryExpr::new(left.clone(), op.clone(), right.clone());
+                    if let Ok(value) = expr.eval_op(l, r) {
+                        return LogicalExpr::Literal(value);
+                    }
+                    LogicalExpr::BinaryExpr(Box::new(expr))
+                }
+                (e, BinaryOperator::And, t) | (t, BinaryOperator::And, e)
+                    if is_literal(t, true) =>
+                {
+                    e.clone()
+                }
+                (e, BinaryOperator::Or, f) | (f, BinaryOperator::Or, e) if is_literal(f, false) => {
+                    e.clone()
+                }
+                _ => LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(left, op, right))),
+            }
+        }
+        LogicalExpr::AliasedExpr(e, alias) => LogicalExpr::AliasedExpr(Box::new(fold(*e)), alias),
+        LogicalExpr::AggregateExpr(e) => {
+            let AggregateExpr { func, arg } = *e;
+            LogicalExpr::AggregateExpr(Box::new(AggregateExpr::new(func, arg.map(fold))))
+        }
+        LogicalExpr::FunctionExpr(e) => {
+            let FunctionExpr { func, args } = *e;
+            let args = args.into_iter().map(fold).collect::<Vec<_>>();
+
+            let values = args
+                .iter()
+                .map(|arg| match arg {
+                    LogicalExpr::Literal(v) => Some(v.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>();
+
+            if let Some(Ok(value)) = values.map(|values| func.call(&values)) {
+                return LogicalExpr::Literal(value);
+            }
+            LogicalExpr::FunctionExpr(Box::new(FunctionExpr::new(func, args)))
+        }
+        LogicalExpr::DistinctFrom(e) => {
+            let DistinctFromExpr {
+                left,
+                right,
+                negated,
+            } = *e;
+            let e = DistinctFromExpr::new(fold(left), fold(right), negated);
+
+            if let (LogicalExpr::Literal(l), LogicalExpr::Literal(r)) = (&e.left, &e.right) {
+                if let Ok(value) = e.eval_op(l, r) {
+                    return LogicalExpr::Literal(value);
+                }
+            }
+            LogicalExpr::DistinctFrom(Box::new(e))
+        }
+        LogicalExpr::Case(e) => {
+            let CaseExpr {
+                branches,
+                mut else_result,
+            } = *e;
+
+            // branches that can't be taken are dropped, one that's always
+            // taken ends the CASE
+            let mut folded = vec![];
+            for (when, then) in branches {
+                match fold(when) {
+                    LogicalExpr::Literal(v) if !v.is_truthy() => {}
+                    LogicalExpr::Literal(_) => {
+                        else_result = Some(then);
+                        break;
+                    }
+                    when => folded.push((when, fold(then))),
+                }
+            }
+            let else_result = else_result.map(fold);
+
+            match else_result {
+                _ if !folded.is_empty() => {
+                    LogicalExpr::Case(Box::new(CaseExpr::new(folded, else_result)))
+                }
+                Some(else_result) => else_result,
+                None => LogicalExpr::Literal(Value::Null),
+            }
+        }
+        LogicalExpr::Cast(e, ty) => {
+            let e = fold(*e);
+            if let LogicalExpr::Literal(v) = &e {
+                if let Ok(value) = v.cast(&ty) {
+                    return LogicalExpr::Literal(value);
+                }
+            }
+            LogicalExpr::Cast(Box::new(e), ty)
+        }
+        LogicalExpr::Not(e) => {
+            let e = fold(*e);
+            if let LogicalExpr::Literal(v) = &e {
+                if let Ok(value) = v.not() {
+                    return LogicalExpr::Literal(value);
+                }
+            }
+            LogicalExpr::Not(Box::new(e))
+        }
+        LogicalExpr::InList(e) => {
+            let InListExpr {
+                expr,
+                list,
+                negated,
+            } = *e;
+            let e = InListExpr::new(fold(expr), list.into_iter().map(fold).collect(), negated);
+
+            let values = e
+                .exprs()
+                .map(|e| match e {
+                    LogicalExpr::Literal(v) => Some(v.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>();
+
+            if let Some(Ok(value)) = values.map(|values| e.eval_op(&values[0], &values[1..])) {
+                return LogicalExpr::Literal(value);
+            }
+            LogicalExpr::InList(Box::new(e))
+        }
+        // replaced by the builder, Window plans keep their functions as they are
+        LogicalExpr::WindowExpr(_)
+        | LogicalExpr::Literal(_)
+        | LogicalExpr::Column(_)
+        | LogicalExpr::Parameter(..) => expr,
+    }
+}
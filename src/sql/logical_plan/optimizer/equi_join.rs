@@ -0,0 +1,72 @@
+use std::collections::HashSet;
+
+use sqlparser::ast::BinaryOperator;
+
+use super::{
+    add_filter, conjoin, is_literal, join_columns, predicate_to_expr, split_conjunction,
+    transform_up, OptimizerRule,
+};
+use crate::sql::logical_plan::collect_columns;
+use crate::sql::logical_plan::expr::LogicalExpr;
+use crate::sql::logical_plan::plan::{Filter, JoinType, LogicalPlan};
+
+/// Turns the conjuncts of a filter over an inner join that compare both sides
+/// into the join's condition, a cross join followed by `WHERE a = b` becomes
+/// an equi-join instead of producing every pair of rows
+pub struct EquiJoin;
+
+impl OptimizerRule for EquiJoin {
+    fn name(&self) -> &str {
+        "Equi Join"
+    }
+
+    fn optimize(&self, plan: LogicalPlan) -> LogicalPlan {
+        transform_up(plan, &|plan| match plan {
+            LogicalPlan::Filter(filter) if is_inner_join(&filter.input) => {
+                let Filter { input, expr } = *filter;
+                let LogicalPlan::Join(mut join) = input else {
+                    unreachable!()
+                };
+
+                let (left_columns, right_columns) = join_columns(&join);
+                let left_columns: HashSet<_> = left_columns.into_iter().map(|(c, _)| c).collect();
+                let right_columns: HashSet<_> = right_columns.into_iter().map(|(c, _)| c).collect();
+
+                let (conditions, kept): (Vec<_>, Vec<_>) =
+                    split_conjunction(predicate_to_expr(expr))
+                        .into_iter()
+                        .partition(|conjunct| {
+                            let columns = collect_columns(conjunct);
+                            matches!(conjunct, LogicalExpr::BinaryExpr(_))
+                                && columns.iter().any(|c| left_columns.contains(c))
+                                && columns.iter().any(|c| right_columns.contains(c))
+                                && columns
+                                    .iter()
+                                    .all(|c| left_columns.contains(c) || right_columns.contains(c))
+                        });
+
+                let on = &join.on;
+                let cross = on.op == BinaryOperator::Eq
+                    && is_literal(&on.left, true)
+                    && is_literal(&on.right, true);
+
+                let mut on = match cross {
+                    true => vec![],
+                    false => vec![LogicalExpr::BinaryExpr(Box::new(join.on.clone()))],
+                };
+                on.extend(conditions);
+
+                if let Some(LogicalExpr::BinaryExpr(on)) = conjoin(on) {
+                    join.on = *on;
+                }
+
+                add_filter(LogicalPlan::Join(join), kept)
+            }
+            plan => plan,
+        })
+    }
+}
+
+fn is_inner_join(plan: &LogicalPlan) -> bool {
+    matches!(plan, LogicalPlan::Join(join) if join.join_type == JoinType::Inner)
+}
@@ -0,0 +1,281 @@
+use sqlparser::ast::BinaryOperator;
+
+use super::{add_filter, predicate_to_expr, split_conjunction, transform_up, OptimizerRule};
+use crate::pages::indexes::b_plus_tree::Key;
+use crate::sql::logical_plan::expr::{BinaryExpr, LogicalExpr};
+use crate::sql::logical_plan::plan::{Delete, Filter, IndexScan, LogicalPlan, Scan, Update};
+use crate::tuple::schema::Field;
+use crate::types::{Types, Value};
+
+/// Replaces a scan filtered on the table's indexed column with an index scan
+/// over the range of keys the filter allows, or with lookups of the keys of
+/// an IN list. Conjuncts the index scan answers exactly are dropped, the
+/// rest stay as a filter over it
+pub struct IndexSelection;
+
+impl OptimizerRule for IndexSelection {
+    fn name(&self) -> &str {
+        "Index Selection"
+    }
+
+    fn optimize(&self, plan: LogicalPlan) -> LogicalPlan {
+        transform_up(plan, &|plan| match plan {
+            LogicalPlan::Filter(filter) if matches!(filter.input, LogicalPlan::Scan(_)) => {
+                let Filter { input, expr } = *filter;
+                let LogicalPlan::Scan(scan) = input else {
+                    unreachable!()
+                };
+
+                let conjuncts = split_conjunction(predicate_to_expr(expr));
+                match select_index(&scan, &conjuncts) {
+                    Some((index_scan, answered)) => {
+                        let residual = conjuncts
+                            .into_iter()
+                            .enumerate()
+                            .filter(|(i, _)| !answered.contains(i))
+                            .map(|(_, c)| c)
+                            .collect();
+                        add_filter(LogicalPlan::IndexScan(index_scan), residual)
+                    }
+                    None => add_filter(LogicalPlan::Scan(scan), conjuncts),
+                }
+            }
+            // the selection is still evaluated over the rows of the index scan
+            LogicalPlan::Update(update) if matches!(update.input, LogicalPlan::Scan(_)) => {
+                let Update {
+                    input,
+                    table_name,
+                    assignments,
+                    selection,
+                    returning,
+                } = *update;
+                let input = index_input(input, &selection);
+                LogicalPlan::Update(Box::new(Update::new(
+                    input,
+                    table_name,
+                    assignments,
+                    selection,
+                    returning,
+                )))
+            }
+            LogicalPlan::Delete(delete) if matches!(delete.input, LogicalPlan::Scan(_)) => {
+                let Delete {
+                    input,
+                    table_name,
+                    selection,
+                    returning,
+                } = *delete;
+                let input = index_input(input, &selection);
+                LogicalPlan::Delete(Box::new(Delete::new(
+                    input, table_name, selection, returning,
+                )))
+            }
+            plan => plan,
+        })
+    }
+}
+
+fn index_input(input: LogicalPlan, selection: &LogicalExpr) -> LogicalPlan {
+    let LogicalPlan::Scan(scan) = input else {
+        return input;
+    };
+
+    match select_index(&scan, &split_conjunction(selection.clone())) {
+        Some((index_scan, _)) => LogicalPlan::IndexScan(index_scan),
+        None => LogicalPlan::Scan(scan),
+    }
+}
+
+/// A bound on the index key, and whether it's inclusive
+type Bound = (Key, bool);
+
+/// The index scan over the keys the conjuncts allow, with the positions of the
+/// conjuncts it answers exactly. None if the table has no index or no
+/// conjunct bounds it
+fn select_index(scan: &Scan, conjuncts: &[LogicalExpr]) -> Option<(IndexScan, Vec<usize>)> {
+    let field = scan.schema.fields.iter().find(|f| f.constraints.unique)?;
+
+    let mut from: Option<Bound> = None;
+    let mut to: Option<Bound> = None;
+    let mut used = vec![];
+    let mut exact = true;
+
+    for (i, conjunct) in conjuncts.iter().enumerate() {
+        let Some((op, value)) = compare_column(conjunct, &field.name) else {
+            continue;
+        };
+        let Some((key, key_exact)) = to_key(field, value) else {
+            continue;
+        };
+
+        // an inexact key is the truncated value, so its bounds must include it
+        let strict = |bound: bool| (key, !key_exact || bound);
+        let (lower, upper) = match op {
+            BinaryOperator::Eq => (Some((key, true)), Some((key, true))),
+            BinaryOperator::Gt => (Some(strict(false)), None),
+            BinaryOperator::GtEq => (Some((key, true)), None),
+            BinaryOperator::Lt => (None, Some(strict(false))),
+            BinaryOperator::LtEq => (None, Some((key, true))),
+            _ => continue,
+        };
+
+        from = tighter(from, lower, |a, b| a > b);
+        to = tighter(to, upper, |a, b| a < b);
+        exact &= key_exact;
+        used.push(i);
+    }
+
+    // the keys of an IN list are looked up one by one, the range only
+    // drops some of them
+    let lookups = conjuncts
+        .iter()
+        .enumerate()
+        .find_map(|(i, c)| Some((i, in_list_keys(c, field)?)));
+    if let Some((i, (keys, keys_exact))) = lookups {
+        let keys = keys.into_iter().filter(|&k| within(k, from, to)).collect();
+        used.push(i);
+
+        let answered = match exact && keys_exact {
+            true => used,
+            false => vec![],
+        };
+        let index_scan = IndexScan::lookups(
+            scan.table_name.clone(),
+            scan.schema.clone(),
+            field.name.clone(),
+            keys,
+        );
+        return Some((index_scan, answered));
+    }
+
+    if used.is_empty() {
+        return None;
+    }
+
+    if field.ty == Types::Int {
+        // negative ints are stored after every positive one, a range
+        // without a lower bound would miss them
+        from?;
+        // and one without an upper bound would include them
+        exact &= to.is_some();
+    }
+
+    let answered = match exact {
+        true => used,
+        false => vec![],
+    };
+
+    let (from, include_from) = from.map_or((None, false), |(k, i)| (Some(k), i));
+    let (to, include_to) = to.map_or((None, false), |(k, i)| (Some(k), i));
+
+    let index_scan = IndexScan::new(
+        scan.table_name.clone(),
+        scan.schema.clone(),
+        field.name.clone(),
+        from,
+        include_from,
+        to,
+        include_to,
+    );
+
+    Some((index_scan, answered))
+}
+
+/// The operator and value of a comparison between the column and a literal,
+/// flipped to have the column on the left
+fn compare_column<'a>(expr: &'a LogicalExpr, column: &str) -> Option<(BinaryOperator, &'a Value)> {
+    let LogicalExpr::BinaryExpr(e) = expr else {
+        return None;
+    };
+    let BinaryExpr { left, op, right } = e.as_ref();
+
+    match (left, right) {
+        (LogicalExpr::Column(c), LogicalExpr::Literal(v)) if c == column => Some((op.clone(), v)),
+        (LogicalExpr::Literal(v), LogicalExpr::Column(c)) if c == column => {
+            let op = match op {
+                BinaryOperator::Gt => BinaryOperator::Lt,
+                BinaryOperator::GtEq => BinaryOperator::LtEq,
+                BinaryOperator::Lt => BinaryOperator::Gt,
+                BinaryOperator::LtEq => BinaryOperator::GtEq,
+                op => op.clone(),
+            };
+            Some((op, v))
+        }
+        _ => None,
+    }
+}
+
+/// The sorted keys of an IN list of literals on the column, and whether
+/// they're the values themselves. NULLs never match and have no key. A lone
+/// IN list is a filter of its own, compared with true
+fn in_list_keys(expr: &LogicalExpr, field: &Field) -> Option<(Vec<Key>, bool)> {
+    let expr = match expr {
+        LogicalExpr::BinaryExpr(e)
+            if e.op == BinaryOperator::Eq
+                && matches!(&e.right, LogicalExpr::Literal(v) if v.is_truthy()) =>
+        {
+            &e.left
+        }
+        expr => expr,
+    };
+    let LogicalExpr::InList(e) = expr else {
+        return None;
+    };
+    if e.negated || !matches!(&e.expr, LogicalExpr::Column(c) if *c == field.name) {
+        return None;
+    }
+
+    let mut keys = vec![];
+    let mut exact = true;
+    for value in e.list.iter() {
+        match value {
+            LogicalExpr::Literal(Value::Null) => {}
+            LogicalExpr::Literal(value) => {
+                let (key, key_exact) = to_key(field, value)?;
+                keys.push(key);
+                exact &= key_exact;
+            }
+            _ => return None,
+        }
+    }
+
+    keys.sort();
+    keys.dedup();
+    Some((keys, exact))
+}
+
+/// Whether the key is within both bounds
+fn within(key: Key, from: Option<Bound>, to: Option<Bound>) -> bool {
+    let above = |(k, inclusive): Bound| key > k || (inclusive && key == k);
+    let below = |(k, inclusive): Bound| key < k || (inclusive && key == k);
+    from.is_none_or(above) && to.is_none_or(below)
+}
+
+/// The index key of a value compared with the column, and whether the key
+/// is the value itself. Keys are the column's values cast to u32, floats are
+/// truncated and negative ints wrap around
+pub(crate) fn to_key(field: &Field, value: &Value) -> Option<(Key, bool)> {
+    match (&field.ty, value) {
+        (Types::Float, Value::UInt(v)) => Some((v.0 as f32 as Key, false)),
+        (Types::Float, Value::Int(v)) => Some((v.0 as f32 as Key, false)),
+        (Types::Float, Value::Float(v)) => Some((v.0 as Key, false)),
+        (Types::UInt, Value::UInt(v)) => Some((v.0, true)),
+        (Types::Int, Value::UInt(v)) => Some((v.0, v.0 <= i32::MAX as u32)),
+        (_, Value::Int(v)) if v.0 >= 0 => Some((v.0 as Key, true)),
+        (_, Value::Float(v)) if v.0 >= 0.0 => Some((v.0 as Key, false)),
+        _ => None,
+    }
+}
+
+/// The tighter of two bounds, `is_tighter(a, b)` tells if key `a` is tighter than `b`
+fn tighter(
+    a: Option<Bound>,
+    b: Option<Bound>,
+    is_tighter: impl Fn(Key, Key) -> bool,
+) -> Option<Bound> {
+    match (a, b) {
+        (Some(a), Some(b)) if a.0 == b.0 => Some((a.0, a.1 && b.1)),
+        (Some(a), Some(b)) => Some(if is_tighter(a.0, b.0) { a } else { b }),
+        (a, b) => a.or(b),
+    }
+}
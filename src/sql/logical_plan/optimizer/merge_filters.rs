@@ -0,0 +1,21 @@
+use super::{add_filter, predicate_to_expr, split_conjunction, transform_up, OptimizerRule};
+use crate::sql::logical_plan::plan::{Filter, LogicalPlan};
+
+/// Combines a filter over another filter into a single one
+pub struct MergeFilters;
+
+impl OptimizerRule for MergeFilters {
+    fn name(&self) -> &str {
+        "Merge Filters"
+    }
+
+    fn optimize(&self, plan: LogicalPlan) -> LogicalPlan {
+        transform_up(plan, &|plan| match plan {
+            LogicalPlan::Filter(filter) if matches!(filter.input, LogicalPlan::Filter(_)) => {
+                let Filter { input, expr } = *filter;
+                add_filter(input, split_conjunction(predicate_to_expr(expr)))
+            }
+            plan => plan,
+        })
+    }
+}
@@ -0,0 +1,355 @@
+mod constant_folding;
+mod equi_join;
+mod index_selection;
+mod merge_filters;
+mod projection_pruning;
+mod push_down_predicates;
+
+use constant_folding::ConstantFolding;
+use equi_join::EquiJoin;
+pub(crate) use index_selection::to_key;
+use index_selection::IndexSelection;
+use merge_filters::MergeFilters;
+use projection_pruning::ProjectionPruning;
+use push_down_predicates::PushDownPredicates;
+use sqlparser::ast::BinaryOperator;
+
+use super::expr::{BinaryExpr, BooleanBinaryExpr, LogicalExpr};
+use super::plan::{Filter, Join, LogicalPlan};
+use crate::is_boolean_op;
+use crate::printdbg;
+use crate::tuple::schema::Field;
+use crate::types::{Bool, Value};
+
+/// A rewrite of a plan into an equivalent one that's cheaper to execute
+trait OptimizerRule {
+    fn name(&self) -> &str;
+
+    fn optimize(&self, plan: LogicalPlan) -> LogicalPlan;
+}
+
+/// Rules run once each, in this order. Filters are merged before
+/// being pushed down so that every conjunct gets a chance to move,
+/// and indexes are picked once filters sit right above their scans
+fn rules() -> Vec<Box<dyn OptimizerRule>> {
+    vec![
+        Box::new(ConstantFolding),
+        Box::new(MergeFilters),
+        Box::new(PushDownPredicates),
+        Box::new(EquiJoin),
+        Box::new(IndexSelection),
+        Box::new(ProjectionPruning),
+    ]
+}
+
+pub fn optimize_logical_plan(plan: LogicalPlan) -> LogicalPlan {
+    match plan {
+        // keeps the plan as it was built, EXPLAIN shows both
+        LogicalPlan::Explain(mut explain) => {
+            explain.initial_plan = Some(explain.input.print());
+            explain.input = optimize_logical_plan(std::mem::take(&mut explain.input));
+            LogicalPlan::Explain(explain)
+        }
+        plan => rules().into_iter().fold(plan, |plan, rule| {
+            let plan = rule.optimize(plan);
+            printdbg!("After {}: {}", rule.name(), plan.print());
+            plan
+        }),
+    }
+}
+
+/// Optimizes a plan already optimized for a prepared statement again once
+/// its parameters are bound, with the rules that need their values: a
+/// parameter compared to an indexed column picks the index only then
+pub fn optimize_bound_plan(plan: LogicalPlan) -> LogicalPlan {
+    let rules: [Box<dyn OptimizerRule>; 2] = [Box::new(ConstantFolding), Box::new(IndexSelection)];
+    rules.into_iter().fold(plan, |plan, rule| {
+        let plan = rule.optimize(plan);
+        printdbg!("After {}: {}", rule.name(), plan.print());
+        plan
+    })
+}
+
+/// Applies `f` to every node of the plan, inputs first
+fn transform_up(plan: LogicalPlan, f: &impl Fn(LogicalPlan) -> LogicalPlan) -> LogicalPlan {
+    f(plan.map_inputs(|input| transform_up(input, f)))
+}
+
+/// Applies `f` to every node of the plan, inputs last
+fn transform_down(plan: LogicalPlan, f: &impl Fn(LogicalPlan) -> LogicalPlan) -> LogicalPlan {
+    f(plan).map_inputs(|input| transform_down(input, f))
+}
+
+fn is_literal(expr: &LogicalExpr, value: bool) -> bool {
+    matches!(expr, LogicalExpr::Literal(Value::Bool(Bool(b))) if *b == value)
+}
+
+/// Splits a predicate on its top-level ANDs
+pub(crate) fn split_conjunction(expr: LogicalExpr) -> Vec<LogicalExpr> {
+    match expr {
+        LogicalExpr::BinaryExpr(e) if e.op == BinaryOperator::And => {
+            let BinaryExpr { left, right, .. } = *e;
+            let mut conjuncts = split_conjunction(left);
+            conjuncts.extend(split_conjunction(right));
+            conjuncts
+        }
+        e => vec![e],
+    }
+}
+
+fn conjoin(conjuncts: Vec<LogicalExpr>) -> Option<LogicalExpr> {
+    conjuncts.into_iter().reduce(|left, right| {
+        LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(left, BinaryOperator::And, right)))
+    })
+}
+
+fn predicate_to_expr(predicate: BooleanBinaryExpr) -> LogicalExpr {
+    let BooleanBinaryExpr { left, op, right } = predicate;
+    LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(left, op, right)))
+}
+
+/// Boolean columns and literals are compared with true
+fn expr_to_predicate(expr: LogicalExpr) -> BooleanBinaryExpr {
+    match expr {
+        LogicalExpr::BinaryExpr(e) => {
+            let BinaryExpr { left, op, right } = *e;
+            if is_boolean_op!(op) {
+                return BooleanBinaryExpr::new(left, op, right);
+            }
+            let expr = LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(left, op, right)));
+            BooleanBinaryExpr::new(expr, BinaryOperator::Eq, true.into())
+        }
+        e => BooleanBinaryExpr::new(e, BinaryOperator::Eq, true.into()),
+    }
+}
+
+/// The names a join's condition uses for the columns of one of its inputs,
+/// paired with the names the input knows them by. Joined columns are
+/// qualified when two inputs share a column name
+type JoinColumns = Vec<(String, String)>;
+
+fn join_columns(join: &Join) -> (JoinColumns, JoinColumns) {
+    let left = join.left.schema().fields;
+    let right = join.right.schema().fields;
+
+    let (join_left, join_right) = join.schema.fields.split_at(left.len());
+    // the right side isn't part of the output
+    let join_right = match join.join_type.drops_right() {
+        true => &right,
+        false => join_right,
+    };
+
+    let pairs = |outer: &[_], inner: &[_]| {
+        outer
+            .iter()
+            .zip(inner)
+            .map(|(o, i): (&Field, &Field)| (o.name.clone(), i.name.clone()))
+            .collect()
+    };
+
+    (pairs(join_left, &left), pairs(join_right, &right))
+}
+
+/// Filters the input by the conjuncts, merged into the input if it's a filter
+fn add_filter(input: LogicalPlan, mut conjuncts: Vec<LogicalExpr>) -> LogicalPlan {
+    let input = match input {
+        LogicalPlan::Filter(filter) => {
+            let Filter { input, expr } = *filter;
+            let mut merged = split_conjunction(predicate_to_expr(expr));
+            merged.append(&mut conjuncts);
+            conjuncts = merged;
+            input
+        }
+        input => input,
+    };
+
+    match conjoin(conjuncts) {
+        Some(expr) => LogicalPlan::Filter(Box::new(Filter::new(input, expr_to_predicate(expr)))),
+        None => input,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::tests::test_context;
+    use anyhow::Result;
+
+    /// The optimized plan of a query
+    fn optimized(sql: &str) -> Result<String> {
+        let mut ctx = test_context();
+        ctx.execute_sql("CREATE TABLE t1 (a int, b int, c int);")?;
+        ctx.execute_sql("CREATE TABLE t2 (d int, e int, f int);")?;
+        ctx.execute_sql("CREATE TABLE t3 (g int UNIQUE NOT NULL, h float);")?;
+
+        let result = ctx.execute_sql(format!("EXPLAIN {sql}"))?;
+        let info = result.get_info();
+        let (_, plan) = info.split_once("Optimized Logical Plan:\n").unwrap();
+        let (plan, _) = plan.split_once("\nPhysical Plan:\n").unwrap();
+        Ok(plan.to_string())
+    }
+
+    #[test]
+    fn test_constant_folding() -> Result<()> {
+        let plan = optimized("SELECT a + (1 + 2) FROM t1 WHERE b > 2 * 3 AND 1 = 1;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [(#a + 3) AS a + 1 + 2]\n\
+             ---- Filter: #b > 6\n\
+             ------ Scan: t1 [#a,#b]\n"
+        );
+
+        // an always true filter is dropped
+        let plan = optimized("SELECT a FROM t1 WHERE 2 > 1;")?;
+        assert_eq!(plan, "-- Projection: [#a]\n---- Scan: t1 [#a]\n");
+
+        // CASE branches that can't be taken are dropped, one that's always
+        // taken becomes the ELSE
+        let plan = optimized(
+            "SELECT CASE WHEN 1 > 2 THEN a WHEN b > 1 THEN b WHEN 1 = 1 THEN c ELSE 0 END FROM t1;",
+        )?;
+        assert_eq!(
+            plan,
+            "-- Projection: [CASE WHEN (#b > 1) THEN #b ELSE #c END AS case]\n\
+             ---- Scan: t1 [#b,#c]\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_filters() -> Result<()> {
+        let plan = optimized("SELECT a FROM (SELECT a, b FROM t1 WHERE a > 1) s WHERE s.b < 5;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#a]\n\
+             ---- Filter: #b < 5\n\
+             ------ Projection: [#a,#b]\n\
+             -------- Filter: #a > 1\n\
+             ---------- Scan: t1 [#a,#b]\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_push_down_predicates() -> Result<()> {
+        let plan = optimized(
+            "SELECT a, d FROM t1 INNER JOIN t2 ON a = d WHERE b > 1 AND e < 2 AND b = e;",
+        )?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#a,#d]\n\
+             ---- Join: ((#a = #d) AND (#b = #e))\n\
+             ------ Filter: #b > 1\n\
+             -------- Scan: t1 [#a,#b]\n\
+             ------ Filter: #e < 2\n\
+             -------- Scan: t2 [#d,#e]\n"
+        );
+
+        // the padded side of an outer join keeps its filter
+        let plan = optimized("SELECT a FROM t1 LEFT JOIN t2 ON a = d WHERE b > 1 AND e < 2;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#a]\n\
+             ---- Filter: #e < 2\n\
+             ------ Left Join: (#a = #d)\n\
+             -------- Filter: #b > 1\n\
+             ---------- Scan: t1 [#a,#b]\n\
+             -------- Scan: t2 [#d,#e]\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cross_join_to_equi_join() -> Result<()> {
+        let plan = optimized("SELECT b, f FROM t1, t2 WHERE a = d AND c > 5;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#b,#f]\n\
+             ---- Join: (#a = #d)\n\
+             ------ Filter: #c > 5\n\
+             -------- Scan: t1 [#a,#b,#c]\n\
+             ------ Scan: t2 [#d,#f]\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_projection_pruning() -> Result<()> {
+        // a row count still needs a column to count rows of
+        let plan = optimized("SELECT count(*) FROM (SELECT a, b FROM t1) s;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#count(*)]\n\
+             ---- Aggregate: group_by=[], aggregates=[count(*)]\n\
+             ------ Projection: [#a]\n\
+             -------- Scan: t1 [#a]\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_selection() -> Result<()> {
+        // conjuncts the index answers exactly are dropped
+        let plan = optimized("SELECT h FROM t3 WHERE g = 5 AND h > 1.5;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#h]\n\
+             ---- Filter: #h > 1.5\n\
+             ------ IndexScan: t3 Scan( g range [5,5] ) [#h]\n"
+        );
+
+        let plan = optimized("SELECT h FROM t3 WHERE 2 < g AND g BETWEEN 0 AND 8;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#h]\n\
+             ---- IndexScan: t3 Scan( g range (2,8] ) [#h]\n"
+        );
+
+        // negative ints come after the positive ones in the index
+        let plan = optimized("SELECT h FROM t3 WHERE g >= 3;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#h]\n\
+             ---- Filter: #g >= 3\n\
+             ------ IndexScan: t3 Scan( g range [3,) ) [#g,#h]\n"
+        );
+
+        let plan = optimized("SELECT h FROM t3 WHERE g < 3;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#h]\n\
+             ---- Filter: #g < 3\n\
+             ------ Scan: t3 [#g,#h]\n"
+        );
+
+        // IN lists are looked up key by key, within the range
+        let plan = optimized("SELECT h FROM t3 WHERE g IN (7, 3, 1, 3) AND g > 2;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#h]\n\
+             ---- IndexScan: t3 Scan( g keys [3,7] ) [#h]\n"
+        );
+
+        // a lone IN list is a filter compared with true
+        let plan = optimized("SELECT h FROM t3 WHERE g IN (3, 1);")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#h]\n\
+             ---- IndexScan: t3 Scan( g keys [1,3] ) [#h]\n"
+        );
+
+        let plan = optimized("SELECT h FROM t3 WHERE g IN (1, -1);")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#h]\n\
+             ---- Filter: (#g IN (1, -1)) = true\n\
+             ------ Scan: t3 [#g,#h]\n"
+        );
+
+        Ok(())
+    }
+}
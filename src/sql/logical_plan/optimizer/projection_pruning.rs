@@ -0,0 +1,163 @@
+use std::collections::HashSet;
+use std::mem::take;
+
+use super::{join_columns, OptimizerRule};
+use crate::sql::logical_plan::collect_columns;
+use crate::sql::logical_plan::expr::LogicalExpr;
+use crate::sql::logical_plan::plan::{LogicalPlan, Projection, Scan};
+use crate::tuple::schema::Schema;
+
+/// Drops the columns no plan above reads, from projections of subqueries and
+/// down to the scans. Every plan keeps at least one column, it still has to
+/// carry its row count. Updates, deletes and merges rewrite whole rows, so are left
+/// alone
+pub struct ProjectionPruning;
+
+impl OptimizerRule for ProjectionPruning {
+    fn name(&self) -> &str {
+        "Projection Pruning"
+    }
+
+    fn optimize(&self, plan: LogicalPlan) -> LogicalPlan {
+        match plan {
+            LogicalPlan::Update(_)
+            | LogicalPlan::Delete(_)
+            | LogicalPlan::Merge(_)
+            | LogicalPlan::Insert(_)
+            | LogicalPlan::CreateTable(_) => plan,
+            plan => prune_all(plan),
+        }
+    }
+}
+
+fn names(schema: &Schema) -> HashSet<String> {
+    schema.fields.iter().map(|f| f.name.clone()).collect()
+}
+
+fn columns<'a>(exprs: impl IntoIterator<Item = &'a LogicalExpr>) -> HashSet<String> {
+    exprs.into_iter().flat_map(collect_columns).collect()
+}
+
+/// Prunes the inputs of a plan whose own columns are all read
+fn prune_all(plan: LogicalPlan) -> LogicalPlan {
+    let required = names(&plan.schema());
+    prune(plan, &required)
+}
+
+fn prune(plan: LogicalPlan, required: &HashSet<String>) -> LogicalPlan {
+    match plan {
+        LogicalPlan::Projection(projection) => {
+            let Projection { input, projections } = *projection;
+            let schema = input.schema();
+
+            let mut kept: Vec<_> = projections
+                .iter()
+                .filter(|p| required.contains(&p.to_field(&schema).name))
+                .cloned()
+                .collect();
+            if kept.is_empty() {
+                kept.extend(projections.into_iter().take(1));
+            }
+
+            let input = prune(input, &columns(&kept));
+            LogicalPlan::Projection(Box::new(Projection::new(input, kept)))
+        }
+        LogicalPlan::Filter(mut filter) => {
+            let mut required = required.clone();
+            required.extend(columns([&filter.expr.left, &filter.expr.right]));
+            filter.input = prune(take(&mut filter.input), &required);
+            LogicalPlan::Filter(filter)
+        }
+        LogicalPlan::Sort(mut sort) => {
+            let mut required = required.clone();
+            required.extend(columns(sort.exprs.iter().map(|e| &e.expr)));
+            sort.input = prune(take(&mut sort.input), &required);
+            LogicalPlan::Sort(sort)
+        }
+        LogicalPlan::Window(mut window) => {
+            let mut required = required.clone();
+            required.extend(columns(window.windows.iter().flat_map(|w| w.exprs())));
+            window.input = prune(take(&mut window.input), &required);
+            LogicalPlan::Window(window)
+        }
+        LogicalPlan::Limit(mut limit) => {
+            limit.input = prune(take(&mut limit.input), required);
+            LogicalPlan::Limit(limit)
+        }
+        LogicalPlan::Aggregate(mut aggregate) => {
+            let mut required = columns(&aggregate.group_by);
+            required.extend(columns(
+                aggregate.aggregates.iter().filter_map(|a| a.arg.as_ref()),
+            ));
+            aggregate.input = prune(take(&mut aggregate.input), &required);
+            LogicalPlan::Aggregate(aggregate)
+        }
+        LogicalPlan::Join(mut join) => {
+            let (left_columns, right_columns) = join_columns(&join);
+            let on = LogicalExpr::BinaryExpr(Box::new(join.on.clone()));
+            let mut needed = columns([&on]);
+            needed.extend(required.iter().cloned());
+
+            let side_required = |side: &[(String, String)]| {
+                let mut required: HashSet<_> = side
+                    .iter()
+                    .filter(|(outer, _)| needed.contains(outer))
+                    .map(|(_, inner)| inner.clone())
+                    .collect();
+                if required.is_empty() {
+                    required.extend(side.iter().take(1).map(|(_, inner)| inner.clone()));
+                }
+                required
+            };
+
+            join.left = prune(take(&mut join.left), &side_required(&left_columns));
+            join.right = prune(take(&mut join.right), &side_required(&right_columns));
+
+            // the join's columns whose inputs still produce them
+            let left_kept = names(&join.left.schema());
+            let right_kept = names(&join.right.schema());
+            let right_in_output = !join.join_type.drops_right();
+
+            let fields = join
+                .schema
+                .fields
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| match i.checked_sub(left_columns.len()) {
+                    None => left_kept.contains(&left_columns[*i].1),
+                    Some(i) if right_in_output => right_kept.contains(&right_columns[i].1),
+                    // the mark column
+                    Some(_) => true,
+                })
+                .map(|(_, field)| field.clone())
+                .collect();
+
+            join.schema = Schema::new(fields);
+            LogicalPlan::Join(join)
+        }
+        LogicalPlan::Scan(scan) => {
+            let schema = prune_fields(scan.schema, required);
+            LogicalPlan::Scan(Scan::new(scan.table_name, schema))
+        }
+        LogicalPlan::IndexScan(mut scan) => {
+            scan.schema = prune_fields(take(&mut scan.schema), required);
+            LogicalPlan::IndexScan(scan)
+        }
+        // the rest read every column of their inputs, by position
+        plan => plan.map_inputs(prune_all),
+    }
+}
+
+fn prune_fields(schema: Schema, required: &HashSet<String>) -> Schema {
+    let mut fields: Vec<_> = schema
+        .fields
+        .iter()
+        .filter(|f| required.contains(&f.name))
+        .cloned()
+        .collect();
+    if fields.is_empty() {
+        fields.extend(schema.fields.into_iter().take(1));
+    }
+
+    Schema::new(fields)
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::mem::take;
+
+use super::{
+    add_filter, join_columns, predicate_to_expr, split_conjunction, transform_down, OptimizerRule,
+};
+use crate::sql::logical_plan::expr::LogicalExpr;
+use crate::sql::logical_plan::plan::{Filter, JoinType, LogicalPlan};
+use crate::sql::logical_plan::{collect_columns, map_columns};
+
+/// Moves the conjuncts of a filter over a join that only read one side of
+/// the join below it, so fewer rows reach the join. A side padded with NULLs
+/// by an outer join keeps its filters above the join
+pub struct PushDownPredicates;
+
+impl OptimizerRule for PushDownPredicates {
+    fn name(&self) -> &str {
+        "Push Down Predicates"
+    }
+
+    fn optimize(&self, plan: LogicalPlan) -> LogicalPlan {
+        transform_down(plan, &|plan| match plan {
+            LogicalPlan::Filter(filter) if matches!(filter.input, LogicalPlan::Join(_)) => {
+                let Filter { input, expr } = *filter;
+                let LogicalPlan::Join(mut join) = input else {
+                    unreachable!()
+                };
+
+                let (left_columns, right_columns) = join_columns(&join);
+                let left_columns: HashMap<_, _> = left_columns.into_iter().collect();
+                let right_columns: HashMap<_, _> = right_columns.into_iter().collect();
+
+                let to_left = !join.join_type.keeps_right();
+                let to_right = matches!(join.join_type, JoinType::Inner | JoinType::Right);
+
+                let mut left = vec![];
+                let mut right = vec![];
+                let mut kept = vec![];
+
+                for conjunct in split_conjunction(predicate_to_expr(expr)) {
+                    let columns = collect_columns(&conjunct);
+                    let reads = |side: &HashMap<String, String>| {
+                        !columns.is_empty() && columns.iter().all(|c| side.contains_key(c))
+                    };
+
+                    if to_left && reads(&left_columns) {
+                        left.push(rename(conjunct, &left_columns));
+                    } else if to_right && reads(&right_columns) {
+                        right.push(rename(conjunct, &right_columns));
+                    } else {
+                        kept.push(conjunct);
+                    }
+                }
+
+                join.left = add_filter(take(&mut join.left), left);
+                join.right = add_filter(take(&mut join.right), right);
+
+                add_filter(LogicalPlan::Join(join), kept)
+            }
+            plan => plan,
+        })
+    }
+}
+
+/// Renames the columns to the names the join's input knows them by
+fn rename(expr: LogicalExpr, columns: &HashMap<String, String>) -> LogicalExpr {
+    map_columns(expr, &mut |name| columns[&name].clone())
+}
@@ -1,13 +1,18 @@
 use std::collections::HashMap;
 
+use crate::catalog::AlterOperation;
 use crate::errors::Error;
 use crate::pages::indexes::b_plus_tree::Key;
-use crate::tuple::schema::Schema;
+use crate::tuple::constraints::Constraints;
+use crate::tuple::schema::{Field, Schema};
+use crate::types::Types;
 
-use super::expr::{BinaryExpr, BooleanBinaryExpr, LogicalExpr};
+use super::expr::{
+    AggregateExpr, BinaryExpr, BooleanBinaryExpr, LogicalExpr, SortExpr, WindowExpr,
+};
 use anyhow::Result;
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub enum LogicalPlan {
     Projection(Box<Projection>),
     Scan(Scan),
@@ -19,11 +24,21 @@ pub enum LogicalPlan {
     Values(Values),
     DropTables(DropTables),
     Truncate(Truncate),
+    AlterTable(AlterTable),
     Update(Box<Update>),
     Delete(Box<Delete>),
+    Merge(Box<Merge>),
     Union(Box<Union>),
     Limit(Box<Limit>),
     IndexScan(IndexScan),
+    Aggregate(Box<Aggregate>),
+    Sort(Box<Sort>),
+    Window(Box<Window>),
+    Distinct(Box<Distinct>),
+    Intersect(Box<Intersect>),
+    Except(Box<Except>),
+    RecursiveQuery(Box<RecursiveQuery>),
+    WorkTableScan(WorkTableScan),
     StartTxn,
     CommitTxn,
     RollbackTxn,
@@ -47,8 +62,10 @@ impl LogicalPlan {
             LogicalPlan::Values(v) => v.print(indent),
             LogicalPlan::DropTables(d) => d.print(indent),
             LogicalPlan::Truncate(t) => t.print(indent),
+            LogicalPlan::AlterTable(a) => a.print(indent),
             LogicalPlan::Update(u) => u.print(indent),
             LogicalPlan::Delete(d) => d.print(indent),
+            LogicalPlan::Merge(m) => m.print(indent),
             LogicalPlan::Join(j) => j.print(indent),
             LogicalPlan::IndexScan(i) => i.print(indent),
             LogicalPlan::StartTxn => format!("{} StartTransaction", "-".repeat(indent * 2)),
@@ -57,6 +74,205 @@ impl LogicalPlan {
             LogicalPlan::Empty => format!("{} Empty", "-".repeat(indent * 2)),
             LogicalPlan::Union(u) => u.print(indent),
             LogicalPlan::Limit(l) => l.print(indent),
+            LogicalPlan::Aggregate(a) => a.print(indent),
+            LogicalPlan::Sort(s) => s.print(indent),
+            LogicalPlan::Window(w) => w.print(indent),
+            LogicalPlan::Distinct(d) => d.print(indent),
+            LogicalPlan::Intersect(i) => i.print(indent),
+            LogicalPlan::Except(e) => e.print(indent),
+            LogicalPlan::RecursiveQuery(r) => r.print(indent),
+            LogicalPlan::WorkTableScan(w) => w.print(indent),
+        }
+    }
+
+    /// Rebuilds the plan with `f` applied to each of its inputs
+    pub fn map_inputs(self, mut f: impl FnMut(LogicalPlan) -> LogicalPlan) -> LogicalPlan {
+        use std::mem::take;
+
+        match self {
+            LogicalPlan::Projection(mut p) => {
+                p.input = f(take(&mut p.input));
+                LogicalPlan::Projection(p)
+            }
+            LogicalPlan::Filter(mut p) => {
+                p.input = f(take(&mut p.input));
+                LogicalPlan::Filter(p)
+            }
+            LogicalPlan::Explain(mut p) => {
+                p.input = f(take(&mut p.input));
+                LogicalPlan::Explain(p)
+            }
+            LogicalPlan::Insert(mut p) => {
+                p.input = f(take(&mut p.input));
+                LogicalPlan::Insert(p)
+            }
+            LogicalPlan::CreateTable(mut p) => {
+                p.input = f(take(&mut p.input));
+                LogicalPlan::CreateTable(p)
+            }
+            LogicalPlan::Update(mut p) => {
+                p.input = f(take(&mut p.input));
+                LogicalPlan::Update(p)
+            }
+            LogicalPlan::Delete(mut p) => {
+                p.input = f(take(&mut p.input));
+                LogicalPlan::Delete(p)
+            }
+            LogicalPlan::Merge(mut p) => {
+                p.input = f(take(&mut p.input));
+                LogicalPlan::Merge(p)
+            }
+            LogicalPlan::Limit(mut p) => {
+                p.input = f(take(&mut p.input));
+                LogicalPlan::Limit(p)
+            }
+            LogicalPlan::Aggregate(mut p) => {
+                p.input = f(take(&mut p.input));
+                LogicalPlan::Aggregate(p)
+            }
+            LogicalPlan::Sort(mut p) => {
+                p.input = f(take(&mut p.input));
+                LogicalPlan::Sort(p)
+            }
+            LogicalPlan::Window(mut p) => {
+                p.input = f(take(&mut p.input));
+                LogicalPlan::Window(p)
+            }
+            LogicalPlan::Distinct(mut p) => {
+                p.input = f(take(&mut p.input));
+                LogicalPlan::Distinct(p)
+            }
+            LogicalPlan::Join(mut p) => {
+                p.left = f(take(&mut p.left));
+                p.right = f(take(&mut p.right));
+                LogicalPlan::Join(p)
+            }
+            LogicalPlan::Union(mut p) => {
+                p.left = f(take(&mut p.left));
+                p.right = f(take(&mut p.right));
+                LogicalPlan::Union(p)
+            }
+            LogicalPlan::Intersect(mut p) => {
+                p.left = f(take(&mut p.left));
+                p.right = f(take(&mut p.right));
+                LogicalPlan::Intersect(p)
+            }
+            LogicalPlan::Except(mut p) => {
+                p.left = f(take(&mut p.left));
+                p.right = f(take(&mut p.right));
+                LogicalPlan::Except(p)
+            }
+            LogicalPlan::RecursiveQuery(mut p) => {
+                p.static_term = f(take(&mut p.static_term));
+                p.recursive_term = f(take(&mut p.recursive_term));
+                LogicalPlan::RecursiveQuery(p)
+            }
+            LogicalPlan::Scan(_)
+            | LogicalPlan::IndexScan(_)
+            | LogicalPlan::Values(_)
+            | LogicalPlan::WorkTableScan(_)
+            | LogicalPlan::DropTables(_)
+            | LogicalPlan::Truncate(_)
+            | LogicalPlan::AlterTable(_)
+            | LogicalPlan::StartTxn
+            | LogicalPlan::CommitTxn
+            | LogicalPlan::RollbackTxn
+            | LogicalPlan::Empty => self,
+        }
+    }
+
+    /// Rebuilds the node with `f` applied to each of its own expressions,
+    /// its inputs are left as they are
+    pub fn map_exprs(self, mut f: impl FnMut(LogicalExpr) -> LogicalExpr) -> LogicalPlan {
+        use std::mem::take;
+
+        let f = &mut f;
+        match self {
+            LogicalPlan::Projection(mut p) => {
+                p.projections = map_all(take(&mut p.projections), f);
+                LogicalPlan::Projection(p)
+            }
+            LogicalPlan::Filter(mut p) => {
+                p.expr.left = f(take(&mut p.expr.left));
+                p.expr.right = f(take(&mut p.expr.right));
+                LogicalPlan::Filter(p)
+            }
+            LogicalPlan::Join(mut p) => {
+                p.on.left = f(take(&mut p.on.left));
+                p.on.right = f(take(&mut p.on.right));
+                LogicalPlan::Join(p)
+            }
+            LogicalPlan::Values(mut p) => {
+                p.rows = take(&mut p.rows)
+                    .into_iter()
+                    .map(|row| map_all(row, f))
+                    .collect();
+                LogicalPlan::Values(p)
+            }
+            LogicalPlan::Insert(mut p) => {
+                if let Some(OnConflict {
+                    action:
+                        ConflictAction::Update {
+                            assignments,
+                            selection,
+                        },
+                    ..
+                }) = &mut p.on_conflict
+                {
+                    *assignments = map_assignments(take(assignments), f);
+                    *selection = f(take(selection));
+                }
+                p.returning = map_all(take(&mut p.returning), f);
+                LogicalPlan::Insert(p)
+            }
+            LogicalPlan::Update(mut p) => {
+                p.assignments = map_assignments(take(&mut p.assignments), f);
+                p.selection = f(take(&mut p.selection));
+                p.returning = map_all(take(&mut p.returning), f);
+                LogicalPlan::Update(p)
+            }
+            LogicalPlan::Delete(mut p) => {
+                p.selection = f(take(&mut p.selection));
+                p.returning = map_all(take(&mut p.returning), f);
+                LogicalPlan::Delete(p)
+            }
+            LogicalPlan::Merge(mut p) => {
+                for clause in &mut p.clauses {
+                    clause.predicate = f(take(&mut clause.predicate));
+                    match &mut clause.action {
+                        MergeAction::Update(assignments) => {
+                            *assignments = map_assignments(take(assignments), f)
+                        }
+                        MergeAction::Insert(values) => *values = map_all(take(values), f),
+                        MergeAction::Delete => {}
+                    }
+                }
+                LogicalPlan::Merge(p)
+            }
+            LogicalPlan::Aggregate(mut p) => {
+                p.group_by = map_all(take(&mut p.group_by), f);
+                for aggregate in &mut p.aggregates {
+                    aggregate.arg = aggregate.arg.take().map(&mut *f);
+                }
+                LogicalPlan::Aggregate(p)
+            }
+            LogicalPlan::Sort(mut p) => {
+                for e in &mut p.exprs {
+                    e.expr = f(take(&mut e.expr));
+                }
+                LogicalPlan::Sort(p)
+            }
+            LogicalPlan::Window(mut p) => {
+                for window in &mut p.windows {
+                    window.args = map_all(take(&mut window.args), f);
+                    window.partition_by = map_all(take(&mut window.partition_by), f);
+                    for e in &mut window.order_by {
+                        e.expr = f(take(&mut e.expr));
+                    }
+                }
+                LogicalPlan::Window(p)
+            }
+            plan => plan,
         }
     }
 
@@ -71,8 +287,10 @@ impl LogicalPlan {
             LogicalPlan::Values(v) => v.schema(),
             LogicalPlan::DropTables(d) => d.schema(),
             LogicalPlan::Truncate(t) => t.schema(),
+            LogicalPlan::AlterTable(a) => a.schema(),
             LogicalPlan::Update(u) => u.schema(),
             LogicalPlan::Delete(d) => d.schema(),
+            LogicalPlan::Merge(m) => m.schema(),
             LogicalPlan::Join(j) => j.schema(),
             LogicalPlan::IndexScan(i) => i.schema(),
             LogicalPlan::Empty => Schema::default(),
@@ -81,18 +299,70 @@ impl LogicalPlan {
             LogicalPlan::RollbackTxn => Schema::default(),
             LogicalPlan::Union(u) => u.schema(),
             LogicalPlan::Limit(l) => l.schema(),
+            LogicalPlan::Aggregate(a) => a.schema(),
+            LogicalPlan::Sort(s) => s.schema(),
+            LogicalPlan::Window(w) => w.schema(),
+            LogicalPlan::Distinct(d) => d.schema(),
+            LogicalPlan::Intersect(i) => i.schema(),
+            LogicalPlan::Except(e) => e.schema(),
+            LogicalPlan::RecursiveQuery(r) => r.schema(),
+            LogicalPlan::WorkTableScan(w) => w.schema(),
         }
     }
 }
 
+fn map_all(
+    exprs: Vec<LogicalExpr>,
+    f: &mut impl FnMut(LogicalExpr) -> LogicalExpr,
+) -> Vec<LogicalExpr> {
+    exprs.into_iter().map(f).collect()
+}
+
+fn map_assignments(
+    assignments: Vec<(String, LogicalExpr)>,
+    f: &mut impl FnMut(LogicalExpr) -> LogicalExpr,
+) -> Vec<(String, LogicalExpr)> {
+    assignments
+        .into_iter()
+        .map(|(column, value)| (column, f(value)))
+        .collect()
+}
+
+/// The number of rows of a LIMIT or OFFSET
+#[derive(Clone, Copy, PartialEq)]
+pub enum RowCount {
+    Rows(u32),
+    /// `$n` of a prepared statement, replaced by its value before the plan is executed
+    Parameter(usize),
+}
+
+impl RowCount {
+    pub fn rows(self) -> u32 {
+        match self {
+            RowCount::Rows(rows) => rows,
+            RowCount::Parameter(n) => unreachable!("parameter ${n} is bound before execution"),
+        }
+    }
+}
+
+impl std::fmt::Display for RowCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RowCount::Rows(rows) => write!(f, "{rows}"),
+            RowCount::Parameter(n) => write!(f, "${n}"),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Limit {
     pub input: LogicalPlan,
-    pub limit: u32,
-    pub offset: u32,
+    pub limit: RowCount,
+    pub offset: RowCount,
 }
 
 impl Limit {
-    pub fn new(input: LogicalPlan, limit: u32, offset: u32) -> Self {
+    pub fn new(input: LogicalPlan, limit: RowCount, offset: RowCount) -> Self {
         Self {
             input,
             limit,
@@ -105,7 +375,7 @@ impl Limit {
     }
 
     pub fn print(&self, indent: usize) -> String {
-        if self.offset == 0 {
+        if self.offset == RowCount::Rows(0) {
             format!(
                 "{} Limit: {}\n{}",
                 "-".repeat(indent * 2),
@@ -124,6 +394,130 @@ impl Limit {
     }
 }
 
+#[derive(Clone)]
+pub struct Aggregate {
+    pub input: LogicalPlan,
+    pub group_by: Vec<LogicalExpr>,
+    pub aggregates: Vec<AggregateExpr>,
+}
+
+impl Aggregate {
+    pub fn new(
+        input: LogicalPlan,
+        group_by: Vec<LogicalExpr>,
+        aggregates: Vec<AggregateExpr>,
+    ) -> Self {
+        Self {
+            input,
+            group_by,
+            aggregates,
+        }
+    }
+
+    /// group by columns first, then one column per aggregate
+    pub fn schema(&self) -> Schema {
+        let input_schema = self.input.schema();
+
+        let fields = self
+            .group_by
+            .iter()
+            .map(|e| e.to_field(&input_schema))
+            .chain(self.aggregates.iter().map(|a| a.to_field(&input_schema)))
+            .collect();
+
+        Schema::new(fields)
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Aggregate: group_by=[{}], aggregates=[{}]\n{}",
+            "-".repeat(indent * 2),
+            self.group_by
+                .iter()
+                .map(|e| e.print())
+                .collect::<Vec<_>>()
+                .join(","),
+            self.aggregates
+                .iter()
+                .map(|a| a.print())
+                .collect::<Vec<_>>()
+                .join(","),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct Sort {
+    pub input: LogicalPlan,
+    pub exprs: Vec<SortExpr>,
+}
+
+impl Sort {
+    pub fn new(input: LogicalPlan, exprs: Vec<SortExpr>) -> Self {
+        Self { input, exprs }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.input.schema()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Sort: {}\n{}",
+            "-".repeat(indent * 2),
+            self.exprs
+                .iter()
+                .map(|e| e.print())
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+/// Window functions sharing a PARTITION BY and ORDER BY, the input is
+/// sorted by the partition keys and then the order keys
+#[derive(Clone)]
+pub struct Window {
+    pub input: LogicalPlan,
+    pub windows: Vec<WindowExpr>,
+}
+
+impl Window {
+    pub fn new(input: LogicalPlan, windows: Vec<WindowExpr>) -> Self {
+        Self { input, windows }
+    }
+
+    /// the input's columns, then one column per window function
+    pub fn schema(&self) -> Schema {
+        let input_schema = self.input.schema();
+
+        let fields = input_schema
+            .fields
+            .iter()
+            .cloned()
+            .chain(self.windows.iter().map(|w| w.to_field(&input_schema)))
+            .collect();
+
+        Schema::new(fields)
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Window: [{}]\n{}",
+            "-".repeat(indent * 2),
+            self.windows
+                .iter()
+                .map(|w| w.print())
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+#[derive(Clone)]
 pub struct Union {
     pub left: LogicalPlan,
     pub right: LogicalPlan,
@@ -148,38 +542,199 @@ impl Union {
     }
 }
 
+#[derive(Clone)]
+pub struct Distinct {
+    pub input: LogicalPlan,
+}
+
+impl Distinct {
+    pub fn new(input: LogicalPlan) -> Self {
+        Self { input }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.input.schema()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Distinct:\n{}",
+            "-".repeat(indent * 2),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct Intersect {
+    pub left: LogicalPlan,
+    pub right: LogicalPlan,
+    pub all: bool,
+}
+
+impl Intersect {
+    pub fn new(left: LogicalPlan, right: LogicalPlan, all: bool) -> Self {
+        Self { left, right, all }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.left.schema()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Intersect{}:\n{}{}",
+            "-".repeat(indent * 2),
+            if self.all { " All" } else { "" },
+            self.left.print_indent(indent + 1),
+            self.right.print_indent(indent + 1)
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct Except {
+    pub left: LogicalPlan,
+    pub right: LogicalPlan,
+    pub all: bool,
+}
+
+impl Except {
+    pub fn new(left: LogicalPlan, right: LogicalPlan, all: bool) -> Self {
+        Self { left, right, all }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.left.schema()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Except{}:\n{}{}",
+            "-".repeat(indent * 2),
+            if self.all { " All" } else { "" },
+            self.left.print_indent(indent + 1),
+            self.right.print_indent(indent + 1)
+        )
+    }
+}
+
+/// A recursive CTE. The recursive term is evaluated over the rows the
+/// previous iteration added, read through a work table, until it adds none
+#[derive(Clone)]
+pub struct RecursiveQuery {
+    pub name: String,
+    pub static_term: LogicalPlan,
+    pub recursive_term: LogicalPlan,
+    /// UNION instead of UNION ALL, rows already produced are dropped
+    pub distinct: bool,
+}
+
+impl RecursiveQuery {
+    pub fn new(
+        name: String,
+        static_term: LogicalPlan,
+        recursive_term: LogicalPlan,
+        distinct: bool,
+    ) -> Self {
+        Self {
+            name,
+            static_term,
+            recursive_term,
+            distinct,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.static_term.schema()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Recursive Query{}: {}\n{}{}",
+            "-".repeat(indent * 2),
+            if self.distinct { "" } else { " All" },
+            self.name,
+            self.static_term.print_indent(indent + 1),
+            self.recursive_term.print_indent(indent + 1)
+        )
+    }
+}
+
+/// Rows of the previous iteration of a recursive CTE
+#[derive(Clone)]
+pub struct WorkTableScan {
+    pub name: String,
+    pub schema: Schema,
+}
+
+impl WorkTableScan {
+    pub fn new(name: String, schema: Schema) -> Self {
+        Self { name, schema }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Work Table Scan: {} [{}]\n",
+            "-".repeat(indent * 2),
+            self.name,
+            self.schema
+                .fields
+                .iter()
+                .map(|f| format!("#{}", f.name))
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    }
+}
+
+#[derive(Clone)]
 pub struct Delete {
     // only reason this is here is because we want Scan
     // to be the only way to access tuples, mainly for str indirection
     pub input: LogicalPlan,
     pub table_name: String,
     pub selection: LogicalExpr,
+    /// evaluated over the old contents of the deleted rows
+    pub returning: Vec<LogicalExpr>,
 }
 
 impl Delete {
-    pub fn new(input: LogicalPlan, table_name: String, filter: LogicalExpr) -> Self {
+    pub fn new(
+        input: LogicalPlan,
+        table_name: String,
+        filter: LogicalExpr,
+        returning: Vec<LogicalExpr>,
+    ) -> Self {
         Self {
             input,
             table_name,
             selection: filter,
+            returning,
         }
     }
 
     pub fn schema(&self) -> Schema {
-        Schema::new(vec![])
+        returning_schema(&self.returning, &self.input.schema())
     }
 
     pub fn print(&self, indent: usize) -> String {
         format!(
-            "{} Delete: {} [{}]\n{}",
+            "{} Delete: {} [{}]{}\n{}",
             "-".repeat(indent * 2),
             self.table_name,
             self.selection.print(),
+            print_returning(&self.returning),
             self.input.print_indent(indent + 1)
         )
     }
 }
 
+#[derive(Clone)]
 pub struct IndexScan {
     pub table_name: String,
     pub schema: Schema,
@@ -189,6 +744,8 @@ pub struct IndexScan {
     pub include_from: bool,
     pub to: Option<Key>,
     pub include_to: bool,
+    /// looked up one by one instead of scanning the range, for IN lists
+    pub keys: Option<Vec<Key>>,
 }
 
 impl IndexScan {
@@ -209,18 +766,59 @@ impl IndexScan {
             include_from,
             to,
             include_to,
+            keys: None,
         }
     }
 
-    fn name(&self) -> String {
+    /// Point lookups of the keys, in the order given
+    pub fn lookups(
+        table_name: String,
+        schema: Schema,
+        column_name: String,
+        keys: Vec<Key>,
+    ) -> Self {
+        Self {
+            keys: Some(keys),
+            ..Self::new(table_name, schema, column_name, None, false, None, false)
+        }
+    }
+
+    pub fn name(&self) -> String {
         "IndexScan".to_string()
     }
 
-    fn schema(&self) -> Schema {
+    pub fn schema(&self) -> Schema {
         self.schema.clone()
     }
 
-    fn print(&self, indent: usize) -> String {
+    pub fn print(&self, indent: usize) -> String {
+        let range = match &self.keys {
+            Some(keys) => format!(
+                "keys [{}]",
+                keys.iter()
+                    .map(|k| k.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            None => self.range(),
+        };
+        format!(
+            "{} {}: {} Scan( {} {} ) [{}]\n",
+            "-".repeat(indent * 2),
+            self.name(),
+            self.table_name,
+            self.column_name,
+            range,
+            self.schema
+                .fields
+                .iter()
+                .map(|f| format!("#{}", f.name))
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    }
+
+    fn range(&self) -> String {
         let range = format!(
             "{}{},{}{}",
             if self.include_from { "[" } else { "(" },
@@ -236,36 +834,88 @@ impl IndexScan {
             },
             if self.include_to { "]" } else { ")" },
         );
-        format!(
-            "{} {}: {} Scan( {} range {} ) [{}]\n",
-            "-".repeat(indent * 2),
-            self.name(),
-            self.table_name,
-            self.column_name,
-            range,
-            self.schema
-                .fields
-                .iter()
-                .map(|f| format!("#{}", f.name))
-                .collect::<Vec<_>>()
-                .join(",")
+        format!("range {range}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinType {
+    Inner,
+    /// keeps unmatched rows of the left side, padded with NULLs
+    Left,
+    /// keeps unmatched rows of the right side, padded with NULLs
+    Right,
+    /// keeps unmatched rows of both sides
+    Full,
+    /// keeps the left rows that have a match, for EXISTS and IN subqueries
+    Semi,
+    /// keeps the left rows without a match, for NOT EXISTS and NOT IN subqueries
+    Anti,
+    /// a left join that fails when a left row has more than one match,
+    /// for subqueries used as a value
+    Single,
+    /// keeps every left row and adds a column telling whether it has a
+    /// match, for EXISTS subqueries nested in an expression
+    Mark,
+    /// an anti join that also drops the left rows whose condition is unknown
+    /// for some right row, for NOT IN subqueries
+    NullAwareAnti,
+    /// a mark join whose mark is NULL when a left row has no match but its
+    /// condition is unknown for some right row, for IN subqueries nested in
+    /// an expression
+    NullAwareMark,
+}
+
+impl JoinType {
+    pub fn keeps_left(&self) -> bool {
+        matches!(self, JoinType::Left | JoinType::Full | JoinType::Single)
+    }
+
+    pub fn keeps_right(&self) -> bool {
+        matches!(self, JoinType::Right | JoinType::Full)
+    }
+
+    /// Whether the right side's columns are left out of the output
+    pub fn drops_right(&self) -> bool {
+        matches!(
+            self,
+            JoinType::Semi
+                | JoinType::Anti
+                | JoinType::Mark
+                | JoinType::NullAwareAnti
+                | JoinType::NullAwareMark
         )
     }
+
+    /// Whether a left row whose condition is unknown for a right row is
+    /// told apart from one without a match, as IN and NOT IN require
+    pub fn is_null_aware(&self) -> bool {
+        matches!(self, JoinType::NullAwareAnti | JoinType::NullAwareMark)
+    }
 }
 
+#[derive(Clone)]
 pub struct Join {
     pub left: LogicalPlan,
     pub right: LogicalPlan,
     pub on: BinaryExpr,
+    pub join_type: JoinType,
     pub schema: Schema,
 }
 
 impl Join {
-    pub fn new(left: LogicalPlan, right: LogicalPlan, on: BinaryExpr, schema: Schema) -> Self {
+    pub fn new(
+        left: LogicalPlan,
+        right: LogicalPlan,
+        on: BinaryExpr,
+        join_type: JoinType,
+        schema: Schema,
+    ) -> Self {
         Self {
             left,
             right,
             on,
+            join_type,
             schema,
         }
     }
@@ -275,9 +925,23 @@ impl Join {
     }
 
     pub fn print(&self, indent: usize) -> String {
+        let name = match self.join_type {
+            JoinType::Inner => "Join",
+            JoinType::Left => "Left Join",
+            JoinType::Right => "Right Join",
+            JoinType::Full => "Full Join",
+            JoinType::Semi => "Semi Join",
+            JoinType::Anti => "Anti Join",
+            JoinType::Single => "Single Join",
+            JoinType::Mark => "Mark Join",
+            JoinType::NullAwareAnti => "Null Aware Anti Join",
+            JoinType::NullAwareMark => "Null Aware Mark Join",
+        };
+
         format!(
-            "{} Join: {}\n{}{}",
+            "{} {}: {}\n{}{}",
             "-".repeat(indent * 2),
+            name,
             self.on.print(),
             self.left.print_indent(indent + 1),
             self.right.print_indent(indent + 1),
@@ -285,6 +949,7 @@ impl Join {
     }
 }
 
+#[derive(Clone)]
 pub struct Update {
     // only reason this is here is because we want Scan
     // to be the only way to access tuples, mainly for str indirection
@@ -292,6 +957,8 @@ pub struct Update {
     pub table_name: String,
     pub assignments: Vec<(String, LogicalExpr)>,
     pub selection: LogicalExpr,
+    /// evaluated over the new contents of the updated rows
+    pub returning: Vec<LogicalExpr>,
 }
 
 impl Update {
@@ -300,22 +967,24 @@ impl Update {
         table_name: String,
         assignments: Vec<(String, LogicalExpr)>,
         filter: LogicalExpr,
+        returning: Vec<LogicalExpr>,
     ) -> Self {
         Self {
             input,
             table_name,
             assignments,
             selection: filter,
+            returning,
         }
     }
 
     pub fn schema(&self) -> Schema {
-        Schema::new(vec![])
+        returning_schema(&self.returning, &self.input.schema())
     }
 
     pub fn print(&self, indent: usize) -> String {
         format!(
-            "{} Update: {} [{}]\n{}",
+            "{} Update: {} [{}]{}\n{}",
             "-".repeat(indent * 2),
             self.table_name,
             self.assignments
@@ -323,11 +992,114 @@ impl Update {
                 .map(|(col, value)| format!("#{} = {}", col, value.print()))
                 .collect::<Vec<_>>()
                 .join(", "),
+            print_returning(&self.returning),
             self.input.print_indent(indent + 1)
         )
     }
 }
 
+/// MERGE of a source relation into a table. Each target row the ON condition
+/// matches takes the first WHEN MATCHED clause whose condition holds, and
+/// each source row that matches no target row the first WHEN NOT MATCHED one
+#[derive(Clone)]
+pub struct Merge {
+    /// the target's scan, with its tuple ids, right joined to the source on
+    /// the ON condition. Source rows that match nothing have NULL tuple ids
+    pub input: LogicalPlan,
+    pub table_name: String,
+    /// what the clauses read, the target's columns and then the source's
+    pub input_schema: Schema,
+    pub clauses: Vec<MergeClause>,
+}
+
+#[derive(Clone)]
+pub struct MergeClause {
+    pub matched: bool,
+    pub predicate: LogicalExpr,
+    pub action: MergeAction,
+}
+
+#[derive(Clone)]
+pub enum MergeAction {
+    Update(Vec<(String, LogicalExpr)>),
+    Delete,
+    /// a value for each of the table's columns, in order
+    Insert(Vec<LogicalExpr>),
+}
+
+impl Merge {
+    pub fn new(
+        input: LogicalPlan,
+        table_name: String,
+        input_schema: Schema,
+        clauses: Vec<MergeClause>,
+    ) -> Self {
+        Self {
+            input,
+            table_name,
+            input_schema,
+            clauses,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        Schema::default()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Merge: {} [{}]\n{}",
+            "-".repeat(indent * 2),
+            self.table_name,
+            self.clauses
+                .iter()
+                .map(MergeClause::print)
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+impl MergeClause {
+    pub fn new(matched: bool, predicate: LogicalExpr, action: MergeAction) -> Self {
+        Self {
+            matched,
+            predicate,
+            action,
+        }
+    }
+
+    pub fn print(&self) -> String {
+        let action = match &self.action {
+            MergeAction::Update(assignments) => format!(
+                "UPDATE {}",
+                assignments
+                    .iter()
+                    .map(|(col, value)| format!("#{} = {}", col, value.print()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            MergeAction::Delete => "DELETE".to_string(),
+            MergeAction::Insert(values) => format!(
+                "INSERT ({})",
+                values
+                    .iter()
+                    .map(LogicalExpr::print)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+
+        format!(
+            "WHEN {}MATCHED AND {} THEN {action}",
+            if self.matched { "" } else { "NOT " },
+            self.predicate.print()
+        )
+    }
+}
+
+#[derive(Clone)]
 pub struct Truncate {
     pub table_names: Vec<String>,
 }
@@ -354,6 +1126,40 @@ impl Truncate {
     }
 }
 
+#[derive(Clone)]
+pub struct AlterTable {
+    pub table_name: String,
+    /// applied in order, a rename applies to the ones after it
+    pub operations: Vec<AlterOperation>,
+}
+
+impl AlterTable {
+    pub fn new(table_name: String, operations: Vec<AlterOperation>) -> Self {
+        Self {
+            table_name,
+            operations,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        Schema::default()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} AlterTable: {} [{}]",
+            "-".repeat(indent * 2),
+            self.table_name,
+            self.operations
+                .iter()
+                .map(|o| o.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+#[derive(Clone)]
 pub struct DropTables {
     pub table_names: Vec<String>,
     pub if_exists: bool,
@@ -367,15 +1173,15 @@ impl DropTables {
         }
     }
 
-    fn name(&self) -> String {
+    pub fn name(&self) -> String {
         "DropTable".to_string()
     }
 
-    fn schema(&self) -> Schema {
+    pub fn schema(&self) -> Schema {
         Schema::new(vec![])
     }
 
-    fn print(&self, indent: usize) -> String {
+    pub fn print(&self, indent: usize) -> String {
         format!(
             "{} {}: [{}]",
             "-".repeat(indent * 2),
@@ -389,6 +1195,7 @@ impl DropTables {
     }
 }
 
+#[derive(Clone)]
 pub struct Values {
     pub rows: Vec<Vec<LogicalExpr>>,
     pub schema: Schema,
@@ -399,15 +1206,15 @@ impl Values {
         Self { rows, schema }
     }
 
-    fn name(&self) -> String {
+    pub fn name(&self) -> String {
         "Values".to_string()
     }
 
-    fn schema(&self) -> Schema {
+    pub fn schema(&self) -> Schema {
         self.schema.clone()
     }
 
-    fn print(&self, indent: usize) -> String {
+    pub fn print(&self, indent: usize) -> String {
         format!(
             "{} {}: [{}]",
             "-".repeat(indent * 2),
@@ -424,12 +1231,15 @@ impl Values {
     }
 }
 
+#[derive(Clone)]
 pub struct Insert {
     pub input: LogicalPlan,
     pub columns: Vec<String>,
     pub table_name: String,
     pub table_schema: Schema,
-    pub returning_schema: Schema, // RETURNING statement
+    pub on_conflict: Option<OnConflict>,
+    /// evaluated over the inserted rows
+    pub returning: Vec<LogicalExpr>,
 }
 
 impl Insert {
@@ -438,14 +1248,16 @@ impl Insert {
         columns: Vec<String>,
         table_name: String,
         table_schema: Schema,
-        schema: Schema,
+        on_conflict: Option<OnConflict>,
+        returning: Vec<LogicalExpr>,
     ) -> Self {
         Self {
             input,
             columns,
             table_name,
             table_schema,
-            returning_schema: schema,
+            on_conflict,
+            returning,
         }
     }
 
@@ -453,13 +1265,13 @@ impl Insert {
         "Insert".to_string()
     }
 
-    fn schema(&self) -> Schema {
-        self.returning_schema.clone()
+    pub fn schema(&self) -> Schema {
+        returning_schema(&self.returning, &self.table_schema)
     }
 
     fn print(&self, indent: usize) -> String {
         format!(
-            "{} {}: {}[{}]\n{}",
+            "{} {}: {}[{}]{}{}\n{}",
             "-".repeat(indent * 2),
             self.name(),
             self.columns
@@ -468,41 +1280,143 @@ impl Insert {
                 .collect::<Vec<_>>()
                 .join(", "),
             self.table_name,
+            self.on_conflict
+                .as_ref()
+                .map(OnConflict::print)
+                .unwrap_or_default(),
+            print_returning(&self.returning),
             self.input.print_indent(indent + 1)
         )
     }
 
     pub fn reorder<T>(&self, data: Vec<T>) -> Result<Vec<T>> {
-        if data.is_empty() || data.len() != self.columns.len() {
-            return Err(Error::Expected(
-                format!("{} values", self.columns.len()),
-                data.len().to_string(),
-            )
-            .into());
+        reorder_columns(&self.columns, &self.table_schema, data)
+    }
+}
+
+/// What an INSERT does with a row whose key is already in the table's unique
+/// column, the conflict is found through the column's index
+#[derive(Clone)]
+pub struct OnConflict {
+    pub column: String,
+    pub action: ConflictAction,
+}
+
+#[derive(Clone)]
+pub enum ConflictAction {
+    Nothing,
+    /// Assignments to the existing row, which read its columns and the
+    /// inserted row's as `excluded.column`. Rows the selection isn't true
+    /// for are left as they are
+    Update {
+        assignments: Vec<(String, LogicalExpr)>,
+        selection: LogicalExpr,
+    },
+}
+
+impl OnConflict {
+    pub fn new(column: String, action: ConflictAction) -> Self {
+        Self { column, action }
+    }
+
+    /// What the assignments read, the existing row's columns and then the
+    /// inserted row's as `excluded.column`
+    pub fn input_schema(table_schema: &Schema) -> Schema {
+        let excluded = table_schema.fields.iter().map(|f| Field {
+            name: format!("excluded.{}", f.name),
+            ..f.clone()
+        });
+
+        Schema::new(
+            table_schema
+                .fields
+                .iter()
+                .cloned()
+                .chain(excluded)
+                .collect(),
+        )
+    }
+
+    pub fn print(&self) -> String {
+        match &self.action {
+            ConflictAction::Nothing => format!(" ON CONFLICT (#{}) DO NOTHING", self.column),
+            ConflictAction::Update {
+                assignments,
+                selection,
+            } => format!(
+                " ON CONFLICT (#{}) DO UPDATE [{}] WHERE {}",
+                self.column,
+                assignments
+                    .iter()
+                    .map(|(col, value)| format!("#{} = {}", col, value.print()))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                selection.print()
+            ),
         }
+    }
+}
 
-        let mut mapping: HashMap<&String, T> = self.columns.iter().zip(data).collect();
+/// The columns of a RETURNING list over the rows of `table_schema`, a
+/// statement without one returns no columns
+pub fn returning_schema(returning: &[LogicalExpr], table_schema: &Schema) -> Schema {
+    Schema::new(returning.iter().map(|e| e.to_field(table_schema)).collect())
+}
 
-        self.table_schema
-            .fields
-            .iter()
-            .map(|f| {
-                mapping
-                    .remove(&f.name)
-                    .ok_or(Error::ColumnNotFound(f.name.clone()).into())
-            })
-            .collect::<Result<Vec<_>>>()
+pub fn print_returning(returning: &[LogicalExpr]) -> String {
+    match returning.is_empty() {
+        true => String::new(),
+        false => format!(
+            " RETURNING [{}]",
+            returning
+                .iter()
+                .map(LogicalExpr::print)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Lays out values given for `columns` in the order of the table's columns
+pub fn reorder_columns<T>(
+    columns: &[String],
+    table_schema: &Schema,
+    data: Vec<T>,
+) -> Result<Vec<T>> {
+    if data.is_empty() || data.len() != columns.len() {
+        return Err(
+            Error::Expected(format!("{} values", columns.len()), data.len().to_string()).into(),
+        );
     }
+
+    let mut mapping: HashMap<&String, T> = columns.iter().zip(data).collect();
+
+    table_schema
+        .fields
+        .iter()
+        .map(|f| {
+            mapping
+                .remove(&f.name)
+                .ok_or(Error::ColumnNotFound(f.name.clone()).into())
+        })
+        .collect::<Result<Vec<_>>>()
 }
 
+#[derive(Clone)]
 pub struct Explain {
     pub input: LogicalPlan,
     pub analyze: bool,
+    /// the input as it was built, set by the optimizer
+    pub initial_plan: Option<String>,
 }
 
 impl Explain {
     pub fn new(input: LogicalPlan, analyze: bool) -> Self {
-        Self { input, analyze }
+        Self {
+            input,
+            analyze,
+            initial_plan: None,
+        }
     }
 
     fn name(&self) -> String {
@@ -523,6 +1437,7 @@ impl Explain {
     }
 }
 
+#[derive(Clone)]
 pub struct CreateTable {
     pub table_name: String,
     pub input: LogicalPlan,
@@ -565,16 +1480,35 @@ impl CreateTable {
     }
 }
 
+#[derive(Clone)]
 pub struct Scan {
     pub table_name: String,
     pub schema: Schema,
 }
 
+/// The columns a scan made by [`Scan::with_tuple_ids`] starts with
+pub const TUPLE_ID_COLUMNS: [&str; 2] = ["__page_id", "__slot_id"];
+
 impl Scan {
     pub fn new(table_name: String, schema: Schema) -> Self {
         Self { table_name, schema }
     }
 
+    /// A scan that returns each row's tuple id as its first columns, so
+    /// that it can still be found after a join
+    pub fn with_tuple_ids(table_name: String, schema: Schema) -> Self {
+        let mut fields = Self::tuple_id_fields();
+        fields.extend(schema.fields);
+        Self::new(table_name, Schema::new(fields))
+    }
+
+    pub fn tuple_id_fields() -> Vec<Field> {
+        TUPLE_ID_COLUMNS
+            .iter()
+            .map(|name| Field::new(name, Types::UInt, Constraints::nullable(false)))
+            .collect()
+    }
+
     fn name(&self) -> String {
         "Scan".to_string()
     }
@@ -599,6 +1533,7 @@ impl Scan {
     }
 }
 
+#[derive(Clone)]
 pub struct Filter {
     pub input: LogicalPlan,
     pub expr: BooleanBinaryExpr,
@@ -628,6 +1563,7 @@ impl Filter {
     }
 }
 
+#[derive(Clone)]
 pub struct Projection {
     pub input: LogicalPlan,
     pub projections: Vec<LogicalExpr>,
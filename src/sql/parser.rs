@@ -1,8 +1,96 @@
-use anyhow::Result;
+use anyhow::{bail, ensure, Result};
+use sqlparser::tokenizer::{Token, Tokenizer};
 use sqlparser::{ast::Statement, dialect::GenericDialect, parser::Parser};
 
+use crate::errors::Error;
+
 pub fn parse(sql: impl Into<String>) -> Result<Vec<Statement>> {
     Ok(Parser::new(&GenericDialect)
         .try_with_sql(&sql.into())?
         .parse_statements()?)
 }
+
+/// Parses a single statement with `$1`, `$2`... or `?` placeholders for its
+/// parameters, `?` ones are numbered in order. Returns the statement and how
+/// many parameters it takes
+pub fn parse_prepared(sql: impl Into<String>) -> Result<(Statement, usize)> {
+    let sql: String = sql.into();
+    let mut tokens = Tokenizer::new(&GenericDialect, &sql).tokenize_with_location()?;
+    let params = number_placeholders(tokens.iter_mut().map(|t| &mut t.token))?;
+
+    let mut statements = Parser::new(&GenericDialect)
+        .with_tokens_with_locations(tokens)
+        .parse_statements()?;
+
+    ensure!(
+        statements.len() == 1,
+        Error::Expected(
+            "a single statement to prepare".into(),
+            format!("{} statements", statements.len())
+        )
+    );
+
+    Ok((statements.remove(0), params))
+}
+
+/// Rewrites `?` placeholders as `$1`, `$2`... in order, they can't be mixed
+/// with numbered ones. Returns the highest parameter number
+fn number_placeholders<'a>(tokens: impl Iterator<Item = &'a mut Token>) -> Result<usize> {
+    let mut positional = 0;
+    let mut numbered = 0;
+
+    for token in tokens {
+        let Token::Placeholder(placeholder) = token else {
+            continue;
+        };
+
+        if placeholder == "?" {
+            positional += 1;
+            *placeholder = format!("${positional}");
+            continue;
+        }
+
+        match placeholder.strip_prefix('$').map(str::parse::<usize>) {
+            Some(Ok(n)) if n > 0 => numbered = numbered.max(n),
+            _ => bail!(Error::Expected(
+                "a placeholder like $1 or ?".into(),
+                placeholder.clone()
+            )),
+        }
+    }
+
+    ensure!(
+        positional == 0 || numbered == 0,
+        Error::Unsupported("Mixing ? and numbered placeholders".into())
+    );
+
+    Ok(positional.max(numbered))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_placeholders() -> Result<()> {
+        let (statement, params) = parse_prepared("SELECT a FROM t WHERE a > ? AND b = ?;")?;
+        assert_eq!(params, 2);
+        assert_eq!(
+            statement.to_string(),
+            "SELECT a FROM t WHERE a > $1 AND b = $2"
+        );
+
+        let (_, params) = parse_prepared("SELECT $2 FROM t WHERE a = $2 OR b = $1;")?;
+        assert_eq!(params, 2);
+
+        // question marks in strings are not placeholders
+        let (_, params) = parse_prepared("SELECT a FROM t WHERE b = '?';")?;
+        assert_eq!(params, 0);
+
+        assert!(parse_prepared("SELECT a FROM t WHERE a = ? AND b = $1;").is_err());
+        assert!(parse_prepared("SELECT $0;").is_err());
+        assert!(parse_prepared("SELECT 1; SELECT 2;").is_err());
+
+        Ok(())
+    }
+}
@@ -1 +1,600 @@
-// TODO: no physical plan for now; we'll execute the logical plan directly
+pub mod plan;
+
+use std::collections::HashSet;
+
+use plan::{
+    CreateTable, Delete, Distinct, Except, Explain, Filter, HashAggregate, HashJoin,
+    IndexNestedLoopJoin, Insert, Intersect, Limit, Merge, NestedLoopJoin, PhysicalPlan, Projection,
+    RecursiveQuery, SeqScan, Sort, Union, Update, Window,
+};
+use sqlparser::ast::BinaryOperator;
+
+use super::logical_plan::expr::{BinaryExpr, BooleanBinaryExpr, LogicalExpr};
+use super::logical_plan::optimizer::split_conjunction;
+use super::logical_plan::plan::{self as logical, JoinType, LogicalPlan};
+use super::logical_plan::{collect_columns, join_condition};
+use crate::tuple::schema::{Field, Schema};
+
+/// Picks the operator that executes each node of the logical plan
+pub fn create_physical_plan(plan: LogicalPlan) -> PhysicalPlan {
+    match plan {
+        LogicalPlan::Scan(scan) => {
+            PhysicalPlan::SeqScan(SeqScan::new(scan.table_name, scan.schema))
+        }
+        LogicalPlan::IndexScan(scan) => PhysicalPlan::IndexScan(scan),
+        LogicalPlan::Filter(filter) => {
+            let logical::Filter { input, expr } = *filter;
+            let input = create_physical_plan(input);
+            PhysicalPlan::Filter(Box::new(Filter::new(input, expr)))
+        }
+        LogicalPlan::Projection(projection) => {
+            let logical::Projection { input, projections } = *projection;
+            let input = create_physical_plan(input);
+            PhysicalPlan::Projection(Box::new(Projection::new(input, projections)))
+        }
+        LogicalPlan::Join(join) => create_join(*join),
+        LogicalPlan::Sort(sort) => {
+            let logical::Sort { input, exprs } = *sort;
+            let input = create_physical_plan(input);
+            PhysicalPlan::Sort(Box::new(Sort::new(input, exprs)))
+        }
+        LogicalPlan::Aggregate(aggregate) => {
+            let schema = aggregate.schema();
+            let logical::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } = *aggregate;
+            let input = create_physical_plan(input);
+            PhysicalPlan::HashAggregate(Box::new(HashAggregate::new(
+                input, group_by, aggregates, schema,
+            )))
+        }
+        LogicalPlan::Window(window) => {
+            let schema = window.schema();
+            let logical::Window { input, windows } = *window;
+            let input = create_physical_plan(input);
+            PhysicalPlan::Window(Box::new(Window::new(input, windows, schema)))
+        }
+        LogicalPlan::Limit(limit) => {
+            let logical::Limit {
+                input,
+                limit,
+                offset,
+            } = *limit;
+            let input = create_physical_plan(input);
+            PhysicalPlan::Limit(Box::new(Limit::new(input, limit.rows(), offset.rows())))
+        }
+        LogicalPlan::Union(union) => {
+            let logical::Union { left, right } = *union;
+            let (left, right) = (create_physical_plan(left), create_physical_plan(right));
+            PhysicalPlan::Union(Box::new(Union::new(left, right)))
+        }
+        LogicalPlan::Distinct(distinct) => {
+            let input = create_physical_plan(distinct.input);
+            PhysicalPlan::Distinct(Box::new(Distinct::new(input)))
+        }
+        LogicalPlan::Intersect(intersect) => {
+            let logical::Intersect { left, right, all } = *intersect;
+            let (left, right) = (create_physical_plan(left), create_physical_plan(right));
+            PhysicalPlan::Intersect(Box::new(Intersect::new(left, right, all)))
+        }
+        LogicalPlan::Except(except) => {
+            let logical::Except { left, right, all } = *except;
+            let (left, right) = (create_physical_plan(left), create_physical_plan(right));
+            PhysicalPlan::Except(Box::new(Except::new(left, right, all)))
+        }
+        LogicalPlan::RecursiveQuery(query) => {
+            let logical::RecursiveQuery {
+                name,
+                static_term,
+                recursive_term,
+                distinct,
+            } = *query;
+            PhysicalPlan::RecursiveQuery(Box::new(RecursiveQuery::new(
+                name,
+                create_physical_plan(static_term),
+                create_physical_plan(recursive_term),
+                distinct,
+            )))
+        }
+        LogicalPlan::WorkTableScan(scan) => PhysicalPlan::WorkTableScan(scan),
+        LogicalPlan::Values(values) => PhysicalPlan::Values(values),
+        LogicalPlan::Insert(insert) => {
+            let returning_schema = insert.schema();
+            let logical::Insert {
+                input,
+                columns,
+                table_name,
+                table_schema,
+                on_conflict,
+                returning,
+            } = *insert;
+            PhysicalPlan::Insert(Box::new(Insert::new(
+                create_physical_plan(input),
+                columns,
+                table_name,
+                table_schema,
+                on_conflict,
+                returning,
+                returning_schema,
+            )))
+        }
+        LogicalPlan::Update(update) => {
+            let returning_schema = update.schema();
+            let logical::Update {
+                input,
+                table_name,
+                assignments,
+                selection,
+                returning,
+            } = *update;
+            PhysicalPlan::Update(Box::new(Update::new(
+                create_physical_plan(input),
+                table_name,
+                assignments,
+                selection,
+                returning,
+                returning_schema,
+            )))
+        }
+        LogicalPlan::Delete(delete) => {
+            let returning_schema = delete.schema();
+            let logical::Delete {
+                input,
+                table_name,
+                selection,
+                returning,
+            } = *delete;
+            let input = create_physical_plan(input);
+            PhysicalPlan::Delete(Box::new(Delete::new(
+                input,
+                table_name,
+                selection,
+                returning,
+                returning_schema,
+            )))
+        }
+        LogicalPlan::Merge(merge) => {
+            let logical::Merge {
+                input,
+                table_name,
+                input_schema,
+                clauses,
+            } = *merge;
+            PhysicalPlan::Merge(Box::new(Merge::new(
+                create_physical_plan(input),
+                table_name,
+                input_schema,
+                clauses,
+            )))
+        }
+        LogicalPlan::CreateTable(create) => {
+            let logical::CreateTable {
+                table_name,
+                schema,
+                if_not_exists,
+                ..
+            } = *create;
+            PhysicalPlan::CreateTable(CreateTable::new(table_name, schema, if_not_exists))
+        }
+        LogicalPlan::DropTables(drop) => PhysicalPlan::DropTables(drop),
+        LogicalPlan::Truncate(truncate) => PhysicalPlan::Truncate(truncate),
+        LogicalPlan::AlterTable(alter) => PhysicalPlan::AlterTable(alter),
+        LogicalPlan::Explain(explain) => {
+            let logical::Explain {
+                input,
+                analyze,
+                initial_plan,
+            } = *explain;
+
+            let logical_plan = match initial_plan {
+                Some(initial) => format!("{initial}\nOptimized Logical Plan:\n{}", input.print()),
+                None => input.print(),
+            };
+
+            let input = create_physical_plan(input);
+            PhysicalPlan::Explain(Box::new(Explain::new(input, analyze, logical_plan)))
+        }
+        LogicalPlan::StartTxn => PhysicalPlan::StartTxn,
+        LogicalPlan::CommitTxn => PhysicalPlan::CommitTxn,
+        LogicalPlan::RollbackTxn => PhysicalPlan::RollbackTxn,
+        LogicalPlan::Empty => PhysicalPlan::Empty,
+    }
+}
+
+/// An index nested loop join when the condition equates a side's key with
+/// the unique column of a table scanned by the other, a hash join when it
+/// equates other expressions of the two sides. The rest of the condition
+/// filters the pairs with equal keys. Otherwise a nested loop join, which
+/// is also the only one to tell unknown conditions apart for IN and NOT IN
+/// when the condition reads nullable columns
+fn create_join(join: logical::Join) -> PhysicalPlan {
+    let logical::Join {
+        left,
+        right,
+        on,
+        join_type,
+        schema,
+    } = join;
+
+    let left_schema = left.schema();
+    let right_schema = right.schema();
+
+    // a condition over columns without NULLs is never unknown, the
+    // null-aware join is then a plain one
+    let nullable = collect_columns(&LogicalExpr::BinaryExpr(Box::new(on.clone())))
+        .iter()
+        .any(|column| {
+            left_schema
+                .fields
+                .iter()
+                .chain(&right_schema.fields)
+                .find(|f| &f.name == column)
+                .is_none_or(|f| f.constraints.nullable)
+        });
+    let join_type = match join_type {
+        JoinType::NullAwareAnti if !nullable => JoinType::Anti,
+        JoinType::NullAwareMark if !nullable => JoinType::Mark,
+        join_type if join_type.is_null_aware() => {
+            let (left, right) = (create_physical_plan(left), create_physical_plan(right));
+            let join = NestedLoopJoin::new(left, right, on, join_type, schema);
+            return PhysicalPlan::NestedLoopJoin(Box::new(join));
+        }
+        join_type => join_type,
+    };
+
+    let (left_fields, right_fields) = schema.fields.split_at(left_schema.fields.len());
+    // the right side's columns are not part of the output
+    let right_fields = match join_type.drops_right() {
+        true => &right_schema.fields,
+        false => right_fields,
+    };
+
+    let left_columns: HashSet<_> = left_fields.iter().map(|f| f.name.clone()).collect();
+    let right_columns: HashSet<_> = right_fields.iter().map(|f| f.name.clone()).collect();
+    let reads = |expr: &LogicalExpr, side: &HashSet<String>| {
+        let columns = collect_columns(expr);
+        !columns.is_empty() && columns.iter().all(|c| side.contains(c))
+    };
+
+    let mut keys = vec![];
+    let mut residual = vec![];
+
+    for conjunct in split_conjunction(LogicalExpr::BinaryExpr(Box::new(on.clone()))) {
+        match conjunct {
+            LogicalExpr::BinaryExpr(e) if e.op == BinaryOperator::Eq => {
+                let BinaryExpr { left, op, right } = *e;
+                if reads(&left, &left_columns) && reads(&right, &right_columns) {
+                    keys.push((left, right));
+                } else if reads(&right, &left_columns) && reads(&left, &right_columns) {
+                    keys.push((right, left));
+                } else {
+                    residual.push(LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+                        left, op, right,
+                    ))));
+                }
+            }
+            conjunct => residual.push(conjunct),
+        }
+    }
+
+    let unique_key = |plan: &LogicalPlan, fields: &[Field], side: fn(&KeyPair) -> &LogicalExpr| {
+        let (scan, _) = indexed_scan(plan)?;
+        let column = scan
+            .schema
+            .fields
+            .iter()
+            .position(|f| f.constraints.unique)?;
+        let column = &fields[column].name;
+        keys.iter()
+            .position(|key| matches!(side(key), LogicalExpr::Column(c) if c == column))
+    };
+
+    // the looked up side's rows can't be kept when nothing matches them
+    let right_key = match join_type.keeps_right() {
+        true => None,
+        false => unique_key(&right, right_fields, |(_, right)| right),
+    };
+    let left_key = match join_type {
+        JoinType::Inner | JoinType::Right => unique_key(&left, left_fields, |(left, _)| left),
+        _ => None,
+    };
+
+    if let Some((key, index_left)) = right_key
+        .map(|k| (k, false))
+        .or(left_key.map(|k| (k, true)))
+    {
+        let join = create_index_join(
+            left, right, keys, key, residual, index_left, join_type, schema,
+        );
+        return PhysicalPlan::IndexNestedLoopJoin(Box::new(join));
+    }
+
+    let left = create_physical_plan(left);
+    let right = create_physical_plan(right);
+
+    if keys.is_empty() {
+        let join = NestedLoopJoin::new(left, right, on, join_type, schema);
+        return PhysicalPlan::NestedLoopJoin(Box::new(join));
+    }
+
+    let filter = (!residual.is_empty()).then(|| join_condition(residual));
+    let join = HashJoin::new(left, right, keys, filter, join_type, schema);
+    PhysicalPlan::HashJoin(Box::new(join))
+}
+
+/// A pair of expressions equated by a join condition, the left side's first
+type KeyPair = (LogicalExpr, LogicalExpr);
+
+/// The scan of a table, and the filter over it if any
+fn indexed_scan(plan: &LogicalPlan) -> Option<(&logical::Scan, Option<&BooleanBinaryExpr>)> {
+    match plan {
+        LogicalPlan::Scan(scan) => Some((scan, None)),
+        LogicalPlan::Filter(filter) => match &filter.input {
+            LogicalPlan::Scan(scan) => Some((scan, Some(&filter.expr))),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Looks up the rows of the indexed side's table by the `key`th key, the
+/// other keys and the filter over the indexed table join the rest of the
+/// condition
+#[allow(clippy::too_many_arguments)]
+fn create_index_join(
+    left: LogicalPlan,
+    right: LogicalPlan,
+    mut keys: Vec<KeyPair>,
+    key: usize,
+    residual: Vec<LogicalExpr>,
+    index_left: bool,
+    join_type: JoinType,
+    schema: Schema,
+) -> IndexNestedLoopJoin {
+    let (left_key, right_key) = keys.remove(key);
+    let (indexed, outer, outer_key) = match index_left {
+        true => (left, right, right_key),
+        false => (right, left, left_key),
+    };
+
+    let (scan, scan_filter) = match indexed {
+        LogicalPlan::Scan(scan) => (scan, None),
+        LogicalPlan::Filter(filter) => match *filter {
+            logical::Filter {
+                input: LogicalPlan::Scan(scan),
+                expr,
+            } => (scan, Some(expr)),
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    };
+    let column_name = scan.schema.fields.iter().find(|f| f.constraints.unique);
+    let column_name = column_name.unwrap().name.clone();
+
+    let mut conjuncts: Vec<_> = keys
+        .into_iter()
+        .map(|(left, right)| {
+            LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(left, BinaryOperator::Eq, right)))
+        })
+        .collect();
+    conjuncts.extend(residual);
+    if let Some(filter) = scan_filter {
+        let BooleanBinaryExpr { left, op, right } = filter;
+        conjuncts.push(LogicalExpr::BinaryExpr(Box::new(BinaryExpr::new(
+            left, op, right,
+        ))));
+    }
+    let filter = (!conjuncts.is_empty()).then(|| join_condition(conjuncts));
+
+    IndexNestedLoopJoin::new(
+        create_physical_plan(outer),
+        outer_key,
+        scan.table_name,
+        scan.schema,
+        column_name,
+        index_left,
+        filter,
+        join_type,
+        schema,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::tests::test_context;
+    use crate::context::Context;
+    use anyhow::Result;
+
+    /// The physical plan of a query over two tables without an index
+    fn physical(sql: &str) -> Result<String> {
+        let mut ctx = test_context();
+        ctx.execute_sql("CREATE TABLE t1 (a int, b int);")?;
+        ctx.execute_sql("CREATE TABLE t2 (c int, d int);")?;
+        explain(&mut ctx, sql)
+    }
+
+    /// The physical plan of a query
+    fn explain(ctx: &mut Context, sql: &str) -> Result<String> {
+        let result = ctx.execute_sql(format!("EXPLAIN {sql}"))?;
+        let info = result.get_info();
+        let (_, plan) = info.split_once("Physical Plan:\n").unwrap();
+        Ok(plan.to_string())
+    }
+
+    #[test]
+    fn test_join_selection() -> Result<()> {
+        // the rest of the condition filters the pairs with equal keys
+        let plan = physical("SELECT a, c FROM t1 INNER JOIN t2 ON a = c AND b > d;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#a,#c]\n\
+             ---- HashJoin: [#a = #c] Filter: (#b > #d)\n\
+             ------ SeqScan: t1 [#a,#b]\n\
+             ------ SeqScan: t2 [#c,#d]\n"
+        );
+
+        let plan = physical("SELECT a FROM t1 LEFT JOIN t2 ON d = b + 1;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#a]\n\
+             ---- Left HashJoin: [(#b + 1) = #d]\n\
+             ------ SeqScan: t1 [#a,#b]\n\
+             ------ SeqScan: t2 [#d]\n"
+        );
+
+        // without equalities between the two sides
+        let plan = physical("SELECT a FROM t1 INNER JOIN t2 ON a < c;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#a]\n\
+             ---- NestedLoopJoin: (#a < #c)\n\
+             ------ SeqScan: t1 [#a]\n\
+             ------ SeqScan: t2 [#c]\n"
+        );
+
+        // NOT IN has to see every comparison to tell unknown ones apart
+        let plan = physical("SELECT a FROM t1 WHERE a NOT IN (SELECT c FROM t2);")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#a]\n\
+             ---- Null Aware Anti NestedLoopJoin: (#a = #__subquery_1.c)\n\
+             ------ SeqScan: t1 [#a]\n\
+             ------ Projection: [#c AS __subquery_1.c]\n\
+             -------- Projection: [#c]\n\
+             ---------- SeqScan: t2 [#c]\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_null_aware_join_selection() -> Result<()> {
+        let mut ctx = test_context();
+        ctx.execute_sql("CREATE TABLE t1 (a int NOT NULL, b int);")?;
+        ctx.execute_sql("CREATE TABLE t2 (c int NOT NULL, d int);")?;
+
+        // without NULLs NOT IN is a plain anti join
+        let plan = explain(
+            &mut ctx,
+            "SELECT a FROM t1 WHERE a NOT IN (SELECT c FROM t2);",
+        )?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#a]\n\
+             ---- Anti HashJoin: [#a = #__subquery_1.c]\n\
+             ------ SeqScan: t1 [#a]\n\
+             ------ Projection: [#c AS __subquery_1.c]\n\
+             -------- Projection: [#c]\n\
+             ---------- SeqScan: t2 [#c]\n"
+        );
+
+        let plan = explain(
+            &mut ctx,
+            "SELECT a FROM t1 WHERE b NOT IN (SELECT c FROM t2);",
+        )?;
+        assert!(plan.contains("Null Aware Anti NestedLoopJoin"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_list_lookups() -> Result<()> {
+        let mut ctx = test_context();
+        ctx.execute_sql("CREATE TABLE t (id int UNIQUE NOT NULL, a int);")?;
+
+        let plan = explain(&mut ctx, "SELECT a FROM t WHERE id IN (1, 3);")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#a]\n\
+             ---- IndexScan: t Scan( id keys [1,3] ) [#a]\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_join() -> Result<()> {
+        let mut ctx = test_context();
+        ctx.execute_sql("CREATE TABLE t1 (a int, b int);")?;
+        ctx.execute_sql("CREATE TABLE t2 (c int UNIQUE NOT NULL, d int);")?;
+
+        // the source is joined to the target, through its index if it has one
+        let sql = "MERGE INTO t1 USING t2 ON a = c WHEN MATCHED THEN DELETE;";
+        let plan = explain(&mut ctx, sql)?;
+        assert_eq!(
+            plan,
+            "-- Merge: t1 [WHEN MATCHED AND true THEN DELETE]\n\
+             ---- Right HashJoin: [#a = #c]\n\
+             ------ SeqScan: t1 [#__page_id,#__slot_id,#a,#b]\n\
+             ------ SeqScan: t2 [#c,#d]\n"
+        );
+
+        let sql = "MERGE INTO t2 USING t1 ON c = a WHEN MATCHED THEN DELETE;";
+        let plan = explain(&mut ctx, sql)?;
+        assert_eq!(
+            plan,
+            "-- Merge: t2 [WHEN MATCHED AND true THEN DELETE]\n\
+             ---- Right IndexNestedLoopJoin: [#a = #c]\n\
+             ------ IndexLookup: t2 Lookup( c ) [#__page_id,#__slot_id,#c,#d]\n\
+             ------ SeqScan: t1 [#a,#b]\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_join_selection() -> Result<()> {
+        let mut ctx = test_context();
+        ctx.execute_sql("CREATE TABLE t1 (a int, b int);")?;
+        ctx.execute_sql("CREATE TABLE t2 (c int UNIQUE NOT NULL, d int);")?;
+
+        // the filter over the looked up table joins the rest of the condition
+        let sql = "SELECT a, d FROM t1 JOIN t2 ON a = c AND b > d WHERE d < 3;";
+        let plan = explain(&mut ctx, sql)?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#a,#d]\n\
+             ---- IndexNestedLoopJoin: [#a = #c] Filter: ((#b > #d) AND (#d < 3))\n\
+             ------ SeqScan: t1 [#a,#b]\n\
+             ------ IndexLookup: t2 Lookup( c ) [#c,#d]\n"
+        );
+
+        let plan = explain(&mut ctx, "SELECT a FROM t2 RIGHT JOIN t1 ON c = b;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#a]\n\
+             ---- Right IndexNestedLoopJoin: [#b = #c]\n\
+             ------ IndexLookup: t2 Lookup( c ) [#c]\n\
+             ------ SeqScan: t1 [#a,#b]\n"
+        );
+
+        // unmatched rows of the indexed table would never be seen
+        let plan = explain(&mut ctx, "SELECT a FROM t1 RIGHT JOIN t2 ON a = c;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#a]\n\
+             ---- Right HashJoin: [#a = #c]\n\
+             ------ SeqScan: t1 [#a]\n\
+             ------ SeqScan: t2 [#c]\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_operators() -> Result<()> {
+        let plan = physical("SELECT b, COUNT(*) FROM t1 GROUP BY b ORDER BY b LIMIT 1;")?;
+        assert_eq!(
+            plan,
+            "-- Projection: [#b,#count(*)]\n\
+             ---- Limit: 1\n\
+             ------ Sort: #b ASC NULLS LAST\n\
+             -------- HashAggregate: group_by=[#b], aggregates=[count(*)]\n\
+             ---------- SeqScan: t1 [#b]\n"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,929 @@
+use crate::sql::logical_plan::expr::{
+    AggregateExpr, BinaryExpr, BooleanBinaryExpr, LogicalExpr, SortExpr, WindowExpr,
+};
+use crate::sql::logical_plan::plan::JoinType;
+use crate::sql::logical_plan::plan::{print_returning, reorder_columns, MergeClause, OnConflict};
+use crate::tuple::schema::Schema;
+use anyhow::Result;
+
+// leaves with a single implementation are executed as they were planned
+pub use crate::sql::logical_plan::plan::{
+    AlterTable, DropTables, IndexScan, Truncate, Values, WorkTableScan,
+};
+
+#[derive(Default)]
+pub enum PhysicalPlan {
+    SeqScan(SeqScan),
+    IndexScan(IndexScan),
+    Filter(Box<Filter>),
+    Projection(Box<Projection>),
+    HashJoin(Box<HashJoin>),
+    NestedLoopJoin(Box<NestedLoopJoin>),
+    IndexNestedLoopJoin(Box<IndexNestedLoopJoin>),
+    Sort(Box<Sort>),
+    HashAggregate(Box<HashAggregate>),
+    Window(Box<Window>),
+    Limit(Box<Limit>),
+    Union(Box<Union>),
+    Distinct(Box<Distinct>),
+    Intersect(Box<Intersect>),
+    Except(Box<Except>),
+    RecursiveQuery(Box<RecursiveQuery>),
+    WorkTableScan(WorkTableScan),
+    Values(Values),
+    Insert(Box<Insert>),
+    Update(Box<Update>),
+    Delete(Box<Delete>),
+    Merge(Box<Merge>),
+    CreateTable(CreateTable),
+    DropTables(DropTables),
+    Truncate(Truncate),
+    AlterTable(AlterTable),
+    Explain(Box<Explain>),
+    StartTxn,
+    CommitTxn,
+    RollbackTxn,
+    #[default]
+    Empty,
+}
+
+impl PhysicalPlan {
+    pub fn print(&self) -> String {
+        self.print_indent(1)
+    }
+
+    pub(super) fn print_indent(&self, indent: usize) -> String {
+        match self {
+            PhysicalPlan::SeqScan(s) => s.print(indent),
+            PhysicalPlan::IndexScan(i) => i.print(indent),
+            PhysicalPlan::Filter(f) => f.print(indent),
+            PhysicalPlan::Projection(p) => p.print(indent),
+            PhysicalPlan::HashJoin(j) => j.print(indent),
+            PhysicalPlan::NestedLoopJoin(j) => j.print(indent),
+            PhysicalPlan::IndexNestedLoopJoin(j) => j.print(indent),
+            PhysicalPlan::Sort(s) => s.print(indent),
+            PhysicalPlan::HashAggregate(a) => a.print(indent),
+            PhysicalPlan::Window(w) => w.print(indent),
+            PhysicalPlan::Limit(l) => l.print(indent),
+            PhysicalPlan::Union(u) => u.print(indent),
+            PhysicalPlan::Distinct(d) => d.print(indent),
+            PhysicalPlan::Intersect(i) => i.print(indent),
+            PhysicalPlan::Except(e) => e.print(indent),
+            PhysicalPlan::RecursiveQuery(r) => r.print(indent),
+            PhysicalPlan::WorkTableScan(w) => w.print(indent),
+            PhysicalPlan::Values(v) => v.print(indent),
+            PhysicalPlan::Insert(i) => i.print(indent),
+            PhysicalPlan::Update(u) => u.print(indent),
+            PhysicalPlan::Delete(d) => d.print(indent),
+            PhysicalPlan::Merge(m) => m.print(indent),
+            PhysicalPlan::CreateTable(c) => c.print(indent),
+            PhysicalPlan::DropTables(d) => d.print(indent),
+            PhysicalPlan::Truncate(t) => t.print(indent),
+            PhysicalPlan::AlterTable(a) => a.print(indent),
+            PhysicalPlan::Explain(e) => e.print(indent),
+            PhysicalPlan::StartTxn => format!("{} StartTransaction", "-".repeat(indent * 2)),
+            PhysicalPlan::CommitTxn => format!("{} CommitTransaction", "-".repeat(indent * 2)),
+            PhysicalPlan::RollbackTxn => format!("{} RollbackTransaction", "-".repeat(indent * 2)),
+            PhysicalPlan::Empty => format!("{} Empty", "-".repeat(indent * 2)),
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        match self {
+            PhysicalPlan::SeqScan(s) => s.schema(),
+            PhysicalPlan::IndexScan(i) => i.schema(),
+            PhysicalPlan::Filter(f) => f.schema(),
+            PhysicalPlan::Projection(p) => p.schema(),
+            PhysicalPlan::HashJoin(j) => j.schema(),
+            PhysicalPlan::NestedLoopJoin(j) => j.schema(),
+            PhysicalPlan::IndexNestedLoopJoin(j) => j.schema(),
+            PhysicalPlan::Sort(s) => s.schema(),
+            PhysicalPlan::HashAggregate(a) => a.schema(),
+            PhysicalPlan::Window(w) => w.schema(),
+            PhysicalPlan::Limit(l) => l.schema(),
+            PhysicalPlan::Union(u) => u.schema(),
+            PhysicalPlan::Distinct(d) => d.schema(),
+            PhysicalPlan::Intersect(i) => i.schema(),
+            PhysicalPlan::Except(e) => e.schema(),
+            PhysicalPlan::RecursiveQuery(r) => r.schema(),
+            PhysicalPlan::WorkTableScan(w) => w.schema(),
+            PhysicalPlan::Values(v) => v.schema(),
+            PhysicalPlan::Insert(i) => i.schema(),
+            PhysicalPlan::Update(u) => u.schema(),
+            PhysicalPlan::Delete(d) => d.schema(),
+            PhysicalPlan::Merge(_)
+            | PhysicalPlan::CreateTable(_)
+            | PhysicalPlan::DropTables(_)
+            | PhysicalPlan::Truncate(_)
+            | PhysicalPlan::AlterTable(_)
+            | PhysicalPlan::StartTxn
+            | PhysicalPlan::CommitTxn
+            | PhysicalPlan::RollbackTxn
+            | PhysicalPlan::Empty => Schema::default(),
+            PhysicalPlan::Explain(e) => e.input.schema(),
+        }
+    }
+}
+
+fn print_fields(schema: &Schema) -> String {
+    schema
+        .fields
+        .iter()
+        .map(|f| format!("#{}", f.name))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn print_exprs(exprs: &[LogicalExpr]) -> String {
+    exprs
+        .iter()
+        .map(|e| e.print())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn join_name(join_type: JoinType, name: &str) -> String {
+    match join_type {
+        JoinType::Inner => name.to_string(),
+        JoinType::Left => format!("Left {name}"),
+        JoinType::Right => format!("Right {name}"),
+        JoinType::Full => format!("Full {name}"),
+        JoinType::Semi => format!("Semi {name}"),
+        JoinType::Anti => format!("Anti {name}"),
+        JoinType::Single => format!("Single {name}"),
+        JoinType::Mark => format!("Mark {name}"),
+        JoinType::NullAwareAnti => format!("Null Aware Anti {name}"),
+        JoinType::NullAwareMark => format!("Null Aware Mark {name}"),
+    }
+}
+
+/// Reads every tuple of the table, in storage order
+pub struct SeqScan {
+    pub table_name: String,
+    pub schema: Schema,
+}
+
+impl SeqScan {
+    pub fn new(table_name: String, schema: Schema) -> Self {
+        Self { table_name, schema }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} SeqScan: {} [{}]\n",
+            "-".repeat(indent * 2),
+            self.table_name,
+            print_fields(&self.schema)
+        )
+    }
+}
+
+pub struct Filter {
+    pub input: PhysicalPlan,
+    pub expr: BooleanBinaryExpr,
+}
+
+impl Filter {
+    pub fn new(input: PhysicalPlan, expr: BooleanBinaryExpr) -> Self {
+        Self { input, expr }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.input.schema()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Filter: {}\n{}",
+            "-".repeat(indent * 2),
+            self.expr.print(),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+pub struct Projection {
+    pub input: PhysicalPlan,
+    pub projections: Vec<LogicalExpr>,
+}
+
+impl Projection {
+    pub fn new(input: PhysicalPlan, projections: Vec<LogicalExpr>) -> Self {
+        Self { input, projections }
+    }
+
+    pub fn schema(&self) -> Schema {
+        let schema = self.input.schema();
+        Schema::new(
+            self.projections
+                .iter()
+                .map(|p| p.to_field(&schema))
+                .collect(),
+        )
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Projection: [{}]\n{}",
+            "-".repeat(indent * 2),
+            print_exprs(&self.projections),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+/// Builds a hash table over the smaller side's keys and probes it with the
+/// other side's rows, the pairs with equal keys must also pass the filter
+pub struct HashJoin {
+    pub left: PhysicalPlan,
+    pub right: PhysicalPlan,
+    pub left_keys: Vec<LogicalExpr>,
+    pub right_keys: Vec<LogicalExpr>,
+    pub filter: Option<BinaryExpr>,
+    pub join_type: JoinType,
+    pub schema: Schema,
+}
+
+impl HashJoin {
+    pub fn new(
+        left: PhysicalPlan,
+        right: PhysicalPlan,
+        keys: Vec<(LogicalExpr, LogicalExpr)>,
+        filter: Option<BinaryExpr>,
+        join_type: JoinType,
+        schema: Schema,
+    ) -> Self {
+        let (left_keys, right_keys) = keys.into_iter().unzip();
+        Self {
+            left,
+            right,
+            left_keys,
+            right_keys,
+            filter,
+            join_type,
+            schema,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        let keys = self
+            .left_keys
+            .iter()
+            .zip(&self.right_keys)
+            .map(|(l, r)| format!("{} = {}", l.print(), r.print()))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "{} {}: [{}]{}\n{}{}",
+            "-".repeat(indent * 2),
+            join_name(self.join_type, "HashJoin"),
+            keys,
+            match &self.filter {
+                Some(filter) => format!(" Filter: {}", filter.print()),
+                None => "".to_string(),
+            },
+            self.left.print_indent(indent + 1),
+            self.right.print_indent(indent + 1),
+        )
+    }
+}
+
+/// Evaluates the condition over every pair of rows
+pub struct NestedLoopJoin {
+    pub left: PhysicalPlan,
+    pub right: PhysicalPlan,
+    pub on: BinaryExpr,
+    pub join_type: JoinType,
+    pub schema: Schema,
+}
+
+impl NestedLoopJoin {
+    pub fn new(
+        left: PhysicalPlan,
+        right: PhysicalPlan,
+        on: BinaryExpr,
+        join_type: JoinType,
+        schema: Schema,
+    ) -> Self {
+        Self {
+            left,
+            right,
+            on,
+            join_type,
+            schema,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} {}: {}\n{}{}",
+            "-".repeat(indent * 2),
+            join_name(self.join_type, "NestedLoopJoin"),
+            self.on.print(),
+            self.left.print_indent(indent + 1),
+            self.right.print_indent(indent + 1),
+        )
+    }
+}
+
+/// Looks up the row of the indexed table whose unique column equals the key
+/// of each outer row, through the table's index instead of scanning it. The
+/// pairs must also pass the filter
+pub struct IndexNestedLoopJoin {
+    pub outer: PhysicalPlan,
+    pub outer_key: LogicalExpr,
+    // the indexed table, never scanned
+    pub table_name: String,
+    pub table_schema: Schema,
+    pub column_name: String,
+    // whether the indexed table is the left side of the join
+    pub index_left: bool,
+    pub filter: Option<BinaryExpr>,
+    pub join_type: JoinType,
+    pub schema: Schema,
+}
+
+impl IndexNestedLoopJoin {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        outer: PhysicalPlan,
+        outer_key: LogicalExpr,
+        table_name: String,
+        table_schema: Schema,
+        column_name: String,
+        index_left: bool,
+        filter: Option<BinaryExpr>,
+        join_type: JoinType,
+        schema: Schema,
+    ) -> Self {
+        Self {
+            outer,
+            outer_key,
+            table_name,
+            table_schema,
+            column_name,
+            index_left,
+            filter,
+            join_type,
+            schema,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        let lookup = format!(
+            "{} IndexLookup: {} Lookup( {} ) [{}]\n",
+            "-".repeat((indent + 1) * 2),
+            self.table_name,
+            self.column_name,
+            print_fields(&self.table_schema)
+        );
+        let outer = self.outer.print_indent(indent + 1);
+        let (left, right) = match self.index_left {
+            true => (lookup, outer),
+            false => (outer, lookup),
+        };
+
+        format!(
+            "{} {}: [{} = #{}]{}\n{}{}",
+            "-".repeat(indent * 2),
+            join_name(self.join_type, "IndexNestedLoopJoin"),
+            self.outer_key.print(),
+            self.column_name,
+            match &self.filter {
+                Some(filter) => format!(" Filter: {}", filter.print()),
+                None => "".to_string(),
+            },
+            left,
+            right,
+        )
+    }
+}
+
+/// Sorts in memory, spilling sorted runs to disk once the buffer is full
+pub struct Sort {
+    pub input: PhysicalPlan,
+    pub exprs: Vec<SortExpr>,
+}
+
+impl Sort {
+    pub fn new(input: PhysicalPlan, exprs: Vec<SortExpr>) -> Self {
+        Self { input, exprs }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.input.schema()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Sort: {}\n{}",
+            "-".repeat(indent * 2),
+            self.exprs
+                .iter()
+                .map(|e| e.print())
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+/// Groups rows in a hash table keyed by the group by values
+pub struct HashAggregate {
+    pub input: PhysicalPlan,
+    pub group_by: Vec<LogicalExpr>,
+    pub aggregates: Vec<AggregateExpr>,
+    pub schema: Schema,
+}
+
+impl HashAggregate {
+    pub fn new(
+        input: PhysicalPlan,
+        group_by: Vec<LogicalExpr>,
+        aggregates: Vec<AggregateExpr>,
+        schema: Schema,
+    ) -> Self {
+        Self {
+            input,
+            group_by,
+            aggregates,
+            schema,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} HashAggregate: group_by=[{}], aggregates=[{}]\n{}",
+            "-".repeat(indent * 2),
+            print_exprs(&self.group_by),
+            self.aggregates
+                .iter()
+                .map(|a| a.print())
+                .collect::<Vec<_>>()
+                .join(","),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+/// Buffers the sorted input and computes the window functions partition
+/// by partition
+pub struct Window {
+    pub input: PhysicalPlan,
+    pub windows: Vec<WindowExpr>,
+    pub schema: Schema,
+}
+
+impl Window {
+    pub fn new(input: PhysicalPlan, windows: Vec<WindowExpr>, schema: Schema) -> Self {
+        Self {
+            input,
+            windows,
+            schema,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Window: [{}]\n{}",
+            "-".repeat(indent * 2),
+            self.windows
+                .iter()
+                .map(|w| w.print())
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+pub struct Limit {
+    pub input: PhysicalPlan,
+    pub limit: u32,
+    pub offset: u32,
+}
+
+impl Limit {
+    pub fn new(input: PhysicalPlan, limit: u32, offset: u32) -> Self {
+        Self {
+            input,
+            limit,
+            offset,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.input.schema()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        let offset = match self.offset {
+            0 => "".to_string(),
+            offset => format!(" Offset: {offset}"),
+        };
+
+        format!(
+            "{} Limit: {}{}\n{}",
+            "-".repeat(indent * 2),
+            self.limit,
+            offset,
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+pub struct Union {
+    pub left: PhysicalPlan,
+    pub right: PhysicalPlan,
+}
+
+impl Union {
+    pub fn new(left: PhysicalPlan, right: PhysicalPlan) -> Self {
+        Self { left, right }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.left.schema()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Union:\n{}{}",
+            "-".repeat(indent * 2),
+            self.left.print_indent(indent + 1),
+            self.right.print_indent(indent + 1)
+        )
+    }
+}
+
+pub struct Distinct {
+    pub input: PhysicalPlan,
+}
+
+impl Distinct {
+    pub fn new(input: PhysicalPlan) -> Self {
+        Self { input }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.input.schema()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Distinct:\n{}",
+            "-".repeat(indent * 2),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+pub struct Intersect {
+    pub left: PhysicalPlan,
+    pub right: PhysicalPlan,
+    pub all: bool,
+}
+
+impl Intersect {
+    pub fn new(left: PhysicalPlan, right: PhysicalPlan, all: bool) -> Self {
+        Self { left, right, all }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.left.schema()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Intersect{}:\n{}{}",
+            "-".repeat(indent * 2),
+            if self.all { " All" } else { "" },
+            self.left.print_indent(indent + 1),
+            self.right.print_indent(indent + 1)
+        )
+    }
+}
+
+pub struct Except {
+    pub left: PhysicalPlan,
+    pub right: PhysicalPlan,
+    pub all: bool,
+}
+
+impl Except {
+    pub fn new(left: PhysicalPlan, right: PhysicalPlan, all: bool) -> Self {
+        Self { left, right, all }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.left.schema()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Except{}:\n{}{}",
+            "-".repeat(indent * 2),
+            if self.all { " All" } else { "" },
+            self.left.print_indent(indent + 1),
+            self.right.print_indent(indent + 1)
+        )
+    }
+}
+
+pub struct RecursiveQuery {
+    pub name: String,
+    pub static_term: PhysicalPlan,
+    pub recursive_term: PhysicalPlan,
+    pub distinct: bool,
+}
+
+impl RecursiveQuery {
+    pub fn new(
+        name: String,
+        static_term: PhysicalPlan,
+        recursive_term: PhysicalPlan,
+        distinct: bool,
+    ) -> Self {
+        Self {
+            name,
+            static_term,
+            recursive_term,
+            distinct,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.static_term.schema()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Recursive Query{}: {}\n{}{}",
+            "-".repeat(indent * 2),
+            if self.distinct { "" } else { " All" },
+            self.name,
+            self.static_term.print_indent(indent + 1),
+            self.recursive_term.print_indent(indent + 1)
+        )
+    }
+}
+
+pub struct Insert {
+    pub input: PhysicalPlan,
+    pub columns: Vec<String>,
+    pub table_name: String,
+    pub table_schema: Schema,
+    pub on_conflict: Option<OnConflict>,
+    pub returning: Vec<LogicalExpr>,
+    pub returning_schema: Schema,
+}
+
+impl Insert {
+    pub fn new(
+        input: PhysicalPlan,
+        columns: Vec<String>,
+        table_name: String,
+        table_schema: Schema,
+        on_conflict: Option<OnConflict>,
+        returning: Vec<LogicalExpr>,
+        returning_schema: Schema,
+    ) -> Self {
+        Self {
+            input,
+            columns,
+            table_name,
+            table_schema,
+            on_conflict,
+            returning,
+            returning_schema,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.returning_schema.clone()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Insert: {}[{}]{}{}\n{}",
+            "-".repeat(indent * 2),
+            self.columns
+                .iter()
+                .map(|c| format!("#{c}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.table_name,
+            self.on_conflict
+                .as_ref()
+                .map(OnConflict::print)
+                .unwrap_or_default(),
+            print_returning(&self.returning),
+            self.input.print_indent(indent + 1)
+        )
+    }
+
+    pub fn reorder<T>(&self, data: Vec<T>) -> Result<Vec<T>> {
+        reorder_columns(&self.columns, &self.table_schema, data)
+    }
+}
+
+pub struct Update {
+    pub input: PhysicalPlan,
+    pub table_name: String,
+    pub assignments: Vec<(String, LogicalExpr)>,
+    pub selection: LogicalExpr,
+    pub returning: Vec<LogicalExpr>,
+    pub returning_schema: Schema,
+}
+
+impl Update {
+    pub fn new(
+        input: PhysicalPlan,
+        table_name: String,
+        assignments: Vec<(String, LogicalExpr)>,
+        selection: LogicalExpr,
+        returning: Vec<LogicalExpr>,
+        returning_schema: Schema,
+    ) -> Self {
+        Self {
+            input,
+            table_name,
+            assignments,
+            selection,
+            returning,
+            returning_schema,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.returning_schema.clone()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Update: {} [{}]{}\n{}",
+            "-".repeat(indent * 2),
+            self.table_name,
+            self.assignments
+                .iter()
+                .map(|(col, value)| format!("#{} = {}", col, value.print()))
+                .collect::<Vec<_>>()
+                .join(", "),
+            print_returning(&self.returning),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+pub struct Delete {
+    pub input: PhysicalPlan,
+    pub table_name: String,
+    pub selection: LogicalExpr,
+    pub returning: Vec<LogicalExpr>,
+    pub returning_schema: Schema,
+}
+
+impl Delete {
+    pub fn new(
+        input: PhysicalPlan,
+        table_name: String,
+        selection: LogicalExpr,
+        returning: Vec<LogicalExpr>,
+        returning_schema: Schema,
+    ) -> Self {
+        Self {
+            input,
+            table_name,
+            selection,
+            returning,
+            returning_schema,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.returning_schema.clone()
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Delete: {} [{}]{}\n{}",
+            "-".repeat(indent * 2),
+            self.table_name,
+            self.selection.print(),
+            print_returning(&self.returning),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+pub struct Merge {
+    pub input: PhysicalPlan,
+    pub table_name: String,
+    pub input_schema: Schema,
+    pub clauses: Vec<MergeClause>,
+}
+
+impl Merge {
+    pub fn new(
+        input: PhysicalPlan,
+        table_name: String,
+        input_schema: Schema,
+        clauses: Vec<MergeClause>,
+    ) -> Self {
+        Self {
+            input,
+            table_name,
+            input_schema,
+            clauses,
+        }
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Merge: {} [{}]\n{}",
+            "-".repeat(indent * 2),
+            self.table_name,
+            self.clauses
+                .iter()
+                .map(MergeClause::print)
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
+
+pub struct CreateTable {
+    pub table_name: String,
+    pub schema: Schema,
+    pub if_not_exists: bool,
+}
+
+impl CreateTable {
+    pub fn new(table_name: String, schema: Schema, if_not_exists: bool) -> Self {
+        Self {
+            table_name,
+            schema,
+            if_not_exists,
+        }
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} CreateTable: {} [skip if exists: {}]\n",
+            "-".repeat(indent * 2),
+            self.table_name,
+            self.if_not_exists,
+        )
+    }
+}
+
+pub struct Explain {
+    pub input: PhysicalPlan,
+    pub analyze: bool,
+    /// the logical plans the input was planned from
+    pub logical_plan: String,
+}
+
+impl Explain {
+    pub fn new(input: PhysicalPlan, analyze: bool, logical_plan: String) -> Self {
+        Self {
+            input,
+            analyze,
+            logical_plan,
+        }
+    }
+
+    pub fn print(&self, indent: usize) -> String {
+        format!(
+            "{} Explain:\n{}",
+            "-".repeat(indent * 2),
+            self.input.print_indent(indent + 1)
+        )
+    }
+}
@@ -1,5 +1,5 @@
 use crate::buffer_pool::ArcBufferPool;
-use crate::catalog::{CatalogRow, CATALOG_NAME};
+use crate::catalog::{AlterOperation, CatalogRow, CATALOG_NAME};
 use crate::errors::Error;
 use crate::indexes::b_plus_tree::btree::BPlusTree;
 use crate::pages::table_page::{TablePage, META_SIZE, PAGE_END, SLOT_SIZE};
@@ -110,17 +110,18 @@ impl Table {
     }
 
     pub fn get_tuple(&self, id: TupleId) -> Option<Tuple> {
-        let (page, slot) = TupleId::from_bytes(&id.to_bytes());
+        let (page_id, slot) = TupleId::from_bytes(&id.to_bytes());
 
         let page: TablePage = self
             .bpm
             .lock()
-            .fetch_frame(page, self.active_txn)
+            .fetch_frame(page_id, self.active_txn)
             .unwrap()
             .reader()
             .into();
 
         let (meta, tuple) = page.read_tuple(slot);
+        self.bpm.lock().unpin(&page_id, self.active_txn);
 
         if meta.is_deleted() {
             None
@@ -157,7 +158,7 @@ impl Table {
         self.blob_page
     }
 
-    fn iter(&self, txn_id: Option<TxnId>) -> table_iterator::TableIterator {
+    pub fn iter(&self, txn_id: Option<TxnId>) -> table_iterator::TableIterator {
         table_iterator::TableIterator::new(self, txn_id)
     }
 
@@ -331,11 +332,9 @@ impl Table {
             .collect())
     }
 
-    fn check_nullability(&self, tuple: &Tuple) -> Result<()> {
-        for (i, field) in self.schema.fields.iter().enumerate() {
-            if !field.constraints.nullable
-                && tuple.get_value_at(i as u8, &self.schema).unwrap().is_null()
-            {
+    fn check_nullability(&self, values: &[Value]) -> Result<()> {
+        for (field, value) in self.schema.fields.iter().zip(values) {
+            if !field.constraints.nullable && value.is_null() {
                 bail!(Error::NullNotAllowed(field.name.clone()));
             }
         }
@@ -343,14 +342,18 @@ impl Table {
     }
 
     /// Returns None if no uniqueness is defined for the schema.
-    /// Or Some(Key) if the tuple is unique, where Key is the unique value.
-    pub fn check_uniqueness(&self, tuple: &Tuple) -> Result<Option<Value>> {
+    /// Or Some(Key) if the row is unique, where Key is the unique value.
+    /// The key is read from the values rather than a tuple, whose strings
+    /// aren't stored yet so the offsets of the columns after them are off
+    pub fn check_uniqueness(&self, values: &[Value]) -> Result<Option<Value>> {
         for (i, field) in self.schema.fields.iter().enumerate() {
             if field.constraints.unique {
-                // unwrap on option because nullability is checked first and
-                // uniquness disallows null values
+                // nullability is checked first and uniquness disallows null values
                 // also, schema forces unique columns to be castable to u32 (int, uint, float)
-                let key = tuple.get_value_at(i as u8, &self.schema)?;
+                let key = values
+                    .get(i)
+                    .cloned()
+                    .ok_or(Error::Internal("field id out of bounds".into()))?;
 
                 return match self
                     .index
@@ -367,6 +370,13 @@ impl Table {
     }
 
     pub fn insert(&mut self, values: Vec<Value>) -> Result<TupleId> {
+        // catalog rows are rebuilt from CreateTable/DropTable records;
+        // logging them too would double-apply on replay
+        let log = !self.lm.recovering() && self.name != CATALOG_NAME;
+        self.insert_values(values, log)
+    }
+
+    fn insert_values(&mut self, values: Vec<Value>, log: bool) -> Result<TupleId> {
         let txn = self.active_txn.ok_or(Error::NoActiveTransaction)?;
 
         let tuple = Tuple::new(values.clone(), &self.schema);
@@ -378,8 +388,8 @@ impl Table {
             ));
         }
 
-        self.check_nullability(&tuple)?;
-        let key = self.check_uniqueness(&tuple)?;
+        self.check_nullability(&values)?;
+        let key = self.check_uniqueness(&values)?;
 
         let tuple = self.insert_strings(tuple)?;
 
@@ -405,9 +415,7 @@ impl Table {
                         .insert(self.active_txn, key.as_u32(), id)?;
                 };
 
-                // catalog rows are rebuilt from CreateTable/DropTable records;
-                // logging them too would double-apply on replay
-                if !self.lm.recovering() && self.name != CATALOG_NAME {
+                if log {
                     self.lm.lock().append(
                         txn,
                         Record::Operation(RowOperation::Insert(self.name.clone(), values)),
@@ -509,19 +517,17 @@ impl Table {
             Error::Internal("Table: No active transaction".into())
         );
 
-        let new_tuple = Tuple::new(new_values.clone(), &self.schema);
-
         let id = tuple_id.unwrap(); //TODO: Handle None
 
-        self.check_nullability(&new_tuple)?;
-        let key = self.check_uniqueness(&new_tuple);
+        self.check_nullability(&new_values)?;
+        let key = self.check_uniqueness(&new_values);
 
         // value is not unique, does it collide with
         // the old (to be deleted) tuple or an existing tuple?
         if key.is_err() {
             let unique_column_id = self.get_unique_column_id().unwrap();
 
-            let new_key = new_tuple.get_value_at(unique_column_id, &self.schema)?;
+            let new_key = new_values[unique_column_id as usize].clone();
             let old_tuple = self.get_tuple(id).unwrap();
             let old_key = old_tuple.get_value_at(unique_column_id, &self.schema)?;
 
@@ -547,6 +553,77 @@ impl Table {
         Ok(tuple_id)
     }
 
+    /// Needs to return a duplicate because of how catalog handles ownership.
+    /// Renames keep the pages, adding or dropping a column copies the rows
+    /// into new pages laid out in the new schema
+    pub fn alter(&self, operation: &AlterOperation, txn: TxnId) -> Result<Table> {
+        let schema = operation.apply(&self.schema)?;
+
+        if !self.lm.recovering() {
+            self.lm.lock().append(
+                txn,
+                Record::AlterTable(self.name.clone(), operation.clone()),
+            );
+        }
+
+        let name = match operation {
+            AlterOperation::RenameTable(name) => name.clone(),
+            _ => self.name.clone(),
+        };
+
+        if !operation.rewrites_rows() {
+            let index = self.index.as_ref().map(|index| {
+                BPlusTree::fetch(
+                    index.get_root_page_id(),
+                    self.bpm.clone(),
+                    self.txn_manager.clone(),
+                )
+            });
+
+            return Ok(Self {
+                name,
+                first_page: self.first_page,
+                last_page: self.last_page,
+                blob_page: self.blob_page,
+                bpm: self.bpm.clone(),
+                txn_manager: self.txn_manager.clone(),
+                lm: self.lm.clone(),
+                active_txn: self.active_txn,
+                schema,
+                index,
+            });
+        }
+
+        let first_page = self.bpm.lock().new_page()?.reader().get_page_id();
+        let index = BPlusTree::new(self.bpm.clone(), self.txn_manager.clone(), self.active_txn);
+
+        let mut table = Self {
+            name,
+            first_page,
+            last_page: first_page,
+            blob_page: self.blob_page,
+            bpm: self.bpm.clone(),
+            txn_manager: self.txn_manager.clone(),
+            lm: self.lm.clone(),
+            active_txn: self.active_txn,
+            schema,
+            index: Some(index),
+        };
+
+        let mut rows = vec![];
+        self.scan(self.active_txn, |(_, (_, tuple))| {
+            rows.push(self.get_portable_values(tuple)?);
+            Ok(())
+        })?;
+
+        // replaying the AlterTable record copies the rows again
+        for row in rows {
+            table.insert_values(operation.apply_to_row(&self.schema, row), false)?;
+        }
+
+        Ok(table)
+    }
+
     /// Needs to return a duplicate because of how catalog handles ownership
     pub fn truncate(&self, txn: TxnId) -> Result<Table> {
         if !self.lm.recovering() {
@@ -7,13 +7,14 @@ use crate::txn_manager::TxnId;
 use super::Table;
 
 // TODO: try to iterate over pages not tuples
-pub(super) struct TableIterator {
+pub struct TableIterator {
     page: TablePage,
     current_slot: SlotId,
     next_page: PageId,
     bpm: ArcBufferPool,
     num_tuples: SlotId,
     active_txn: Option<TxnId>,
+    done: bool,
 }
 
 impl TableIterator {
@@ -35,6 +36,7 @@ impl TableIterator {
             page,
             bpm,
             active_txn: txn_id,
+            done: false,
         }
     }
 }
@@ -43,48 +45,57 @@ impl Iterator for TableIterator {
     type Item = (TupleId, Entry);
 
     fn next(&mut self) -> Option<Self::Item> {
-        // current page is done, drop it
-        if self.current_slot >= self.num_tuples {
-            let page_id = self.page.get_page_id();
-            self.bpm.lock().unpin(&page_id, self.active_txn);
-        }
+        loop {
+            if self.done {
+                return None;
+            }
+
+            // current page is done, drop it
+            if self.current_slot >= self.num_tuples {
+                let page_id = self.page.get_page_id();
+                self.bpm.lock().unpin(&page_id, self.active_txn);
+
+                if self.next_page == INVALID_PAGE || self.next_page == 0 {
+                    self.done = true;
+                    return None;
+                }
+
+                self.page = self
+                    .bpm
+                    .lock()
+                    .fetch_frame(self.next_page, self.active_txn)
+                    .unwrap()
+                    .reader()
+                    .into();
+
+                self.current_slot = 0;
+                let header = self.page.header();
+                self.next_page = header.get_next_page();
+                self.num_tuples = header.get_num_tuples();
+                continue;
+            }
+
+            let (meta, tuple) = self.page.read_tuple(self.current_slot);
+            self.current_slot += 1;
+
+            if meta.is_deleted() {
+                continue;
+            }
 
-        if self.current_slot >= self.num_tuples
-            && (self.next_page == INVALID_PAGE || self.next_page == 0)
-        {
-            return None;
-        }
+            let page_id = self.page.get_page_id();
 
-        if self.current_slot >= self.num_tuples {
-            self.page = self
-                .bpm
-                .lock()
-                .fetch_frame(self.next_page, self.active_txn)
-                .unwrap()
-                .reader()
-                .into();
-
-            self.current_slot = 0;
-            let header = self.page.header();
-            self.next_page = header.get_next_page();
-            self.num_tuples = header.get_num_tuples();
-            return self.next();
+            return Some(((page_id, self.current_slot - 1), (meta, tuple)));
         }
+    }
+}
 
-        let (meta, tuple) = self.page.read_tuple(self.current_slot);
-        self.current_slot += 1;
-
-        // TODO: Can run into stack overflows because of recursion
-        // I hate how rust doesn't have tail recursion
-        if meta.is_deleted() {
-            return self.next();
+// scans that stop early leave the current page pinned
+impl Drop for TableIterator {
+    fn drop(&mut self) {
+        if !self.done {
+            let page_id = self.page.get_page_id();
+            self.bpm.lock().unpin(&page_id, self.active_txn);
         }
-
-        let entry = (meta, tuple);
-
-        let page_id = self.page.get_page_id();
-
-        Some(((page_id, self.current_slot - 1), entry))
     }
 }
 
@@ -194,4 +205,38 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_stop_early() -> Result<()> {
+        let schema = Schema::new(vec![Field::new(
+            "a",
+            Types::Int,
+            Constraints::nullable(true),
+        )]);
+        let mut table = test_table(3, &schema)?;
+
+        let txn = begin(&mut table)?;
+        table.insert(vec![lit!(Int, "1")?])?;
+        table.insert(vec![lit!(Int, "2")?])?;
+        commit(&mut table, txn)?;
+
+        let pin_count =
+            |table: &super::Table| table.bpm.lock().get_pin_count(&table.first_page).unwrap();
+
+        let mut iter = TableIterator::new(&table, None);
+        assert!(iter.next().is_some());
+        assert_eq!(pin_count(&table), 1);
+
+        // the page is unpinned once, when the iterator is dropped
+        drop(iter);
+        assert_eq!(pin_count(&table), 0);
+
+        let mut iter = TableIterator::new(&table, None);
+        assert_eq!(iter.by_ref().count(), 2);
+        assert!(iter.next().is_none());
+        drop(iter);
+        assert_eq!(pin_count(&table), 0);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,233 @@
+use std::cmp::Ordering;
+
+use anyhow::{bail, ensure, Result};
+
+use super::{Bool, Float, Int, Str, Types, UInt, Value, ValueFactory};
+use crate::errors::Error;
+
+/// Implicit coercions go along UINT -> INT -> FLOAT. NULL coerces to any
+/// type, everything else needs an explicit CAST. Not every coercion widens:
+/// a UINT past INT's range fails to coerce and a large INT loses precision
+/// as a FLOAT
+impl Types {
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Types::UInt | Types::Int | Types::Float)
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Types::UInt | Types::Int)
+    }
+
+    /// Whether a value of this type can be used where `target` is expected,
+    /// the value may still not fit in `target`
+    pub fn coerces_to(&self, target: &Types) -> bool {
+        self == target
+            || matches!(
+                (self, target),
+                (Types::Null, _)
+                    | (Types::UInt, Types::Int | Types::Float)
+                    | (Types::Int, Types::Float)
+            )
+    }
+
+    /// The narrowest type all of `types` coerce to, NULL if they're all NULL
+    pub fn common(types: &[Types]) -> Option<Types> {
+        types.iter().try_fold(Types::Null, |common, ty| {
+            if common.coerces_to(ty) {
+                Some(ty.clone())
+            } else if ty.coerces_to(&common) {
+                Some(common)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Whether CAST can convert values of this type to `target`, anything
+    /// converts to and from text
+    pub fn can_cast(&self, target: &Types) -> bool {
+        !matches!(
+            (self, target),
+            (Types::Bool, Types::Float)
+                | (Types::Float, Types::Bool)
+                | (Types::StrAddr, _)
+                | (_, Types::StrAddr | Types::Null)
+        )
+    }
+}
+
+fn integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Int(Int(v)) => Some(*v as i64),
+        Value::UInt(UInt(v)) => Some(*v as i64),
+        _ => None,
+    }
+}
+
+fn float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float(Float(v)) => Some(*v as f64),
+        v => integer(v).map(|v| v as f64),
+    }
+}
+
+impl Value {
+    /// Explicit conversion, fails if the value doesn't fit in the type.
+    /// Floats are rounded to the nearest integer
+    pub fn cast(&self, ty: &Types) -> Result<Value> {
+        let out_of_range = || {
+            Error::Expected(
+                format!("a value in the range of {}", ty.to_sql()),
+                self.to_string(),
+            )
+        };
+
+        Ok(match (self, ty) {
+            (Value::Null, _) => Value::Null,
+            (v, ty) if v.get_type() == *ty => v.clone(),
+            (Value::UInt(UInt(v)), Types::Int) => {
+                Value::Int(Int(i32::try_from(*v).map_err(|_| out_of_range())?))
+            }
+            (Value::Int(Int(v)), Types::UInt) => {
+                Value::UInt(UInt(u32::try_from(*v).map_err(|_| out_of_range())?))
+            }
+            (Value::UInt(_) | Value::Int(_), Types::Float) => Value::Float(Float(self.as_f32())),
+            (Value::Float(Float(v)), Types::Int | Types::UInt) => {
+                let v = (*v as f64).round();
+                let (min, max) = match ty {
+                    Types::Int => (i32::MIN as f64, i32::MAX as f64),
+                    _ => (0.0, u32::MAX as f64),
+                };
+                ensure!(v >= min && v <= max, out_of_range());
+                match ty {
+                    Types::Int => Value::Int(Int(v as i32)),
+                    _ => Value::UInt(UInt(v as u32)),
+                }
+            }
+            (Value::Bool(Bool(v)), Types::Int) => Value::Int(Int(*v as i32)),
+            (Value::Bool(Bool(v)), Types::UInt) => Value::UInt(UInt(*v as u32)),
+            (Value::Int(_) | Value::UInt(_), Types::Bool) => {
+                Value::Bool(Bool(integer(self) != Some(0)))
+            }
+            (v, Types::Str) => Value::Str(Str(v.to_string_unquoted())),
+            (Value::Str(Str(s)), Types::Bool) => match s.trim().to_lowercase().as_str() {
+                "true" | "t" | "yes" | "y" | "on" | "1" => Value::Bool(Bool(true)),
+                "false" | "f" | "no" | "n" | "off" | "0" => Value::Bool(Bool(false)),
+                _ => bail!(Error::ParseFailed(
+                    s.clone(),
+                    Types::Bool,
+                    "not a boolean".into()
+                )),
+            },
+            (Value::Str(Str(s)), Types::UInt | Types::Int | Types::Float) => {
+                ValueFactory::from_string(ty, s.trim())?
+            }
+            (v, ty) => bail!(Error::Unsupported(format!("CAST({v} AS {})", ty.to_sql()))),
+        })
+    }
+
+    /// Implicit conversion to the type the value is used as
+    pub fn coerce(&self, ty: &Types) -> Result<Value> {
+        let from = self.get_type();
+        ensure!(
+            from.coerces_to(ty),
+            Error::TypeMismatch(vec![ty.clone()], vec![from])
+        );
+        self.cast(ty)
+    }
+
+    /// Applies `op` to numbers of different types converted to their
+    /// common type
+    pub(super) fn with_common_type(
+        &self,
+        other: &Self,
+        op: fn(&Value, &Value) -> Result<Value>,
+        symbol: &str,
+    ) -> Result<Value> {
+        let (l, r) = (self.get_type(), other.get_type());
+        match Types::common(&[l.clone(), r.clone()]) {
+            Some(ty) if l != r && ty.is_numeric() => op(&self.cast(&ty)?, &other.cast(&ty)?),
+            _ => bail!(Error::Unimplemented(format!("{self} {symbol} {other}"))),
+        }
+    }
+
+    /// Numbers of different types compare as their common type, integers
+    /// compare exactly with each other
+    pub(super) fn numeric_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (integer(self), integer(other)) {
+            (Some(l), Some(r)) => Some(l.cmp(&r)),
+            _ => float(self)?.partial_cmp(&float(other)?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lit;
+
+    #[test]
+    fn test_common_type() {
+        use Types::*;
+
+        assert_eq!(Types::common(&[UInt, Int]), Some(Int));
+        assert_eq!(Types::common(&[Null, UInt, Float, Int]), Some(Float));
+        assert_eq!(Types::common(&[Null, Null]), Some(Null));
+        assert_eq!(Types::common(&[Str, Null]), Some(Str));
+        assert_eq!(Types::common(&[Int, Str]), None);
+        assert_eq!(Types::common(&[Bool, Int]), None);
+
+        assert!(UInt.coerces_to(&Float));
+        assert!(!Float.coerces_to(&Int));
+        assert!(!Int.coerces_to(&UInt));
+        assert!(Str.can_cast(&Int));
+        assert!(!Bool.can_cast(&Float));
+    }
+
+    #[test]
+    fn test_cast() -> Result<()> {
+        assert_eq!(lit!(UInt, "3")?.cast(&Types::Float)?, lit!(Float, "3.0")?);
+        assert_eq!(lit!(Float, "2.5")?.cast(&Types::Int)?, lit!(Int, "3")?);
+        assert_eq!(lit!(Float, "-2.5")?.cast(&Types::Int)?, lit!(Int, "-3")?);
+        assert!(lit!(Float, "-1.0")?.cast(&Types::UInt).is_err());
+        assert!(lit!(Int, "-1")?.cast(&Types::UInt).is_err());
+        assert!(lit!(UInt, "3000000000")?.cast(&Types::Int).is_err());
+        assert!(lit!(Float, "1e10")?.cast(&Types::Int).is_err());
+
+        assert_eq!(lit!(Str, " 42 ")?.cast(&Types::Int)?, lit!(Int, "42")?);
+        assert!(lit!(Str, "4x")?.cast(&Types::Int).is_err());
+        assert_eq!(lit!(Str, "yes")?.cast(&Types::Bool)?, lit!(Bool, "true")?);
+        assert!(lit!(Str, "maybe")?.cast(&Types::Bool).is_err());
+        assert_eq!(lit!(Float, "1.5")?.cast(&Types::Str)?, lit!(Str, "1.5")?);
+        assert_eq!(lit!(Bool, "true")?.cast(&Types::UInt)?, lit!(UInt, "1")?);
+        assert!(Value::Null.cast(&Types::Int)?.is_null());
+
+        // implicit coercions go along UINT -> INT -> FLOAT
+        assert_eq!(lit!(UInt, "1")?.coerce(&Types::Float)?, lit!(Float, "1.0")?);
+        assert!(lit!(UInt, "4294967295")?.coerce(&Types::Int).is_err());
+        assert!(lit!(Float, "1.0")?.coerce(&Types::Int).is_err());
+        assert!(lit!(Str, "1")?.coerce(&Types::Int).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mixed_arithmetic() -> Result<()> {
+        let one = lit!(UInt, "1")?;
+        let half = lit!(Float, "0.5")?;
+        let minus_two = lit!(Int, "-2")?;
+
+        assert_eq!(one.add(&half)?, lit!(Float, "1.5")?);
+        assert_eq!(half.sub(&one)?, lit!(Float, "-0.5")?);
+        assert_eq!(one.div(&minus_two)?, lit!(Int, "0")?);
+        assert_eq!(minus_two.mul(&half)?, lit!(Float, "-1.0")?);
+        assert!(one.add(&lit!(Str, "a")?).is_err());
+
+        assert!(one < lit!(Float, "1.5")?);
+        assert!(minus_two < one);
+        assert_eq!(lit!(Float, "1.0")?, one);
+        assert_ne!(lit!(Int, "-1")?, lit!(UInt, "4294967295")?);
+
+        Ok(())
+    }
+}
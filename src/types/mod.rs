@@ -2,6 +2,8 @@ use anyhow::bail;
 use anyhow::Result;
 use std::fmt::Debug;
 use std::fmt::Display;
+use std::hash::Hash;
+use std::hash::Hasher;
 use std::num::ParseFloatError;
 use std::num::ParseIntError;
 
@@ -12,6 +14,10 @@ use crate::tuple::TupleExt;
 use crate::tuple::TupleId;
 use crate::tuple::TUPLE_ID_SIZE;
 
+mod coercion;
+mod pattern;
+pub use pattern::with_escape;
+
 #[derive(PartialEq, Eq, Clone, Debug)] // others
 #[derive(Serialize, Deserialize)] // for schema serde
 pub enum Types {
@@ -48,24 +54,6 @@ impl Types {
         }
     }
 
-    // used when checking inserted rows for compatibility
-    // it is the user's responsibility to ensure that values match
-    // the table's schema (inserting UINT value in an INT column)
-    pub fn is_compatible(&self, other: &Types) -> bool {
-        matches!(
-            (self, other),
-            (Types::UInt, Types::UInt)
-                | (Types::Int, Types::Int)
-                | (Types::Float, Types::Float)
-                | (Types::Bool, Types::Bool)
-                | (Types::Str, Types::Str)
-                | (Types::Int, Types::UInt)
-                | (Types::StrAddr, Types::StrAddr)
-                | (Types::Null, _)
-                | (_, Types::Null)
-        )
-    }
-
     pub fn from_sql(s: &str) -> Result<Self> {
         Ok(match s.to_uppercase().as_str() {
             "UINT" | "INT UNSIGNED" => Types::UInt,
@@ -96,6 +84,42 @@ macro_rules! impl_cast_to_u32 {
     };
 }
 
+macro_rules! impl_cast_to_f32 {
+    ($($variant:ident),+ $(,)?) => {
+        impl Value {
+            pub fn as_f32(&self) -> f32 {
+                match self {
+                    $(
+                        Value::$variant(v) => v.0 as f32,
+                    )+
+                    _ => panic!(
+                        "Internal Error: forced cast error: {:?} => f32",
+                        self,
+                    ),
+                }
+            }
+        }
+    };
+}
+
+macro_rules! impl_cast_to_f64 {
+    ($($variant:ident),+ $(,)?) => {
+        impl Value {
+            pub fn as_f64(&self) -> f64 {
+                match self {
+                    $(
+                        Value::$variant(v) => v.0 as f64,
+                    )+
+                    _ => panic!(
+                        "Internal Error: forced cast error: {:?} => f64",
+                        self,
+                    ),
+                }
+            }
+        }
+    };
+}
+
 macro_rules! impl_value_methods {
     ($($variant:ident($ty:ident)),+ $(,)?) => {
         impl Value {
@@ -136,6 +160,8 @@ impl Value {
 
 impl_value_methods!(Int(i32), Float(f32), UInt(u32), Bool(bool));
 impl_cast_to_u32!(Int, Float, UInt);
+impl_cast_to_f32!(Int, Float, UInt);
+impl_cast_to_f64!(Int, Float, UInt);
 
 pub type StrAddr = TupleId;
 
@@ -165,78 +191,120 @@ impl Value {
 
     pub fn add(&self, other: &Self) -> Result<Self> {
         match (self, other) {
-            (Value::UInt(UInt(l)), Value::UInt(UInt(r))) => Ok(Value::UInt(UInt(l + r))),
-            (Value::Int(Int(l)), Value::Int(Int(r))) => Ok(Value::Int(Int(l + r))),
+            (Value::UInt(UInt(l)), Value::UInt(UInt(r))) => Ok(Value::UInt(UInt(self.checked(
+                l.checked_add(*r),
+                "+",
+                other,
+            )?))),
+            (Value::Int(Int(l)), Value::Int(Int(r))) => Ok(Value::Int(Int(self.checked(
+                l.checked_add(*r),
+                "+",
+                other,
+            )?))),
             (Value::Float(Float(l)), Value::Float(Float(r))) => Ok(Value::Float(Float(l + r))),
-            (Value::UInt(UInt(l)), Value::Int(Int(r))) => Ok(Value::Int(Int(*l as i32 + r))),
-            (Value::Int(Int(l)), Value::UInt(UInt(r))) => Ok(Value::Int(Int(l + *r as i32))),
-            (Value::Int(Int(l)), Value::Float(Float(r))) => Ok(Value::Float(Float(*l as f32 + r))),
-            (Value::Float(Float(l)), Value::Int(Int(r))) => Ok(Value::Float(Float(l + *r as f32))),
-            (l, r) => bail!(Error::Unimplemented(format!("{l} + {r}"))),
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+            (l, r) => l.with_common_type(r, Value::add, "+"),
         }
     }
 
     pub fn sub(&self, other: &Self) -> Result<Self> {
         match (self, other) {
-            (Value::UInt(UInt(l)), Value::UInt(UInt(r))) => Ok(Value::UInt(UInt(l - r))),
-            (Value::Int(Int(l)), Value::Int(Int(r))) => Ok(Value::Int(Int(l - r))),
+            (Value::UInt(UInt(l)), Value::UInt(UInt(r))) => Ok(Value::UInt(UInt(self.checked(
+                l.checked_sub(*r),
+                "-",
+                other,
+            )?))),
+            (Value::Int(Int(l)), Value::Int(Int(r))) => Ok(Value::Int(Int(self.checked(
+                l.checked_sub(*r),
+                "-",
+                other,
+            )?))),
             (Value::Float(Float(l)), Value::Float(Float(r))) => Ok(Value::Float(Float(l - r))),
-            (Value::UInt(UInt(l)), Value::Int(Int(r))) => Ok(Value::Int(Int(*l as i32 - r))),
-            (Value::Int(Int(l)), Value::UInt(UInt(r))) => Ok(Value::Int(Int(l - *r as i32))),
-            (Value::Int(Int(l)), Value::Float(Float(r))) => Ok(Value::Float(Float(*l as f32 - r))),
-            (Value::Float(Float(l)), Value::Int(Int(r))) => Ok(Value::Float(Float(l - *r as f32))),
-            (l, r) => bail!(Error::Unimplemented(format!("{l} - {r}"))),
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+            (l, r) => l.with_common_type(r, Value::sub, "-"),
         }
     }
 
     pub fn mul(&self, other: &Self) -> Result<Self> {
         match (self, other) {
-            (Value::UInt(UInt(l)), Value::UInt(UInt(r))) => Ok(Value::UInt(UInt(l * r))),
-            (Value::Int(Int(l)), Value::Int(Int(r))) => Ok(Value::Int(Int(l * r))),
+            (Value::UInt(UInt(l)), Value::UInt(UInt(r))) => Ok(Value::UInt(UInt(self.checked(
+                l.checked_mul(*r),
+                "*",
+                other,
+            )?))),
+            (Value::Int(Int(l)), Value::Int(Int(r))) => Ok(Value::Int(Int(self.checked(
+                l.checked_mul(*r),
+                "*",
+                other,
+            )?))),
             (Value::Float(Float(l)), Value::Float(Float(r))) => Ok(Value::Float(Float(l * r))),
-            (Value::UInt(UInt(l)), Value::Int(Int(r))) => Ok(Value::Int(Int(*l as i32 * r))),
-            (Value::Int(Int(l)), Value::UInt(UInt(r))) => Ok(Value::Int(Int(l * *r as i32))),
-            (Value::Int(Int(l)), Value::Float(Float(r))) => Ok(Value::Float(Float(*l as f32 * r))),
-            (Value::Float(Float(l)), Value::Int(Int(r))) => Ok(Value::Float(Float(l * *r as f32))),
-            (Value::UInt(UInt(l)), Value::Float(r)) => Ok(Value::Float(Float(*l as f32 * r.0))),
             (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
-            (Value::Float(Float(l)), Value::UInt(UInt(r))) => {
-                Ok(Value::Float(Float(l * *r as f32)))
-            }
-            (l, r) => bail!(Error::Unimplemented(format!("{l} * {r}"))),
+            (l, r) => l.with_common_type(r, Value::mul, "*"),
         }
     }
 
     pub fn div(&self, other: &Self) -> Result<Self> {
         match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
             (_, Value::UInt(UInt(0))) | (_, Value::Int(Int(0))) | (_, Value::Float(Float(0.0))) => {
                 bail!(Error::DivisionByZero)
             }
             (Value::UInt(UInt(l)), Value::UInt(UInt(r))) => Ok(Value::UInt(UInt(l / r))),
-            (Value::Int(Int(l)), Value::Int(Int(r))) => Ok(Value::Int(Int(l / r))),
+            (Value::Int(Int(l)), Value::Int(Int(r))) => Ok(Value::Int(Int(self.checked(
+                l.checked_div(*r),
+                "/",
+                other,
+            )?))),
             (Value::Float(Float(l)), Value::Float(Float(r))) => Ok(Value::Float(Float(l / r))),
-            (Value::UInt(UInt(l)), Value::Int(Int(r))) => Ok(Value::Int(Int(*l as i32 / r))),
-            (Value::Int(Int(l)), Value::UInt(UInt(r))) => Ok(Value::Int(Int(l / *r as i32))),
-            (Value::Int(Int(l)), Value::Float(Float(r))) => Ok(Value::Float(Float(*l as f32 / r))),
-            (Value::Float(Float(l)), Value::Int(Int(r))) => Ok(Value::Float(Float(l / *r as f32))),
-            (l, r) => bail!(Error::Unimplemented(format!("{l} / {r}"))),
+            (l, r) => l.with_common_type(r, Value::div, "/"),
         }
     }
 
+    /// The result of an integer operation, an error if it overflowed
+    fn checked<T>(&self, result: Option<T>, symbol: &str, other: &Self) -> Result<T> {
+        result.ok_or(Error::OutOfRange(format!("{self} {symbol} {other}")).into())
+    }
+
+    /// NULL is unknown, false if the other side is false and unknown otherwise
     pub fn and(&self, other: &Self) -> Result<Self> {
         match (self, other) {
             (Value::Bool(Bool(l)), Value::Bool(Bool(r))) => Ok(Value::Bool(Bool(*l && *r))),
+            (Value::Bool(Bool(false)), Value::Null) | (Value::Null, Value::Bool(Bool(false))) => {
+                Ok(Value::Bool(Bool(false)))
+            }
+            (Value::Bool(_) | Value::Null, Value::Bool(_) | Value::Null) => Ok(Value::Null),
             (l, r) => bail!(Error::Unimplemented(format!("{l} && {r}"))),
         }
     }
 
+    /// NULL is unknown, true if the other side is true and unknown otherwise
     pub fn or(&self, other: &Self) -> Result<Self> {
         match (self, other) {
             (Value::Bool(Bool(l)), Value::Bool(Bool(r))) => Ok(Value::Bool(Bool(*l || *r))),
+            (Value::Bool(Bool(true)), Value::Null) | (Value::Null, Value::Bool(Bool(true))) => {
+                Ok(Value::Bool(Bool(true)))
+            }
+            (Value::Bool(_) | Value::Null, Value::Bool(_) | Value::Null) => Ok(Value::Null),
             (l, r) => bail!(Error::Unimplemented(format!("{l} || {r}"))),
         }
     }
 
+    pub fn not(&self) -> Result<Self> {
+        match self {
+            Value::Bool(Bool(v)) => Ok(Value::Bool(Bool(!v))),
+            Value::Null => Ok(Value::Null),
+            v => bail!(Error::Unimplemented(format!("NOT {v}"))),
+        }
+    }
+
+    /// Values of other types are concatenated as text, like in postgres
+    pub fn concat(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => Value::Null,
+            (l, r) => Value::Str(Str(l.to_string_unquoted() + &r.to_string_unquoted())),
+        }
+    }
+
     pub fn equ(&self, other: &Self) -> Result<bool> {
         match (self, other) {
             (Value::Null, Value::Null) => Ok(true),
@@ -246,9 +314,10 @@ impl Value {
             (Value::Float(l), Value::Float(r)) => Ok(l == r),
             (Value::Bool(l), Value::Bool(r)) => Ok(l == r),
             (Value::Str(l), Value::Str(r)) => Ok(l == r),
-            (Value::Int(Int(l)), Value::UInt(UInt(r))) => Ok(*l as u32 == *r),
-            (Value::UInt(UInt(l)), Value::Int(Int(r))) => Ok(*l == *r as u32),
-            (l, r) => bail!(Error::Unimplemented(format!("{l} = {r}"))),
+            (l, r) => match l.numeric_cmp(r) {
+                Some(ordering) => Ok(ordering.is_eq()),
+                None => bail!(Error::Unimplemented(format!("{l} = {r}"))),
+            },
         }
     }
 }
@@ -259,6 +328,29 @@ impl PartialEq for Value {
     }
 }
 
+impl Eq for Value {}
+
+/// Must agree with [`Value::equ`], so that rows can be used as keys
+/// when grouping or eliminating duplicates
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            // numbers of different types can be equal, integral floats hash
+            // like the integer they're equal to, and 0.0 == -0.0
+            Value::Int(Int(v)) => (*v as i64).hash(state),
+            Value::UInt(UInt(v)) => (*v as i64).hash(state),
+            Value::Float(Float(v)) if v.fract() == 0.0 && v.abs() < i64::MAX as f32 => {
+                (*v as i64).hash(state)
+            }
+            Value::Float(Float(v)) => v.to_bits().hash(state),
+            Value::Bool(Bool(v)) => v.hash(state),
+            Value::Str(Str(v)) => v.hash(state),
+            Value::StrAddr(v) => v.hash(state),
+            Value::Null => 0u8.hash(state),
+        }
+    }
+}
+
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         match (self, other) {
@@ -270,13 +362,7 @@ impl PartialOrd for Value {
             (Value::Null, Value::Null) => Some(std::cmp::Ordering::Equal),
             (Value::Null, _) => Some(std::cmp::Ordering::Less),
             (_, Value::Null) => Some(std::cmp::Ordering::Greater),
-            (Value::Int(Int(l)), Value::UInt(UInt(r))) => l.partial_cmp(&(*r as i32)),
-            (Value::UInt(UInt(l)), Value::Int(Int(r))) => (*l as i32).partial_cmp(r),
-            (Value::UInt(UInt(l)), Value::Float(Float(r))) => (*l as f32).partial_cmp(r),
-            (Value::Float(Float(l)), Value::UInt(UInt(r))) => l.partial_cmp(&(*r as f32)),
-            (Value::Int(Int(l)), Value::Float(Float(r))) => (*l as f32).partial_cmp(r),
-            (Value::Float(Float(l)), Value::Int(Int(r))) => l.partial_cmp(&(*r as f32)),
-            _ => None,
+            (l, r) => l.numeric_cmp(r),
         }
     }
 }
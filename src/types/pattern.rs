@@ -0,0 +1,227 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use regex::{Regex, RegexBuilder};
+
+use super::{Bool, Str, Value};
+use crate::errors::Error;
+
+/// Compiled regular expressions are reused across rows, they're dropped
+/// once there are too many
+const REGEX_CACHE_SIZE: usize = 64;
+
+thread_local! {
+    static REGEXES: RefCell<HashMap<(String, bool), Regex>> = RefCell::new(HashMap::new());
+}
+
+enum Token {
+    /// `%`
+    Any,
+    /// `_`
+    One,
+    Char(char),
+}
+
+/// `\` escapes the next character of the pattern
+fn tokenize(pattern: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = pattern.chars();
+
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '%' => Token::Any,
+            '_' => Token::One,
+            '\\' => match chars.next() {
+                Some(c) => Token::Char(c),
+                None => bail!(Error::Expected(
+                    "a LIKE pattern that doesn't end with an escape character".into(),
+                    pattern.to_string()
+                )),
+            },
+            c => Token::Char(c),
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// Backtracks to the last `%` on a mismatch, so it's linear in the
+/// string for each `%` of the pattern
+fn like(s: &[char], pattern: &[Token]) -> bool {
+    let (mut si, mut pi) = (0, 0);
+    // position of the last `%` and of the character it matched up to
+    let mut any = None;
+
+    while si < s.len() {
+        match pattern.get(pi) {
+            Some(Token::Any) => {
+                any = Some((pi, si));
+                pi += 1;
+            }
+            Some(Token::One) => {
+                si += 1;
+                pi += 1;
+            }
+            Some(Token::Char(c)) if *c == s[si] => {
+                si += 1;
+                pi += 1;
+            }
+            _ => match any {
+                Some((any_pi, any_si)) => {
+                    pi = any_pi + 1;
+                    si = any_si + 1;
+                    any = Some((any_pi, any_si + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[pi..].iter().all(|t| matches!(t, Token::Any))
+}
+
+/// Rewrites a LIKE pattern with a custom ESCAPE character to one escaped
+/// with `\`, an empty escape disables escaping
+pub fn with_escape(pattern: &str, escape: &str) -> Result<String> {
+    let mut escape_chars = escape.chars();
+    let escape = match (escape_chars.next(), escape_chars.next()) {
+        (escape, None) => escape,
+        _ => bail!(Error::Expected(
+            "a single ESCAPE character".into(),
+            escape.to_string()
+        )),
+    };
+
+    let mut rewritten = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+
+    while let Some(c) = chars.next() {
+        if Some(c) == escape {
+            rewritten.push('\\');
+            match chars.next() {
+                Some(c) => rewritten.push(c),
+                None => bail!(Error::Expected(
+                    "a LIKE pattern that doesn't end with an escape character".into(),
+                    pattern.to_string()
+                )),
+            }
+        } else if c == '\\' {
+            rewritten.push_str("\\\\");
+        } else {
+            rewritten.push(c);
+        }
+    }
+
+    Ok(rewritten)
+}
+
+impl Value {
+    /// `%` matches any sequence of characters and `_` any single one,
+    /// the whole string has to match
+    pub fn like(&self, pattern: &Self, case_insensitive: bool) -> Result<Self> {
+        match (self, pattern) {
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+            (Value::Str(Str(s)), Value::Str(Str(p))) => {
+                let (s, p) = match case_insensitive {
+                    true => (s.to_lowercase(), p.to_lowercase()),
+                    false => (s.clone(), p.clone()),
+                };
+                let s = s.chars().collect::<Vec<_>>();
+                Ok(Value::Bool(Bool(like(&s, &tokenize(&p)?))))
+            }
+            (l, r) => bail!(Error::Unimplemented(format!("{l} LIKE {r}"))),
+        }
+    }
+
+    /// The expression matches anywhere in the string unless it's anchored
+    pub fn regex_match(&self, pattern: &Self, case_insensitive: bool) -> Result<Self> {
+        match (self, pattern) {
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+            (Value::Str(Str(s)), Value::Str(Str(p))) => REGEXES.with_borrow_mut(|regexes| {
+                let key = (p.clone(), case_insensitive);
+                if !regexes.contains_key(&key) {
+                    let regex = RegexBuilder::new(p)
+                        .case_insensitive(case_insensitive)
+                        .build()
+                        .map_err(|e| {
+                            Error::Expected("a valid regular expression".into(), e.to_string())
+                        })?;
+
+                    if regexes.len() == REGEX_CACHE_SIZE {
+                        regexes.clear();
+                    }
+                    regexes.insert(key.clone(), regex);
+                }
+
+                Ok(Value::Bool(Bool(regexes[&key].is_match(s))))
+            }),
+            (l, r) => bail!(Error::Unimplemented(format!("{l} ~ {r}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lit;
+    use crate::types::{Types, ValueFactory};
+
+    fn like(s: &str, pattern: &str) -> Result<bool> {
+        Ok(lit!(Str, s)?.like(&lit!(Str, pattern)?, false)?.bool())
+    }
+
+    #[test]
+    fn test_like() -> Result<()> {
+        assert!(like("hello", "hello")?);
+        assert!(like("hello", "h%")?);
+        assert!(like("hello", "%llo")?);
+        assert!(like("hello", "%l%l%")?);
+        assert!(like("hello", "h_llo")?);
+        assert!(like("", "%")?);
+        assert!(!like("hello", "h_lo")?);
+        assert!(!like("hello", "hello_")?);
+        assert!(!like("hello", "H%")?);
+        assert!(like("mississippi", "%iss%ppi")?);
+        assert!(!like("mississippi", "%iss%ppx")?);
+
+        // escaped wildcards are literal characters
+        assert!(like("50%", "50\\%")?);
+        assert!(!like("500", "50\\%")?);
+        assert!(like("a_b", "a\\_b")?);
+        assert!(!like("axb", "a\\_b")?);
+        assert!(like("a\\b", "a\\\\b")?);
+        assert!(like("hello", "h\\ello")?);
+        assert!(like("x", "x\\").is_err());
+
+        let s = lit!(Str, "HeLLo")?;
+        assert!(s.like(&lit!(Str, "hel%")?, true)?.bool());
+        assert!(s.like(&Value::Null, false)?.is_null());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_escape() -> Result<()> {
+        assert_eq!(with_escape("50#%", "#")?, "50\\%");
+        assert_eq!(with_escape("a##b", "#")?, "a\\#b");
+        assert_eq!(with_escape("a\\b%", "#")?, "a\\\\b%");
+        assert_eq!(with_escape("a\\%", "")?, "a\\\\%");
+        assert!(with_escape("a#", "#").is_err());
+        assert!(with_escape("a", "##").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_regex_match() -> Result<()> {
+        let s = lit!(Str, "support-team")?;
+        assert!(s.regex_match(&lit!(Str, "^sup.*team$")?, false)?.bool());
+        assert!(s.regex_match(&lit!(Str, "port")?, false)?.bool());
+        assert!(!s.regex_match(&lit!(Str, "PORT")?, false)?.bool());
+        assert!(s.regex_match(&lit!(Str, "PORT")?, true)?.bool());
+        assert!(s.regex_match(&lit!(Str, "(")?, false).is_err());
+
+        Ok(())
+    }
+}
@@ -175,7 +175,8 @@ impl LogManager {
             Record::Operation(_)
             | Record::CreateTable(_, _)
             | Record::DropTable(_)
-            | Record::Truncate(_) => {
+            | Record::Truncate(_)
+            | Record::AlterTable(_, _) => {
                 self.prev_lsn.insert(txn_id, lsn);
             }
         }
@@ -2,6 +2,7 @@
 
 use std::fmt::Display;
 
+use crate::catalog::AlterOperation;
 use crate::types::Value;
 use crate::wal::Lsn;
 use crate::{tuple::schema::Schema, txn_manager::TxnId};
@@ -30,6 +31,7 @@ pub enum Record {
     CreateTable(TableName, Schema),
     DropTable(TableName),
     Truncate(TableName),
+    AlterTable(TableName, AlterOperation),
 }
 
 impl Display for Record {
@@ -66,6 +68,7 @@ impl Display for Record {
             Record::CreateTable(table, schema) => write!(f, "CREATE {table} {}", schema.to_sql()),
             Record::DropTable(table) => write!(f, "DROP {table}"),
             Record::Truncate(table) => write!(f, "TRUNCATE {table}"),
+            Record::AlterTable(table, operation) => write!(f, "ALTER {table} {operation}"),
         }
     }
 }